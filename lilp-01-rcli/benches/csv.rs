@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rcli::{convert_csv, OutputFormat};
+use std::{fmt::Write, fs};
+
+const ROWS: usize = 100_000;
+
+// a large csv file generated from the juventus fixture
+fn gen_csv() -> String {
+    let path = std::env::temp_dir().join("rcli_bench.csv");
+    let mut content = String::from("Name,Position,DOB,Nationality,Kit Number\n");
+    for i in 0..ROWS {
+        writeln!(
+            content,
+            "Player {},Midfielder,\"Jan {}, 1990\",Italy,{}",
+            i,
+            i % 28 + 1,
+            i % 99
+        )
+        .unwrap();
+    }
+    fs::write(&path, content).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let inputs = vec![gen_csv()];
+    let mut group = c.benchmark_group("csv_to_json");
+    group.sample_size(10);
+    for threads in [1, 4] {
+        group.bench_function(format!("threads_{}", threads), |b| {
+            b.iter(|| {
+                convert_csv(
+                    black_box(&inputs),
+                    OutputFormat::Json,
+                    threads,
+                    4096,
+                    None,
+                    None,
+                )
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -3,7 +3,7 @@ use std::{fmt, str::FromStr};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use crate::CmdExector;
+use crate::{CmdExector, RcliError};
 
 use super::verify_file;
 
@@ -70,7 +70,7 @@ impl fmt::Display for Base64Format {
 }
 
 impl CmdExector for Base64EncodeOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         let mut reader = crate::get_reader(&self.input)?;
         let ret = crate::process_encode(&mut reader, self.format)?;
         println!("{}", ret);
@@ -79,7 +79,7 @@ impl CmdExector for Base64EncodeOpts {
 }
 
 impl CmdExector for Base64DecodeOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         let mut reader = crate::get_reader(&self.input)?;
         let ret = crate::process_decode(&mut reader, self.format)?;
         println!("{}", ret);
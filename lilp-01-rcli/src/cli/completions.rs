@@ -0,0 +1,16 @@
+use crate::{CmdExector, RcliError};
+use clap::Parser;
+use clap_complete::Shell;
+
+#[derive(Debug, Parser)]
+pub struct CompletionsOpts {
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+impl CmdExector for CompletionsOpts {
+    async fn execute(self) -> Result<(), RcliError> {
+        crate::process_completions(self.shell, &mut std::io::stdout());
+        Ok(())
+    }
+}
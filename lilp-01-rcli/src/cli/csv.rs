@@ -1,4 +1,4 @@
-use crate::CmdExector;
+use crate::{CmdExector, RcliError, RowSchema};
 
 use super::verify_file;
 use clap::Parser;
@@ -8,12 +8,14 @@ use std::{fmt, str::FromStr};
 pub enum OutputFormat {
     Json,
     Yaml,
+    Ndjson,
 }
 
 #[derive(Debug, Parser)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file)]
-    pub input: String,
+    /// input csv file, repeat to merge several files with the same headers
+    #[arg(short, long, value_parser = verify_file, required = true)]
+    pub input: Vec<String>,
 
     #[arg(short, long)] // "output.json".into()
     pub output: Option<String>,
@@ -26,16 +28,54 @@ pub struct CsvOpts {
 
     #[arg(long, default_value_t = true)]
     pub header: bool,
+
+    /// number of threads used to convert rows, 1 means sequential
+    #[arg(long, default_value_t = 1)]
+    pub threads: usize,
+
+    /// number of rows read and converted at a time
+    #[arg(long, default_value_t = 4096)]
+    pub chunk_size: usize,
+
+    /// json schema every row must satisfy, columns it types as number or integer are written as numbers
+    #[arg(long, value_parser = verify_file)]
+    pub schema: Option<String>,
+
+    /// drop rows failing the schema instead of stopping at the first one
+    #[arg(long, requires = "schema")]
+    pub skip_invalid: bool,
+
+    /// show a progress bar on stderr while reading the input
+    #[arg(long)]
+    pub progress: bool,
 }
 
 impl CmdExector for CsvOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         let output = if let Some(output) = self.output {
             output
         } else {
             format!("output.{}", self.format)
         };
-        crate::process_csv(&self.input, output, self.format)
+        let schema = self
+            .schema
+            .as_deref()
+            .map(|path| RowSchema::from_file(path, self.skip_invalid))
+            .transpose()?;
+        let progress = self.progress.then(|| crate::progress_bar(&self.input));
+        let ret = crate::process_csv(
+            &self.input,
+            output,
+            self.format,
+            self.threads,
+            self.chunk_size,
+            schema.as_ref(),
+            progress.as_ref(),
+        );
+        if let Some(bar) = progress {
+            bar.finish_and_clear();
+        }
+        ret
     }
 }
 
@@ -48,6 +88,7 @@ impl From<OutputFormat> for &'static str {
         match format {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Ndjson => "ndjson",
         }
     }
 }
@@ -59,6 +100,7 @@ impl FromStr for OutputFormat {
         match s {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
+            "ndjson" => Ok(OutputFormat::Ndjson),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -1,4 +1,4 @@
-use crate::CmdExector;
+use crate::{CmdExector, RcliError};
 use clap::Parser;
 use zxcvbn::zxcvbn;
 
@@ -21,7 +21,7 @@ pub struct GenPassOpts {
 }
 
 impl CmdExector for GenPassOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         let ret = crate::process_genpass(
             self.length,
             self.uppercase,
@@ -32,7 +32,7 @@ impl CmdExector for GenPassOpts {
         println!("{}", ret);
 
         // output password strength in stderr
-        let estimate = zxcvbn(&ret, &[])?;
+        let estimate = zxcvbn(&ret, &[]).map_err(|e| RcliError::Parse(e.to_string()))?;
         eprintln!("Password strength: {}", estimate.score());
         Ok(())
     }
@@ -1,15 +1,19 @@
-use crate::{process_http_serve, CmdExector};
+use crate::{
+    process_http_get, process_http_serve, BasicAuth, CmdExector, RcliError, DEFAULT_MAX_BODY_SIZE,
+};
 
 use super::verify_path;
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
-use std::path::PathBuf;
+use std::{io::Write, path::PathBuf, time::Duration};
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum HttpSubCommand {
     #[command(about = "Serve a directory over HTTP")]
     Serve(HttpServeOpts),
+    #[command(about = "Fetch a URL, print status and headers to stderr and the body to stdout")]
+    Get(HttpGetOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -18,10 +22,64 @@ pub struct HttpServeOpts {
     pub dir: PathBuf,
     #[arg(short, long, default_value_t = 8080)]
     pub port: u16,
+    /// require HTTP Basic auth, in the form of `user:pass`
+    #[arg(long, value_parser = parse_basic_auth)]
+    pub auth: Option<BasicAuth>,
+    /// serve the root `index.html` for missing paths, for client-side routing of single-page apps
+    #[arg(long)]
+    pub spa: bool,
+    /// reject requests with a bigger body with 413, in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_BODY_SIZE)]
+    pub max_body_size: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct HttpGetOpts {
+    pub url: String,
+    /// extra request header in the form of `Name: value`, can be repeated
+    #[arg(short = 'H', long = "header")]
+    pub headers: Vec<String>,
+    /// request timeout in seconds
+    #[arg(long, default_value_t = 30)]
+    pub timeout: u64,
+    /// write the body to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// maximum number of redirects to follow
+    #[arg(long, default_value_t = 10)]
+    pub max_redirects: usize,
 }
 
 impl CmdExector for HttpServeOpts {
-    async fn execute(self) -> anyhow::Result<()> {
-        process_http_serve(self.dir, self.port).await
+    async fn execute(self) -> Result<(), RcliError> {
+        process_http_serve(self.dir, self.port, self.auth, self.spa, self.max_body_size).await
+    }
+}
+
+impl CmdExector for HttpGetOpts {
+    async fn execute(self) -> Result<(), RcliError> {
+        let res = process_http_get(
+            &self.url,
+            &self.headers,
+            Duration::from_secs(self.timeout),
+            self.max_redirects,
+        )
+        .await?;
+        eprintln!("{}", res.status);
+        for (name, value) in res.headers.iter() {
+            eprintln!("{}: {}", name, value.to_str().unwrap_or("<binary>"));
+        }
+        match self.output {
+            Some(path) => tokio::fs::write(path, &res.body).await?,
+            None => std::io::stdout().write_all(&res.body)?,
+        }
+        Ok(())
+    }
+}
+
+fn parse_basic_auth(auth: &str) -> Result<BasicAuth, &'static str> {
+    match auth.split_once(':') {
+        Some((user, pass)) if !user.is_empty() => Ok(BasicAuth::new(user, pass)),
+        _ => Err("Auth must be in the form of user:pass"),
     }
 }
@@ -1,4 +1,4 @@
-use crate::{get_reader, process_gen_jwt_token, process_verify_jwt_token, CmdExector};
+use crate::{get_reader, process_gen_jwt_token, process_verify_jwt_token, CmdExector, RcliError};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 use regex::Regex;
@@ -63,7 +63,7 @@ pub struct JwtVerifyOpts {
 }
 
 impl CmdExector for JwtSignOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         // 从fixtures/jwt-secret.txt中读取密钥
         let mut secret_reader = get_reader("fixtures/jwt-secret.txt")?;
         let token = process_gen_jwt_token(&self, &mut secret_reader)?;
@@ -76,7 +76,7 @@ impl CmdExector for JwtSignOpts {
 }
 
 impl CmdExector for JwtVerifyOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         // 从fixtures/jwt-secret.txt中读取密钥
         let mut secret_reader = get_reader("fixtures/jwt-secret.txt")?;
         let mut token_reader = get_reader(&self.token)?;
@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+use crate::{CmdExector, RcliError};
+use clap::Parser;
+
+use super::verify_path;
+
+#[derive(Debug, Parser)]
+pub struct ManOpts {
+    /// write one page per subcommand into this directory instead of printing to stdout
+    #[arg(short, long, value_parser = verify_path)]
+    pub out: Option<PathBuf>,
+}
+
+impl CmdExector for ManOpts {
+    async fn execute(self) -> Result<(), RcliError> {
+        match self.out {
+            Some(dir) => {
+                crate::process_man_pages(&dir)?;
+                eprintln!("Man pages written to {}", dir.display());
+            }
+            None => crate::process_man(&mut std::io::stdout())?,
+        }
+        Ok(())
+    }
+}
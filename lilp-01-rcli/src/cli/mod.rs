@@ -1,21 +1,33 @@
 mod base64;
+mod completions;
 mod csv;
 mod genpass;
 mod http;
 mod jwt;
+mod man;
 mod text;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use enum_dispatch::enum_dispatch;
 use std::path::{Path, PathBuf};
 
-pub use self::{base64::*, csv::*, genpass::*, http::*, jwt::*, text::*};
+pub use self::{base64::*, completions::*, csv::*, genpass::*, http::*, jwt::*, man::*, text::*};
 
 #[derive(Debug, Parser)]
 #[command(name = "rcli", version, author, about, long_about = None)]
 pub struct Opts {
     #[command(subcommand)]
     pub cmd: SubCommand,
+
+    /// format of the logs, json emits one object per line with timestamp and level
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
 }
 
 #[derive(Debug, Parser)]
@@ -33,6 +45,10 @@ pub enum SubCommand {
     Http(HttpSubCommand),
     #[command(subcommand, about = "json web token(jwt) sign/verify")]
     Jwt(JwtSubCommand),
+    #[command(name = "completions", about = "Print the shell completion script")]
+    Completions(CompletionsOpts),
+    #[command(name = "man", about = "Generate man pages")]
+    Man(ManOpts),
 }
 
 fn verify_file(filename: &str) -> Result<String, &'static str> {
@@ -6,8 +6,10 @@ use enum_dispatch::enum_dispatch;
 use tokio::fs;
 
 use crate::{
-    get_content, get_reader, process_text_decrypt, process_text_encrypt, process_text_key_generate,
-    process_text_sign, process_text_verify, CmdExector,
+    get_content, get_multi_reader, get_reader, process_text_decrypt,
+    process_text_decrypt_with_passphrase, process_text_encrypt,
+    process_text_encrypt_with_passphrase, process_text_key_generate, process_text_sign,
+    process_text_verify_any, CmdExector, RcliError,
 };
 
 use super::{verify_file, verify_path};
@@ -19,7 +21,7 @@ pub enum TextSubCommand {
     Sign(TextSignOpts),
     #[command(about = "Verify a signature with a public/session key")]
     Verify(TextVerifyOpts),
-    #[command(about = "Generate a random blake3 key or ed25519 key pair")]
+    #[command(about = "Generate a random blake3/hmac-sha256 key or ed25519 key pair")]
     Generate(KeyGenerateOpts),
     #[command(about = "Encrypt a text with a public key")]
     Encrypt(TextEncryptOpts),
@@ -29,22 +31,36 @@ pub enum TextSubCommand {
 
 #[derive(Debug, Parser)]
 pub struct TextSignOpts {
+    /// input file, repeat to sign the concatenation of several files
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
-    pub input: String,
+    pub input: Vec<String>,
     #[arg(short, long, value_parser = verify_file)]
     pub key: String,
     #[arg(long, default_value = "blake3", value_parser = parse_text_sign_format)]
     pub format: TextSignFormat,
+    /// write the base64 signature to this file (e.g. `input.sig`) instead of stdout
+    #[arg(long)]
+    pub sig_out: Option<PathBuf>,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextVerifyOpts {
+    /// input file, repeat to verify the concatenation of several files
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
-    pub input: String,
-    #[arg(short, long, value_parser = verify_file)]
-    pub key: String,
-    #[arg(long)]
-    pub sig: String,
+    pub input: Vec<String>,
+    /// key file, repeat to try several keys (e.g. while rotating keys)
+    #[arg(short, long, value_parser = verify_file, required = true)]
+    pub key: Vec<String>,
+    /// inline base64 signature
+    #[arg(
+        long,
+        required_unless_present = "sig_file",
+        conflicts_with = "sig_file"
+    )]
+    pub sig: Option<String>,
+    /// file containing the base64 signature, as written by `sign --sig-out`
+    #[arg(long, value_parser = verify_file)]
+    pub sig_file: Option<String>,
     #[arg(long, default_value = "blake3", value_parser = parse_text_sign_format)]
     pub format: TextSignFormat,
 }
@@ -55,12 +71,17 @@ pub struct KeyGenerateOpts {
     pub format: TextSignFormat,
     #[arg(short, long, value_parser = verify_path)]
     pub output_path: PathBuf,
+    /// 32-byte seed in hex (shorter seeds are zero padded) for reproducible keys.
+    /// insecure, only for tests: the key can be recomputed from the seed
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<[u8; 32]>,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum TextSignFormat {
     Blake3,
     Ed25519,
+    HmacSha256,
 }
 
 #[derive(Debug, Parser)]
@@ -69,8 +90,23 @@ pub struct TextEncryptOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
     /// 输入密钥文件路径 32位
-    #[arg(short, long, value_parser = verify_file)]
-    pub key: String,
+    #[arg(
+        short,
+        long,
+        value_parser = verify_file,
+        required_unless_present = "passphrase",
+        conflicts_with = "passphrase"
+    )]
+    pub key: Option<String>,
+    /// 使用口令加密，密钥由argon2id派生
+    #[arg(long)]
+    pub passphrase: Option<String>,
+    /// 关联数据（如文件名），解密时需要提供相同的值
+    #[arg(long)]
+    pub aad: Option<String>,
+    /// 在 stderr 显示读取进度
+    #[arg(long)]
+    pub progress: bool,
 }
 
 #[derive(Debug, Parser)]
@@ -79,14 +115,43 @@ pub struct TextDecryptOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
     /// 输入密钥文件路径
-    #[arg(short, long, value_parser = verify_file)]
-    pub key: String,
+    #[arg(
+        short,
+        long,
+        value_parser = verify_file,
+        required_unless_present = "passphrase",
+        conflicts_with = "passphrase"
+    )]
+    pub key: Option<String>,
+    /// 加密时使用的口令
+    #[arg(long)]
+    pub passphrase: Option<String>,
+    /// 加密时使用的关联数据
+    #[arg(long)]
+    pub aad: Option<String>,
 }
 
 fn parse_text_sign_format(format: &str) -> Result<TextSignFormat, anyhow::Error> {
     format.parse()
 }
 
+fn parse_seed(seed: &str) -> Result<[u8; 32], anyhow::Error> {
+    let invalid = || anyhow::anyhow!("seed must be 1 to 32 bytes in hex");
+    if seed.is_empty() || seed.len() > 64 {
+        return Err(invalid());
+    }
+    let mut ret = [0u8; 32];
+    for (byte, pair) in ret.iter_mut().zip(seed.as_bytes().chunks(2)) {
+        if pair.len() != 2 || !pair.iter().all(u8::is_ascii_hexdigit) {
+            return Err(invalid());
+        }
+        // 两个字符都是 ascii, 一定是合法的 utf8
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(ret)
+}
+
 impl FromStr for TextSignFormat {
     type Err = anyhow::Error;
 
@@ -94,6 +159,7 @@ impl FromStr for TextSignFormat {
         match s {
             "blake3" => Ok(TextSignFormat::Blake3),
             "ed25519" => Ok(TextSignFormat::Ed25519),
+            "hmac-sha256" => Ok(TextSignFormat::HmacSha256),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -104,6 +170,7 @@ impl From<TextSignFormat> for &'static str {
         match format {
             TextSignFormat::Blake3 => "blake3",
             TextSignFormat::Ed25519 => "ed25519",
+            TextSignFormat::HmacSha256 => "hmac-sha256",
         }
     }
 }
@@ -115,35 +182,54 @@ impl fmt::Display for TextSignFormat {
 }
 
 impl CmdExector for TextSignOpts {
-    async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = get_reader(&self.input)?;
+    async fn execute(self) -> Result<(), RcliError> {
+        let mut reader = get_multi_reader(&self.input)?;
         let key = get_content(&self.key)?;
         let sig = process_text_sign(&mut reader, &key, self.format)?;
         // base64 output
         let encoded = URL_SAFE_NO_PAD.encode(sig);
-        println!("{}", encoded);
+        match &self.sig_out {
+            Some(path) => fs::write(path, format!("{}\n", encoded)).await?,
+            None => println!("{}", encoded),
+        }
         Ok(())
     }
 }
 
+impl TextVerifyOpts {
+    /// the decoded signature, either inline or read from `--sig-file`
+    async fn signature(&self) -> Result<Vec<u8>, RcliError> {
+        let encoded = match (&self.sig, &self.sig_file) {
+            (Some(sig), _) => sig.clone(),
+            (None, Some(path)) => fs::read_to_string(path).await?,
+            (None, None) => return Err(RcliError::Parse("missing signature".to_string())),
+        };
+        Ok(URL_SAFE_NO_PAD.decode(encoded.trim())?)
+    }
+}
+
 impl CmdExector for TextVerifyOpts {
-    async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = get_reader(&self.input)?;
-        let key = get_content(&self.key)?;
-        let decoded = URL_SAFE_NO_PAD.decode(&self.sig)?;
-        let verified = process_text_verify(&mut reader, &key, &decoded, self.format)?;
-        if verified {
-            println!("✓ Signature verified");
-        } else {
-            println!("⚠ Signature not verified");
+    async fn execute(self) -> Result<(), RcliError> {
+        let mut reader = get_multi_reader(&self.input)?;
+        let keys = self
+            .key
+            .iter()
+            .map(|key| get_content(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let decoded = self.signature().await?;
+        match process_text_verify_any(&mut reader, &keys, &decoded, self.format)? {
+            Some(_) if keys.len() == 1 => println!("✓ Signature verified"),
+            Some(i) => println!("✓ Signature verified with key {}", self.key[i]),
+            None if keys.len() == 1 => println!("⚠ Signature not verified"),
+            None => println!("⚠ Signature not verified with any of {} keys", keys.len()),
         }
         Ok(())
     }
 }
 
 impl CmdExector for KeyGenerateOpts {
-    async fn execute(self) -> anyhow::Result<()> {
-        let key = process_text_key_generate(self.format)?;
+    async fn execute(self) -> Result<(), RcliError> {
+        let key = process_text_key_generate(self.format, self.seed.as_ref())?;
         for (k, v) in key {
             fs::write(self.output_path.join(k), v).await?;
         }
@@ -152,14 +238,27 @@ impl CmdExector for KeyGenerateOpts {
 }
 
 impl CmdExector for TextEncryptOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         // 获取用户输入内容
+        let progress = self
+            .progress
+            .then(|| crate::progress_bar(std::slice::from_ref(&self.input)));
         let mut reader = get_reader(&self.input)?;
-        // 获取用户输入的key地址
-        let key = get_content(&self.key)?;
-        // encrypt
-        // let sig = process_text_sign(&mut reader, &key, self.format)?;
-        let ciphertext = process_text_encrypt(&mut reader, &key)?;
+        if let Some(bar) = &progress {
+            reader = Box::new(bar.wrap_read(reader));
+        }
+        let aad = self.aad.as_deref().unwrap_or_default().as_bytes();
+        // 使用key文件或者口令加密
+        let ciphertext = match (&self.key, &self.passphrase) {
+            (Some(key), _) => process_text_encrypt(&mut reader, &get_content(key)?, aad)?,
+            (None, Some(passphrase)) => {
+                process_text_encrypt_with_passphrase(&mut reader, passphrase, aad)?
+            }
+            (None, None) => return Err(RcliError::Parse("missing key or passphrase".to_string())),
+        };
+        if let Some(bar) = progress {
+            bar.finish_and_clear();
+        }
         // base64 output
         let encoded = URL_SAFE_NO_PAD.encode(ciphertext);
         println!(" 加密文本： {}", encoded);
@@ -168,13 +267,18 @@ impl CmdExector for TextEncryptOpts {
 }
 
 impl CmdExector for TextDecryptOpts {
-    async fn execute(self) -> anyhow::Result<()> {
+    async fn execute(self) -> Result<(), RcliError> {
         // 获取用户输入内容
         let mut reader = get_reader(&self.input)?;
-        // 获取用户输入的key地址
-        let key = get_content(&self.key)?;
-        // decrypt
-        let plaintext = process_text_decrypt(&mut reader, &key)?;
+        let aad = self.aad.as_deref().unwrap_or_default().as_bytes();
+        // 使用key文件或者口令解密
+        let plaintext = match (&self.key, &self.passphrase) {
+            (Some(key), _) => process_text_decrypt(&mut reader, &get_content(key)?, aad)?,
+            (None, Some(passphrase)) => {
+                process_text_decrypt_with_passphrase(&mut reader, passphrase, aad)?
+            }
+            (None, None) => return Err(RcliError::Parse("missing key or passphrase".to_string())),
+        };
         println!(" 解密文本：{}", String::from_utf8_lossy(&plaintext));
         Ok(())
     }
@@ -182,11 +286,67 @@ impl CmdExector for TextDecryptOpts {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::process_text_verify;
     use chacha20poly1305::{
         aead::{Aead, AeadCore, KeyInit, OsRng},
         ChaCha20Poly1305, Key,
     };
 
+    #[tokio::test]
+    async fn test_sign_to_file_and_verify() -> anyhow::Result<()> {
+        let sig_out = std::env::temp_dir().join("rcli_test_sign.sig");
+        let opts = TextSignOpts {
+            input: vec!["Cargo.toml".into(), "fixtures/b64.txt".into()],
+            key: "fixtures/blake3.txt".into(),
+            format: TextSignFormat::Blake3,
+            sig_out: Some(sig_out.clone()),
+        };
+        opts.execute().await?;
+
+        let opts = TextVerifyOpts {
+            input: vec!["Cargo.toml".into(), "fixtures/b64.txt".into()],
+            key: vec!["fixtures/blake3.txt".into()],
+            sig: None,
+            sig_file: Some(sig_out.to_string_lossy().into_owned()),
+            format: TextSignFormat::Blake3,
+        };
+        let sig = opts.signature().await?;
+        let key = get_content(&opts.key[0])?;
+        let mut reader = get_multi_reader(&opts.input)?;
+        assert!(process_text_verify(&mut reader, &key, &sig, opts.format)?);
+        std::fs::remove_file(sig_out)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_seed() {
+        let seed = parse_seed("0102ff").unwrap();
+        assert_eq!(&seed[..3], &[1, 2, 255]);
+        assert!(seed[3..].iter().all(|b| *b == 0));
+        assert_eq!(parse_seed(&"ab".repeat(32)).unwrap(), [0xab; 32]);
+
+        for invalid in ["", "1", "zz", "+1", "aéa", &"00".repeat(33)] {
+            assert!(parse_seed(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_verify_requires_a_signature() {
+        let ret = TextVerifyOpts::try_parse_from(["verify", "-k", "Cargo.toml"]);
+        assert!(ret.is_err());
+        let ret = TextVerifyOpts::try_parse_from([
+            "verify",
+            "-k",
+            "Cargo.toml",
+            "--sig",
+            "abc",
+            "--sig-file",
+            "Cargo.toml",
+        ]);
+        assert!(ret.is_err());
+    }
+
     #[test]
     fn test_chacha20poly1305() -> anyhow::Result<()> {
         // 生成32位密码用来生成key
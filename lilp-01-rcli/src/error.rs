@@ -0,0 +1,113 @@
+use thiserror::Error;
+
+pub(crate) type Result<T> = std::result::Result<T, RcliError>;
+
+/// errors returned by rcli commands, each kind maps to its own process exit code
+#[derive(Debug, Error)]
+pub enum RcliError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("parse error: {0}")]
+    Parse(String),
+    #[error("crypto error: {0}")]
+    Crypto(String),
+    #[error("jwt error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("http error: {0}")]
+    Http(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl RcliError {
+    /// exit code for the process, so scripts can tell the error kinds apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RcliError::Crypto(_) => 2,
+            RcliError::Io(_) => 3,
+            RcliError::Parse(_) => 4,
+            RcliError::Jwt(_) => 5,
+            RcliError::Http(_) => 6,
+        }
+    }
+}
+
+macro_rules! impl_from_error {
+    ($variant:ident: $($ty:ty),+) => {
+        $(
+            impl From<$ty> for RcliError {
+                fn from(e: $ty) -> Self {
+                    RcliError::$variant(e.to_string())
+                }
+            }
+        )+
+    };
+}
+
+impl_from_error!(
+    Parse: serde_json::Error,
+    serde_yaml::Error,
+    base64::DecodeError,
+    std::string::FromUtf8Error,
+    std::str::Utf8Error
+);
+impl_from_error!(
+    Crypto: ed25519_dalek::SignatureError,
+    chacha20poly1305::aead::Error,
+    argon2::Error
+);
+
+impl From<reqwest::Error> for RcliError {
+    fn from(e: reqwest::Error) -> Self {
+        RcliError::Http(Box::new(e))
+    }
+}
+
+impl From<csv::Error> for RcliError {
+    fn from(e: csv::Error) -> Self {
+        if !e.is_io_error() {
+            return RcliError::Parse(e.to_string());
+        }
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => RcliError::Io(e),
+            _ => unreachable!("checked by is_io_error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_reader;
+
+    #[test]
+    fn missing_input_file_should_be_io_error() {
+        let err = get_reader("not-exist").err().unwrap();
+        assert!(matches!(err, RcliError::Io(_)));
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[test]
+    fn missing_csv_file_should_be_io_error() {
+        let err = crate::convert_csv(
+            &["not-exist.csv".to_string()],
+            crate::OutputFormat::Json,
+            1,
+            4096,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, RcliError::Io(_)));
+    }
+
+    #[test]
+    fn bad_key_should_be_crypto_error() {
+        let err = crate::process_text_sign(
+            &mut "hello".as_bytes(),
+            b"too short",
+            crate::TextSignFormat::Blake3,
+        )
+        .unwrap_err();
+        assert!(matches!(err, RcliError::Crypto(_)));
+        assert_eq!(err.exit_code(), 2);
+    }
+}
@@ -1,14 +1,16 @@
 mod cli;
+mod error;
 mod process;
 mod utils;
 
 pub use cli::*;
 use enum_dispatch::enum_dispatch;
+pub use error::RcliError;
 pub use process::*;
 pub use utils::*;
 
 #[allow(async_fn_in_trait)]
 #[enum_dispatch]
 pub trait CmdExector {
-    async fn execute(self) -> anyhow::Result<()>;
+    async fn execute(self) -> Result<(), RcliError>;
 }
@@ -1,16 +1,17 @@
 // rcli csv -i input.csv -o output.json --header -d ','
 
 use clap::Parser;
-use rcli::{CmdExector, Opts};
+use rcli::{log_subscriber, CmdExector, Opts};
+use tracing_subscriber::util::SubscriberInitExt;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO) // 设置日志级别为INFO
-        .init();
-
+async fn main() {
+    // 先解析参数, 日志格式由 --log-format 决定
     let opts = Opts::parse();
-    opts.cmd.execute().await?;
-
-    Ok(())
+    log_subscriber(opts.log_format, std::io::stdout).init();
+    if let Err(e) = opts.cmd.execute().await {
+        // 不同的错误类型使用不同的退出码，方便脚本区分
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
 }
@@ -0,0 +1,38 @@
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_complete::{generate, Shell};
+
+use crate::Opts;
+
+/// write the completion script for `shell` to `writer`, e.g. `source <(rcli completions bash)`
+pub fn process_completions(shell: Shell, writer: &mut impl Write) {
+    let mut cmd = Opts::command();
+    let name = cmd.get_name().to_string();
+    generate(shell, &mut cmd, name, writer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_bash_completions() {
+        let mut buf = Vec::new();
+        process_completions(Shell::Bash, &mut buf);
+        let script = String::from_utf8(buf).unwrap();
+        assert!(script.contains("rcli"));
+        assert!(script.contains("genpass"));
+    }
+
+    #[test]
+    fn test_parse_completions_opts() {
+        let opts = Opts::try_parse_from(["rcli", "completions", "zsh"]).unwrap();
+        assert!(matches!(
+            opts.cmd,
+            crate::SubCommand::Completions(crate::CompletionsOpts { shell: Shell::Zsh })
+        ));
+        assert!(Opts::try_parse_from(["rcli", "completions", "tcsh"]).is_err());
+    }
+}
@@ -1,7 +1,16 @@
-use anyhow::Result;
-use csv::Reader;
+use crate::error::Result;
+use crate::RcliError;
+use csv::{Reader, StringRecord};
+use indicatif::ProgressBar;
+use jsonschema::JSONSchema;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read, Write},
+};
 
 use crate::cli::OutputFormat;
 
@@ -17,28 +26,547 @@ struct Player {
     kit: u8,
 }
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
+/// a json schema every converted row must satisfy
+pub struct RowSchema {
+    schema: JSONSchema,
+    /// drop invalid rows instead of failing on the first one
+    skip_invalid: bool,
+    /// columns the schema types as number or integer, they're emitted as json numbers
+    numeric: HashSet<String>,
+}
+
+impl RowSchema {
+    pub fn try_new(schema: &Value, skip_invalid: bool) -> Result<Self> {
+        let numeric = numeric_properties(schema);
+        let schema = JSONSchema::compile(schema)
+            .map_err(|e| RcliError::Parse(format!("invalid json schema: {}", e)))?;
+        Ok(Self {
+            schema,
+            skip_invalid,
+            numeric,
+        })
+    }
+
+    pub fn from_file(path: &str, skip_invalid: bool) -> Result<Self> {
+        let schema: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Self::try_new(&schema, skip_invalid)
+    }
+}
+
+pub fn process_csv(
+    inputs: &[String],
+    output: String,
+    format: OutputFormat,
+    threads: usize,
+    chunk_size: usize,
+    schema: Option<&RowSchema>,
+    progress: Option<&ProgressBar>,
+) -> Result<()> {
+    if let OutputFormat::Ndjson = format {
+        let mut writer = File::create(output)?;
+        return process_csv_ndjson(inputs, &mut writer, threads, chunk_size, schema, progress);
+    }
+    let content = convert_csv(inputs, format, threads, chunk_size, schema, progress)?;
+    fs::write(output, content)?;
+    Ok(())
+}
+
+/// stream the csv files as newline-delimited json, one object per row, flushed per row
+/// so the whole document never needs to be buffered
+pub fn process_csv_ndjson(
+    inputs: &[String],
+    writer: &mut impl Write,
+    threads: usize,
+    chunk_size: usize,
+    schema: Option<&RowSchema>,
+    progress: Option<&ProgressBar>,
+) -> Result<()> {
+    let (headers, mut records) = open_csv(inputs, progress)?;
+    let pool = build_pool(threads)?;
+
+    let mut validation = Validation::new(schema);
+    loop {
+        let chunk = read_chunk(&mut records, chunk_size)?;
+        if chunk.is_empty() {
+            break;
+        }
+        let chunk = validation.check(&headers, chunk)?;
+
+        let to_line =
+            |record: &StringRecord| serde_json::to_string(&row_json(&headers, record, schema));
+        let lines = match &pool {
+            Some(pool) => pool.install(|| chunk.par_iter().map(to_line).collect::<Vec<_>>()),
+            None => chunk.iter().map(to_line).collect(),
+        };
+        for line in lines {
+            writeln!(writer, "{}", line?)?;
+            writer.flush()?;
+        }
+    }
+    validation.report();
+    Ok(())
+}
+
+/// convert the csv files into `format`, rows are converted `chunk_size` at a time.
+/// with more than one thread each chunk is converted in a rayon pool, the output keeps the input order.
+/// `progress` advances by the bytes read from the inputs
+pub fn convert_csv(
+    inputs: &[String],
+    format: OutputFormat,
+    threads: usize,
+    chunk_size: usize,
+    schema: Option<&RowSchema>,
+    progress: Option<&ProgressBar>,
+) -> Result<String> {
+    if let OutputFormat::Ndjson = format {
+        let mut buf = Vec::new();
+        process_csv_ndjson(inputs, &mut buf, threads, chunk_size, schema, progress)?;
+        return Ok(String::from_utf8(buf)?);
+    }
+    let (headers, mut records) = open_csv(inputs, progress)?;
+    let pool = build_pool(threads)?;
+
+    let mut validation = Validation::new(schema);
+    let mut rows = Vec::with_capacity(128);
+    let mut json_rows = Vec::with_capacity(128);
+    loop {
+        let chunk = read_chunk(&mut records, chunk_size)?;
+        if chunk.is_empty() {
+            break;
+        }
+        let chunk = validation.check(&headers, chunk)?;
+
+        match (&pool, format) {
+            // json 的每一行可以单独序列化，再拼成数组
+            (Some(pool), OutputFormat::Json) => {
+                let ret = pool.install(|| {
+                    chunk
+                        .par_iter()
+                        .map(|record| to_pretty_json_element(&row_json(&headers, record, schema)))
+                        .collect::<Result<Vec<_>>>()
+                })?;
+                json_rows.extend(ret);
+            }
+            (Some(pool), _) => {
+                let ret: Vec<_> = pool.install(|| {
+                    chunk
+                        .par_iter()
+                        .map(|record| row_json(&headers, record, schema))
+                        .collect()
+                });
+                rows.extend(ret);
+            }
+            (None, _) => rows.extend(
+                chunk
+                    .iter()
+                    .map(|record| row_json(&headers, record, schema)),
+            ),
+        }
+    }
+
+    let content = match (&pool, format) {
+        (Some(_), OutputFormat::Json) => join_json_elements(&json_rows),
+        (None, OutputFormat::Json) => serde_json::to_string_pretty(&rows)?,
+        (_, OutputFormat::Yaml) => serde_yaml::to_string(&rows)?,
+        (_, OutputFormat::Ndjson) => unreachable!("ndjson is streamed"),
+    };
+    validation.report();
+    Ok(content)
+}
+
+/// validate the records against `schema`, as they're emitted, `first_row` is the 1-based number
+/// of the first record.
+/// an invalid row is an error naming its row number, unless the schema skips invalid rows.
+/// returns the valid records and the number of records dropped
+pub fn validate_rows(
+    headers: &StringRecord,
+    records: Vec<StringRecord>,
+    schema: &RowSchema,
+    first_row: usize,
+) -> Result<(Vec<StringRecord>, usize)> {
+    let mut valid = Vec::with_capacity(records.len());
+    let mut skipped = 0;
+    for (i, record) in records.into_iter().enumerate() {
+        let value = schema.to_json(headers, &record);
+        if let Err(mut errors) = schema.schema.validate(&value) {
+            if !schema.skip_invalid {
+                let error = errors.next().map(|e| e.to_string()).unwrap_or_default();
+                return Err(RcliError::Parse(format!(
+                    "row {} is invalid: {}",
+                    first_row + i,
+                    error
+                )));
+            }
+            skipped += 1;
+            continue;
+        }
+        valid.push(record);
+    }
+    Ok((valid, skipped))
+}
+
+/// validation state across chunks
+struct Validation<'a> {
+    schema: Option<&'a RowSchema>,
+    next_row: usize,
+    skipped: usize,
+}
+
+impl<'a> Validation<'a> {
+    fn new(schema: Option<&'a RowSchema>) -> Self {
+        Self {
+            schema,
+            next_row: 1,
+            skipped: 0,
+        }
+    }
+
+    fn check(
+        &mut self,
+        headers: &StringRecord,
+        chunk: Vec<StringRecord>,
+    ) -> Result<Vec<StringRecord>> {
+        let Some(schema) = self.schema else {
+            return Ok(chunk);
+        };
+        let first_row = self.next_row;
+        self.next_row += chunk.len();
+        let (valid, skipped) = validate_rows(headers, chunk, schema, first_row)?;
+        self.skipped += skipped;
+        Ok(valid)
+    }
+
+    fn report(&self) {
+        if self.skipped > 0 {
+            eprintln!("skipped {} invalid rows", self.skipped);
+        }
+    }
+}
+
+/// a rayon pool for more than one thread, none means converting sequentially
+fn build_pool(threads: usize) -> Result<Option<rayon::ThreadPool>> {
+    if threads <= 1 {
+        return Ok(None);
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| RcliError::Io(io::Error::other(e)))?;
+    Ok(Some(pool))
+}
+
+/// open all the inputs, the headers of the first file define the schema and the others must match.
+/// the records of all files are chained in the input order
+fn open_csv(
+    inputs: &[String],
+    progress: Option<&ProgressBar>,
+) -> Result<(
+    StringRecord,
+    impl Iterator<Item = csv::Result<StringRecord>>,
+)> {
+    let open = |input: &str| -> Result<Reader<Box<dyn Read>>> {
+        let file = File::open(input)?;
+        let reader: Box<dyn Read> = match progress {
+            Some(bar) => Box::new(bar.wrap_read(file)),
+            None => Box::new(file),
+        };
+        Ok(Reader::from_reader(reader))
+    };
+    let (first, rest) = inputs
+        .split_first()
+        .ok_or_else(|| RcliError::Parse("no csv input".to_string()))?;
+    let mut reader = open(first)?;
     let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        // headers.iter() -> 使用 headers 的迭代器
-        // record.iter() -> 使用 record 的迭代器
-        // zip() -> 将两个迭代器合并为一个元组的迭代器 [(header, record), ..]
-        // collect::<Value>() -> 将元组的迭代器转换为 JSON Value
-        let json_value = headers
+
+    let mut readers = vec![reader];
+    for input in rest {
+        let mut reader = open(input)?;
+        if reader.headers()? != &headers {
+            return Err(RcliError::Parse(format!(
+                "headers of {} don't match {}",
+                input, first
+            )));
+        }
+        readers.push(reader);
+    }
+    let records = readers.into_iter().flat_map(|reader| reader.into_records());
+    Ok((headers, records))
+}
+
+fn read_chunk(
+    records: &mut impl Iterator<Item = csv::Result<StringRecord>>,
+    chunk_size: usize,
+) -> Result<Vec<StringRecord>> {
+    let chunk = records
+        .by_ref()
+        .take(chunk_size.max(1))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(chunk)
+}
+
+fn to_json(headers: &StringRecord, record: &StringRecord) -> Value {
+    // headers.iter() -> 使用 headers 的迭代器
+    // record.iter() -> 使用 record 的迭代器
+    // zip() -> 将两个迭代器合并为一个元组的迭代器 [(header, record), ..]
+    // collect::<Value>() -> 将元组的迭代器转换为 JSON Value
+    headers.iter().zip(record.iter()).collect::<Value>()
+}
+
+/// the row as it's emitted, typed by the schema if there's one
+fn row_json(headers: &StringRecord, record: &StringRecord, schema: Option<&RowSchema>) -> Value {
+    match schema {
+        Some(schema) => schema.to_json(headers, record),
+        None => to_json(headers, record),
+    }
+}
+
+impl RowSchema {
+    /// like `to_json`, but the cells of the numeric columns become json numbers when they parse,
+    /// the others are kept as strings, e.g. `01234` of a string column
+    fn to_json(&self, headers: &StringRecord, record: &StringRecord) -> Value {
+        let cell = |header: &str, s: &str| {
+            if !self.numeric.contains(header) {
+                return Value::from(s);
+            }
+            match s.parse::<i64>() {
+                Ok(n) => Value::from(n),
+                Err(_) => match s.parse::<f64>() {
+                    Ok(n) if n.is_finite() => Value::from(n),
+                    _ => Value::from(s),
+                },
+            }
+        };
+        headers
             .iter()
             .zip(record.iter())
-            .collect::<serde_json::Value>();
-
-        ret.push(json_value);
+            .map(|(header, value)| (header.to_string(), cell(header, value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
     }
+}
 
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+/// names of the top level properties whose `type` is, or includes, number or integer
+fn numeric_properties(schema: &Value) -> HashSet<String> {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return HashSet::new();
     };
-    fs::write(output, content)?;
-    Ok(())
+    let is_numeric = |t: &Value| matches!(t.as_str(), Some("number" | "integer"));
+    properties
+        .iter()
+        .filter(|(_, property)| match property.get("type") {
+            Some(Value::Array(types)) => types.iter().any(is_numeric),
+            Some(t) => is_numeric(t),
+            None => false,
+        })
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// pretty print the value as an element of a pretty printed array, i.e. indented by one level
+fn to_pretty_json_element(value: &Value) -> Result<String> {
+    let s = serde_json::to_string_pretty(value)?;
+    // json 字符串中的换行会被转义，所以可以安全地按行缩进
+    let lines: Vec<_> = s.lines().map(|line| format!("  {}", line)).collect();
+    Ok(lines.join("\n"))
+}
+
+/// same output as `serde_json::to_string_pretty` on the whole array
+fn join_json_elements(elements: &[String]) -> String {
+    if elements.is_empty() {
+        return "[]".to_string();
+    }
+    format!("[\n{}\n]", elements.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "assets/juventus.csv";
+
+    fn inputs() -> Vec<String> {
+        vec![INPUT.to_string()]
+    }
+
+    fn write_temp_csv(name: &str, content: &str) -> Result<String> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, content)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn parallel_output_should_equal_sequential() -> Result<()> {
+        for format in [OutputFormat::Json, OutputFormat::Yaml] {
+            let sequential = convert_csv(&inputs(), format, 1, 4096, None, None)?;
+            let parallel = convert_csv(&inputs(), format, 4, 3, None, None)?;
+            assert_eq!(sequential, parallel);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn progress_should_not_change_output() -> Result<()> {
+        let path = write_temp_csv("rcli_test_progress.csv", "a,b\n1,2\n3,4\n")?;
+        let inputs = vec![path.clone()];
+        let bar = crate::progress_bar(&inputs);
+        for format in [OutputFormat::Json, OutputFormat::Ndjson] {
+            bar.reset();
+            let with_progress = convert_csv(&inputs, format, 1, 1, None, Some(&bar))?;
+            assert_eq!(
+                with_progress,
+                convert_csv(&inputs, format, 1, 1, None, None)?
+            );
+            assert_eq!(bar.position(), fs::metadata(&path)?.len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ndjson_should_have_one_object_per_row() -> Result<()> {
+        let rows = Reader::from_path(INPUT)?.records().count();
+        for threads in [1, 4] {
+            let mut buf = Vec::new();
+            process_csv_ndjson(&inputs(), &mut buf, threads, 3, None, None)?;
+            let output = String::from_utf8(buf)?;
+            let lines: Vec<_> = output.lines().collect();
+            assert_eq!(lines.len(), rows);
+            for line in lines {
+                let value: Value = serde_json::from_str(line)?;
+                assert!(value.is_object());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_inputs_should_merge_into_one_array() -> Result<()> {
+        let inputs = vec![
+            write_temp_csv("rcli_merge_1.csv", "Name,Age\nAlice,30\n")?,
+            write_temp_csv("rcli_merge_2.csv", "Name,Age\nBob,25\nCarol,41\n")?,
+        ];
+        let output = convert_csv(&inputs, OutputFormat::Json, 1, 4096, None, None)?;
+        let value: Value = serde_json::from_str(&output)?;
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {"Name": "Alice", "Age": "30"},
+                {"Name": "Bob", "Age": "25"},
+                {"Name": "Carol", "Age": "41"},
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mismatched_headers_should_be_rejected() -> Result<()> {
+        let inputs = vec![
+            write_temp_csv("rcli_mismatch_1.csv", "Name,Age\nAlice,30\n")?,
+            write_temp_csv("rcli_mismatch_2.csv", "Name,City\nBob,Turin\n")?,
+        ];
+        let err = convert_csv(&inputs, OutputFormat::Json, 1, 4096, None, None).unwrap_err();
+        assert!(matches!(err, RcliError::Parse(_)));
+        Ok(())
+    }
+
+    fn kit_number_schema(skip_invalid: bool) -> Result<RowSchema> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"Kit Number": {"type": "integer"}},
+            "required": ["Kit Number"],
+        });
+        RowSchema::try_new(&schema, skip_invalid)
+    }
+
+    #[test]
+    fn schema_should_accept_clean_rows() -> Result<()> {
+        let schema = kit_number_schema(false)?;
+        let with_schema = convert_csv(&inputs(), OutputFormat::Json, 1, 4096, Some(&schema), None)?;
+        let with_schema: Vec<Value> = serde_json::from_str(&with_schema)?;
+        let without: Vec<Value> = serde_json::from_str(&convert_csv(
+            &inputs(),
+            OutputFormat::Json,
+            1,
+            4096,
+            None,
+            None,
+        )?)?;
+        assert_eq!(with_schema.len(), without.len());
+        // only the integer column is typed
+        for (typed, raw) in with_schema.iter().zip(&without) {
+            assert!(typed["Kit Number"].is_i64());
+            assert_eq!(
+                typed["Kit Number"].to_string(),
+                raw["Kit Number"].as_str().unwrap()
+            );
+            assert_eq!(typed["Name"], raw["Name"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn schema_should_validate_the_emitted_values() -> Result<()> {
+        let inputs = vec![write_temp_csv(
+            "rcli_schema_typed.csv",
+            "Code,Kit Number
+01234,7
+",
+        )?];
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "Code": {"type": "string"},
+                "Kit Number": {"type": ["integer", "null"]},
+            },
+        });
+        let schema = RowSchema::try_new(&schema, false)?;
+        for format in [OutputFormat::Json, OutputFormat::Ndjson] {
+            for threads in [1, 4] {
+                let output = convert_csv(&inputs, format, threads, 1, Some(&schema), None)?;
+                let rows: Vec<Value> = match format {
+                    OutputFormat::Ndjson => output
+                        .lines()
+                        .map(serde_json::from_str)
+                        .collect::<std::result::Result<_, _>>()?,
+                    _ => serde_json::from_str(&output)?,
+                };
+                assert_eq!(
+                    rows,
+                    vec![serde_json::json!({"Code": "01234", "Kit Number": 7})]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn schema_should_reject_non_numeric_cell_with_row_number() -> Result<()> {
+        let inputs = vec![write_temp_csv(
+            "rcli_schema.csv",
+            "Name,Kit Number\nBuffon,1\nChiellini,three\n",
+        )?];
+        let schema = kit_number_schema(false)?;
+        let err = convert_csv(&inputs, OutputFormat::Json, 1, 1, Some(&schema), None).unwrap_err();
+        assert!(
+            matches!(&err, RcliError::Parse(msg) if msg.contains("row 2")),
+            "{}",
+            err
+        );
+
+        let schema = kit_number_schema(true)?;
+        let output = convert_csv(&inputs, OutputFormat::Json, 1, 1, Some(&schema), None)?;
+        let value: Value = serde_json::from_str(&output)?;
+        assert_eq!(
+            value,
+            serde_json::json!([{"Name": "Buffon", "Kit Number": 1}])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn empty_json_array_should_match_serde() {
+        assert_eq!(
+            join_json_elements(&[]),
+            serde_json::to_string_pretty(&Vec::<Value>::new()).unwrap()
+        );
+    }
 }
@@ -1,4 +1,6 @@
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::error::Result;
 
 const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
@@ -11,36 +13,54 @@ pub fn process_genpass(
     lower: bool,
     number: bool,
     symbol: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
+) -> Result<String> {
+    process_genpass_with_rng(
+        &mut rand::thread_rng(),
+        length,
+        upper,
+        lower,
+        number,
+        symbol,
+    )
+}
+
+/// like `process_genpass`, but draws from `rng` so a seeded rng gives the same password
+pub fn process_genpass_with_rng(
+    rng: &mut impl Rng,
+    length: u8,
+    upper: bool,
+    lower: bool,
+    number: bool,
+    symbol: bool,
+) -> Result<String> {
     let mut password = Vec::new();
     let mut chars = Vec::new();
 
     if upper {
         chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+        password.push(*UPPER.choose(rng).expect("UPPER won't be empty"));
     }
     if lower {
         chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+        password.push(*LOWER.choose(rng).expect("LOWER won't be empty"));
     }
     if number {
         chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+        password.push(*NUMBER.choose(rng).expect("NUMBER won't be empty"));
     }
     if symbol {
         chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+        password.push(*SYMBOL.choose(rng).expect("SYMBOL won't be empty"));
     }
 
     for _ in 0..(length - password.len() as u8) {
         let c = chars
-            .choose(&mut rng)
+            .choose(rng)
             .expect("chars won't be empty in this context");
         password.push(*c);
     }
 
-    password.shuffle(&mut rng);
+    password.shuffle(rng);
 
     Ok(String::from_utf8(password)?)
 }
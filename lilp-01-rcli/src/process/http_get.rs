@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use axum::body::Bytes;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    redirect::Policy,
+    StatusCode,
+};
+
+use crate::error::Result;
+use crate::RcliError;
+
+#[derive(Debug)]
+pub struct HttpGetResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+/// fetch `url`, headers are in the form of `Name: value`, redirects are followed up to `max_redirects`
+pub async fn process_http_get(
+    url: &str,
+    headers: &[String],
+    timeout: Duration,
+    max_redirects: usize,
+) -> Result<HttpGetResponse> {
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(Policy::limited(max_redirects))
+        .build()?;
+    let res = client
+        .get(url)
+        .headers(parse_headers(headers)?)
+        .send()
+        .await?;
+    Ok(HttpGetResponse {
+        status: res.status(),
+        headers: res.headers().clone(),
+        body: res.bytes().await?,
+    })
+}
+
+fn parse_headers(headers: &[String]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| RcliError::Parse(format!("invalid header: {}", header)))?;
+        let name = HeaderName::try_from(name.trim())
+            .map_err(|e| RcliError::Parse(format!("invalid header name {}: {}", name, e)))?;
+        let value = HeaderValue::try_from(value.trim())
+            .map_err(|e| RcliError::Parse(format!("invalid header value {}: {}", value, e)))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_headers() -> Result<()> {
+        let headers = parse_headers(&["Accept: text/plain".into(), "X-Foo:bar".into()])?;
+        assert_eq!(headers["accept"], "text/plain");
+        assert_eq!(headers["x-foo"], "bar");
+        assert!(parse_headers(&["no-colon".into()]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http_get_from_local_server() -> anyhow::Result<()> {
+        let port = 18_123;
+        tokio::spawn(crate::process_http_serve(
+            PathBuf::from("."),
+            port,
+            None,
+            false,
+            crate::DEFAULT_MAX_BODY_SIZE,
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let res = process_http_get(
+            &format!("http://127.0.0.1:{}/Cargo.toml", port),
+            &["Accept: text/html".into()],
+            Duration::from_secs(5),
+            10,
+        )
+        .await?;
+        assert_eq!(res.status, StatusCode::OK);
+        let expected = std::fs::read("Cargo.toml")?;
+        assert_eq!(res.body, expected);
+        Ok(())
+    }
+}
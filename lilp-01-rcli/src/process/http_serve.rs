@@ -1,28 +1,70 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::Result;
 use askama_axum::Template;
-use axum::response::{Html, IntoResponse};
+use axum::response::{Html, IntoResponse, Response};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
     routing::get,
     Router,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use tokio::fs;
-use tower_http::services::ServeDir;
+use tower_http::{limit::RequestBodyLimitLayer, services::ServeDir};
 use tracing::{info, warn};
 
+use crate::RcliError;
+
+/// nothing is uploaded yet, but leave some room for forms
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
 #[derive(Debug)]
 struct HttpServeState {
     path: PathBuf,
+    /// fallback to the root `index.html` for missing paths
+    spa: bool,
 }
 
-pub async fn process_http_serve(path: PathBuf, port: u16) -> Result<()> {
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+pub async fn process_http_serve(
+    path: PathBuf,
+    port: u16,
+    auth: Option<BasicAuth>,
+    spa: bool,
+    max_body_size: usize,
+) -> Result<(), RcliError> {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     info!("Serving {:?} on {}", path, addr);
 
-    let state = HttpServeState { path: path.clone() };
+    let router = build_router(path, auth, spa, max_body_size);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| RcliError::Http(e.into()))?;
+    axum::serve(listener, router)
+        .await
+        .map_err(|e| RcliError::Http(e.into()))?;
+    Ok(())
+}
+
+fn build_router(path: PathBuf, auth: Option<BasicAuth>, spa: bool, max_body_size: usize) -> Router {
+    let state = HttpServeState {
+        path: path.clone(),
+        spa,
+    };
     // axum router
     let router = Router::new()
         .nest_service("/tower", ServeDir::new(path))
@@ -30,9 +72,62 @@ pub async fn process_http_serve(path: PathBuf, port: u16) -> Result<()> {
         .route("/*path", get(file_handler))
         .with_state(Arc::new(state));
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, router).await?;
-    Ok(())
+    let router = match auth {
+        Some(auth) => router.layer(middleware::from_fn_with_state(
+            Arc::new(auth),
+            basic_auth_middleware,
+        )),
+        None => router,
+    };
+    // outermost, so oversized bodies are rejected before auth
+    router.layer(RequestBodyLimitLayer::new(max_body_size))
+}
+
+async fn basic_auth_middleware(
+    State(auth): State<Arc<BasicAuth>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|v| STANDARD.decode(v.trim()).ok())
+        .map(|credentials| auth.verify(&credentials))
+        .unwrap_or(false);
+
+    if authorized {
+        next.run(req).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"rcli\"")],
+        )
+            .into_response()
+    }
+}
+
+impl BasicAuth {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    /// verify the decoded `user:pass` credentials in constant time
+    fn verify(&self, credentials: &[u8]) -> bool {
+        let expected = format!("{}:{}", self.username, self.password);
+        constant_time_eq(expected.as_bytes(), credentials)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 #[derive(Template)]
@@ -46,15 +141,115 @@ struct DirectoryTemplate {
 struct FileInfo {
     name: String,
     path: String,
+    /// humanized, `-` for directories
+    size: String,
+    modified: String,
+    // 排序用的原始值
+    is_dir: bool,
+    bytes: u64,
+    modified_at: Option<SystemTime>,
+}
+
+/// `?sort=name|size|modified&order=asc|desc` of the directory listing
+#[derive(Debug, Default, Deserialize)]
+struct ListingQuery {
+    #[serde(default)]
+    sort: SortBy,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortBy {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl FileInfo {
+    fn new(name: String, path: String, meta: Option<&std::fs::Metadata>) -> Self {
+        let size = match meta {
+            Some(meta) if meta.is_file() => human_size(meta.len()),
+            _ => "-".to_string(),
+        };
+        let modified_at = meta.and_then(|meta| meta.modified().ok());
+        Self {
+            name,
+            path,
+            size,
+            modified: modified_at.map_or("-".to_string(), format_time),
+            is_dir: meta.is_some_and(|meta| meta.is_dir()),
+            bytes: meta.map_or(0, |meta| meta.len()),
+            modified_at,
+        }
+    }
+}
+
+impl ListingQuery {
+    /// directories first, then by the requested key, ties are broken by name
+    fn sort(&self, files: &mut [FileInfo]) {
+        files.sort_by(|a, b| {
+            let ord = match self.sort {
+                SortBy::Name => a.name.cmp(&b.name),
+                SortBy::Size => a.bytes.cmp(&b.bytes).then_with(|| a.name.cmp(&b.name)),
+                SortBy::Modified => a
+                    .modified_at
+                    .cmp(&b.modified_at)
+                    .then_with(|| a.name.cmp(&b.name)),
+            };
+            let ord = match self.order {
+                SortOrder::Asc => ord,
+                SortOrder::Desc => ord.reverse(),
+            };
+            b.is_dir.cmp(&a.is_dir).then(ord)
+        });
+    }
+}
+
+/// e.g. `512 B`, `1.2 MiB`
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
 }
 
-async fn file_index_handler(state: State<Arc<HttpServeState>>) -> impl IntoResponse {
-    file_handler(state, Path(".".to_string())).await
+async fn file_index_handler(
+    state: State<Arc<HttpServeState>>,
+    headers: HeaderMap,
+    query: Query<ListingQuery>,
+) -> impl IntoResponse {
+    file_handler(state, headers, Path(".".to_string()), query).await
 }
 
 async fn file_handler(
     State(state): State<Arc<HttpServeState>>,
+    headers: HeaderMap,
     Path(req_path): Path<String>,
+    Query(query): Query<ListingQuery>,
 ) -> impl IntoResponse {
     let full_path = state.path.join(&req_path);
     info!(
@@ -63,16 +258,6 @@ async fn file_handler(
     );
     if full_path.is_dir() {
         let mut files = Vec::new();
-        // 添加返回上一级目录的链接
-        if req_path != "." {
-            let parent_path = std::path::Path::new(&req_path)
-                .parent()
-                .map_or(".".to_string(), |p| p.to_str().unwrap_or(".").to_string());
-            files.push(FileInfo {
-                name: "../".to_string(),
-                path: "/".to_owned() + &parent_path,
-            });
-        }
         if let Ok(mut entries) = fs::read_dir(&full_path).await {
             while let Ok(Some(entry)) = entries.next_entry().await {
                 if let Ok(file_name) = entry.file_name().into_string() {
@@ -83,23 +268,30 @@ async fn file_handler(
                         format!("{}/{}", req_path, file_name)
                     };
 
+                    // 跟随符号链接, 和实际提供的文件保持一致
+                    let meta = fs::metadata(entry.path()).await.ok();
                     let display_path = if entry.path().is_dir() {
                         // 如果是目录，则在显示名称末尾添加'/'
-                        FileInfo {
-                            name: file_name + "/",
-                            path: file_path,
-                        }
+                        FileInfo::new(file_name + "/", file_path, meta.as_ref())
                     } else {
                         println!("文件 file_path: {:?}", "/".to_owned() + &file_path);
-                        FileInfo {
-                            name: file_name,
-                            path: "/".to_owned() + &file_path,
-                        }
+                        FileInfo::new(file_name, "/".to_owned() + &file_path, meta.as_ref())
                     };
                     files.push(display_path);
                 }
             }
         }
+        query.sort(&mut files);
+        // 添加返回上一级目录的链接, 总是在最前面
+        if req_path != "." {
+            let parent_path = std::path::Path::new(&req_path)
+                .parent()
+                .map_or(".".to_string(), |p| p.to_str().unwrap_or(".").to_string());
+            files.insert(
+                0,
+                FileInfo::new("../".to_string(), "/".to_owned() + &parent_path, None),
+            );
+        }
         Html(
             DirectoryTemplate {
                 path: req_path,
@@ -110,17 +302,13 @@ async fn file_handler(
         )
         .into_response()
     } else if full_path.exists() {
-        match fs::read_to_string(full_path).await {
-            Ok(content) => {
-                info!("Read {} bytes", content.len());
-                Html(content).into_response()
-            }
-            Err(e) => {
-                warn!("Error reading file: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-            }
-        }
+        file_response(&full_path, &headers).await
     } else {
+        // 单页应用的路由由前端处理, 找不到的路径都返回 index.html
+        let index = state.path.join("index.html");
+        if state.spa && index.is_file() {
+            return file_response(&index, &headers).await;
+        }
         (
             StatusCode::NOT_FOUND,
             format!("File {} not found", req_path),
@@ -129,6 +317,51 @@ async fn file_handler(
     }
 }
 
+async fn file_response(full_path: &std::path::Path, headers: &HeaderMap) -> Response {
+    // 目录列表不带 ETag，只对文件计算
+    let etag = fs::metadata(full_path)
+        .await
+        .ok()
+        .and_then(|meta| file_etag(&meta));
+    if let Some(etag) = &etag {
+        if etag_matches(headers, etag) {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.clone())]).into_response();
+        }
+    }
+    match fs::read_to_string(full_path).await {
+        Ok(content) => {
+            info!("Read {} bytes", content.len());
+            let mut res = Html(content).into_response();
+            if let Some(value) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+                res.headers_mut().insert(header::ETAG, value);
+            }
+            res
+        }
+        Err(e) => {
+            warn!("Error reading file: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+/// weak ETag built from file size and modified time, so we don't need to hash the content
+fn file_etag(meta: &std::fs::Metadata) -> Option<String> {
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("W/\"{:x}-{:x}\"", meta.len(), modified.as_nanos()))
+}
+
+/// check `If-None-Match` against the current ETag, using weak comparison
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let current = etag.trim_start_matches("W/");
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim())
+        .any(|v| v == "*" || v.trim_start_matches("W/") == current)
+}
+
 #[cfg(test)]
 mod tests {
     use axum::body::Body;
@@ -143,6 +376,7 @@ mod tests {
     async fn test_file_handler() {
         let state = Arc::new(HttpServeState {
             path: PathBuf::from("."),
+            spa: false,
         });
         let app = Router::new()
             .route("/*path", get(file_handler))
@@ -165,4 +399,239 @@ mod tests {
         println!("body: {}", body);
         assert!(body.contains("[package]"));
     }
+
+    #[tokio::test]
+    async fn test_file_handler_etag() {
+        let state = Arc::new(HttpServeState {
+            path: PathBuf::from("."),
+            spa: false,
+        });
+        let app = Router::new()
+            .route("/*path", get(file_handler))
+            .with_state(state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/Cargo.toml")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .expect("ETag should be set")
+            .clone();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/Cargo.toml")
+                    .header(header::IF_NONE_MATCH, etag)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let body = response.collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let app = build_router(
+            PathBuf::from("."),
+            Some(BasicAuth::new("user", "pass")),
+            false,
+            DEFAULT_MAX_BODY_SIZE,
+        );
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/Cargo.toml")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            "Basic realm=\"rcli\""
+        );
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/Cargo.toml")
+                    .header(
+                        header::AUTHORIZATION,
+                        format!("Basic {}", STANDARD.encode("user:pass")),
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/Cargo.toml")
+                    .header(
+                        header::AUTHORIZATION,
+                        format!("Basic {}", STANDARD.encode("user:wrong")),
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_directory_has_no_etag() {
+        let state = Arc::new(HttpServeState {
+            path: PathBuf::from("."),
+            spa: false,
+        });
+        let app = Router::new()
+            .route("/*path", get(file_handler))
+            .with_state(state.clone());
+
+        let response = app
+            .oneshot(Request::builder().uri("/src").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_spa_fallback() {
+        let dir = std::env::temp_dir().join(format!("rcli_test_spa_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "<div id=\"app\"></div>").unwrap();
+        std::fs::write(dir.join("app.js"), "console.log(1)").unwrap();
+
+        let get = |app: Router, uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            async move {
+                let response = app.oneshot(req).await.unwrap();
+                let status = response.status();
+                let body = response.collect().await.unwrap().to_bytes();
+                (status, String::from_utf8_lossy(&body).to_string())
+            }
+        };
+
+        let app = build_router(dir.clone(), None, true, DEFAULT_MAX_BODY_SIZE);
+        let (status, body) = get(app.clone(), "/app/route").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "<div id=\"app\"></div>");
+        let (status, body) = get(app, "/app.js").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "console.log(1)");
+
+        let app = build_router(dir.clone(), None, false, DEFAULT_MAX_BODY_SIZE);
+        let (status, _) = get(app, "/app/route").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let app = build_router(PathBuf::from("."), None, false, 16);
+        let upload = |size: usize| {
+            Request::builder()
+                .method("POST")
+                .uri("/Cargo.toml")
+                .header(header::CONTENT_LENGTH, size)
+                .body(Body::from(vec![b'x'; size]))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(upload(17)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let response = app.oneshot(upload(16)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_directory_listing_has_size() {
+        let state = Arc::new(HttpServeState {
+            path: PathBuf::from("."),
+            spa: false,
+        });
+        let app = Router::new()
+            .route("/", get(file_index_handler))
+            .with_state(state);
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.collect().await.unwrap().to_bytes();
+        let body = String::from_utf8_lossy(&body);
+
+        let size = human_size(std::fs::metadata("Cargo.toml").unwrap().len());
+        assert!(body.contains(&format!("<td>{}</td>", size)), "{}", body);
+        assert!(body.contains("<td>-</td>"));
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1024), "1.0 KiB");
+        assert_eq!(human_size(1258291), "1.2 MiB");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[tokio::test]
+    async fn test_directory_listing_sort() {
+        let dir = std::env::temp_dir().join(format!("rcli_test_sort_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "aaa").unwrap();
+        std::fs::write(dir.join("b.txt"), "bbbbbbbbbb").unwrap();
+        std::fs::write(dir.join("c.txt"), "c").unwrap();
+
+        let app = build_router(dir.clone(), None, false, DEFAULT_MAX_BODY_SIZE);
+        let listing = |uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(req).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.collect().await.unwrap().to_bytes();
+                let body = String::from_utf8_lossy(&body).to_string();
+                let mut names = ["sub/", "a.txt", "b.txt", "c.txt"];
+                names.sort_by_key(|name| body.find(&format!(">{}</a>", name)).unwrap());
+                names
+            }
+        };
+
+        assert_eq!(
+            listing("/?sort=size&order=desc").await,
+            ["sub/", "b.txt", "a.txt", "c.txt"]
+        );
+        assert_eq!(listing("/").await, ["sub/", "a.txt", "b.txt", "c.txt"]);
+        assert_eq!(
+            listing("/?order=desc").await,
+            ["sub/", "c.txt", "b.txt", "a.txt"]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
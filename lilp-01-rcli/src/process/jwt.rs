@@ -1,11 +1,11 @@
 use std::io::Read;
 
-use crate::JwtSignOpts;
+use crate::{error::Result, JwtSignOpts};
 
 pub fn process_gen_jwt_token(
     claims: &JwtSignOpts,
     secret_reader: &mut Box<dyn Read>,
-) -> anyhow::Result<String> {
+) -> Result<String> {
     let mut secret_buf = Vec::new();
     secret_reader.read_to_end(&mut secret_buf)?;
     let key = jsonwebtoken::EncodingKey::from_secret(&secret_buf);
@@ -16,7 +16,7 @@ pub fn process_gen_jwt_token(
 pub fn process_verify_jwt_token(
     secret_reader: &mut Box<dyn Read>,
     token_reader: &mut Box<dyn Read>,
-) -> anyhow::Result<JwtSignOpts> {
+) -> Result<JwtSignOpts> {
     let mut secret_buf = Vec::new();
     secret_reader.read_to_end(&mut secret_buf)?;
     let key = jsonwebtoken::DecodingKey::from_secret(secret_buf.as_ref());
@@ -28,7 +28,6 @@ pub fn process_verify_jwt_token(
     validation.validate_aud = false;
     println!("validation: {:?}", validation);
 
-    let token_data = jsonwebtoken::decode::<JwtSignOpts>(token, &key, &validation)
-        .map_err(|e| anyhow::anyhow!("jwt token invalid! {e}"))?;
+    let token_data = jsonwebtoken::decode::<JwtSignOpts>(token, &key, &validation)?;
     Ok(token_data.claims)
 }
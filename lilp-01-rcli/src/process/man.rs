@@ -0,0 +1,49 @@
+use std::{io::Write, path::Path};
+
+use clap::CommandFactory;
+use clap_mangen::Man;
+
+use crate::error::Result;
+use crate::Opts;
+
+/// write the top-level man page of rcli in roff format
+pub fn process_man(writer: &mut impl Write) -> Result<()> {
+    Man::new(Opts::command()).render(writer)?;
+    Ok(())
+}
+
+/// write rcli.1 and one page per subcommand (e.g. rcli-base64-encode.1) into `dir`
+pub fn process_man_pages(dir: &Path) -> Result<()> {
+    clap_mangen::generate_to(Opts::command(), dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_man_page() -> Result<()> {
+        let mut buf = Vec::new();
+        process_man(&mut buf)?;
+        let page = String::from_utf8(buf)?;
+        assert!(page.contains(".TH rcli"));
+        for name in ["csv", "genpass", "base64", "text", "http", "jwt", "man"] {
+            assert!(page.contains(name), "missing subcommand {}", name);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_man_pages_per_subcommand() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli_test_man_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        process_man_pages(&dir)?;
+        for page in ["rcli.1", "rcli-csv.1", "rcli-base64-encode.1"] {
+            let content = std::fs::read_to_string(dir.join(page))?;
+            assert!(content.contains(".TH"), "{} has no .TH header", page);
+        }
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
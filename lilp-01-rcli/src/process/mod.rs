@@ -1,16 +1,23 @@
 mod b64;
+mod completions;
 mod csv_convert;
 mod gen_pass;
+mod http_get;
 mod http_serve;
 mod jwt;
+mod man;
 mod text;
 
 pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
-pub use http_serve::process_http_serve;
+pub use completions::process_completions;
+pub use csv_convert::{convert_csv, process_csv, process_csv_ndjson, validate_rows, RowSchema};
+pub use gen_pass::{process_genpass, process_genpass_with_rng};
+pub use http_get::{process_http_get, HttpGetResponse};
+pub use http_serve::{process_http_serve, BasicAuth, DEFAULT_MAX_BODY_SIZE};
 pub use jwt::{process_gen_jwt_token, process_verify_jwt_token};
+pub use man::{process_man, process_man_pages};
 pub use text::{
-    process_text_decrypt, process_text_encrypt, process_text_key_generate, process_text_sign,
-    process_text_verify,
+    derive_key, process_text_decrypt, process_text_decrypt_with_passphrase, process_text_encrypt,
+    process_text_encrypt_with_passphrase, process_text_key_generate, process_text_sign,
+    process_text_verify, process_text_verify_any,
 };
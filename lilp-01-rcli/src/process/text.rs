@@ -1,12 +1,16 @@
-use crate::{process_genpass, TextSignFormat};
-use anyhow::Result;
+use crate::{error::Result, process_genpass_with_rng, RcliError, TextSignFormat};
+use argon2::Argon2;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
     ChaCha20Poly1305, Key, Nonce,
 };
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hmac::{Hmac, Mac};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::Sha256;
 use std::{collections::HashMap, io::Read};
 
 pub trait TextSigner {
@@ -31,6 +35,10 @@ pub struct Ed25519Verifier {
     key: VerifyingKey,
 }
 
+pub struct HmacSha256 {
+    key: Vec<u8>,
+}
+
 impl TextSigner for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
@@ -62,26 +70,45 @@ impl TextVerifier for Ed25519Verifier {
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
-        let sig = (&sig[..64]).try_into()?;
-        let signature = Signature::from_bytes(sig);
+        let signature = Signature::from_slice(sig)?;
         Ok(self.key.verify(&buf, &signature).is_ok())
     }
 }
 
+impl TextSigner for HmacSha256 {
+    fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let mut mac = self.mac();
+        mac.update(&buf);
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+}
+
+impl TextVerifier for HmacSha256 {
+    fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let mut mac = self.mac();
+        mac.update(&buf);
+        // verify_slice compares in constant time
+        Ok(mac.verify_slice(sig).is_ok())
+    }
+}
+
 impl Blake3 {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        // convert &[u8] to &[u8; 32]
-        let key = (&key[..32]).try_into()?;
-        Ok(Self::new(key))
+        // convert &[u8] to [u8; 32]
+        let key = key_bytes(key.as_ref())?;
+        Ok(Self::new(*key))
     }
 
     pub fn new(key: [u8; 32]) -> Self {
         Self { key }
     }
 
-    fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+    fn generate(rng: &mut (impl CryptoRng + RngCore)) -> Result<HashMap<&'static str, Vec<u8>>> {
+        let key = process_genpass_with_rng(rng, 32, true, true, true, true)?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
         Ok(map)
@@ -90,8 +117,7 @@ impl Blake3 {
 
 impl Ed25519Signer {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
+        let key = key_bytes(key.as_ref())?;
         Ok(Self::new(key))
     }
 
@@ -100,9 +126,8 @@ impl Ed25519Signer {
         Self { key }
     }
 
-    fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let mut csprng = OsRng;
-        let sk: SigningKey = SigningKey::generate(&mut csprng);
+    fn generate(rng: &mut (impl CryptoRng + RngCore)) -> Result<HashMap<&'static str, Vec<u8>>> {
+        let sk: SigningKey = SigningKey::generate(rng);
         let pk: VerifyingKey = (&sk).into();
         let mut map = HashMap::new();
         map.insert("ed25519.sk", sk.to_bytes().to_vec());
@@ -112,15 +137,41 @@ impl Ed25519Signer {
     }
 }
 
+impl HmacSha256 {
+    /// the key file is the raw secret bytes, hmac accepts keys of any length
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("hmac accepts keys of any length")
+    }
+
+    fn generate(rng: &mut (impl CryptoRng + RngCore)) -> Result<HashMap<&'static str, Vec<u8>>> {
+        let key = process_genpass_with_rng(rng, 32, true, true, true, true)?;
+        let mut map = HashMap::new();
+        map.insert("hmac-sha256.txt", key.as_bytes().to_vec());
+        Ok(map)
+    }
+}
+
 impl Ed25519Verifier {
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
+        let key = key_bytes(key.as_ref())?;
         let key = VerifyingKey::from_bytes(key)?;
         Ok(Self { key })
     }
 }
 
+/// take the first 32 bytes of the key, shorter keys are rejected
+fn key_bytes(key: &[u8]) -> Result<&[u8; 32]> {
+    key.get(..32)
+        .and_then(|k| k.try_into().ok())
+        .ok_or_else(|| {
+            RcliError::Crypto(format!("key must be at least 32 bytes, got {}", key.len()))
+        })
+}
+
 pub fn process_text_sign(
     reader: &mut dyn Read,
     key: &[u8], // (ptr, length)
@@ -129,6 +180,7 @@ pub fn process_text_sign(
     let signer: Box<dyn TextSigner> = match format {
         TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
         TextSignFormat::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
+        TextSignFormat::HmacSha256 => Box::new(HmacSha256::new(key)),
     };
 
     signer.sign(reader)
@@ -143,27 +195,118 @@ pub fn process_text_verify(
     let verifier: Box<dyn TextVerifier> = match format {
         TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
         TextSignFormat::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
+        TextSignFormat::HmacSha256 => Box::new(HmacSha256::new(key)),
     };
     verifier.verify(reader, sig)
 }
 
-pub fn process_text_key_generate(format: TextSignFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
+/// verify the signature against each key in turn, returns the index of the first key it verifies under.
+/// the input is read once, so it works with stdin too
+pub fn process_text_verify_any(
+    reader: &mut dyn Read,
+    keys: &[Vec<u8>],
+    sig: &[u8],
+    format: TextSignFormat,
+) -> Result<Option<usize>> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    for (i, key) in keys.iter().enumerate() {
+        if process_text_verify(&mut buf.as_slice(), key, sig, format)? {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+/// generate a key with the os rng. a `seed` makes the key reproducible, which is only meant
+/// for tests: anyone knowing the seed knows the key
+pub fn process_text_key_generate(
+    format: TextSignFormat,
+    seed: Option<&[u8; 32]>,
+) -> Result<HashMap<&'static str, Vec<u8>>> {
+    match seed {
+        Some(seed) => generate_key(format, &mut ChaCha20Rng::from_seed(*seed)),
+        None => generate_key(format, &mut OsRng),
+    }
+}
+
+fn generate_key(
+    format: TextSignFormat,
+    rng: &mut (impl CryptoRng + RngCore),
+) -> Result<HashMap<&'static str, Vec<u8>>> {
     match format {
-        TextSignFormat::Blake3 => Blake3::generate(),
-        TextSignFormat::Ed25519 => Ed25519Signer::generate(),
+        TextSignFormat::Blake3 => Blake3::generate(rng),
+        TextSignFormat::Ed25519 => Ed25519Signer::generate(rng),
+        TextSignFormat::HmacSha256 => HmacSha256::generate(rng),
     }
 }
 
-pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>> {
-    let key = Key::from_slice(&key);
-    let cipher = ChaCha20Poly1305::new(key);
-    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng); // 96-bits; unique per message
-                                                              // 获取reader的内容
+/// length of the random salt stored in front of passphrase encrypted output
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// encrypt the input, the ciphertext is bound to `aad` (empty for none) which must be given again to decrypt
+pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    // 获取reader的内容
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    encrypt(&buf, key, aad)
+}
+
+/// like `process_text_encrypt`, but the key is derived from `passphrase` with a random salt,
+/// the salt is stored in front of the nonce
+pub fn process_text_encrypt_with_passphrase(
+    reader: &mut dyn Read,
+    passphrase: &str,
+    aad: &[u8],
+) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let ciphertext = cipher
-        .encrypt(&nonce, &*buf)
-        .map_err(|e| anyhow::anyhow!("Encryption error: {:?}", e))?;
+    let salt: [u8; SALT_LEN] = rand::random();
+    let key = derive_key(passphrase, &salt)?;
+
+    let mut result = salt.to_vec();
+    result.extend(encrypt(&buf, &key, aad)?);
+    Ok(result)
+}
+
+pub fn process_text_decrypt(reader: &mut dyn Read, key: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let buf = read_ciphertext(reader)?;
+    decrypt(&buf, key, aad)
+}
+
+pub fn process_text_decrypt_with_passphrase(
+    reader: &mut dyn Read,
+    passphrase: &str,
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    let buf = read_ciphertext(reader)?;
+    if buf.len() < SALT_LEN {
+        return Err(RcliError::Crypto("ciphertext is too short".to_string()));
+    }
+    let (salt, buf) = buf.split_at(SALT_LEN);
+    let key = derive_key(passphrase, salt)?;
+    decrypt(buf, &key, aad)
+}
+
+/// derive a chacha20poly1305 key from the passphrase with argon2id
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key)?;
+    Ok(key)
+}
+
+fn encrypt(plaintext: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let key = Key::from_slice(key_bytes(key)?);
+    let cipher = ChaCha20Poly1305::new(key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng); // 96-bits; unique per message
+    let ciphertext = cipher.encrypt(
+        &nonce,
+        Payload {
+            msg: plaintext,
+            aad,
+        },
+    )?;
 
     // 创建一个新的 Vec 来存储 nonce 和 ciphertext
     let mut result = Vec::new();
@@ -173,20 +316,29 @@ pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>
     Ok(result)
 }
 
-pub fn process_text_decrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>> {
-    let key = Key::from_slice(&key);
-    let cipher = ChaCha20Poly1305::new(key);
-    // 读取reader的内容
+/// 读取reader的内容，并进行base64解码
+fn read_ciphertext(reader: &mut dyn Read) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let buf = URL_SAFE_NO_PAD.decode(buf)?;
+    Ok(URL_SAFE_NO_PAD.decode(buf)?)
+}
+
+fn decrypt(buf: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let key = Key::from_slice(key_bytes(key)?);
+    let cipher = ChaCha20Poly1305::new(key);
+    if buf.len() < NONCE_LEN {
+        return Err(RcliError::Crypto("ciphertext is too short".to_string()));
+    }
     // 从buf中获取nonce和ciphertext
-    let nonce = &buf[..12];
-    let nonce = Nonce::from_slice(nonce);
-    let ciphertext = &buf[12..];
-    let plaintext = cipher
-        .decrypt(&nonce, &*ciphertext)
-        .map_err(|e| anyhow::anyhow!("Decryption error: {:?}", e))?;
+    let nonce = Nonce::from_slice(&buf[..NONCE_LEN]);
+    let ciphertext = &buf[NONCE_LEN..];
+    let plaintext = cipher.decrypt(
+        nonce,
+        Payload {
+            msg: ciphertext,
+            aad,
+        },
+    )?;
     Ok(plaintext)
 }
 
@@ -208,6 +360,113 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_process_text_hmac_sha256() -> Result<()> {
+        let format = TextSignFormat::HmacSha256;
+        let sig = process_text_sign(&mut "hello".as_bytes(), KEY, format)?;
+        assert_eq!(sig.len(), 32);
+        assert!(process_text_verify(
+            &mut "hello".as_bytes(),
+            KEY,
+            &sig,
+            format
+        )?);
+        assert!(!process_text_verify(
+            &mut "hellO".as_bytes(),
+            KEY,
+            &sig,
+            format
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hmac_sha256_known_answer() -> Result<()> {
+        // RFC 4231 test case 2
+        let sig = HmacSha256::new("Jefe").sign(&mut "what do ya want for nothing?".as_bytes())?;
+        let hex: String = sig.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_encrypt_with_aad() -> Result<()> {
+        let key = include_bytes!("../../fixtures/crypt-key.txt");
+        let ciphertext = process_text_encrypt(&mut "hello".as_bytes(), key, b"a.txt")?;
+        let encoded = URL_SAFE_NO_PAD.encode(ciphertext);
+
+        let plaintext = process_text_decrypt(&mut encoded.as_bytes(), key, b"a.txt")?;
+        assert_eq!(plaintext, b"hello");
+        let err = process_text_decrypt(&mut encoded.as_bytes(), key, b"b.txt").unwrap_err();
+        assert!(matches!(err, RcliError::Crypto(_)));
+        let err = process_text_decrypt(&mut encoded.as_bytes(), key, b"").unwrap_err();
+        assert!(matches!(err, RcliError::Crypto(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_encrypt_with_passphrase() -> Result<()> {
+        let ciphertext =
+            process_text_encrypt_with_passphrase(&mut "hello".as_bytes(), "s3cret", b"")?;
+        let encoded = URL_SAFE_NO_PAD.encode(ciphertext);
+
+        let plaintext =
+            process_text_decrypt_with_passphrase(&mut encoded.as_bytes(), "s3cret", b"")?;
+        assert_eq!(plaintext, b"hello");
+        let err = process_text_decrypt_with_passphrase(&mut encoded.as_bytes(), "wrong", b"")
+            .unwrap_err();
+        assert!(matches!(err, RcliError::Crypto(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_derive_key_should_depend_on_salt() -> Result<()> {
+        let key = derive_key("s3cret", b"salt-salt-salt-1")?;
+        assert_eq!(key, derive_key("s3cret", b"salt-salt-salt-1")?);
+        assert_ne!(key, derive_key("s3cret", b"salt-salt-salt-2")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_generate_with_seed_should_be_reproducible() -> Result<()> {
+        let seed = [7u8; 32];
+        for format in [
+            TextSignFormat::Blake3,
+            TextSignFormat::Ed25519,
+            TextSignFormat::HmacSha256,
+        ] {
+            let key = process_text_key_generate(format, Some(&seed))?;
+            assert_eq!(key, process_text_key_generate(format, Some(&seed))?);
+            assert_ne!(key, process_text_key_generate(format, Some(&[8u8; 32]))?);
+            assert_ne!(key, process_text_key_generate(format, None)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_verify_any() -> Result<()> {
+        let msg = b"hello";
+        for (format, sk, pk) in [
+            (TextSignFormat::Blake3, "blake3.txt", "blake3.txt"),
+            (TextSignFormat::Ed25519, "ed25519.sk", "ed25519.pk"),
+        ] {
+            let keys = (1..=3)
+                .map(|i| process_text_key_generate(format, Some(&[i; 32])))
+                .collect::<Result<Vec<_>>>()?;
+            let sig = process_text_sign(&mut msg.as_slice(), &keys[1][sk], format)?;
+            let candidates: Vec<_> = keys.iter().map(|key| key[pk].clone()).collect();
+
+            let ret = process_text_verify_any(&mut msg.as_slice(), &candidates, &sig, format)?;
+            assert_eq!(ret, Some(1));
+            let ret = process_text_verify_any(&mut msg.as_slice(), &candidates[2..], &sig, format)?;
+            assert_eq!(ret, None);
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_process_text_verify() -> Result<()> {
         let mut reader = "hello".as_bytes();
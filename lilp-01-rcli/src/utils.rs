@@ -1,5 +1,11 @@
-use anyhow::Result;
-use std::{fs::File, io::Read};
+use crate::{error::Result, LogFormat};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    fs::{self, File},
+    io::Read,
+};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -10,9 +16,110 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     Ok(reader)
 }
 
+/// read the inputs one after another as if they were a single file
+pub fn get_multi_reader(inputs: &[String]) -> Result<Box<dyn Read>> {
+    let mut reader: Box<dyn Read> = Box::new(std::io::empty());
+    for input in inputs {
+        reader = Box::new(reader.chain(get_reader(input)?));
+    }
+    Ok(reader)
+}
+
 pub fn get_content(input: &str) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
     Ok(buf)
 }
+
+/// a progress bar on stderr for reading `inputs`, sized by the files, a spinner if one of them is stdin.
+/// indicatif hides it when stderr is not a terminal, stdout is never touched
+pub fn progress_bar(inputs: &[String]) -> ProgressBar {
+    let total: Option<u64> = inputs
+        .iter()
+        .map(|input| match input.as_str() {
+            "-" => None,
+            path => fs::metadata(path).ok().map(|m| m.len()),
+        })
+        .sum();
+    match total {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({eta})")
+                .expect("valid progress template"),
+        ),
+        None => ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner} {bytes} read")
+                .expect("valid progress template"),
+        ),
+    }
+}
+
+/// the subscriber installed by main, logs at INFO and above go to `writer` in `format`
+pub fn log_subscriber<W>(format: LogFormat, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(Level::INFO)
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn progress_bar_should_be_sized_by_files() {
+        let inputs = vec!["Cargo.toml".to_string(), "Cargo.toml".to_string()];
+        let len = fs::metadata("Cargo.toml").unwrap().len();
+        assert_eq!(progress_bar(&inputs).length(), Some(len * 2));
+        // stdin has no known size
+        assert_eq!(progress_bar(&["-".to_string()]).length(), None);
+    }
+
+    #[test]
+    fn progress_reader_should_not_change_content() -> Result<()> {
+        let bar = progress_bar(&["Cargo.toml".to_string()]);
+        let mut buf = Vec::new();
+        bar.wrap_read(get_reader("Cargo.toml")?)
+            .read_to_end(&mut buf)?;
+        assert_eq!(buf, fs::read("Cargo.toml")?);
+        assert_eq!(bar.position(), buf.len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn json_log_format_should_emit_json_lines() {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let writer = {
+            let buf = buf.clone();
+            move || SharedBuf(buf.clone())
+        };
+        let subscriber = log_subscriber(LogFormat::Json, writer);
+        tracing::subscriber::with_default(subscriber, || tracing::info!(port = 8080, "serving"));
+
+        let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["fields"]["message"], "serving");
+        assert_eq!(line["fields"]["port"], 8080);
+        assert!(line["timestamp"].is_string());
+    }
+
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(data)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use simple_redis::{Backend, BulkString, RespArray, RespFrame};
+
+// a mixed batch of set / get / sadd / sismember commands
+fn gen_frames(n: usize) -> Vec<RespFrame> {
+    (0..n)
+        .flat_map(|i| {
+            let key = format!("key{}", i);
+            [
+                cmd(&["set", &key, "value"]),
+                cmd(&["get", &key]),
+                cmd(&["sadd", "myset", &key]),
+                cmd(&["sismember", "myset", &key]),
+            ]
+        })
+        .collect()
+}
+
+fn cmd(args: &[&str]) -> RespFrame {
+    let args: Vec<RespFrame> = args
+        .iter()
+        .map(|arg| BulkString::new(arg.as_bytes()).into())
+        .collect();
+    RespArray::new(args).into()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let frames = gen_frames(1000);
+    c.bench_function("execute_batch", |b| {
+        b.iter(|| {
+            let backend = Backend::new();
+            backend.execute_batch(black_box(frames.clone()))
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
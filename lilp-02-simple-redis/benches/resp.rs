@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use simple_redis::{BulkString, RespArray, RespEncode, RespFrame};
+
+// a batch of set commands as they would be written to the wire
+fn gen_frames(n: usize) -> Vec<RespFrame> {
+    (0..n)
+        .map(|i| {
+            let args: Vec<RespFrame> = ["set".to_string(), format!("key{}", i), "value".into()]
+                .into_iter()
+                .map(|arg| BulkString::new(arg).into())
+                .collect();
+            RespArray::new(args).into()
+        })
+        .collect()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let frames = gen_frames(1000);
+    c.bench_function("encode", |b| {
+        b.iter(|| {
+            for frame in &frames {
+                black_box(frame.clone().encode());
+            }
+        })
+    });
+    c.bench_function("encode_into", |b| {
+        let mut buf = Vec::with_capacity(4096);
+        b.iter(|| {
+            for frame in &frames {
+                buf.clear();
+                frame.encode_into(&mut buf);
+                black_box(&buf);
+            }
+        })
+    });
+}
+
+fn integer_benchmark(c: &mut Criterion) {
+    let counters: Vec<i64> = (-500..500).map(|i| i * 7919).collect();
+    c.bench_function("encode_integer_format", |b| {
+        b.iter(|| {
+            for n in &counters {
+                black_box(format!(":{}\r\n", n).into_bytes());
+            }
+        })
+    });
+    c.bench_function("encode_integer", |b| {
+        b.iter(|| {
+            for n in &counters {
+                black_box(n.encode());
+            }
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark, integer_benchmark);
+criterion_main!(benches);
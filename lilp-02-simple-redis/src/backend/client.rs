@@ -0,0 +1,83 @@
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use super::SessionState;
+
+/// the connections being served, so they could be listed and killed
+#[derive(Debug, Default)]
+pub(super) struct ClientRegistry {
+    clients: DashMap<u64, ClientHandle>,
+}
+
+#[derive(Debug)]
+struct ClientHandle {
+    addr: Option<SocketAddr>,
+    connected_at: Instant,
+    session: Arc<SessionState>,
+    shutdown: Arc<Notify>,
+}
+
+/// a connected client as shown by CLIENT LIST
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: Option<SocketAddr>,
+    pub name: Option<String>,
+    pub age: Duration,
+}
+
+impl ClientRegistry {
+    pub(super) fn register(
+        &self,
+        id: u64,
+        addr: Option<SocketAddr>,
+        session: Arc<SessionState>,
+    ) -> Arc<Notify> {
+        let shutdown = Arc::new(Notify::new());
+        self.clients.insert(
+            id,
+            ClientHandle {
+                addr,
+                connected_at: Instant::now(),
+                session,
+                shutdown: shutdown.clone(),
+            },
+        );
+        shutdown
+    }
+
+    pub(super) fn unregister(&self, id: u64) {
+        self.clients.remove(&id);
+    }
+
+    /// ordered by id, i.e. by connection time
+    pub(super) fn list(&self) -> Vec<ClientInfo> {
+        let mut clients: Vec<ClientInfo> = self
+            .clients
+            .iter()
+            .map(|entry| ClientInfo {
+                id: *entry.key(),
+                addr: entry.addr,
+                name: entry.session.name.lock().unwrap().clone(),
+                age: entry.connected_at.elapsed(),
+            })
+            .collect();
+        clients.sort_by_key(|client| client.id);
+        clients
+    }
+
+    /// ask the connection to close, it goes away once its current command is answered
+    pub(super) fn kill(&self, id: u64) -> bool {
+        match self.clients.get(&id) {
+            // notify_one keeps the permit, even if the connection isn't waiting right now
+            Some(client) => {
+                client.shutdown.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+/// redis style glob matching: `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and `\` to escape
+pub(crate) fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    match pattern {
+        [] => string.is_empty(),
+        [b'*', rest @ ..] => {
+            // consecutive stars match the same as a single one
+            let rest = trim_stars(rest);
+            rest.is_empty() || (0..=string.len()).any(|i| glob_match(rest, &string[i..]))
+        }
+        [b'?', rest @ ..] => !string.is_empty() && glob_match(rest, &string[1..]),
+        [b'[', rest @ ..] => {
+            let Some((&c, string_rest)) = string.split_first() else {
+                return false;
+            };
+            match match_class(rest, c) {
+                Some((matched, rest)) => matched && glob_match(rest, string_rest),
+                // an unclosed `[` is matched literally
+                None => c == b'[' && glob_match(rest, string_rest),
+            }
+        }
+        [b'\\', escaped, rest @ ..] => {
+            string.first() == Some(escaped) && glob_match(rest, &string[1..])
+        }
+        [c, rest @ ..] => string.first() == Some(c) && glob_match(rest, &string[1..]),
+    }
+}
+
+fn trim_stars(mut pattern: &[u8]) -> &[u8] {
+    while let [b'*', rest @ ..] = pattern {
+        pattern = rest;
+    }
+    pattern
+}
+
+// the class after a `[`, returns whether `c` is in it and the pattern after the `]`
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, &[u8])> {
+    let (negate, mut pattern) = match pattern {
+        [b'^', rest @ ..] => (true, rest),
+        _ => (false, pattern),
+    };
+    let mut matched = false;
+    loop {
+        match pattern {
+            [] => return None,
+            [b']', rest @ ..] => return Some((matched != negate, rest)),
+            [b'\\', escaped, rest @ ..] => {
+                matched |= *escaped == c;
+                pattern = rest;
+            }
+            [start, b'-', end, rest @ ..] if *end != b']' => {
+                let (low, high) = if start <= end {
+                    (*start, *end)
+                } else {
+                    (*end, *start)
+                };
+                matched |= (low..=high).contains(&c);
+                pattern = rest;
+            }
+            [other, rest @ ..] => {
+                matched |= *other == c;
+                pattern = rest;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(matches("news.*", "news.tech"));
+        assert!(matches("news.*", "news."));
+        assert!(!matches("news.*", "sports"));
+        assert!(matches("*", ""));
+        assert!(matches("h?llo", "hello"));
+        assert!(!matches("h?llo", "hllo"));
+        assert!(matches("h**o", "hello"));
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-c]llo", "hbllo"));
+        assert!(matches("h[c-a]llo", "hbllo"));
+        assert!(!matches("h[a-c]llo", "hdllo"));
+        assert!(matches("h\\*llo", "h*llo"));
+        assert!(!matches("h\\*llo", "hello"));
+        assert!(matches("h[llo", "h[llo"));
+    }
+}
@@ -1,38 +1,239 @@
-use crate::{BulkString, RespFrame};
-use dashmap::{DashMap, DashSet};
+mod client;
+mod glob;
+mod pubsub;
+mod replication;
+mod slowlog;
+mod transaction;
+
+pub use client::ClientInfo;
+pub use pubsub::SubscriptionKind;
+pub use replication::PendingWrite;
+pub use slowlog::{
+    CommandTimer, SlowLog, SlowLogEntry, DEFAULT_SLOWLOG_MAX_LEN, DEFAULT_SLOWLOG_THRESHOLD,
+};
+pub use transaction::TransactionError;
+
+use crate::cmd::{command_name, exec_reply, Command, CommandError, CommandExecutor, CommandFilter};
+use crate::{BulkString, RespDecode, RespEncode, RespError, RespFrame, SimpleError, SimpleString};
+use bytes::{Bytes, BytesMut};
+use client::ClientRegistry;
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use futures::FutureExt;
+use pubsub::{PubSub, Subscriptions};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use replication::Replicas;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify};
+use tracing::{info, warn};
+use transaction::Transaction;
+
+pub const DEFAULT_DATABASES: usize = 16;
+/// RESP version used by a connection until it sends HELLO
+pub const DEFAULT_PROTOCOL_VERSION: u8 = 2;
+// string values of integers below this are shared objects in redis, see OBJECT REFCOUNT
+const SHARED_INTEGERS: i64 = 10000;
 
+/// a handle to the backend, each connection should own a session so SELECT only affects itself
 #[derive(Debug, Clone)]
-pub struct Backend(Arc<BackendInner>);
+pub struct Backend {
+    inner: Arc<BackendInner>,
+    // state of the connection, cleared by RESET
+    state: Arc<SessionState>,
+    // commands the server accepts, shared by all sessions
+    filter: Arc<CommandFilter>,
+    // password required by AUTH, shared by all sessions
+    requirepass: Option<Arc<String>>,
+    // slow commands of all sessions
+    slowlog: Arc<SlowLog>,
+    // id of the connection, reported by HELLO
+    client_id: u64,
+}
+
+/// per-connection state, everything a client can change about its own connection lives here
+#[derive(Debug)]
+struct SessionState {
+    // logical db selected by the connection
+    db: AtomicUsize,
+    // whether the connection has passed AUTH
+    authenticated: AtomicBool,
+    // RESP version negotiated by HELLO
+    protocol: AtomicU8,
+    // set by CLIENT SETNAME, kept by RESET like redis does
+    name: Mutex<Option<String>>,
+    // channels and patterns subscribed to, removed by RESET
+    subscriptions: Mutex<Subscriptions>,
+    // commands queued since MULTI, None outside of a transaction
+    transaction: Mutex<Option<Transaction>>,
+}
+
+// how DEBUG SLEEP waits, the network layer mustn't block the runtime
+#[derive(Debug, Clone, Copy)]
+enum SleepMode {
+    Blocking,
+    Timer,
+}
+
+impl SleepMode {
+    async fn sleep(self, duration: Duration) {
+        match self {
+            SleepMode::Blocking => std::thread::sleep(duration),
+            SleepMode::Timer => tokio::time::sleep(duration).await,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthError {
+    NoPasswordSet,
+    InvalidPassword,
+}
 
 #[derive(Debug)]
 pub struct BackendInner {
+    dbs: Vec<KeyspaceInner>,
+    // logical db index -> index in `dbs`, SWAPDB only swaps the mapping
+    db_mapping: Mutex<Vec<usize>>,
+    // used by SPOP / SRANDMEMBER, could be seeded for deterministic tests
+    rng: Mutex<StdRng>,
+    // runtime stats for INFO
+    connected_clients: AtomicUsize,
+    // connections served over the network, for CLIENT LIST / CLIENT KILL
+    clients: ClientRegistry,
+    started_at: Instant,
+    // the last id handed out to a session
+    last_client_id: AtomicU64,
+    // number of calls per command, unrecognized commands are not counted
+    command_stats: DashMap<&'static str, AtomicU64>,
+    // write commands are forwarded to them, empty unless the backend is a primary
+    replicas: Replicas,
+    // subscribers of PUBLISH, by channel and by pattern
+    pubsub: PubSub,
+}
+
+#[derive(Debug, Default)]
+pub struct KeyspaceInner {
     pub(crate) map: DashMap<String, RespFrame>,
+    // expire time of keys in `map`, expired keys are removed lazily on access
+    pub(crate) expires: DashMap<String, Instant>,
+    // last access time and number of accesses of each key, for OBJECT IDLETIME and OBJECT FREQ
+    pub(crate) access: DashMap<String, KeyAccess>,
     pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
     pub(crate) set: DashMap<String, DashSet<BulkString>>,
+    pub(crate) list: DashMap<String, VecDeque<BulkString>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct KeyAccess {
+    at: Instant,
+    count: u64,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct DbIndexOutOfRange;
+
+/// the largest |count| of SRANDMEMBER / HRANDFIELD, a negative count would otherwise build a
+/// reply of any size
+pub const MAX_RANDOM_COUNT: u64 = 1 << 20;
+
+/// |count| is above `MAX_RANDOM_COUNT`
+#[derive(Debug, PartialEq, Eq)]
+pub struct CountOutOfRange;
+
+/// the key holds a value of another type
+#[derive(Debug, PartialEq, Eq)]
+pub struct WrongType;
+
+/// NX / XX option of SET
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    /// NX, only set the key if it does not exist
+    NotExists,
+    /// XX, only set the key if it already exists
+    Exists,
+}
+
+/// NX / XX / GT / LT option of EXPIRE, a key without expire time counts as never expiring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireCondition {
+    /// NX, only if the key has no expire time
+    NotExists,
+    /// XX, only if the key has an expire time
+    Exists,
+    /// GT, only if the new expire time is later than the current one
+    Greater,
+    /// LT, only if the new expire time is earlier than the current one
+    Less,
+}
+
+/// the end of a list LPUSH / RPUSH and LMPOP work on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    /// LEFT, the head of the list
+    Left,
+    /// RIGHT, the tail of the list
+    Right,
+}
+
+// commands operate on the keyspace of the selected db
 impl Deref for Backend {
-    type Target = BackendInner;
+    type Target = KeyspaceInner;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        let index = self.state.db.load(Ordering::Relaxed);
+        let physical = self.inner.db_mapping.lock().unwrap()[index];
+        &self.inner.dbs[physical]
     }
 }
 
-impl Default for Backend {
+impl Default for SessionState {
     fn default() -> Self {
-        Self(Arc::new(BackendInner::default()))
+        Self {
+            db: AtomicUsize::new(0),
+            authenticated: AtomicBool::new(false),
+            protocol: AtomicU8::new(DEFAULT_PROTOCOL_VERSION),
+            name: Mutex::new(None),
+            subscriptions: Mutex::new(Subscriptions::default()),
+            transaction: Mutex::new(None),
+        }
     }
 }
 
-impl Default for BackendInner {
+impl SessionState {
+    // back to the state of a new connection
+    fn reset(&self) {
+        self.db.store(0, Ordering::Relaxed);
+        self.authenticated.store(false, Ordering::Relaxed);
+        self.protocol
+            .store(DEFAULT_PROTOCOL_VERSION, Ordering::Relaxed);
+        *self.transaction.lock().unwrap() = None;
+    }
+}
+
+impl Default for Backend {
     fn default() -> Self {
+        Self::from_inner(BackendInner::new(DEFAULT_DATABASES, StdRng::from_entropy()))
+    }
+}
+
+impl BackendInner {
+    fn new(databases: usize, rng: StdRng) -> Self {
+        let databases = databases.max(1);
         Self {
-            map: DashMap::new(),
-            hmap: DashMap::new(),
-            set: DashMap::new(),
+            dbs: (0..databases).map(|_| KeyspaceInner::default()).collect(),
+            db_mapping: Mutex::new((0..databases).collect()),
+            rng: Mutex::new(rng),
+            connected_clients: AtomicUsize::new(0),
+            clients: ClientRegistry::default(),
+            started_at: Instant::now(),
+            last_client_id: AtomicU64::new(0),
+            command_stats: DashMap::new(),
+            replicas: Replicas::default(),
+            pubsub: PubSub::default(),
         }
     }
 }
@@ -42,39 +243,737 @@ impl Backend {
         Self::default()
     }
 
+    pub fn with_databases(databases: usize) -> Self {
+        Self::from_inner(BackendInner::new(databases, StdRng::from_entropy()))
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_inner(BackendInner::new(
+            DEFAULT_DATABASES,
+            StdRng::seed_from_u64(seed),
+        ))
+    }
+
+    fn from_inner(inner: BackendInner) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            state: Arc::new(SessionState::default()),
+            filter: Arc::new(CommandFilter::default()),
+            requirepass: None,
+            slowlog: Arc::new(SlowLog::default()),
+            client_id: 0,
+        }
+    }
+
+    pub fn with_command_filter(self, filter: CommandFilter) -> Self {
+        Self {
+            filter: Arc::new(filter),
+            ..self
+        }
+    }
+
+    /// connections must AUTH with the password before running other commands
+    pub fn with_requirepass(self, password: impl Into<String>) -> Self {
+        Self {
+            requirepass: Some(Arc::new(password.into())),
+            ..self
+        }
+    }
+
+    /// log commands running longer than `threshold`, keeping the newest `max_len`, 0 disables it
+    pub fn with_slowlog(self, threshold: Duration, max_len: usize) -> Self {
+        Self {
+            slowlog: Arc::new(SlowLog::new(threshold, max_len)),
+            ..self
+        }
+    }
+
+    /// a new handle sharing the same data, with db 0 selected, not authenticated and using RESP2
+    pub fn session(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: Arc::new(SessionState::default()),
+            filter: self.filter.clone(),
+            requirepass: self.requirepass.clone(),
+            slowlog: self.slowlog.clone(),
+            client_id: self.inner.last_client_id.fetch_add(1, Ordering::Relaxed) + 1,
+        }
+    }
+
+    /// check the command filter and authentication, then decode the frame into a command
+    pub fn parse(&self, frame: RespFrame) -> Result<Command, CommandError> {
+        self.filter.check(&frame)?;
+        // HELLO may carry AUTH, it checks authentication itself
+        if !self.is_authenticated()
+            && !command_name(&frame).is_some_and(|name| {
+                ["auth", "hello", "reset"]
+                    .iter()
+                    .any(|allowed| name.eq_ignore_ascii_case(allowed))
+            })
+        {
+            return Err(CommandError::NoAuth);
+        }
+        let cmd = Command::try_from(frame)?;
+        if let Some(name) = cmd.name() {
+            self.record_command(name);
+        }
+        Ok(cmd)
+    }
+
+    // only the first call of a command takes the shard's write lock, later ones share the read lock
+    fn record_command(&self, name: &'static str) {
+        let stats = &self.inner.command_stats;
+        match stats.get(name) {
+            Some(count) => count.fetch_add(1, Ordering::Relaxed),
+            None => stats
+                .entry(name)
+                .or_default()
+                .fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// true if no password is required or the connection has passed AUTH
+    pub fn is_authenticated(&self) -> bool {
+        self.requirepass.is_none() || self.state.authenticated.load(Ordering::Relaxed)
+    }
+
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    pub fn client_name(&self) -> Option<String> {
+        self.state.name.lock().unwrap().clone()
+    }
+
+    /// None clears the name
+    pub fn set_client_name(&self, name: Option<String>) {
+        *self.state.name.lock().unwrap() = name;
+    }
+
+    pub fn protocol_version(&self) -> u8 {
+        self.state.protocol.load(Ordering::Relaxed)
+    }
+
+    pub fn set_protocol_version(&self, version: u8) {
+        self.state.protocol.store(version, Ordering::Relaxed);
+    }
+
+    /// RESET: select db 0, drop authentication, discard the transaction, unsubscribe from
+    /// everything and go back to RESP2, the client id is kept
+    pub fn reset_session(&self) {
+        self.state.reset();
+        self.unsubscribe_all();
+    }
+
+    pub fn auth(&self, password: &str) -> Result<(), AuthError> {
+        match &self.requirepass {
+            None => Err(AuthError::NoPasswordSet),
+            Some(expected) if expected.as_str() == password => {
+                self.state.authenticated.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            Some(_) => Err(AuthError::InvalidPassword),
+        }
+    }
+
+    /// decode the frame into a command and execute it, errors are returned as SimpleError
+    ///
+    /// DEBUG SLEEP blocks the calling thread, use `execute_async` inside the runtime
+    pub fn execute(&self, frame: RespFrame) -> RespFrame {
+        self.dispatch(frame, SleepMode::Blocking, false)
+            .now_or_never()
+            .expect("blocking dispatch never yields")
+    }
+
+    /// same as `execute`, but DEBUG SLEEP, on its own or inside EXEC, waits on the tokio timer
+    /// instead of blocking the runtime; `trace` logs every command parsed
+    pub async fn execute_async(&self, frame: RespFrame, trace: bool) -> RespFrame {
+        self.dispatch(frame, SleepMode::Timer, trace).await
+    }
+
+    async fn dispatch(&self, frame: RespFrame, mode: SleepMode, trace: bool) -> RespFrame {
+        if let Some(reply) = self.queue(&frame) {
+            return reply;
+        }
+        let timer = self.slowlog.timer(&frame);
+        let write = self.pending_write(&frame);
+        let cmd = self.parse(frame);
+        if trace {
+            match &cmd {
+                Ok(cmd) => info!("Command: {}", cmd),
+                Err(e) => info!("Invalid command: {}", e),
+            }
+        }
+        let ret = match cmd {
+            Ok(Command::Exec(_)) => exec_reply(self.exec_with(mode).await),
+            Ok(cmd) => self.run(cmd, mode).await,
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        };
+        self.replicate(write, &ret);
+        self.slowlog.finish(timer);
+        ret
+    }
+
+    // EXEC is handled by the caller, so this never recurses
+    async fn run(&self, cmd: Command, mode: SleepMode) -> RespFrame {
+        if let Command::Debug(debug) = &cmd {
+            mode.sleep(debug.sleep_duration()).await;
+        }
+        cmd.execute(self)
+    }
+
+    /// inside MULTI, queue the command for EXEC and return the reply to send, None if it should
+    /// run right away
+    ///
+    /// a command failing to queue, e.g. a parse error, aborts the transaction
+    pub fn queue(&self, frame: &RespFrame) -> Option<RespFrame> {
+        if transaction::is_control(frame) || self.state.transaction.lock().unwrap().is_none() {
+            return None;
+        }
+        let cmd = self.parse(frame.clone());
+        let mut transaction = self.state.transaction.lock().unwrap();
+        let transaction = transaction.as_mut()?;
+        let reply = match cmd {
+            Ok(cmd @ Command::UnknownCommand(_)) => {
+                transaction.aborted = true;
+                cmd.execute(self)
+            }
+            Ok(cmd) => {
+                transaction.queued.push((frame.clone(), cmd));
+                SimpleString::unchecked("QUEUED").into()
+            }
+            Err(e) => {
+                transaction.aborted = true;
+                SimpleError::new(e.to_string()).into()
+            }
+        };
+        Some(reply)
+    }
+
+    /// MULTI, queue the commands of the session until EXEC or DISCARD
+    pub fn multi(&self) -> Result<(), TransactionError> {
+        let mut transaction = self.state.transaction.lock().unwrap();
+        if transaction.is_some() {
+            return Err(TransactionError::Nested);
+        }
+        *transaction = Some(Transaction::default());
+        Ok(())
+    }
+
+    pub fn discard(&self) -> Result<(), TransactionError> {
+        match self.state.transaction.lock().unwrap().take() {
+            Some(_) => Ok(()),
+            None => Err(TransactionError::NotStarted),
+        }
+    }
+
+    /// run the queued commands in order and return their replies, a command failing doesn't
+    /// stop the others
+    pub fn exec(&self) -> Result<Vec<RespFrame>, TransactionError> {
+        self.exec_with(SleepMode::Blocking)
+            .now_or_never()
+            .expect("blocking exec never yields")
+    }
+
+    async fn exec_with(&self, mode: SleepMode) -> Result<Vec<RespFrame>, TransactionError> {
+        let transaction = self
+            .state
+            .transaction
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(TransactionError::NotStarted)?;
+        if transaction.aborted {
+            return Err(TransactionError::Aborted);
+        }
+        let mut replies = Vec::with_capacity(transaction.queued.len());
+        for (frame, cmd) in transaction.queued {
+            let write = self.pending_write(&frame);
+            let ret = self.run(cmd, mode).await;
+            self.replicate(write, &ret);
+            replies.push(ret);
+        }
+        Ok(replies)
+    }
+
+    /// forward the RESP bytes of every write command applied from now on to `replica`,
+    /// the replica is dropped once the receiver goes away
+    pub fn add_replica(&self, replica: mpsc::UnboundedSender<Bytes>) {
+        self.inner.replicas.add(replica);
+    }
+
+    /// None unless the frame is a write command and there're replicas to forward it to
+    pub fn pending_write(&self, frame: &RespFrame) -> Option<PendingWrite> {
+        self.inner.replicas.pending(frame, self.selected_db())
+    }
+
+    /// forward the write command to the replicas, unless it's been rejected
+    pub fn replicate(&self, write: Option<PendingWrite>, ret: &RespFrame) {
+        self.inner.replicas.forward(write, ret);
+    }
+
+    /// replay the stream of a primary until it's closed, in a session of its own so SELECT in
+    /// the stream doesn't affect other connections
+    ///
+    /// the commands are applied as is, bypassing the command filter and AUTH
+    pub async fn apply_replication_stream(
+        &self,
+        mut stream: mpsc::UnboundedReceiver<Bytes>,
+    ) -> Result<(), RespError> {
+        let session = self.session();
+        let mut buf = BytesMut::new();
+        while let Some(bytes) = stream.recv().await {
+            buf.extend_from_slice(&bytes);
+            loop {
+                let frame = match RespFrame::decode(&mut buf) {
+                    Ok(frame) => frame,
+                    Err(RespError::NotComplete) => break,
+                    Err(e) => return Err(e),
+                };
+                match Command::try_from(frame) {
+                    Ok(cmd) => {
+                        cmd.execute(&session);
+                    }
+                    Err(e) => warn!("Failed to replay replicated command: {}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// messages published to the channels and patterns of the session, None once it's been taken
+    pub fn pubsub_messages(&self) -> Option<mpsc::UnboundedReceiver<RespFrame>> {
+        self.state.subscriptions.lock().unwrap().take_receiver()
+    }
+
+    /// returns the number of channels and patterns the session is subscribed to afterwards
+    pub fn subscribe(&self, kind: SubscriptionKind, name: BulkString) -> usize {
+        let mut subscriptions = self.state.subscriptions.lock().unwrap();
+        if subscriptions.insert(kind, name.clone()) {
+            let sender = subscriptions.sender();
+            self.inner.pubsub.add(kind, name, self.client_id, sender);
+        }
+        subscriptions.count()
+    }
+
+    /// returns the number of channels and patterns the session is subscribed to afterwards
+    pub fn unsubscribe(&self, kind: SubscriptionKind, name: &BulkString) -> usize {
+        let mut subscriptions = self.state.subscriptions.lock().unwrap();
+        if subscriptions.remove(kind, name) {
+            self.inner.pubsub.remove(kind, name, self.client_id);
+        }
+        subscriptions.count()
+    }
+
+    /// channels or patterns of the session, in subscription order
+    pub fn subscriptions(&self, kind: SubscriptionKind) -> Vec<BulkString> {
+        self.state
+            .subscriptions
+            .lock()
+            .unwrap()
+            .names(kind)
+            .to_vec()
+    }
+
+    /// channels and patterns the session is subscribed to
+    pub fn subscription_count(&self) -> usize {
+        self.state.subscriptions.lock().unwrap().count()
+    }
+
+    pub fn unsubscribe_all(&self) {
+        for kind in [SubscriptionKind::Channel, SubscriptionKind::Pattern] {
+            for name in self.subscriptions(kind) {
+                self.unsubscribe(kind, &name);
+            }
+        }
+    }
+
+    /// send the message to the subscribers of the channel and of the patterns matching it,
+    /// returns the number of subscribers which got it
+    pub fn publish(&self, channel: &BulkString, message: &BulkString) -> usize {
+        self.inner.pubsub.publish(channel, message)
+    }
+
+    pub fn slowlog(&self) -> &SlowLog {
+        &self.slowlog
+    }
+
+    /// execute the frames in order, mainly for benchmarks which don't go through sockets
+    pub fn execute_batch(&self, frames: Vec<RespFrame>) -> Vec<RespFrame> {
+        frames
+            .into_iter()
+            .map(|frame| self.execute(frame))
+            .collect()
+    }
+
+    pub fn databases(&self) -> usize {
+        self.inner.dbs.len()
+    }
+
+    pub fn selected_db(&self) -> usize {
+        self.state.db.load(Ordering::Relaxed)
+    }
+
+    pub fn select(&self, index: usize) -> Result<(), DbIndexOutOfRange> {
+        if index >= self.databases() {
+            return Err(DbIndexOutOfRange);
+        }
+        self.state.db.store(index, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// swap two logical dbs, visible to all connections
+    pub fn swapdb(&self, a: usize, b: usize) -> Result<(), DbIndexOutOfRange> {
+        if a >= self.databases() || b >= self.databases() {
+            return Err(DbIndexOutOfRange);
+        }
+        self.inner.db_mapping.lock().unwrap().swap(a, b);
+        Ok(())
+    }
+
+    /// keyspaces in logical order
+    pub fn keyspaces(&self) -> Vec<&KeyspaceInner> {
+        let mapping = self.inner.db_mapping.lock().unwrap();
+        mapping.iter().map(|i| &self.inner.dbs[*i]).collect()
+    }
+
+    pub fn client_connected(&self) {
+        self.inner.connected_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn client_disconnected(&self) {
+        self.inner.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn connected_clients(&self) -> usize {
+        self.inner.connected_clients.load(Ordering::Relaxed)
+    }
+
+    /// list the connection of this session in CLIENT LIST, it should close once the handle is notified
+    pub fn register_client(&self, addr: Option<SocketAddr>) -> Arc<Notify> {
+        self.inner
+            .clients
+            .register(self.client_id, addr, self.state.clone())
+    }
+
+    pub fn unregister_client(&self) {
+        self.inner.clients.unregister(self.client_id);
+    }
+
+    pub fn clients(&self) -> Vec<ClientInfo> {
+        self.inner.clients.list()
+    }
+
+    /// CLIENT KILL, returns false if there's no such connection
+    pub fn kill_client(&self, id: u64) -> bool {
+        self.inner.clients.kill(id)
+    }
+
+    /// number of calls per command name, counted once the command is parsed
+    pub fn command_stats(&self) -> HashMap<String, u64> {
+        self.inner
+            .command_stats
+            .iter()
+            .map(|entry| {
+                (
+                    entry.key().to_string(),
+                    entry.value().load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.inner.started_at.elapsed()
+    }
+
+    /// approximate memory used by all dbs, in bytes
+    pub fn used_memory(&self) -> usize {
+        self.keyspaces().iter().map(|ks| ks.used_memory()).sum()
+    }
+
     pub fn get(&self, key: &str) -> Option<RespFrame> {
-        self.map.get(key).map(|v| v.value().clone())
+        if self.remove_if_expired(key) {
+            return None;
+        }
+        let value = self.map.get(key).map(|v| v.value().clone());
+        if value.is_some() {
+            self.record_access(key);
+        }
+        value
     }
 
+    /// set the value and clear any previous expire time
     pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+        self.set_with(key, value, None, None);
     }
 
-    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
-        self.hmap
+    /// set the value with an optional expire time, returns false if the condition is not met
+    pub fn set_with(
+        &self,
+        key: String,
+        value: RespFrame,
+        expire: Option<Duration>,
+        condition: Option<SetCondition>,
+    ) -> bool {
+        self.remove_if_expired(&key);
+        // hold the entry so the condition check and the write are atomic
+        let entry = self.map.entry(key);
+        let exists = matches!(entry, Entry::Occupied(_));
+        match condition {
+            Some(SetCondition::NotExists) if exists => return false,
+            Some(SetCondition::Exists) if !exists => return false,
+            _ => {}
+        }
+        // an expire time too far away to represent never comes
+        match expire.and_then(|expire| Instant::now().checked_add(expire)) {
+            Some(at) => {
+                self.expires.insert(entry.key().clone(), at);
+            }
+            None => {
+                self.expires.remove(entry.key());
+            }
+        }
+        let key = entry.key().clone();
+        entry.insert(value);
+        self.record_access(&key);
+        true
+    }
+
+    /// remaining time to live of the key, None if the key doesn't exist or has no expire time
+    pub fn ttl(&self, key: &str) -> Option<Duration> {
+        let at = self.expire_at(key).flatten()?;
+        Some(at.saturating_duration_since(Instant::now()))
+    }
+
+    /// expire time of the key, None if the key doesn't exist, Some(None) if it never expires
+    pub fn expire_at(&self, key: &str) -> Option<Option<Instant>> {
+        if !self.exists(key) {
+            return None;
+        }
+        Some(self.expires.get(key).map(|at| *at))
+    }
+
+    /// set the time to live of an existing key, a zero ttl removes the key right away,
+    /// returns false if the key doesn't exist
+    pub fn expire(&self, key: &str, ttl: Duration) -> bool {
+        self.expire_with(key, ttl, None)
+    }
+
+    /// like `expire`, also returns false if the condition is not met
+    pub fn expire_with(
+        &self,
+        key: &str,
+        ttl: Duration,
+        condition: Option<ExpireCondition>,
+    ) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        let at = Instant::now() + ttl;
+        let current = self.expires.get(key).map(|at| *at);
+        let met = match condition {
+            None => true,
+            Some(ExpireCondition::NotExists) => current.is_none(),
+            Some(ExpireCondition::Exists) => current.is_some(),
+            Some(ExpireCondition::Greater) => current.is_some_and(|current| at > current),
+            Some(ExpireCondition::Less) => current.is_none_or(|current| at < current),
+        };
+        if !met {
+            return false;
+        }
+        self.expires.insert(key.to_string(), at);
+        // the key may have been removed meanwhile, don't leave its expire time behind
+        if !self.contains_key(key) {
+            self.expires.remove(key);
+            return false;
+        }
+        if !self.remove_if_expired(key) {
+            self.record_access(key);
+        }
+        true
+    }
+
+    /// remove the expire time, returns false if the key doesn't exist or has no expire time
+    pub fn persist(&self, key: &str) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        self.expires.remove(key).is_some()
+    }
+
+    // remove the key if it has expired, returns whether it was removed
+    fn remove_if_expired(&self, key: &str) -> bool {
+        let expired = self
+            .expires
+            .get(key)
+            .is_some_and(|at| *at <= Instant::now());
+        if expired {
+            self.expires.remove(key);
+            self.map.remove(key);
+            self.hmap.remove(key);
+            self.set.remove(key);
+            self.list.remove(key);
+            self.access.remove(key);
+        }
+        expired
+    }
+
+    /// time since the key was last read or written, None if the key doesn't exist
+    pub fn idletime(&self, key: &str) -> Option<Duration> {
+        if !self.exists(key) {
+            return None;
+        }
+        let idle = self
+            .access
             .get(key)
-            .and_then(|v| v.get(field).map(|v| v.value().clone()))
+            .map_or(Duration::ZERO, |access| access.at.elapsed());
+        Some(idle)
+    }
+
+    /// number of times the key was read or written, None if the key doesn't exist
+    pub fn access_count(&self, key: &str) -> Option<u64> {
+        if !self.exists(key) {
+            return None;
+        }
+        Some(self.access.get(key).map_or(0, |access| access.count))
+    }
+
+    /// like redis, small integers are shared objects and report INT_MAX,
+    /// everything else has a single reference. None if the key doesn't exist
+    pub fn refcount(&self, key: &str) -> Option<i64> {
+        if !self.exists(key) {
+            return None;
+        }
+        let shared = self.map.get(key).is_some_and(|value| match value.value() {
+            // "042" is not stored as an integer
+            RespFrame::BulkString(BulkString(Some(s))) => std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok().filter(|n| n.to_string() == s))
+                .is_some_and(|n| (0..SHARED_INTEGERS).contains(&n)),
+            _ => false,
+        });
+        Some(if shared { i32::MAX as i64 } else { 1 })
+    }
+
+    /// mark the key as accessed without reading it, returns false if the key doesn't exist
+    pub fn touch(&self, key: &str) -> bool {
+        let exists = self.exists(key);
+        if exists {
+            self.record_access(key);
+        }
+        exists
+    }
+
+    // whether the key holds a string, hash or set, i.e. can't be used as a list
+    fn holds_non_list(&self, key: &str) -> bool {
+        self.remove_if_expired(key);
+        self.map.contains_key(key) || self.hmap.contains_key(key) || self.set.contains_key(key)
+    }
+
+    // whether the key exists as a string, hash, set or list
+    fn exists(&self, key: &str) -> bool {
+        self.remove_if_expired(key);
+        self.contains_key(key)
+    }
+
+    // like `exists`, without removing the key if it has expired
+    fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+            || self.hmap.contains_key(key)
+            || self.set.contains_key(key)
+            || self.list.contains_key(key)
+    }
+
+    // record an access to an existing key, only allocates the first time the key is seen
+    fn record_access(&self, key: &str) {
+        let now = Instant::now();
+        match self.access.get_mut(key) {
+            Some(mut access) => {
+                access.at = now;
+                access.count = access.count.saturating_add(1);
+            }
+            None => {
+                self.access
+                    .insert(key.to_string(), KeyAccess { at: now, count: 1 });
+            }
+        }
+    }
+
+    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
+        self.remove_if_expired(key);
+        let hmap = self.hmap.get(key)?;
+        self.record_access(key);
+        hmap.get(field).map(|v| v.value().clone())
     }
 
     pub fn hset(&self, key: String, field: String, value: RespFrame) {
+        self.remove_if_expired(&key);
+        self.record_access(&key);
         let hmap = self.hmap.entry(key).or_default();
         hmap.insert(field, value);
     }
 
+    /// set the field only if it doesn't exist yet, returns whether the field was set
+    pub fn hsetnx(&self, key: String, field: String, value: RespFrame) -> bool {
+        self.remove_if_expired(&key);
+        self.record_access(&key);
+        let hmap = self.hmap.entry(key).or_default();
+        let ret = match hmap.entry(field) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                true
+            }
+        };
+        ret
+    }
+
+    /// return random fields with their values, a negative count allows duplicates
+    pub fn hrandfield(
+        &self,
+        key: &str,
+        count: i64,
+    ) -> Result<Vec<(String, RespFrame)>, CountOutOfRange> {
+        self.remove_if_expired(key);
+        let fields = match self.hmap.get(key) {
+            Some(hmap) => {
+                let mut fields: Vec<(String, RespFrame)> = hmap
+                    .iter()
+                    .map(|e| (e.key().clone(), e.value().clone()))
+                    .collect();
+                // 和 sorted_members 一样, 排序后同一个 seed 结果才一致
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                self.record_access(key);
+                fields
+            }
+            None => return Ok(vec![]),
+        };
+        self.random_pick(&fields, count)
+    }
+
     pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|v| v.clone())
+        self.remove_if_expired(key);
+        let hmap = self.hmap.get(key)?;
+        self.record_access(key);
+        Some(hmap.clone())
     }
 
     pub fn hmget(&self, key: &str, fields: &[String]) -> Option<Vec<Option<RespFrame>>> {
-        self.hmap.get(key).map(|v| {
+        self.remove_if_expired(key);
+        let hmap = self.hmap.get(key)?;
+        self.record_access(key);
+        Some(
             fields
                 .iter()
-                .map(|f| v.get(f).map(|v| v.value().clone()))
-                .collect()
-        })
+                .map(|f| hmap.get(f).map(|v| v.value().clone()))
+                .collect(),
+        )
     }
 
     pub fn sadd(&self, key: String, members: Vec<BulkString>) -> usize {
+        self.remove_if_expired(&key);
+        self.record_access(&key);
         let set = self.set.entry(key).or_default();
         let mut count = 0;
         for member in members {
@@ -85,7 +984,334 @@ impl Backend {
         count
     }
 
+    /// all members of the set, sorted so the reply is stable
+    pub fn smembers(&self, key: &str) -> Vec<BulkString> {
+        self.remove_if_expired(key);
+        match self.set.get(key) {
+            Some(set) => {
+                self.record_access(key);
+                sorted_members(&set)
+            }
+            None => vec![],
+        }
+    }
+
     pub fn sismember(&self, key: &str, member: &BulkString) -> bool {
-        self.set.get(key).map_or(false, |set| set.contains(member))
+        self.remove_if_expired(key);
+        match self.set.get(key) {
+            Some(set) => {
+                self.record_access(key);
+                set.contains(member)
+            }
+            None => false,
+        }
+    }
+
+    /// remove and return up to `count` random members, the key is removed once the set is empty
+    pub fn spop(&self, key: &str, count: usize) -> Vec<BulkString> {
+        self.remove_if_expired(key);
+        let ret = match self.set.get(key) {
+            Some(set) => {
+                let members = sorted_members(&set);
+                let mut rng = self.inner.rng.lock().unwrap();
+                let ret: Vec<BulkString> =
+                    members.choose_multiple(&mut *rng, count).cloned().collect();
+                for member in &ret {
+                    set.remove(member);
+                }
+                ret
+            }
+            None => return vec![],
+        };
+        self.remove_emptied_set(key);
+        ret
+    }
+
+    /// remove the members from the set, returns how many of them were in it
+    pub fn srem(&self, key: &str, members: &[BulkString]) -> usize {
+        self.remove_if_expired(key);
+        let removed = match self.set.get(key) {
+            Some(set) => members.iter().filter(|m| set.remove(*m).is_some()).count(),
+            None => return 0,
+        };
+        self.remove_emptied_set(key);
+        removed
+    }
+
+    // a set left without members no longer exists, along with its expire
+    fn remove_emptied_set(&self, key: &str) {
+        if self.set.remove_if(key, |_, set| set.is_empty()).is_some() {
+            self.expires.remove(key);
+            self.access.remove(key);
+        } else {
+            self.record_access(key);
+        }
+    }
+
+    /// push the elements one by one to the given end, returns the length of the list afterwards
+    pub fn push(
+        &self,
+        key: String,
+        end: ListEnd,
+        elements: Vec<BulkString>,
+    ) -> Result<usize, WrongType> {
+        if self.holds_non_list(&key) {
+            return Err(WrongType);
+        }
+        self.record_access(&key);
+        let mut list = self.list.entry(key).or_default();
+        for element in elements {
+            match end {
+                ListEnd::Left => list.push_front(element),
+                ListEnd::Right => list.push_back(element),
+            }
+        }
+        Ok(list.len())
+    }
+
+    /// pop up to `count` elements from the first non-empty list among `keys`, returns the key
+    /// and the elements, the key is removed once the list is empty
+    pub fn lmpop(
+        &self,
+        keys: &[String],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(String, Vec<BulkString>)>, WrongType> {
+        for key in keys {
+            self.remove_if_expired(key);
+            let Some(mut list) = self.list.get_mut(key) else {
+                if self.holds_non_list(key) {
+                    return Err(WrongType);
+                }
+                continue;
+            };
+            let len = list.len();
+            let n = count.min(len);
+            let elements: Vec<BulkString> = match end {
+                ListEnd::Left => list.drain(..n).collect(),
+                ListEnd::Right => list.drain(len - n..).rev().collect(),
+            };
+            drop(list);
+            if elements.is_empty() {
+                continue;
+            }
+            if self
+                .list
+                .remove_if(key, |_, list| list.is_empty())
+                .is_some()
+            {
+                self.expires.remove(key);
+                self.access.remove(key);
+            } else {
+                self.record_access(key);
+            }
+            return Ok(Some((key.clone(), elements)));
+        }
+        Ok(None)
+    }
+
+    /// return random members without removing them, a negative count allows duplicates
+    pub fn srandmember(&self, key: &str, count: i64) -> Result<Vec<BulkString>, CountOutOfRange> {
+        self.remove_if_expired(key);
+        let members = match self.set.get(key) {
+            Some(set) => {
+                self.record_access(key);
+                sorted_members(&set)
+            }
+            None => return Ok(vec![]),
+        };
+        self.random_pick(&members, count)
+    }
+
+    // a positive count picks distinct items, a negative count allows duplicates
+    fn random_pick<T: Clone>(&self, items: &[T], count: i64) -> Result<Vec<T>, CountOutOfRange> {
+        if count.unsigned_abs() > MAX_RANDOM_COUNT {
+            return Err(CountOutOfRange);
+        }
+        let mut rng = self.inner.rng.lock().unwrap();
+        let picked = if count >= 0 {
+            items
+                .choose_multiple(&mut *rng, count as usize)
+                .cloned()
+                .collect()
+        } else {
+            (0..count.unsigned_abs())
+                .filter_map(|_| items.choose(&mut *rng).cloned())
+                .collect()
+        };
+        Ok(picked)
+    }
+
+    pub fn smismember(&self, key: &str, members: &[BulkString]) -> Vec<bool> {
+        self.remove_if_expired(key);
+        match self.set.get(key) {
+            Some(set) => {
+                self.record_access(key);
+                members.iter().map(|m| set.contains(m)).collect()
+            }
+            None => vec![false; members.len()],
+        }
+    }
+}
+
+impl KeyspaceInner {
+    /// number of keys across string / hash / set / list
+    pub fn key_count(&self) -> usize {
+        self.map.len() + self.hmap.len() + self.set.len() + self.list.len()
+    }
+
+    /// approximate memory used by keys and values, in bytes
+    pub fn used_memory(&self) -> usize {
+        let map: usize = self
+            .map
+            .iter()
+            .map(|e| e.key().len() + e.value().clone().encode().len())
+            .sum();
+        let hmap: usize = self
+            .hmap
+            .iter()
+            .map(|e| {
+                e.key().len()
+                    + e.value()
+                        .iter()
+                        .map(|f| f.key().len() + f.value().clone().encode().len())
+                        .sum::<usize>()
+            })
+            .sum();
+        let set: usize = self
+            .set
+            .iter()
+            .map(|e| e.key().len() + e.value().iter().map(|m| m.len()).sum::<usize>())
+            .sum();
+        let list: usize = self
+            .list
+            .iter()
+            .map(|e| e.key().len() + e.value().iter().map(|m| m.len()).sum::<usize>())
+            .sum();
+        map + hmap + set + list
+    }
+}
+
+// DashSet 的遍历顺序不固定, 排序后再随机选择才能保证同一个 seed 结果一致
+fn sorted_members(set: &DashSet<BulkString>) -> Vec<BulkString> {
+    let mut members: Vec<BulkString> = set.iter().map(|m| m.key().clone()).collect();
+    members.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, RespNull, SimpleString};
+
+    #[test]
+    fn test_execute_batch() {
+        let backend = Backend::new();
+        let frames = vec![
+            cmd(&["set", "hello", "world"]),
+            cmd(&["get", "hello"]),
+            cmd(&["sadd", "myset", "a", "b", "a"]),
+            cmd(&["sismember", "myset", "b"]),
+            cmd(&["get", "missing"]),
+        ];
+
+        let ret = backend.execute_batch(frames);
+        assert_eq!(
+            ret,
+            vec![
+                SimpleString::unchecked("OK").into(),
+                BulkString::new("world").into(),
+                2.into(),
+                1.into(),
+                RespFrame::Null(RespNull),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_invalid_command() {
+        let backend = Backend::new();
+        let ret = backend.execute(BulkString::new("get").into());
+        assert!(matches!(ret, RespFrame::Error(_)));
+    }
+
+    #[test]
+    fn test_command_stats() {
+        let backend = Backend::new();
+        let other = backend.session();
+        backend.execute(cmd(&["set", "a", "1"]));
+        backend.execute(cmd(&["SET", "b", "2"]));
+        other.execute(cmd(&["get", "a"]));
+        backend.execute(cmd(&["pexpire", "a", "1000"]));
+        backend.execute(cmd(&["expire", "b", "10"]));
+        backend.execute(cmd(&["get"]));
+        backend.execute(cmd(&["nosuchcommand"]));
+
+        let stats = backend.command_stats();
+        let expected: HashMap<String, u64> =
+            [("set", 2), ("get", 1), ("pexpire", 1), ("expire", 1)]
+                .into_iter()
+                .map(|(name, calls)| (name.to_string(), calls))
+                .collect();
+        assert_eq!(stats, expected);
+    }
+
+    #[tokio::test]
+    async fn test_replication() -> anyhow::Result<()> {
+        let primary = Backend::new();
+        let replica = Backend::new();
+        let (tx, rx) = mpsc::unbounded_channel();
+        primary.add_replica(tx);
+        let replaying = {
+            let replica = replica.clone();
+            tokio::spawn(async move { replica.apply_replication_stream(rx).await })
+        };
+
+        let session = primary.session();
+        session.execute(cmd(&["set", "hello", "world"]));
+        session.execute(cmd(&["select", "1"]));
+        session.execute(cmd(&["sadd", "myset", "a"]));
+        // read-only and rejected commands are not forwarded
+        session.execute(cmd(&["get", "hello"]));
+        session.execute(cmd(&["set", "hello"]));
+        // dropping the primary closes the stream
+        drop((primary, session));
+        tokio::time::timeout(Duration::from_secs(5), replaying).await???;
+
+        assert_eq!(replica.get("hello"), Some(BulkString::new("world").into()));
+        assert_eq!(replica.selected_db(), 0);
+        replica.select(1).unwrap();
+        assert_eq!(replica.smembers("myset"), vec![BulkString::new("a")]);
+        assert_eq!(replica.command_stats(), HashMap::new());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replication_of_spop() -> anyhow::Result<()> {
+        // different seeds, a replica re-running SPOP would pop other members
+        let primary = Backend::with_seed(1);
+        let replica = Backend::with_seed(2);
+        let (tx, rx) = mpsc::unbounded_channel();
+        primary.add_replica(tx);
+        let replaying = {
+            let replica = replica.clone();
+            tokio::spawn(async move { replica.apply_replication_stream(rx).await })
+        };
+
+        let session = primary.session();
+        let members = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        session.execute(cmd(&[&["sadd", "myset"][..], &members].concat()));
+        session.execute(cmd(&["spop", "myset", "3"]));
+        session.execute(cmd(&["spop", "myset"]));
+        // nothing to pop
+        session.execute(cmd(&["spop", "missing"]));
+        let expected = primary.smembers("myset");
+        assert_eq!(expected.len(), 4);
+        drop((primary, session));
+        tokio::time::timeout(Duration::from_secs(5), replaying).await???;
+
+        assert_eq!(replica.smembers("myset"), expected);
+        assert_eq!(replica.smembers("missing"), vec![]);
+        Ok(())
     }
 }
@@ -0,0 +1,161 @@
+use super::glob::glob_match;
+use crate::{BulkString, RespArray, RespFrame};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+/// SUBSCRIBE subscribes to channels, PSUBSCRIBE to glob patterns matched against the channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionKind {
+    Channel,
+    Pattern,
+}
+
+// channel or pattern -> client id -> where the messages of the client go
+type Subscribers = DashMap<BulkString, HashMap<u64, mpsc::UnboundedSender<RespFrame>>>;
+
+/// the subscribers of all sessions, exact channels and patterns are kept apart
+#[derive(Debug, Default)]
+pub(super) struct PubSub {
+    channels: Subscribers,
+    patterns: Subscribers,
+}
+
+/// the channels and patterns a session subscribes to, and the queue their messages go to
+#[derive(Debug, Default)]
+pub(super) struct Subscriptions {
+    channels: Vec<BulkString>,
+    patterns: Vec<BulkString>,
+    sender: Option<mpsc::UnboundedSender<RespFrame>>,
+    // taken by the connection, which writes the messages to the socket
+    receiver: Option<mpsc::UnboundedReceiver<RespFrame>>,
+}
+
+impl PubSub {
+    fn subscribers(&self, kind: SubscriptionKind) -> &Subscribers {
+        match kind {
+            SubscriptionKind::Channel => &self.channels,
+            SubscriptionKind::Pattern => &self.patterns,
+        }
+    }
+
+    pub(super) fn add(
+        &self,
+        kind: SubscriptionKind,
+        name: BulkString,
+        id: u64,
+        sender: mpsc::UnboundedSender<RespFrame>,
+    ) {
+        self.subscribers(kind)
+            .entry(name)
+            .or_default()
+            .insert(id, sender);
+    }
+
+    pub(super) fn remove(&self, kind: SubscriptionKind, name: &BulkString, id: u64) {
+        let subscribers = self.subscribers(kind);
+        if let Some(mut clients) = subscribers.get_mut(name) {
+            clients.remove(&id);
+        }
+        subscribers.remove_if(name, |_, clients| clients.is_empty());
+    }
+
+    /// returns the number of subscribers the message was delivered to,
+    /// a client matching several patterns gets the message once per pattern
+    pub(super) fn publish(&self, channel: &BulkString, message: &BulkString) -> usize {
+        let mut receivers = 0;
+        if let Some(clients) = self.channels.get(channel) {
+            let frame: RespFrame = RespArray::new([
+                BulkString::new("message").into(),
+                channel.clone().into(),
+                message.clone().into(),
+            ])
+            .into();
+            receivers += send_all(clients.values(), &frame);
+        }
+        for entry in self.patterns.iter() {
+            if !glob_match(entry.key(), channel) {
+                continue;
+            }
+            let frame: RespFrame = RespArray::new([
+                BulkString::new("pmessage").into(),
+                entry.key().clone().into(),
+                channel.clone().into(),
+                message.clone().into(),
+            ])
+            .into();
+            receivers += send_all(entry.value().values(), &frame);
+        }
+        receivers
+    }
+}
+
+// connections gone away are skipped, they unsubscribe when they're dropped
+fn send_all<'a>(
+    senders: impl Iterator<Item = &'a mpsc::UnboundedSender<RespFrame>>,
+    frame: &RespFrame,
+) -> usize {
+    senders
+        .filter(|sender| sender.send(frame.clone()).is_ok())
+        .count()
+}
+
+impl Subscriptions {
+    fn names_mut(&mut self, kind: SubscriptionKind) -> &mut Vec<BulkString> {
+        match kind {
+            SubscriptionKind::Channel => &mut self.channels,
+            SubscriptionKind::Pattern => &mut self.patterns,
+        }
+    }
+
+    // the queue is created on first use, most sessions never subscribe
+    fn init(&mut self) {
+        if self.sender.is_none() {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            self.sender = Some(sender);
+            self.receiver = Some(receiver);
+        }
+    }
+
+    pub(super) fn sender(&mut self) -> mpsc::UnboundedSender<RespFrame> {
+        self.init();
+        self.sender.clone().expect("created by init")
+    }
+
+    /// None once it's been taken
+    pub(super) fn take_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<RespFrame>> {
+        self.init();
+        self.receiver.take()
+    }
+
+    /// returns false if the session is already subscribed
+    pub(super) fn insert(&mut self, kind: SubscriptionKind, name: BulkString) -> bool {
+        let names = self.names_mut(kind);
+        if names.contains(&name) {
+            return false;
+        }
+        names.push(name);
+        true
+    }
+
+    /// returns false if the session isn't subscribed
+    pub(super) fn remove(&mut self, kind: SubscriptionKind, name: &BulkString) -> bool {
+        let names = self.names_mut(kind);
+        let len = names.len();
+        names.retain(|n| n != name);
+        names.len() != len
+    }
+
+    /// in subscription order
+    pub(super) fn names(&self, kind: SubscriptionKind) -> &[BulkString] {
+        match kind {
+            SubscriptionKind::Channel => &self.channels,
+            SubscriptionKind::Pattern => &self.patterns,
+        }
+    }
+
+    /// channels and patterns, the count in the replies of (P)SUBSCRIBE and (P)UNSUBSCRIBE
+    pub(super) fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
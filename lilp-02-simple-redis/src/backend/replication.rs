@@ -0,0 +1,142 @@
+use crate::{BulkString, RespArray, RespEncode, RespFrame};
+use bytes::Bytes;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+// commands changing the keyspace, forwarded to the replicas once applied
+const WRITE_COMMANDS: &[&str] = &[
+    "set", "setex", "psetex", "getex", "hset", "hsetnx", "sadd", "spop", "srem", "lpush", "rpush",
+    "lmpop", "expire", "pexpire", "swapdb",
+];
+
+/// the replicas of a primary, each one receives the RESP bytes of the write commands in order
+#[derive(Debug, Default)]
+pub(super) struct Replicas {
+    // checked before every command, so backends without replicas don't take the lock
+    count: AtomicUsize,
+    inner: Mutex<ReplicasInner>,
+}
+
+#[derive(Debug, Default)]
+struct ReplicasInner {
+    senders: Vec<mpsc::UnboundedSender<Bytes>>,
+    // db selected by the stream, a SELECT is sent first when a command runs on another one
+    db: Option<usize>,
+}
+
+/// started before a write command runs, hand it back to `Replicas::forward` once it's applied
+#[derive(Debug)]
+pub struct PendingWrite {
+    db: usize,
+    frame: PendingFrame,
+}
+
+#[derive(Debug)]
+enum PendingFrame {
+    Encoded(Vec<u8>),
+    // SPOP picks its members at random, the replicas SREM the ones popped here instead
+    Spop(RespFrame),
+}
+
+impl Replicas {
+    pub(super) fn add(&self, sender: mpsc::UnboundedSender<Bytes>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.senders.push(sender);
+        // a new replica hasn't seen any SELECT yet
+        inner.db = None;
+        self.count.store(inner.senders.len(), Ordering::Relaxed);
+    }
+
+    /// the frame is encoded up front, since the command consumes it
+    pub(super) fn pending(&self, frame: &RespFrame, db: usize) -> Option<PendingWrite> {
+        if self.count.load(Ordering::Relaxed) == 0 || !is_write(frame) {
+            return None;
+        }
+        let frame = match spop_key(frame) {
+            Some(key) => PendingFrame::Spop(key.clone()),
+            None => PendingFrame::Encoded(frame.clone().encode()),
+        };
+        Some(PendingWrite { db, frame })
+    }
+
+    /// send the command to every replica unless it failed, replicas gone away are dropped
+    pub(super) fn forward(&self, write: Option<PendingWrite>, ret: &RespFrame) {
+        let Some(write) = write else {
+            return;
+        };
+        if matches!(ret, RespFrame::Error(_)) {
+            return;
+        }
+        let frame = match write.frame {
+            PendingFrame::Encoded(frame) => frame,
+            PendingFrame::Spop(key) => match srem_popped(key, ret) {
+                Some(frame) => frame,
+                // nothing popped, nothing changed
+                None => return,
+            },
+        };
+        let mut inner = self.inner.lock().unwrap();
+        let mut bytes = Vec::new();
+        if inner.db != Some(write.db) {
+            let select = RespArray::new([
+                BulkString::new("select").into(),
+                BulkString::new(write.db.to_string()).into(),
+            ]);
+            bytes.extend_from_slice(&RespFrame::from(select).encode());
+            inner.db = Some(write.db);
+        }
+        bytes.extend_from_slice(&frame);
+        let bytes = Bytes::from(bytes);
+        inner
+            .senders
+            .retain(|sender| sender.send(bytes.clone()).is_ok());
+        self.count.store(inner.senders.len(), Ordering::Relaxed);
+    }
+}
+
+fn is_write(frame: &RespFrame) -> bool {
+    let RespFrame::Array(array) = frame else {
+        return false;
+    };
+    array
+        .first()
+        .and_then(|name| name.as_bytes())
+        .is_some_and(|name| {
+            WRITE_COMMANDS
+                .iter()
+                .any(|cmd| name.eq_ignore_ascii_case(cmd.as_bytes()))
+        })
+}
+
+fn spop_key(frame: &RespFrame) -> Option<&RespFrame> {
+    let RespFrame::Array(array) = frame else {
+        return None;
+    };
+    let is_spop = array
+        .first()
+        .and_then(|name| name.as_bytes())
+        .is_some_and(|name| name.eq_ignore_ascii_case(b"spop"));
+    if is_spop {
+        array.get(1)
+    } else {
+        None
+    }
+}
+
+// SREM key <members popped>, from the SPOP reply: a single member, null or an array of them
+fn srem_popped(key: RespFrame, ret: &RespFrame) -> Option<Vec<u8>> {
+    let members = match ret {
+        RespFrame::BulkString(_) => vec![ret.clone()],
+        RespFrame::Array(array) => array.to_vec(),
+        _ => vec![],
+    };
+    if members.is_empty() {
+        return None;
+    }
+    let srem: Vec<RespFrame> = [BulkString::new("srem").into(), key]
+        .into_iter()
+        .chain(members)
+        .collect();
+    Some(RespFrame::from(RespArray::new(srem)).encode())
+}
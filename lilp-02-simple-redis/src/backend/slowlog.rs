@@ -0,0 +1,182 @@
+use crate::{BulkString, RespFrame};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// commands taking longer than this are logged by default
+pub const DEFAULT_SLOWLOG_THRESHOLD: Duration = Duration::from_millis(10);
+/// entries kept by default, older ones are dropped
+pub const DEFAULT_SLOWLOG_MAX_LEN: usize = 128;
+
+// like redis, long commands are truncated so the log stays small
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+/// commands that ran longer than the threshold, newest first
+#[derive(Debug)]
+pub struct SlowLog {
+    threshold: Duration,
+    max_len: usize,
+    next_id: AtomicU64,
+    entries: Mutex<VecDeque<SlowLogEntry>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    /// when the command finished
+    pub timestamp: SystemTime,
+    pub duration: Duration,
+    pub args: Vec<BulkString>,
+}
+
+/// started before a command runs, hand it back to `SlowLog::finish` once the command is done
+#[derive(Debug)]
+pub struct CommandTimer {
+    start: Instant,
+    args: Vec<BulkString>,
+}
+
+impl Default for SlowLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_SLOWLOG_THRESHOLD, DEFAULT_SLOWLOG_MAX_LEN)
+    }
+}
+
+impl SlowLog {
+    pub fn new(threshold: Duration, max_len: usize) -> Self {
+        Self {
+            threshold,
+            max_len,
+            next_id: AtomicU64::new(0),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// the arguments are copied up front, since the command consumes the frame
+    pub fn timer(&self, frame: &RespFrame) -> Option<CommandTimer> {
+        if self.max_len == 0 {
+            return None;
+        }
+        Some(CommandTimer {
+            start: Instant::now(),
+            args: truncated_args(frame),
+        })
+    }
+
+    /// log the command if it ran longer than the threshold
+    pub fn finish(&self, timer: Option<CommandTimer>) {
+        let Some(timer) = timer else {
+            return;
+        };
+        let duration = timer.start.elapsed();
+        if duration < self.threshold {
+            return;
+        }
+        let entry = SlowLogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now(),
+            duration,
+            args: timer.args,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_front(entry);
+        entries.truncate(self.max_len);
+    }
+
+    /// the newest `count` entries
+    pub fn entries(&self, count: usize) -> Vec<SlowLogEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+fn truncated_args(frame: &RespFrame) -> Vec<BulkString> {
+    let RespFrame::Array(array) = frame else {
+        return Vec::new();
+    };
+    let mut args: Vec<BulkString> = array
+        .iter()
+        .take(MAX_ARGS)
+        .map(|arg| {
+            let bytes = arg.as_bytes().unwrap_or_default();
+            if bytes.len() > MAX_ARG_LEN {
+                let mut arg = bytes[..MAX_ARG_LEN].to_vec();
+                arg.extend_from_slice(
+                    format!("... ({} more bytes)", bytes.len() - MAX_ARG_LEN).as_bytes(),
+                );
+                BulkString::new(arg)
+            } else {
+                BulkString::new(bytes)
+            }
+        })
+        .collect();
+    if array.len() > MAX_ARGS {
+        // the last slot tells how many were dropped
+        args.truncate(MAX_ARGS - 1);
+        args.push(BulkString::new(format!(
+            "... ({} more arguments)",
+            array.len() - MAX_ARGS + 1
+        )));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::test_utils::cmd;
+
+    #[test]
+    fn test_slowlog_should_keep_newest_entries() {
+        let slowlog = SlowLog::new(Duration::ZERO, 2);
+        for key in ["a", "b", "c"] {
+            let timer = slowlog.timer(&cmd(&["get", key]));
+            slowlog.finish(timer);
+        }
+        let entries = slowlog.entries(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, 2);
+        assert_eq!(entries[0].args[1], BulkString::new("c"));
+        assert_eq!(entries[1].args[1], BulkString::new("b"));
+
+        slowlog.reset();
+        assert!(slowlog.is_empty());
+    }
+
+    #[test]
+    fn test_slowlog_should_skip_fast_commands() {
+        let slowlog = SlowLog::new(Duration::from_secs(1), 10);
+        let timer = slowlog.timer(&cmd(&["get", "a"]));
+        slowlog.finish(timer);
+        assert!(slowlog.is_empty());
+    }
+
+    #[test]
+    fn test_slowlog_should_truncate_args() {
+        let long = "x".repeat(MAX_ARG_LEN + 5);
+        let mut args = vec!["sadd", long.as_str()];
+        args.extend(["m"; MAX_ARGS]);
+        let args = truncated_args(&cmd(&args));
+
+        assert_eq!(args.len(), MAX_ARGS);
+        assert!(args[1].ends_with(b"... (5 more bytes)"));
+        assert_eq!(
+            args[MAX_ARGS - 1],
+            BulkString::new(format!("... ({} more arguments)", 3))
+        );
+    }
+}
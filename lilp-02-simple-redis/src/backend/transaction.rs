@@ -0,0 +1,32 @@
+use crate::cmd::{command_name, Command};
+use crate::RespFrame;
+
+// run right away inside MULTI instead of being queued
+const CONTROL_COMMANDS: &[&str] = &["multi", "exec", "discard", "reset"];
+
+/// the commands queued by a session since MULTI
+#[derive(Debug, Default)]
+pub(super) struct Transaction {
+    // the frame is kept for the replicas, the command is what EXEC runs
+    pub(super) queued: Vec<(RespFrame, Command)>,
+    // a command failed to queue, EXEC discards the transaction
+    pub(super) aborted: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransactionError {
+    /// MULTI inside MULTI
+    Nested,
+    /// EXEC or DISCARD without MULTI
+    NotStarted,
+    /// EXEC after a command failed to queue, the transaction is discarded
+    Aborted,
+}
+
+pub(super) fn is_control(frame: &RespFrame) -> bool {
+    command_name(frame).is_some_and(|name| {
+        CONTROL_COMMANDS
+            .iter()
+            .any(|control| name.eq_ignore_ascii_case(control))
+    })
+}
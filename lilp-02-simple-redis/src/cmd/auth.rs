@@ -0,0 +1,87 @@
+use crate::cmd::{extract_args, validate_command, Auth, CommandError, CommandExecutor, RESP_OK};
+use crate::{AuthError, Backend, BulkString, RespArray, RespFrame, SimpleError};
+
+impl CommandExecutor for Auth {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.auth(&self.password) {
+            Ok(()) => RESP_OK.clone(),
+            Err(AuthError::NoPasswordSet) => SimpleError::new(
+                "ERR AUTH <password> called without any password configured".to_string(),
+            )
+            .into(),
+            Err(AuthError::InvalidPassword) => {
+                SimpleError::new("ERR invalid password".to_string()).into()
+            }
+        }
+    }
+}
+
+// AUTH password
+// *2\r\n$4\r\nAUTH\r\n$6\r\nsecret\r\n
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["auth"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(password)))) => Ok(Auth {
+                password: String::from_utf8(password)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid password".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, SimpleString};
+
+    #[test]
+    fn test_auth_required() {
+        let ok: RespFrame = SimpleString::unchecked("OK").into();
+        let backend = Backend::new().with_requirepass("secret").session();
+
+        let ret = backend.execute(cmd(&["set", "hello", "world"]));
+        assert_eq!(
+            ret,
+            SimpleError::new("NOAUTH Authentication required").into()
+        );
+
+        let ret = backend.execute(cmd(&["auth", "wrong"]));
+        assert_eq!(ret, SimpleError::new("ERR invalid password").into());
+        assert!(!backend.is_authenticated());
+
+        assert_eq!(backend.execute(cmd(&["AUTH", "secret"])), ok);
+        assert_eq!(backend.execute(cmd(&["set", "hello", "world"])), ok);
+        assert_eq!(
+            backend.execute(cmd(&["get", "hello"])),
+            BulkString::new("world").into()
+        );
+
+        // auth state is per connection
+        let other = backend.session();
+        assert!(!other.is_authenticated());
+        assert!(matches!(
+            other.execute(cmd(&["get", "hello"])),
+            RespFrame::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_auth_without_password() {
+        let backend = Backend::new();
+        assert!(backend.is_authenticated());
+        assert!(matches!(
+            backend.execute(cmd(&["auth", "secret"])),
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            backend.execute(cmd(&["get", "hello"])),
+            crate::RespNull.into()
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::cmd::{validate_command, Client, ClientAction, CommandError, CommandExecutor, RESP_OK};
+use crate::{Backend, BulkString, RespArray, RespFrame};
+
+impl CommandExecutor for Client {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.action {
+            ClientAction::SetName(name) => {
+                backend.set_client_name((!name.is_empty()).then_some(name));
+                RESP_OK.clone()
+            }
+            ClientAction::GetName => match backend.client_name() {
+                Some(name) => BulkString::new(name).into(),
+                None => BulkString::null().into(),
+            },
+            ClientAction::Id => RespFrame::Integer(backend.client_id() as i64),
+            ClientAction::List => {
+                let lines: String = backend
+                    .clients()
+                    .into_iter()
+                    .map(|client| {
+                        let addr = client.addr.map(|addr| addr.to_string());
+                        format!(
+                            "id={} addr={} name={} age={}\n",
+                            client.id,
+                            addr.unwrap_or_default(),
+                            client.name.unwrap_or_default(),
+                            client.age.as_secs()
+                        )
+                    })
+                    .collect();
+                BulkString::new(lines).into()
+            }
+            // the number of connections closed, 0 or 1 with the ID filter
+            ClientAction::Kill(id) => RespFrame::Integer(backend.kill_client(id) as i64),
+        }
+    }
+}
+
+// CLIENT SETNAME name / CLIENT GETNAME / CLIENT ID / CLIENT LIST / CLIENT KILL ID id
+// *3\r\n$6\r\nCLIENT\r\n$7\r\nSETNAME\r\n$3\r\napp\r\n
+impl TryFrom<RespArray> for Client {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = value
+            .get(1)
+            .and_then(|arg| arg.as_bytes())
+            .map(|sub| sub.to_ascii_lowercase());
+        let action = match sub.as_deref() {
+            Some(b"setname") => {
+                validate_command(&value, &["client", "setname"], 1)?;
+                let name = value[2].as_bytes().ok_or_else(|| {
+                    CommandError::InvalidArgument("Invalid client name".to_string())
+                })?;
+                // 名字会出现在 CLIENT LIST 这类按空格分隔的输出里
+                if !name.iter().all(|b| b.is_ascii_graphic()) {
+                    return Err(CommandError::InvalidArgument(
+                        "Client names cannot contain spaces, newlines or special characters"
+                            .to_string(),
+                    ));
+                }
+                ClientAction::SetName(String::from_utf8(name.to_vec())?)
+            }
+            Some(b"getname") => {
+                validate_command(&value, &["client", "getname"], 0)?;
+                ClientAction::GetName
+            }
+            Some(b"id") => {
+                validate_command(&value, &["client", "id"], 0)?;
+                ClientAction::Id
+            }
+            Some(b"list") => {
+                validate_command(&value, &["client", "list"], 0)?;
+                ClientAction::List
+            }
+            Some(b"kill") => {
+                // only the ID filter is supported
+                validate_command(&value, &["client", "kill", "id"], 1)?;
+                let id = value[3]
+                    .as_bytes()
+                    .and_then(|id| std::str::from_utf8(id).ok())
+                    .and_then(|id| id.parse::<u64>().ok())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument(
+                            "client-id should be greater than 0".to_string(),
+                        )
+                    })?;
+                ClientAction::Kill(id)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "unknown CLIENT subcommand, try SETNAME, GETNAME, ID, LIST or KILL".to_string(),
+                ))
+            }
+        };
+        Ok(Client { action })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{test_utils::cmd, Command};
+
+    #[test]
+    fn test_client_from_resp_array() {
+        let action = |args: &[&str]| match Command::try_from(cmd(args)) {
+            Ok(Command::Client(cmd)) => Some(cmd.action),
+            _ => None,
+        };
+        assert_eq!(
+            action(&["CLIENT", "SetName", "app"]),
+            Some(ClientAction::SetName("app".to_string()))
+        );
+        assert_eq!(action(&["client", "getname"]), Some(ClientAction::GetName));
+        assert_eq!(action(&["client", "id"]), Some(ClientAction::Id));
+        assert_eq!(action(&["client", "list"]), Some(ClientAction::List));
+        assert_eq!(
+            action(&["client", "kill", "ID", "3"]),
+            Some(ClientAction::Kill(3))
+        );
+        assert_eq!(action(&["client", "kill", "127.0.0.1:6379"]), None);
+        assert_eq!(action(&["client", "kill", "id", "x"]), None);
+
+        assert_eq!(action(&["client", "setname", "my app"]), None);
+        assert_eq!(action(&["client", "setname"]), None);
+        assert_eq!(action(&["client", "kill"]), None);
+    }
+
+    #[test]
+    fn test_client_name_should_be_per_connection() {
+        let backend = Backend::new();
+        let (conn, other) = (backend.session(), backend.session());
+        let null: RespFrame = BulkString::null().into();
+
+        assert_eq!(conn.execute(cmd(&["client", "getname"])), null);
+        assert_eq!(
+            conn.execute(cmd(&["client", "setname", "app"])),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            conn.execute(cmd(&["client", "getname"])),
+            BulkString::new("app").into()
+        );
+        assert_eq!(other.execute(cmd(&["client", "getname"])), null);
+
+        // RESET keeps the name, an empty name clears it
+        conn.execute(cmd(&["reset"]));
+        assert_eq!(
+            conn.execute(cmd(&["client", "getname"])),
+            BulkString::new("app").into()
+        );
+        conn.execute(cmd(&["client", "setname", ""]));
+        assert_eq!(conn.execute(cmd(&["client", "getname"])), null);
+    }
+
+    #[test]
+    fn test_client_id_should_be_unique() {
+        let backend = Backend::new();
+        let (conn, other) = (backend.session(), backend.session());
+        let id = conn.execute(cmd(&["client", "id"]));
+        assert_eq!(id, RespFrame::Integer(conn.client_id() as i64));
+        assert_ne!(id, other.execute(cmd(&["client", "id"])));
+    }
+}
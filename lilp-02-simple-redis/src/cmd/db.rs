@@ -0,0 +1,139 @@
+use crate::cmd::{
+    extract_args, validate_command, CommandError, CommandExecutor, Select, SwapDb, RESP_OK,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, SimpleError};
+
+impl CommandExecutor for Select {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.select(self.index) {
+            Ok(()) => RESP_OK.clone(),
+            Err(_) => SimpleError::new("ERR DB index is out of range".to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for SwapDb {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.swapdb(self.a, self.b) {
+            Ok(()) => RESP_OK.clone(),
+            Err(_) => SimpleError::new("ERR DB index is out of range".to_string()).into(),
+        }
+    }
+}
+
+// SELECT index
+// *2\r\n$6\r\nSELECT\r\n$1\r\n1\r\n
+impl TryFrom<RespArray> for Select {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["select"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Select {
+            index: parse_index(args.next())?,
+        })
+    }
+}
+
+// SWAPDB index1 index2
+// *3\r\n$6\r\nSWAPDB\r\n$1\r\n0\r\n$1\r\n1\r\n
+impl TryFrom<RespArray> for SwapDb {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["swapdb"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(SwapDb {
+            a: parse_index(args.next())?,
+            b: parse_index(args.next())?,
+        })
+    }
+}
+
+fn parse_index(arg: Option<RespFrame>) -> Result<usize, CommandError> {
+    match arg {
+        Some(RespFrame::BulkString(BulkString(Some(index)))) => String::from_utf8(index)?
+            .parse()
+            .map_err(|_| CommandError::InvalidArgument("invalid DB index".to_string())),
+        _ => Err(CommandError::InvalidArgument(
+            "invalid DB index".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, RespDecode, RespNull};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_select_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$6\r\nSELECT\r\n$1\r\n1\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Select = frame.try_into()?;
+        assert_eq!(result.index, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select() {
+        let backend = Backend::new();
+
+        assert_eq!(
+            backend.execute(cmd(&["set", "hello", "world"])),
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.execute(cmd(&["select", "1"])), RESP_OK.clone());
+        assert_eq!(
+            backend.execute(cmd(&["get", "hello"])),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(backend.execute(cmd(&["select", "0"])), RESP_OK.clone());
+        assert_eq!(
+            backend.execute(cmd(&["get", "hello"])),
+            BulkString::new("world").into()
+        );
+
+        let ret = backend.execute(cmd(&["select", "16"]));
+        assert!(matches!(ret, RespFrame::Error(_)));
+        assert_eq!(backend.selected_db(), 0);
+    }
+
+    #[test]
+    fn test_select_is_per_session() {
+        let backend = Backend::new();
+        let other = backend.session();
+
+        backend.execute(cmd(&["select", "1"]));
+        backend.execute(cmd(&["set", "hello", "world"]));
+        assert_eq!(
+            other.execute(cmd(&["get", "hello"])),
+            RespFrame::Null(RespNull)
+        );
+    }
+
+    #[test]
+    fn test_swapdb() {
+        let backend = Backend::new();
+        let other = backend.session();
+
+        backend.execute(cmd(&["set", "hello", "world"]));
+        assert_eq!(backend.execute(cmd(&["swapdb", "0", "1"])), RESP_OK.clone());
+
+        // visible to all sessions
+        assert_eq!(
+            other.execute(cmd(&["get", "hello"])),
+            RespFrame::Null(RespNull)
+        );
+        other.execute(cmd(&["select", "1"]));
+        assert_eq!(
+            other.execute(cmd(&["get", "hello"])),
+            BulkString::new("world").into()
+        );
+    }
+}
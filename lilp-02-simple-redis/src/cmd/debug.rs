@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use crate::cmd::{
+    borrow_args, validate_command, CommandError, CommandExecutor, Debug, Wait, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame};
+
+impl Debug {
+    // 执行本身不阻塞, 由网络层异步 sleep
+    pub fn sleep_duration(&self) -> Duration {
+        self.sleep
+    }
+}
+
+impl CommandExecutor for Debug {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        RESP_OK.clone()
+    }
+}
+
+// replicas don't acknowledge the writes they apply, so none is ever counted
+impl CommandExecutor for Wait {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        RespFrame::Integer(0)
+    }
+}
+
+// DEBUG SLEEP seconds
+// *3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$1\r\n0\r\n
+impl TryFrom<RespArray> for Debug {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["debug", "sleep"], 1)?;
+        let mut args = borrow_args(&value, 2)?.iter();
+        let seconds: f64 = parse_arg(args.next(), "seconds")?;
+        // 负数、NaN、inf 以及超出 Duration 范围的值 (如 1e20) 都拒绝, 不能 panic
+        let sleep =
+            Duration::try_from_secs_f64(seconds).map_err(|_| CommandError::InvalidSleepTime)?;
+        Ok(Debug { sleep })
+    }
+}
+
+// WAIT numreplicas timeout
+// *3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$1\r\n0\r\n
+impl TryFrom<RespArray> for Wait {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["wait"], 2)?;
+        // 参数只做校验, 借用即可
+        let mut args = borrow_args(&value, 1)?.iter();
+        let _: i64 = parse_arg(args.next(), "numreplicas")?;
+        let _: i64 = parse_arg(args.next(), "timeout")?;
+        Ok(Wait)
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&RespFrame>, name: &str) -> Result<T, CommandError> {
+    let invalid = || CommandError::InvalidArgument(format!("invalid {}", name));
+    let arg = arg.and_then(RespFrame::as_bytes).ok_or_else(invalid)?;
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespDecode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_debug_sleep_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$3\r\n0.5\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Debug = frame.try_into()?;
+        assert_eq!(result.sleep_duration(), Duration::from_millis(500));
+
+        buf.extend_from_slice(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Debug::try_from(frame).is_err());
+
+        for seconds in ["1e20", "inf", "NaN"] {
+            let frame = RespArray::new(vec![
+                BulkString::new("DEBUG").into(),
+                BulkString::new("SLEEP").into(),
+                BulkString::new(seconds).into(),
+            ]);
+            let err = Debug::try_from(frame).unwrap_err();
+            assert_eq!(err.to_string(), "ERR invalid sleep time");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$3\r\n100\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Wait = frame.try_into()?;
+        assert_eq!(result.execute(&Backend::new()), RespFrame::Integer(0));
+
+        buf.extend_from_slice(b"*3\r\n$4\r\nWAIT\r\n$1\r\na\r\n$3\r\n100\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Wait::try_from(frame).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::{ClientAction, Command, GetExOption, ObjectAction, SlowlogAction, TimeUnit};
+use crate::{BulkString, ExpireCondition, ListEnd, RespFrame, SetCondition, SubscriptionKind};
+
+// 以可读的形式输出解析后的命令, 用于协议调试, 例如: SET foo "bar"
+// key 和 field 只在需要时加引号, value 总是加引号, 非 UTF-8 的内容以十六进制输出
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Get(cmd) => write!(f, "GET {}", Key(&cmd.key)),
+            Command::Set(cmd) => {
+                write!(f, "SET {} {}", Key(&cmd.key), Value(&cmd.value))?;
+                if let Some(expire) = cmd.expire {
+                    write!(f, " PX {}", expire.as_millis())?;
+                }
+                match cmd.condition {
+                    Some(SetCondition::NotExists) => f.write_str(" NX"),
+                    Some(SetCondition::Exists) => f.write_str(" XX"),
+                    None => Ok(()),
+                }
+            }
+            Command::SetEx(cmd) => write!(
+                f,
+                "{}SETEX {} {} {}",
+                prefix(cmd.unit),
+                Key(&cmd.key),
+                cmd.timeout,
+                Value(&cmd.value)
+            ),
+            Command::GetEx(cmd) => {
+                write!(f, "GETEX {}", Key(&cmd.key))?;
+                match cmd.option {
+                    Some(GetExOption::Expire(expire)) => write!(f, " PX {}", expire.as_millis()),
+                    Some(GetExOption::Persist) => f.write_str(" PERSIST"),
+                    None => Ok(()),
+                }
+            }
+            Command::HGet(cmd) => write!(f, "HGET {} {}", Key(&cmd.key), Key(&cmd.field)),
+            Command::HSet(cmd) => write!(
+                f,
+                "HSET {} {} {}",
+                Key(&cmd.key),
+                Key(&cmd.field),
+                Value(&cmd.value)
+            ),
+            Command::HGetAll(cmd) => write!(f, "HGETALL {}", Key(&cmd.key)),
+            Command::HmGet(cmd) => {
+                write!(f, "HMGET {}", Key(&cmd.key))?;
+                cmd.fields
+                    .iter()
+                    .try_for_each(|field| write!(f, " {}", Key(field)))
+            }
+            Command::HSetNx(cmd) => write!(
+                f,
+                "HSETNX {} {} {}",
+                Key(&cmd.key),
+                Key(&cmd.field),
+                Value(&cmd.value)
+            ),
+            Command::HRandField(cmd) => {
+                write!(f, "HRANDFIELD {}", Key(&cmd.key))?;
+                if let Some(count) = cmd.count {
+                    write!(f, " {}", count)?;
+                }
+                if cmd.with_values {
+                    f.write_str(" WITHVALUES")?;
+                }
+                Ok(())
+            }
+            Command::Echo(cmd) => write!(f, "ECHO {}", Bytes(cmd.msg.as_bytes())),
+            Command::Sadd(cmd) => {
+                write!(f, "SADD {}", Key(&cmd.key))?;
+                write_members(f, &cmd.members)
+            }
+            Command::Sismember(cmd) => write!(
+                f,
+                "SISMEMBER {} {}",
+                Key(&cmd.key),
+                Bytes(cmd.member.as_ref())
+            ),
+            Command::Smembers(cmd) => write!(f, "SMEMBERS {}", Key(&cmd.key)),
+            Command::Smismember(cmd) => {
+                write!(f, "SMISMEMBER {}", Key(&cmd.key))?;
+                write_members(f, &cmd.members)
+            }
+            Command::Spop(cmd) => {
+                write!(f, "SPOP {}", Key(&cmd.key))?;
+                cmd.count.map_or(Ok(()), |count| write!(f, " {}", count))
+            }
+            Command::Srem(cmd) => {
+                write!(f, "SREM {}", Key(&cmd.key))?;
+                write_members(f, &cmd.members)
+            }
+            Command::Srandmember(cmd) => {
+                write!(f, "SRANDMEMBER {}", Key(&cmd.key))?;
+                cmd.count.map_or(Ok(()), |count| write!(f, " {}", count))
+            }
+            Command::Push(cmd) => {
+                write!(f, "{}PUSH {}", end_prefix(cmd.end), Key(&cmd.key))?;
+                write_members(f, &cmd.elements)
+            }
+            Command::Lmpop(cmd) => {
+                write!(f, "LMPOP {}", cmd.keys.len())?;
+                cmd.keys
+                    .iter()
+                    .try_for_each(|key| write!(f, " {}", Key(key)))?;
+                let end = match cmd.end {
+                    ListEnd::Left => "LEFT",
+                    ListEnd::Right => "RIGHT",
+                };
+                write!(f, " {} COUNT {}", end, cmd.count)
+            }
+            Command::Info(cmd) => match &cmd.section {
+                Some(section) => write!(f, "INFO {}", Key(section)),
+                None => f.write_str("INFO"),
+            },
+            Command::Select(cmd) => write!(f, "SELECT {}", cmd.index),
+            Command::SwapDb(cmd) => write!(f, "SWAPDB {} {}", cmd.a, cmd.b),
+            Command::Debug(cmd) => write!(f, "DEBUG SLEEP {}", cmd.sleep.as_secs_f64()),
+            // WAIT 的参数只做校验, 没有保存下来
+            Command::Wait(_) => f.write_str("WAIT"),
+            // 不在日志中输出密码
+            Command::Auth(_) => f.write_str("AUTH <redacted>"),
+            Command::Hello(cmd) => {
+                f.write_str("HELLO")?;
+                if let Some(protover) = cmd.protover {
+                    write!(f, " {}", protover)?;
+                }
+                match &cmd.auth {
+                    Some((user, _)) => write!(f, " AUTH {} <redacted>", Key(user)),
+                    None => Ok(()),
+                }
+            }
+            Command::Reset(_) => f.write_str("RESET"),
+            Command::Object(cmd) => {
+                let action = match cmd.action {
+                    ObjectAction::IdleTime => "IDLETIME",
+                    ObjectAction::RefCount => "REFCOUNT",
+                    ObjectAction::Freq => "FREQ",
+                };
+                write!(f, "OBJECT {} {}", action, Key(&cmd.key))
+            }
+            Command::Touch(cmd) => {
+                f.write_str("TOUCH")?;
+                cmd.keys
+                    .iter()
+                    .try_for_each(|key| write!(f, " {}", Key(key)))
+            }
+            Command::Expire(cmd) => {
+                write!(
+                    f,
+                    "{}EXPIRE {} {}",
+                    prefix(cmd.unit),
+                    Key(&cmd.key),
+                    cmd.timeout
+                )?;
+                match cmd.condition {
+                    Some(ExpireCondition::NotExists) => f.write_str(" NX"),
+                    Some(ExpireCondition::Exists) => f.write_str(" XX"),
+                    Some(ExpireCondition::Greater) => f.write_str(" GT"),
+                    Some(ExpireCondition::Less) => f.write_str(" LT"),
+                    None => Ok(()),
+                }
+            }
+            Command::Ttl(cmd) => write!(f, "{}TTL {}", prefix(cmd.unit), Key(&cmd.key)),
+            Command::ExpireTime(cmd) => {
+                write!(f, "{}EXPIRETIME {}", prefix(cmd.unit), Key(&cmd.key))
+            }
+            Command::Slowlog(cmd) => match cmd.action {
+                SlowlogAction::Get(Some(count)) => write!(f, "SLOWLOG GET {}", count),
+                SlowlogAction::Get(None) => f.write_str("SLOWLOG GET"),
+                SlowlogAction::Reset => f.write_str("SLOWLOG RESET"),
+                SlowlogAction::Len => f.write_str("SLOWLOG LEN"),
+            },
+            Command::Client(cmd) => match &cmd.action {
+                ClientAction::SetName(name) => write!(f, "CLIENT SETNAME {}", Key(name)),
+                ClientAction::GetName => f.write_str("CLIENT GETNAME"),
+                ClientAction::Id => f.write_str("CLIENT ID"),
+                ClientAction::List => f.write_str("CLIENT LIST"),
+                ClientAction::Kill(id) => write!(f, "CLIENT KILL ID {}", id),
+            },
+            Command::Subscribe(cmd) => {
+                write!(f, "{}SUBSCRIBE", pattern_prefix(cmd.kind))?;
+                write_members(f, &cmd.names)
+            }
+            Command::Unsubscribe(cmd) => {
+                write!(f, "{}UNSUBSCRIBE", pattern_prefix(cmd.kind))?;
+                write_members(f, &cmd.names)
+            }
+            Command::Publish(cmd) => write!(
+                f,
+                "PUBLISH {} {}",
+                Bytes(cmd.channel.as_ref()),
+                Bytes(cmd.message.as_ref())
+            ),
+            Command::Multi(_) => f.write_str("MULTI"),
+            Command::Exec(_) => f.write_str("EXEC"),
+            Command::Discard(_) => f.write_str("DISCARD"),
+            Command::UnknownCommand(cmd) => write!(f, "<unknown command {}>", Key(&cmd.name)),
+        }
+    }
+}
+
+// PEXPIRE / PTTL / PEXPIRETIME
+fn prefix(unit: TimeUnit) -> &'static str {
+    match unit {
+        TimeUnit::Seconds => "",
+        TimeUnit::Millis => "P",
+    }
+}
+
+// LPUSH / RPUSH
+fn end_prefix(end: ListEnd) -> &'static str {
+    match end {
+        ListEnd::Left => "L",
+        ListEnd::Right => "R",
+    }
+}
+
+// PSUBSCRIBE / PUNSUBSCRIBE
+fn pattern_prefix(kind: SubscriptionKind) -> &'static str {
+    match kind {
+        SubscriptionKind::Channel => "",
+        SubscriptionKind::Pattern => "P",
+    }
+}
+
+fn write_members(f: &mut Formatter<'_>, members: &[BulkString]) -> fmt::Result {
+    members
+        .iter()
+        .try_for_each(|member| write!(f, " {}", Bytes(member.as_ref())))
+}
+
+// key 和 field, 只包含可见字符时不加引号
+struct Key<'a>(&'a str);
+
+// 总是加引号的 value
+struct Bytes<'a>(&'a [u8]);
+
+// 命令中的任意 frame, 只有 BulkString 按 Bytes 输出
+struct Value<'a>(&'a RespFrame);
+
+impl Display for Key<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let plain = !self.0.is_empty()
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_ascii_graphic() && c != '"' && c != '\\');
+        if plain {
+            f.write_str(self.0)
+        } else {
+            write!(f, "{:?}", self.0)
+        }
+    }
+}
+
+impl Display for Bytes<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(self.0) {
+            Ok(s) => write!(f, "{:?}", s),
+            Err(_) => {
+                f.write_str("0x")?;
+                self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+        }
+    }
+}
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            RespFrame::BulkString(s) if s.is_null() => f.write_str("(nil)"),
+            RespFrame::BulkString(s) => Bytes(s.as_ref()).fmt(f),
+            frame => write!(f, "{:?}", frame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespArray;
+    use anyhow::Result;
+
+    fn cmd(args: &[&[u8]]) -> Result<Command> {
+        let args: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::new(*arg).into())
+            .collect();
+        Ok(RespArray::new(args).try_into()?)
+    }
+
+    #[test]
+    fn test_command_display() -> Result<()> {
+        assert_eq!(
+            cmd(&[b"set", b"foo", b"bar"])?.to_string(),
+            r#"SET foo "bar""#
+        );
+        assert_eq!(
+            cmd(&[b"set", b"foo", b"\xff\x00bar"])?.to_string(),
+            "SET foo 0xff00626172"
+        );
+        assert_eq!(
+            cmd(&[b"hset", b"my map", b"f", b"a \"b\"\n"])?.to_string(),
+            r#"HSET "my map" f "a \"b\"\n""#
+        );
+        assert_eq!(
+            cmd(&[b"sadd", b"s", b"m1", b"m2"])?.to_string(),
+            r#"SADD s "m1" "m2""#
+        );
+        assert_eq!(
+            cmd(&[b"srandmember", b"s", b"-2"])?.to_string(),
+            "SRANDMEMBER s -2"
+        );
+        assert_eq!(
+            cmd(&[b"lmpop", b"2", b"a", b"b", b"right"])?.to_string(),
+            "LMPOP 2 a b RIGHT COUNT 1"
+        );
+        assert_eq!(
+            cmd(&[b"debug", b"sleep", b"0.5"])?.to_string(),
+            "DEBUG SLEEP 0.5"
+        );
+        Ok(())
+    }
+}
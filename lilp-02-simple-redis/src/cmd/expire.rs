@@ -0,0 +1,349 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::cmd::{
+    extract_args, validate_command, CommandError, CommandExecutor, Expire, ExpireTime, TimeUnit,
+    Ttl,
+};
+use crate::{Backend, BulkString, ExpireCondition, RespArray, RespFrame};
+
+impl TimeUnit {
+    pub(super) fn to_duration(self, n: u64) -> Duration {
+        match self {
+            TimeUnit::Seconds => Duration::from_secs(n),
+            TimeUnit::Millis => Duration::from_millis(n),
+        }
+    }
+
+    // TTL 四舍五入到秒, 刚设置的 EXPIRE 10 不会因为过去了几微秒就变成 9
+    fn round(self, d: Duration) -> i64 {
+        match self {
+            TimeUnit::Seconds => ((d.as_millis() + 500) / 1000) as i64,
+            TimeUnit::Millis => d.as_millis() as i64,
+        }
+    }
+
+    fn truncate(self, d: Duration) -> i64 {
+        match self {
+            TimeUnit::Seconds => d.as_secs() as i64,
+            TimeUnit::Millis => d.as_millis() as i64,
+        }
+    }
+}
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // 非正数的过期时间会立即删除 key
+        let ttl = self.unit.to_duration(self.timeout.max(0) as u64);
+        RespFrame::Integer(backend.expire_with(&self.key, ttl, self.condition) as i64)
+    }
+}
+
+// -2 if the key doesn't exist, -1 if it has no expire time
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ttl = match backend.expire_at(&self.key) {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(at)) => self
+                .unit
+                .round(at.saturating_duration_since(Instant::now())),
+        };
+        RespFrame::Integer(ttl)
+    }
+}
+
+// the absolute unix timestamp at which the key expires, -2 / -1 like TTL
+impl CommandExecutor for ExpireTime {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let timestamp = match backend.expire_at(&self.key) {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(at)) => {
+                let at = SystemTime::now() + at.saturating_duration_since(Instant::now());
+                let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+                self.unit.truncate(since_epoch)
+            }
+        };
+        RespFrame::Integer(timestamp)
+    }
+}
+
+// EXPIRE key seconds [NX | XX | GT | LT] / PEXPIRE key milliseconds [NX | XX | GT | LT]
+// *3\r\n$7\r\nPEXPIRE\r\n$3\r\nkey\r\n$4\r\n1500\r\n
+impl TryFrom<RespArray> for Expire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = if value.len() > 3 { 3 } else { 2 };
+        let unit = validate_timed_command(&value, "expire", "pexpire", n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_key(args.next())?;
+        let timeout = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(v)))) => {
+                String::from_utf8(v)?.parse::<i64>().ok()
+            }
+            _ => None,
+        };
+        let condition = match args.next() {
+            None => None,
+            Some(RespFrame::BulkString(BulkString(Some(flag)))) => {
+                match flag.to_ascii_lowercase().as_slice() {
+                    b"nx" => Some(ExpireCondition::NotExists),
+                    b"xx" => Some(ExpireCondition::Exists),
+                    b"gt" => Some(ExpireCondition::Greater),
+                    b"lt" => Some(ExpireCondition::Less),
+                    _ => {
+                        return Err(CommandError::InvalidArgument(format!(
+                            "Unsupported option {}",
+                            String::from_utf8_lossy(&flag)
+                        )))
+                    }
+                }
+            }
+            Some(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid expire option".to_string(),
+                ))
+            }
+        };
+        match timeout {
+            // 避免换算成毫秒时溢出
+            Some(timeout) if timeout <= i64::MAX / 1000 => Ok(Expire {
+                key,
+                timeout,
+                unit,
+                condition,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "invalid expire time".to_string(),
+            )),
+        }
+    }
+}
+
+// TTL key / PTTL key
+impl TryFrom<RespArray> for Ttl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let unit = validate_timed_command(&value, "ttl", "pttl", 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Ttl {
+            key: parse_key(args.next())?,
+            unit,
+        })
+    }
+}
+
+// EXPIRETIME key / PEXPIRETIME key
+impl TryFrom<RespArray> for ExpireTime {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let unit = validate_timed_command(&value, "expiretime", "pexpiretime", 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(ExpireTime {
+            key: parse_key(args.next())?,
+            unit,
+        })
+    }
+}
+
+// 这几组命令只有单位不同, P 开头的版本以毫秒为单位
+pub(super) fn validate_timed_command(
+    value: &RespArray,
+    name: &'static str,
+    millis_name: &'static str,
+    n_args: usize,
+) -> Result<TimeUnit, CommandError> {
+    if validate_command(value, &[millis_name], n_args).is_ok() {
+        return Ok(TimeUnit::Millis);
+    }
+    validate_command(value, &[name], n_args)?;
+    Ok(TimeUnit::Seconds)
+}
+
+fn parse_key(arg: Option<RespFrame>) -> Result<String, CommandError> {
+    match arg {
+        Some(RespFrame::BulkString(BulkString(Some(key)))) => Ok(String::from_utf8(key)?),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::test_utils::cmd;
+
+    fn int(frame: RespFrame) -> i64 {
+        frame.as_i64().expect("integer reply")
+    }
+
+    fn now_millis() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    }
+
+    #[test]
+    fn test_expire_from_resp_array() {
+        let frame = RespArray::new(vec![
+            BulkString::new("PEXPIRE").into(),
+            BulkString::new("k").into(),
+            BulkString::new("1500").into(),
+        ]);
+        let ret = Expire::try_from(frame).unwrap();
+        assert_eq!((ret.key.as_str(), ret.timeout), ("k", 1500));
+        assert_eq!(ret.unit, TimeUnit::Millis);
+
+        let frame = RespArray::new(vec![
+            BulkString::new("expire").into(),
+            BulkString::new("k").into(),
+            BulkString::new("abc").into(),
+        ]);
+        assert!(Expire::try_from(frame).is_err());
+    }
+
+    #[test]
+    fn test_ttl_of_missing_and_persistent_keys() {
+        let backend = Backend::new();
+        for name in ["ttl", "pttl", "expiretime", "pexpiretime"] {
+            assert_eq!(int(backend.execute(cmd(&[name, "k"]))), -2);
+        }
+        assert_eq!(int(backend.execute(cmd(&["pexpire", "k", "100"]))), 0);
+
+        backend.execute(cmd(&["set", "k", "v"]));
+        for name in ["ttl", "pttl", "expiretime", "pexpiretime"] {
+            assert_eq!(int(backend.execute(cmd(&[name, "k"]))), -1);
+        }
+    }
+
+    #[test]
+    fn test_pexpire_and_pttl() {
+        let backend = Backend::new();
+        backend.execute(cmd(&["set", "k", "v"]));
+        assert_eq!(int(backend.execute(cmd(&["pexpire", "k", "1500"]))), 1);
+
+        let pttl = int(backend.execute(cmd(&["pttl", "k"])));
+        assert!((1000..=1500).contains(&pttl), "pttl: {}", pttl);
+        let ttl = int(backend.execute(cmd(&["ttl", "k"])));
+        assert!((1..=2).contains(&ttl), "ttl: {}", ttl);
+
+        let before = now_millis();
+        let at = int(backend.execute(cmd(&["pexpiretime", "k"])));
+        assert!(at > before && at <= before + 1500, "pexpiretime: {}", at);
+        let at_secs = int(backend.execute(cmd(&["expiretime", "k"])));
+        // 两次调用之间的时间差可能跨过秒的边界
+        assert!((at_secs - at / 1000).abs() <= 1, "expiretime: {}", at_secs);
+
+        // SET clears the expire time
+        backend.execute(cmd(&["set", "k", "v2"]));
+        assert_eq!(int(backend.execute(cmd(&["pttl", "k"]))), -1);
+    }
+
+    #[test]
+    fn test_expire_of_hash_set_and_list_keys() {
+        let backend = Backend::new();
+        // (write, read of the written value)
+        let cases: &[(&[&str], &[&str])] = &[
+            (&["hset", "h", "f", "v"], &["hget", "h", "f"]),
+            (&["sadd", "s", "m"], &["sismember", "s", "m"]),
+            (&["rpush", "l", "e"], &["lmpop", "1", "l", "left"]),
+        ];
+        for (write, read) in cases {
+            let key = write[1];
+            backend.execute(cmd(write));
+            assert_eq!(int(backend.execute(cmd(&["ttl", key]))), -1, "{}", key);
+            assert_eq!(
+                int(backend.execute(cmd(&["expire", key, "10"]))),
+                1,
+                "{}",
+                key
+            );
+            let ttl = int(backend.execute(cmd(&["ttl", key])));
+            assert!((9..=10).contains(&ttl), "{}: {}", key, ttl);
+
+            // removed lazily by the read, the expire time goes with it
+            backend.execute(cmd(write));
+            assert_eq!(int(backend.execute(cmd(&["pexpire", key, "10"]))), 1);
+            std::thread::sleep(Duration::from_millis(20));
+            let ret = backend.execute(cmd(read));
+            assert!(
+                matches!(ret, RespFrame::Null(_) | RespFrame::Integer(0))
+                    || ret == BulkString::null().into(),
+                "{}: {:?}",
+                key,
+                ret
+            );
+            assert_eq!(int(backend.execute(cmd(&["ttl", key]))), -2, "{}", key);
+            assert_eq!(
+                int(backend.execute(cmd(&["expire", key, "10"]))),
+                0,
+                "{}",
+                key
+            );
+
+            // written again, it doesn't inherit the old expire time
+            backend.execute(cmd(write));
+            assert_eq!(int(backend.execute(cmd(&["ttl", key]))), -1, "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_expire_with_non_positive_timeout_removes_key() {
+        let backend = Backend::new();
+        backend.execute(cmd(&["set", "k", "v"]));
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "-1"]))), 1);
+        assert_eq!(backend.get("k"), None);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), -2);
+    }
+
+    #[test]
+    fn test_expire_nx_xx() {
+        let backend = Backend::new();
+        backend.execute(cmd(&["set", "k", "v"]));
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "100", "xx"]))), 0);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), -1);
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "100", "NX"]))), 1);
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "200", "nx"]))), 0);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), 100);
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "200", "xx"]))), 1);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), 200);
+    }
+
+    #[test]
+    fn test_expire_gt_lt() {
+        let backend = Backend::new();
+        backend.execute(cmd(&["set", "k", "v"]));
+        // no expire time counts as never expiring
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "100", "gt"]))), 0);
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "100", "lt"]))), 1);
+
+        // GT never shortens a longer TTL
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "50", "GT"]))), 0);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), 100);
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "200", "GT"]))), 1);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), 200);
+
+        assert_eq!(
+            int(backend.execute(cmd(&["pexpire", "k", "300000", "LT"]))),
+            0
+        );
+        assert_eq!(int(backend.execute(cmd(&["expire", "k", "150", "LT"]))), 1);
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), 150);
+    }
+
+    #[test]
+    fn test_expire_invalid_option() {
+        let backend = Backend::new();
+        backend.execute(cmd(&["set", "k", "v"]));
+        for args in [
+            &["expire", "k", "100", "ab"][..],
+            &["expire", "k", "100", "nx", "gt"],
+        ] {
+            assert!(matches!(backend.execute(cmd(args)), RespFrame::Error(_)));
+        }
+        assert_eq!(int(backend.execute(cmd(&["ttl", "k"]))), -1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+use super::{command_name, CommandError};
+use crate::RespFrame;
+
+/// which commands the server accepts, checked before a command is parsed and executed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CommandFilter {
+    #[default]
+    AllowAll,
+    /// only the listed commands are permitted
+    Allow(HashSet<String>),
+    /// the listed commands are disabled
+    Deny(HashSet<String>),
+}
+
+impl CommandFilter {
+    pub fn allow<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Self {
+        CommandFilter::Allow(normalize(names))
+    }
+
+    pub fn deny<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Self {
+        CommandFilter::Deny(normalize(names))
+    }
+
+    /// command names are case insensitive
+    pub fn is_allowed(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        match self {
+            CommandFilter::AllowAll => true,
+            CommandFilter::Allow(names) => names.contains(&name),
+            CommandFilter::Deny(names) => !names.contains(&name),
+        }
+    }
+
+    /// frames which are not a command are left to the command parser to reject
+    pub fn check(&self, frame: &RespFrame) -> Result<(), CommandError> {
+        if *self == CommandFilter::AllowAll {
+            return Ok(());
+        }
+        let Some(name) = command_name(frame) else {
+            return Ok(());
+        };
+        if self.is_allowed(&name) {
+            Ok(())
+        } else {
+            Err(CommandError::Disabled(name.to_ascii_lowercase()))
+        }
+    }
+}
+
+fn normalize<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> HashSet<String> {
+    names
+        .into_iter()
+        .map(|name| name.as_ref().trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, Backend, BulkString, RespEncode, SimpleError, SimpleString};
+
+    #[test]
+    fn test_deny_filter() {
+        let backend = Backend::new().with_command_filter(CommandFilter::deny(["FLUSHALL", "keys"]));
+        let ret = backend.execute(cmd(&["flushall"]));
+        assert_eq!(
+            ret,
+            SimpleError::new("ERR command 'flushall' is disabled").into()
+        );
+        assert_eq!(ret.encode(), b"-ERR command 'flushall' is disabled\r\n");
+        assert!(matches!(
+            backend.execute(cmd(&["KEYS", "*"])),
+            RespFrame::Error(_)
+        ));
+
+        assert_eq!(
+            backend.execute(cmd(&["set", "hello", "world"])),
+            SimpleString::unchecked("OK").into()
+        );
+        assert_eq!(
+            backend.execute(cmd(&["get", "hello"])),
+            BulkString::new("world").into()
+        );
+        // sessions share the filter
+        let session = backend.session();
+        assert!(matches!(
+            session.execute(cmd(&["flushall"])),
+            RespFrame::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_allow_filter() {
+        let backend = Backend::new().with_command_filter(CommandFilter::allow(["get"]));
+        assert_eq!(
+            backend.execute(cmd(&["get", "hello"])),
+            RespFrame::Null(crate::RespNull)
+        );
+        assert_eq!(
+            backend.execute(cmd(&["set", "hello", "world"])),
+            SimpleError::new("ERR command 'set' is disabled").into()
+        );
+    }
+
+    #[test]
+    fn test_allow_all() {
+        let filter = CommandFilter::default();
+        assert!(filter.is_allowed("flushall"));
+        assert!(filter.check(&cmd(&["flushall"])).is_ok());
+    }
+}
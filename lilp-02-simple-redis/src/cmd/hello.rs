@@ -0,0 +1,230 @@
+use crate::cmd::{extract_args, validate_command, CommandError, CommandExecutor, Hello};
+use crate::{AuthError, Backend, BulkString, RespArray, RespFrame, RespMap, SimpleError};
+
+// only the default user exists
+const DEFAULT_USER: &str = "default";
+
+impl CommandExecutor for Hello {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some((user, password)) = &self.auth {
+            if user != DEFAULT_USER {
+                return wrong_pass();
+            }
+            match backend.auth(password) {
+                Ok(()) => {}
+                Err(AuthError::InvalidPassword) => return wrong_pass(),
+                Err(AuthError::NoPasswordSet) => {
+                    return SimpleError::new(
+                        "ERR AUTH <password> called without any password configured".to_string(),
+                    )
+                    .into()
+                }
+            }
+        }
+        if !backend.is_authenticated() {
+            return SimpleError::new(CommandError::NoAuth.to_string()).into();
+        }
+        if let Some(protover) = self.protover {
+            backend.set_protocol_version(protover);
+        }
+        server_info(backend)
+    }
+}
+
+fn wrong_pass() -> RespFrame {
+    SimpleError::new("WRONGPASS invalid username-password pair".to_string()).into()
+}
+
+// RESP3 回复 Map, RESP2 回复 key value 交替的数组
+fn server_info(backend: &Backend) -> RespFrame {
+    let mut map = RespMap::new();
+    map.insert("server".to_string(), BulkString::new("redis").into());
+    map.insert(
+        "version".to_string(),
+        BulkString::new(env!("CARGO_PKG_VERSION")).into(),
+    );
+    map.insert(
+        "proto".to_string(),
+        (backend.protocol_version() as i64).into(),
+    );
+    map.insert("id".to_string(), (backend.client_id() as i64).into());
+    map.insert("mode".to_string(), BulkString::new("standalone").into());
+    map.insert("role".to_string(), BulkString::new("master").into());
+    map.insert("modules".to_string(), RespArray::new([]).into());
+
+    if backend.protocol_version() >= 3 {
+        return map.into();
+    }
+    let pairs: Vec<RespFrame> = map
+        .0
+        .into_iter()
+        .flat_map(|(k, v)| [BulkString::from(k).into(), v])
+        .collect();
+    RespArray::new(pairs).into()
+}
+
+// HELLO [protover [AUTH username password]]
+// *2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if !matches!(value.len(), 1 | 2 | 5) {
+            return Err(CommandError::InvalidArgument(
+                "syntax error in HELLO option".to_string(),
+            ));
+        }
+        let n_args = value.len() - 1;
+        validate_command(&value, &["hello"], n_args)?;
+        if n_args == 0 {
+            return Ok(Hello {
+                protover: None,
+                auth: None,
+            });
+        }
+        let mut args = extract_args(value, 1)?.into_iter();
+        let protover = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(v)))) => {
+                String::from_utf8(v)?.parse::<i64>().map_err(|_| {
+                    CommandError::InvalidArgument("Protocol version is not an integer".to_string())
+                })?
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid protover".to_string(),
+                ))
+            }
+        };
+        if !matches!(protover, 2 | 3) {
+            return Err(CommandError::NoProto);
+        }
+
+        let auth = match (args.next(), args.next(), args.next()) {
+            (None, None, None) => None,
+            (
+                Some(RespFrame::BulkString(BulkString(Some(opt)))),
+                Some(RespFrame::BulkString(BulkString(Some(user)))),
+                Some(RespFrame::BulkString(BulkString(Some(password)))),
+            ) if opt.eq_ignore_ascii_case(b"auth") => {
+                Some((String::from_utf8(user)?, String::from_utf8(password)?))
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "syntax error in HELLO option".to_string(),
+                ))
+            }
+        };
+        Ok(Hello {
+            protover: Some(protover as u8),
+            auth,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, RespDecode, RespEncode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_hello_3_should_reply_with_map() -> Result<()> {
+        let backend = Backend::new().session();
+        let mut buf = BytesMut::from(&b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n"[..]);
+        let ret = backend.execute(RespArray::decode(&mut buf)?.into());
+
+        let RespFrame::Map(map) = ret else {
+            panic!("expected a map, got {:?}", ret);
+        };
+        assert_eq!(map.get("proto"), Some(&RespFrame::Integer(3)));
+        assert_eq!(map.get("server"), Some(&BulkString::new("redis").into()));
+        assert_eq!(
+            map.get("id"),
+            Some(&RespFrame::Integer(backend.client_id() as i64))
+        );
+        for key in ["version", "mode", "role", "modules"] {
+            assert!(map.contains_key(key), "{}", key);
+        }
+
+        // the reply is decodable as RESP3 map
+        let mut buf = BytesMut::from(&RespFrame::Map(map.clone()).encode()[..]);
+        assert_eq!(RespFrame::decode(&mut buf)?, RespFrame::Map(map));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_2_should_reply_with_pairs() {
+        let backend = Backend::new().session();
+        let RespFrame::Array(RespArray(Some(pairs))) = backend.execute(cmd(&["hello"])) else {
+            panic!("expected an array");
+        };
+        assert_eq!(pairs.len(), 14);
+        let proto = pairs
+            .chunks(2)
+            .find(|pair| pair[0] == BulkString::new("proto").into())
+            .map(|pair| pair[1].clone());
+        assert_eq!(proto, Some(RespFrame::Integer(2)));
+    }
+
+    #[test]
+    fn test_smembers_should_follow_protocol_version() {
+        let backend = Backend::new().session();
+        backend.execute(cmd(&["sadd", "myset", "b", "a"]));
+
+        let ret = backend.execute(cmd(&["smembers", "myset"]));
+        assert_eq!(ret.encode(), b"*2\r\n$1\r\na\r\n$1\r\nb\r\n");
+
+        backend.execute(cmd(&["hello", "3"]));
+        assert_eq!(backend.protocol_version(), 3);
+        let ret = backend.execute(cmd(&["smembers", "myset"]));
+        assert_eq!(ret.encode(), b"~2\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let ret = backend.execute(cmd(&["smembers", "missing"]));
+        assert_eq!(ret.encode(), b"~0\r\n");
+
+        // other connections still use RESP2
+        let other = backend.session();
+        let ret = other.execute(cmd(&["smembers", "myset"]));
+        assert_eq!(ret.encode(), b"*2\r\n$1\r\na\r\n$1\r\nb\r\n");
+
+        backend.execute(cmd(&["hello", "2"]));
+        let ret = backend.execute(cmd(&["smembers", "myset"]));
+        assert_eq!(ret.encode(), b"*2\r\n$1\r\na\r\n$1\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_hello_with_unsupported_version() {
+        let backend = Backend::new();
+        let ret = backend.execute(cmd(&["hello", "4"]));
+        assert_eq!(
+            ret,
+            SimpleError::new("NOPROTO unsupported protocol version").into()
+        );
+        assert_eq!(backend.protocol_version(), 2);
+        assert!(matches!(
+            backend.execute(cmd(&["hello", "3", "setname", "x"])),
+            RespFrame::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_hello_with_auth() {
+        let backend = Backend::new().with_requirepass("secret").session();
+        let ret = backend.execute(cmd(&["hello", "3"]));
+        assert_eq!(
+            ret,
+            SimpleError::new("NOAUTH Authentication required").into()
+        );
+        let ret = backend.execute(cmd(&["hello", "3", "AUTH", "default", "wrong"]));
+        assert_eq!(
+            ret,
+            SimpleError::new("WRONGPASS invalid username-password pair").into()
+        );
+        assert_eq!(backend.protocol_version(), 2);
+
+        let ret = backend.execute(cmd(&["hello", "3", "AUTH", "default", "secret"]));
+        assert!(matches!(ret, RespFrame::Map(_)));
+        assert!(backend.is_authenticated());
+        assert_eq!(backend.protocol_version(), 3);
+    }
+}
@@ -1,5 +1,8 @@
-use super::{extract_args, validate_command, CommandExecutor, HGet, HGetAll, HSet, HmGet, RESP_OK};
-use crate::{cmd::CommandError, BulkString, RespArray, RespFrame};
+use super::{
+    extract_args, validate_command, CommandExecutor, HGet, HGetAll, HRandField, HSet, HSetNx,
+    HmGet, RESP_OK,
+};
+use crate::{cmd::CommandError, BulkString, RespArray, RespFrame, RespNull, SimpleError};
 
 impl CommandExecutor for HmGet {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
@@ -143,6 +146,110 @@ impl TryFrom<RespArray> for HSet {
     }
 }
 
+impl CommandExecutor for HSetNx {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let set = backend.hsetnx(self.key, self.field, self.value);
+        RespFrame::Integer(set as i64)
+    }
+}
+
+impl CommandExecutor for HRandField {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let fields = match backend.hrandfield(&self.key, self.count.unwrap_or(1)) {
+            Ok(fields) => fields,
+            Err(_) => return SimpleError::new("ERR value is out of range").into(),
+        };
+        // 不带 count 时返回单个 field 或 Null, 带 count 时返回数组
+        if self.count.is_none() {
+            return match fields.into_iter().next() {
+                Some((field, _)) => BulkString::from(field).into(),
+                None => RespFrame::Null(RespNull),
+            };
+        }
+        let ret = fields
+            .into_iter()
+            .flat_map(|(field, value)| {
+                let field = BulkString::from(field).into();
+                if self.with_values {
+                    vec![field, value]
+                } else {
+                    vec![field]
+                }
+            })
+            .collect::<Vec<RespFrame>>();
+        RespArray::new(ret).into()
+    }
+}
+
+// HSETNX key field value
+// *4\r\n$6\r\nhsetnx\r\n$3\r\nmap\r\n$5\r\nhello\r\n$5\r\nworld\r\n
+impl TryFrom<RespArray> for HSetNx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hsetnx"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(BulkString(Some(key)))),
+                Some(RespFrame::BulkString(BulkString(Some(field)))),
+                Some(value),
+            ) => Ok(HSetNx {
+                key: String::from_utf8(key)?,
+                field: String::from_utf8(field)?,
+                value,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key, field or value".to_string(),
+            )),
+        }
+    }
+}
+
+// HRANDFIELD key [count [WITHVALUES]]
+// *4\r\n$10\r\nhrandfield\r\n$3\r\nmap\r\n$2\r\n-2\r\n$10\r\nWITHVALUES\r\n
+impl TryFrom<RespArray> for HRandField {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if !(2..=4).contains(&value.len()) {
+            return Err(CommandError::InvalidArgument(
+                "hrandfield command must have 1 to 3 arguments".to_string(),
+            ));
+        }
+        let n_args = value.len() - 1;
+        validate_command(&value, &["hrandfield"], n_args)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => String::from_utf8(key)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let count = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(count)))) => Some(
+                String::from_utf8(count)?
+                    .parse::<i64>()
+                    .map_err(|_| CommandError::InvalidArgument("Invalid count".to_string()))?,
+            ),
+            Some(_) => return Err(CommandError::InvalidArgument("Invalid count".to_string())),
+            None => None,
+        };
+        let with_values = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(opt))))
+                if opt.eq_ignore_ascii_case(b"withvalues") =>
+            {
+                true
+            }
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            None => false,
+        };
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::RespDecode;
@@ -280,4 +387,114 @@ mod tests {
         assert_eq!(result, expected.into());
         Ok(())
     }
+
+    fn hset(backend: &crate::Backend, key: &str, fields: &[(&str, &str)]) {
+        for (field, value) in fields {
+            HSet {
+                key: key.to_string(),
+                field: field.to_string(),
+                value: BulkString::new(*value).into(),
+            }
+            .execute(backend);
+        }
+    }
+
+    #[test]
+    fn test_hsetnx_should_not_overwrite() -> Result<()> {
+        let backend = crate::Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$6\r\nhsetnx\r\n$3\r\nmap\r\n$5\r\nhello\r\n$5\r\nworld\r\n");
+        let cmd: HSetNx = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = HSetNx {
+            key: "map".to_string(),
+            field: "hello".to_string(),
+            value: BulkString::new("again").into(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+        assert_eq!(
+            backend.hget("map", "hello"),
+            Some(BulkString::new("world").into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*4\r\n$10\r\nhrandfield\r\n$3\r\nmap\r\n$2\r\n-2\r\n$10\r\nWITHVALUES\r\n",
+        );
+        let cmd: HRandField = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.key, "map");
+        assert_eq!(cmd.count, Some(-2));
+        assert!(cmd.with_values);
+
+        buf.extend_from_slice(b"*4\r\n$10\r\nhrandfield\r\n$3\r\nmap\r\n$1\r\n2\r\n$3\r\nfoo\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(HRandField::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield_execute() {
+        let fields = [("f1", "v1"), ("f2", "v2"), ("f3", "v3")];
+        let b1 = crate::Backend::with_seed(42);
+        let b2 = crate::Backend::with_seed(42);
+        hset(&b1, "map", &fields);
+        hset(&b2, "map", &fields);
+
+        let hrandfield = |backend: &crate::Backend, count, with_values| {
+            HRandField {
+                key: "map".to_string(),
+                count,
+                with_values,
+            }
+            .execute(backend)
+        };
+        // same seed, same selection
+        assert_eq!(hrandfield(&b1, None, false), hrandfield(&b2, None, false));
+        assert_eq!(
+            hrandfield(&b1, Some(2), true),
+            hrandfield(&b2, Some(2), true)
+        );
+
+        // positive count returns distinct fields, capped at the hash size
+        let RespFrame::Array(RespArray(Some(v))) = hrandfield(&b1, Some(5), false) else {
+            panic!("expected array");
+        };
+        assert_eq!(v.len(), 3);
+        assert!(v.iter().all(|f| v.iter().filter(|x| *x == f).count() == 1));
+
+        // negative count may return duplicates, values follow their fields
+        let RespFrame::Array(RespArray(Some(v))) = hrandfield(&b1, Some(-10), true) else {
+            panic!("expected array");
+        };
+        assert_eq!(v.len(), 20);
+        for pair in v.chunks(2) {
+            let (RespFrame::BulkString(field), RespFrame::BulkString(value)) = (&pair[0], &pair[1])
+            else {
+                panic!("expected bulk strings");
+            };
+            assert_eq!(&field[1..], &value[1..]);
+        }
+
+        // a huge negative count is rejected instead of building the reply
+        let out_of_range: RespFrame = SimpleError::new("ERR value is out of range").into();
+        assert_eq!(hrandfield(&b1, Some(i64::MIN), true), out_of_range);
+        assert_eq!(hrandfield(&b1, Some(i64::MIN), false), out_of_range);
+
+        // missing key
+        let missing = |count| {
+            HRandField {
+                key: "missing".to_string(),
+                count,
+                with_values: false,
+            }
+            .execute(&b1)
+        };
+        assert_eq!(missing(None), RespFrame::Null(RespNull));
+        assert_eq!(missing(Some(-3)), RespArray::new([]).into());
+    }
 }
@@ -0,0 +1,151 @@
+use crate::cmd::{extract_args, validate_command, CommandError, CommandExecutor, Info};
+use crate::{Backend, BulkString, RespArray, RespFrame};
+
+const SECTIONS: [&str; 5] = ["server", "clients", "memory", "commandstats", "keyspace"];
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let sections: Vec<&str> = match self.section.as_deref() {
+            None | Some("all") | Some("default") | Some("everything") => SECTIONS.to_vec(),
+            Some(section) => SECTIONS.into_iter().filter(|s| *s == section).collect(),
+        };
+
+        let body = sections
+            .into_iter()
+            .map(|section| render_section(section, backend))
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        BulkString::new(body).into()
+    }
+}
+
+fn render_section(section: &str, backend: &Backend) -> String {
+    match section {
+        "server" => format!(
+            "# Server\r\nredis_version:simple-redis-{}\r\nuptime_in_seconds:{}\r\n",
+            env!("CARGO_PKG_VERSION"),
+            backend.uptime().as_secs()
+        ),
+        "clients" => format!(
+            "# Clients\r\nconnected_clients:{}\r\n",
+            backend.connected_clients()
+        ),
+        "memory" => format!("# Memory\r\nused_memory:{}\r\n", backend.used_memory()),
+        "commandstats" => {
+            let mut stats: Vec<_> = backend.command_stats().into_iter().collect();
+            stats.sort();
+            let lines: String = stats
+                .into_iter()
+                .map(|(name, calls)| format!("cmdstat_{}:calls={}\r\n", name, calls))
+                .collect();
+            format!("# Commandstats\r\n{}", lines)
+        }
+        // 只输出非空的 db
+        _ => {
+            let dbs: String = backend
+                .keyspaces()
+                .iter()
+                .enumerate()
+                .filter(|(_, ks)| ks.key_count() > 0)
+                .map(|(i, ks)| {
+                    format!(
+                        "db{}:keys={},expires={},avg_ttl=0\r\n",
+                        i,
+                        ks.key_count(),
+                        ks.expires.len()
+                    )
+                })
+                .collect();
+            format!("# Keyspace\r\n{}", dbs)
+        }
+    }
+}
+
+// INFO [section]
+// *1\r\n$4\r\nINFO\r\n
+impl TryFrom<RespArray> for Info {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        match value.len() {
+            1 => {
+                validate_command(&value, &["info"], 0)?;
+                Ok(Info { section: None })
+            }
+            2 => {
+                validate_command(&value, &["info"], 1)?;
+                let mut args = extract_args(value, 1)?.into_iter();
+                match args.next() {
+                    Some(RespFrame::BulkString(BulkString(Some(section)))) => Ok(Info {
+                        section: Some(String::from_utf8(section)?.to_ascii_lowercase()),
+                    }),
+                    _ => Err(CommandError::InvalidArgument("Invalid section".to_string())),
+                }
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "info command must have at most 1 argument".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn info(backend: &Backend, section: Option<&str>) -> String {
+        let frame = Info {
+            section: section.map(|s| s.to_string()),
+        }
+        .execute(backend);
+        match frame {
+            RespFrame::BulkString(BulkString(Some(body))) => String::from_utf8(body).unwrap(),
+            _ => panic!("expected bulk string"),
+        }
+    }
+
+    #[test]
+    fn test_info_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$4\r\nINFO\r\n$8\r\nKeyspace\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Info = frame.try_into()?;
+        assert_eq!(result.section.as_deref(), Some("keyspace"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_execute() {
+        let backend = Backend::new();
+        backend.set("hello".to_string(), BulkString::new("world").into());
+        backend.set("foo".to_string(), BulkString::new("bar").into());
+        backend.sadd("myset".to_string(), vec![BulkString::new("m1")]);
+        backend.client_connected();
+
+        let body = info(&backend, None);
+        for section in ["# Server", "# Clients", "# Memory", "# Keyspace"] {
+            assert!(body.contains(section), "{}", body);
+        }
+        assert!(body.contains("connected_clients:1\r\n"));
+        assert!(body.contains("db0:keys=3,"));
+
+        backend.execute(
+            RespArray::new([
+                BulkString::new("get").into(),
+                BulkString::new("hello").into(),
+            ])
+            .into(),
+        );
+        let body = info(&backend, Some("commandstats"));
+        assert_eq!(body, "# Commandstats\r\ncmdstat_get:calls=1\r\n");
+
+        let body = info(&backend, Some("keyspace"));
+        assert!(!body.contains("# Server"));
+        assert!(body.contains("db0:keys=3,"));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::cmd::{extract_args, CommandError, CommandExecutor, Lmpop, Push};
+use crate::{Backend, BulkString, ListEnd, RespArray, RespFrame, RespNull, SimpleError};
+
+// the length of the list after the push
+impl CommandExecutor for Push {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.push(self.key, self.end, self.elements) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(_) => wrong_type(),
+        }
+    }
+}
+
+// [key, [element ...]], or Null if all the lists are empty
+impl CommandExecutor for Lmpop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lmpop(&self.keys, self.end, self.count) {
+            Ok(Some((key, elements))) => {
+                let elements: Vec<RespFrame> = elements.into_iter().map(|e| e.into()).collect();
+                RespArray::new([BulkString::new(key).into(), RespArray::new(elements).into()])
+                    .into()
+            }
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(_) => wrong_type(),
+        }
+    }
+}
+
+fn wrong_type() -> RespFrame {
+    SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value").into()
+}
+
+// LPUSH key element [element ...] / RPUSH key element [element ...]
+// *4\r\n$5\r\nLPUSH\r\n$4\r\nlist\r\n$1\r\na\r\n$1\r\nb\r\n
+impl TryFrom<RespArray> for Push {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let end = match value.first().and_then(|name| name.as_bytes()) {
+            Some(name) if name.eq_ignore_ascii_case(b"rpush") => ListEnd::Right,
+            _ => ListEnd::Left,
+        };
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next(), "key")?;
+        let elements = args
+            .map(|arg| match arg {
+                RespFrame::BulkString(element) => Ok(element),
+                _ => Err(CommandError::InvalidArgument(
+                    "Invalid list element".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if elements.is_empty() {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for push command".to_string(),
+            ));
+        }
+        Ok(Push { key, end, elements })
+    }
+}
+
+// LMPOP numkeys key [key ...] LEFT | RIGHT [COUNT count]
+// *5\r\n$5\r\nLMPOP\r\n$1\r\n2\r\n$1\r\na\r\n$1\r\nb\r\n$4\r\nLEFT\r\n
+impl TryFrom<RespArray> for Lmpop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let numkeys = parse_positive(args.next(), "numkeys")?;
+        let keys = (0..numkeys)
+            .map(|_| parse_string(args.next(), "key"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let end = match parse_string(args.next(), "direction")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "left" => ListEnd::Left,
+            "right" => ListEnd::Right,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "direction must be LEFT or RIGHT".to_string(),
+                ))
+            }
+        };
+        let count = match args.next() {
+            None => 1,
+            Some(RespFrame::BulkString(option)) if option.eq_ignore_ascii_case(b"count") => {
+                parse_positive(args.next(), "count")?
+            }
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(Lmpop { keys, end, count })
+    }
+}
+
+fn parse_string(arg: Option<RespFrame>, name: &str) -> Result<String, CommandError> {
+    match arg {
+        Some(RespFrame::BulkString(BulkString(Some(s)))) => Ok(String::from_utf8(s)?),
+        _ => Err(CommandError::InvalidArgument(format!("Invalid {}", name))),
+    }
+}
+
+fn parse_positive(arg: Option<RespFrame>, name: &str) -> Result<usize, CommandError> {
+    match parse_string(arg, name)?.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(CommandError::InvalidArgument(format!(
+            "{} should be greater than 0",
+            name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::test_utils::cmd;
+
+    fn popped(key: &str, elements: &[&str]) -> RespFrame {
+        let elements: Vec<RespFrame> = elements
+            .iter()
+            .map(|e| BulkString::new(*e).into())
+            .collect();
+        RespArray::new([BulkString::new(key).into(), RespArray::new(elements).into()]).into()
+    }
+
+    #[test]
+    fn test_lmpop_should_skip_empty_lists() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.execute(cmd(&["rpush", "jobs", "a", "b", "c"])),
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            backend.execute(cmd(&["lpush", "jobs", "z"])),
+            RespFrame::Integer(4)
+        );
+
+        let ret = backend.execute(cmd(&["lmpop", "2", "empty", "jobs", "left"]));
+        assert_eq!(ret, popped("jobs", &["z"]));
+        let ret = backend.execute(cmd(&["lmpop", "2", "empty", "jobs", "RIGHT", "COUNT", "2"]));
+        assert_eq!(ret, popped("jobs", &["c", "b"]));
+        // COUNT larger than the list pops all of it, and the key goes away
+        let ret = backend.execute(cmd(&["lmpop", "2", "empty", "jobs", "left", "count", "10"]));
+        assert_eq!(ret, popped("jobs", &["a"]));
+        assert!(backend.list.get("jobs").is_none());
+
+        let ret = backend.execute(cmd(&["lmpop", "2", "empty", "jobs", "left"]));
+        assert_eq!(ret, RespFrame::Null(RespNull));
+
+        backend.execute(cmd(&["set", "name", "lilp"]));
+        assert_eq!(backend.execute(cmd(&["lpush", "name", "a"])), wrong_type());
+        let ret = backend.execute(cmd(&["lmpop", "2", "empty", "name", "left"]));
+        assert_eq!(ret, wrong_type());
+    }
+
+    #[test]
+    fn test_lmpop_args() {
+        let backend = Backend::new();
+        for args in [
+            &["lmpop", "0", "left"][..],
+            &["lmpop", "2", "a", "left"],
+            &["lmpop", "1", "a", "up"],
+            &["lmpop", "1", "a", "left", "count", "0"],
+            &["lmpop", "1", "a", "left", "count"],
+            &["lmpop", "1", "a", "left", "count", "1", "extra"],
+            &["lpush", "a"],
+        ] {
+            assert!(
+                matches!(backend.execute(cmd(args)), RespFrame::Error(_)),
+                "{:?}",
+                args
+            );
+        }
+    }
+}
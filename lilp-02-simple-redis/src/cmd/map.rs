@@ -1,7 +1,12 @@
-use super::{extract_args, validate_command, CommandExecutor, Set, RESP_OK};
+use std::time::Duration;
+
+use super::{
+    expire::validate_timed_command, extract_args, validate_command, CommandExecutor, GetEx,
+    GetExOption, Set, SetEx, TimeUnit, RESP_OK,
+};
 use crate::{
     cmd::{CommandError, Get},
-    BulkString, RespArray, RespFrame, RespNull,
+    BulkString, RespArray, RespFrame, RespNull, SetCondition,
 };
 
 impl CommandExecutor for Get {
@@ -15,11 +20,42 @@ impl CommandExecutor for Get {
 
 impl CommandExecutor for Set {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
-        backend.set(self.key, self.value);
+        if backend.set_with(self.key, self.value, self.expire, self.condition) {
+            RESP_OK.clone()
+        } else {
+            // NX / XX 条件不满足时返回 null bulk string
+            BulkString::null().into()
+        }
+    }
+}
+
+impl CommandExecutor for SetEx {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let expire = self.unit.to_duration(self.timeout as u64);
+        backend.set_with(self.key, self.value, Some(expire), None);
         RESP_OK.clone()
     }
 }
 
+// 不带选项时不改变过期时间
+impl CommandExecutor for GetEx {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let Some(value) = backend.get(&self.key) else {
+            return RespFrame::Null(RespNull);
+        };
+        match self.option {
+            Some(GetExOption::Expire(expire)) => {
+                backend.expire(&self.key, expire);
+            }
+            Some(GetExOption::Persist) => {
+                backend.persist(&self.key);
+            }
+            None => {}
+        }
+        value
+    }
+}
+
 impl TryFrom<RespArray> for Get {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
@@ -35,28 +71,144 @@ impl TryFrom<RespArray> for Get {
     }
 }
 
+// SET key value [EX seconds | PX milliseconds] [NX | XX]
+// *5\r\n$3\r\nset\r\n$5\r\nhello\r\n$5\r\nworld\r\n$2\r\nEX\r\n$2\r\n10\r\n
 impl TryFrom<RespArray> for Set {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["set"], 2)?;
+        if value.len() < 3 {
+            return Err(CommandError::InvalidArgument(
+                "set command must have at least 2 arguments".to_string(),
+            ));
+        }
+        let n_args = value.len() - 1;
+        validate_command(&value, &["set"], n_args)?;
 
         let mut args = extract_args(value, 1)?.into_iter();
-        match (args.next(), args.next()) {
-            (Some(RespFrame::BulkString(BulkString(Some(key)))), Some(value)) => Ok(Set {
+        let mut set = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(BulkString(Some(key)))), Some(value)) => Set {
                 key: String::from_utf8(key)?,
                 value,
-            }),
-            _ => Err(CommandError::InvalidArgument(
-                "Invalid key or value".to_string(),
-            )),
+                expire: None,
+                condition: None,
+            },
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or value".to_string(),
+                ))
+            }
+        };
+
+        // 选项的顺序不固定, EX/PX 和 NX/XX 各自只能出现一个
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        while let Some(opt) = args.next() {
+            let RespFrame::BulkString(BulkString(Some(opt))) = opt else {
+                return Err(syntax_error());
+            };
+            match opt.to_ascii_lowercase().as_slice() {
+                opt @ (b"ex" | b"px") if set.expire.is_none() => {
+                    let n = parse_expire(args.next(), "set")?;
+                    set.expire = Some(if opt == b"ex" {
+                        Duration::from_secs(n as u64)
+                    } else {
+                        Duration::from_millis(n as u64)
+                    });
+                }
+                b"nx" if set.condition.is_none() => set.condition = Some(SetCondition::NotExists),
+                b"xx" if set.condition.is_none() => set.condition = Some(SetCondition::Exists),
+                _ => return Err(syntax_error()),
+            }
+        }
+        Ok(set)
+    }
+}
+
+// SETEX key seconds value / PSETEX key milliseconds value
+// *4\r\n$5\r\nSETEX\r\n$5\r\nhello\r\n$2\r\n10\r\n$5\r\nworld\r\n
+impl TryFrom<RespArray> for SetEx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let unit = validate_timed_command(&value, "setex", "psetex", 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => String::from_utf8(key)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let timeout = parse_expire(args.next(), "setex")?;
+        let value = args
+            .next()
+            .ok_or_else(|| CommandError::InvalidArgument("Invalid value".to_string()))?;
+        Ok(SetEx {
+            key,
+            value,
+            timeout,
+            unit,
+        })
+    }
+}
+
+// GETEX key [EX seconds | PX milliseconds | PERSIST]
+// *4\r\n$5\r\nGETEX\r\n$5\r\nhello\r\n$2\r\nEX\r\n$2\r\n10\r\n
+impl TryFrom<RespArray> for GetEx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len().saturating_sub(1).clamp(1, 3);
+        validate_command(&value, &["getex"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => String::from_utf8(key)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        let option = match args.next() {
+            None => None,
+            Some(RespFrame::BulkString(BulkString(Some(opt)))) => {
+                match opt.to_ascii_lowercase().as_slice() {
+                    b"ex" => {
+                        let n = parse_expire(args.next(), "getex")?;
+                        Some(GetExOption::Expire(TimeUnit::Seconds.to_duration(n as u64)))
+                    }
+                    b"px" => {
+                        let n = parse_expire(args.next(), "getex")?;
+                        Some(GetExOption::Expire(TimeUnit::Millis.to_duration(n as u64)))
+                    }
+                    b"persist" => Some(GetExOption::Persist),
+                    _ => return Err(syntax_error()),
+                }
+            }
+            Some(_) => return Err(syntax_error()),
+        };
+        if args.next().is_some() {
+            return Err(syntax_error());
+        }
+        Ok(GetEx { key, option })
+    }
+}
+
+// SETEX / GETEX 的过期时间必须是正数
+fn parse_expire(arg: Option<RespFrame>, name: &str) -> Result<i64, CommandError> {
+    let n = match arg {
+        Some(RespFrame::BulkString(BulkString(Some(n)))) => {
+            String::from_utf8(n)?.parse::<i64>().map_err(|_| {
+                CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+            })?
         }
+        _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+    };
+    // 避免换算成毫秒时溢出
+    if n <= 0 || n > i64::MAX / 1000 {
+        return Err(CommandError::InvalidArgument(format!(
+            "invalid expire time in '{}' command",
+            name
+        )));
     }
+    Ok(n)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Backend, RespDecode};
+    use crate::{cmd::test_utils::cmd, Backend, RespDecode};
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -93,6 +245,8 @@ mod tests {
         let cmd = Set {
             key: "hello".to_string(),
             value: RespFrame::BulkString(b"world".into()),
+            expire: None,
+            condition: None,
         };
         let result = cmd.execute(&backend);
         assert_eq!(result, RESP_OK.clone());
@@ -105,4 +259,204 @@ mod tests {
 
         Ok(())
     }
+
+    fn set(args: &[&str]) -> Result<Set, CommandError> {
+        let args: Vec<RespFrame> = ["set"]
+            .iter()
+            .chain(args)
+            .map(|arg| BulkString::new(*arg).into())
+            .collect();
+        RespArray::new(args).try_into()
+    }
+
+    #[test]
+    fn test_set_with_huge_expire_should_not_panic() {
+        let backend = Backend::new();
+        for unit in ["EX", "PX"] {
+            let ret = backend.execute(cmd(&["set", "k", "v", unit, "9223372036854775807"]));
+            assert!(
+                matches!(&ret, RespFrame::Error(e) if e.0.contains("invalid expire time in 'set' command")),
+                "{:?}",
+                ret
+            );
+        }
+        assert_eq!(backend.get("k"), None);
+
+        // the largest accepted expire time can still be set
+        let ret = backend.execute(cmd(&["set", "k", "v", "EX", "9223372036854775"]));
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(backend.set_with(
+            "k".to_string(),
+            BulkString::new("v").into(),
+            Some(Duration::MAX),
+            None,
+        ));
+        assert_eq!(backend.ttl("k"), None);
+    }
+
+    #[test]
+    fn test_set_options_from_resp_array() -> Result<()> {
+        let cmd = set(&["k", "v"])?;
+        assert_eq!((cmd.expire, cmd.condition), (None, None));
+
+        let cmd = set(&["k", "v", "EX", "10"])?;
+        assert_eq!(cmd.expire, Some(Duration::from_secs(10)));
+        let cmd = set(&["k", "v", "px", "1500", "NX"])?;
+        assert_eq!(cmd.expire, Some(Duration::from_millis(1500)));
+        assert_eq!(cmd.condition, Some(SetCondition::NotExists));
+        let cmd = set(&["k", "v", "xx", "ex", "1"])?;
+        assert_eq!(cmd.expire, Some(Duration::from_secs(1)));
+        assert_eq!(cmd.condition, Some(SetCondition::Exists));
+
+        for args in [
+            &["k", "v", "EX", "10", "PX", "10"][..],
+            &["k", "v", "NX", "XX"],
+            &["k", "v", "EX", "0"],
+            &["k", "v", "PX", "-1"],
+            &["k", "v", "EX", "ten"],
+            &["k", "v", "EX", "9223372036854775807"],
+            &["k", "v", "PX", "9223372036854775807"],
+            &["k", "v", "EX"],
+            &["k", "v", "KEEPTTL"],
+        ] {
+            assert!(set(args).is_err(), "{:?}", args);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_condition_matrix() -> Result<()> {
+        let ok = RESP_OK.clone();
+        let null: RespFrame = BulkString::null().into();
+        // (existing value, options, response, value afterwards)
+        let cases: &[(Option<&str>, &[&str], &RespFrame, &str)] = &[
+            (None, &[], &ok, "new"),
+            (Some("old"), &[], &ok, "new"),
+            (None, &["NX"], &ok, "new"),
+            (Some("old"), &["NX"], &null, "old"),
+            (None, &["XX"], &null, ""),
+            (Some("old"), &["XX"], &ok, "new"),
+            (None, &["EX", "100", "NX"], &ok, "new"),
+            (Some("old"), &["PX", "100000", "NX"], &null, "old"),
+            (None, &["EX", "100", "XX"], &null, ""),
+            (Some("old"), &["PX", "100000", "XX"], &ok, "new"),
+        ];
+        for (existing, opts, expected, after) in cases {
+            let backend = Backend::new();
+            if let Some(v) = existing {
+                backend.set("k".to_string(), BulkString::new(*v).into());
+            }
+            let args: Vec<&str> = ["k", "new"].iter().chain(opts.iter()).copied().collect();
+            let ret = set(&args)?.execute(&backend);
+            assert_eq!(&ret, *expected, "{:?} {:?}", existing, opts);
+            let value = backend.get("k").map(|v| match v {
+                RespFrame::BulkString(s) => String::from_utf8(s.to_vec()).unwrap(),
+                v => panic!("unexpected value {:?}", v),
+            });
+            assert_eq!(
+                value.unwrap_or_default(),
+                *after,
+                "{:?} {:?}",
+                existing,
+                opts
+            );
+            // 只有成功写入且带 EX/PX 时才有过期时间
+            let has_ttl = ret == ok && opts.iter().any(|o| *o == "EX" || *o == "PX");
+            assert_eq!(
+                backend.ttl("k").is_some(),
+                has_ttl,
+                "{:?} {:?}",
+                existing,
+                opts
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_with_expire() -> Result<()> {
+        let backend = Backend::new();
+        set(&["k", "v", "PX", "20"])?.execute(&backend);
+        assert!(backend.get("k").is_some());
+        assert!(backend.ttl("k").unwrap() <= Duration::from_millis(20));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(backend.get("k"), None);
+        assert_eq!(backend.ttl("k"), None);
+        // 过期的 key 视为不存在
+        assert_eq!(
+            set(&["k", "v", "XX"])?.execute(&backend),
+            BulkString::null().into()
+        );
+        assert_eq!(set(&["k", "v", "NX"])?.execute(&backend), RESP_OK.clone());
+
+        // 不带 EX/PX 的 SET 会清除过期时间
+        set(&["k", "v", "EX", "100"])?.execute(&backend);
+        set(&["k", "v"])?.execute(&backend);
+        assert_eq!(backend.ttl("k"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_setex_and_psetex() {
+        let backend = Backend::new();
+        let ret = backend.execute(cmd(&["setex", "k", "100", "v"]));
+        assert_eq!(ret, RESP_OK.clone());
+        assert_eq!(backend.get("k"), Some(BulkString::new("v").into()));
+        assert_eq!(backend.execute(cmd(&["ttl", "k"])), RespFrame::Integer(100));
+
+        backend.execute(cmd(&["psetex", "k", "1500", "v2"]));
+        assert_eq!(backend.get("k"), Some(BulkString::new("v2").into()));
+        assert!(backend.ttl("k").unwrap() <= Duration::from_millis(1500));
+
+        for args in [
+            &["setex", "k", "0", "v"][..],
+            &["psetex", "k", "-1", "v"],
+            &["setex", "k", "ten", "v"],
+            &["setex", "k", "10"],
+        ] {
+            assert!(
+                matches!(backend.execute(cmd(args)), RespFrame::Error(_)),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_getex() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.execute(cmd(&["getex", "k", "EX", "10"])),
+            RespFrame::Null(RespNull)
+        );
+        backend.execute(cmd(&["setex", "k", "100", "v"]));
+
+        // without an option the expire time is kept
+        let ret = backend.execute(cmd(&["getex", "k"]));
+        assert_eq!(ret, BulkString::new("v").into());
+        assert_eq!(backend.execute(cmd(&["ttl", "k"])), RespFrame::Integer(100));
+
+        backend.execute(cmd(&["getex", "k", "ex", "200"]));
+        assert_eq!(backend.execute(cmd(&["ttl", "k"])), RespFrame::Integer(200));
+        backend.execute(cmd(&["getex", "k", "PX", "50000"]));
+        assert_eq!(backend.execute(cmd(&["ttl", "k"])), RespFrame::Integer(50));
+
+        let ret = backend.execute(cmd(&["getex", "k", "PERSIST"]));
+        assert_eq!(ret, BulkString::new("v").into());
+        assert_eq!(backend.execute(cmd(&["ttl", "k"])), RespFrame::Integer(-1));
+
+        for args in [
+            &["getex", "k", "EX"][..],
+            &["getex", "k", "EX", "0"],
+            &["getex", "k", "KEEPTTL"],
+            &["getex", "k", "PERSIST", "EX", "10"],
+        ] {
+            assert!(
+                matches!(backend.execute(cmd(args)), RespFrame::Error(_)),
+                "{:?}",
+                args
+            );
+        }
+    }
 }
@@ -1,16 +1,40 @@
+mod auth;
+mod client;
+mod db;
+mod debug;
+mod display;
 mod echo;
+mod expire;
+mod filter;
+mod hello;
 mod hmap;
+mod info;
+mod list;
 mod map;
+mod object;
+mod pubsub;
+mod reset;
 mod set;
+mod slowlog;
+#[cfg(test)]
+pub(crate) mod test_utils;
+mod transaction;
+
+pub use filter::CommandFilter;
+pub(crate) use transaction::exec_reply;
 
-use crate::{Backend, BulkString, RespArray, RespError, RespFrame, SimpleString};
+use crate::{
+    Backend, BulkString, ExpireCondition, ListEnd, RespArray, RespError, RespFrame, SetCondition,
+    SimpleError, SimpleString, SubscriptionKind,
+};
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
+use std::time::Duration;
 use thiserror::Error;
 
 // you could also use once_cell instead of lazy_static
 lazy_static! {
-    static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
+    static ref RESP_OK: RespFrame = SimpleString::unchecked("OK").into();
 }
 
 #[derive(Error, Debug)]
@@ -20,6 +44,15 @@ pub enum CommandError {
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
 
+    #[error("ERR command '{0}' is disabled")]
+    Disabled(String),
+    #[error("ERR invalid sleep time")]
+    InvalidSleepTime,
+    #[error("NOAUTH Authentication required")]
+    NoAuth,
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+
     #[error("{0}")]
     RespError(#[from] RespError),
     #[error("Utf8 error: {0}")]
@@ -36,15 +69,47 @@ pub trait CommandExecutor {
 pub enum Command {
     Get(Get),
     Set(Set),
+    SetEx(SetEx),
+    GetEx(GetEx),
     HGet(HGet),
     HSet(HSet),
     HGetAll(HGetAll),
     HmGet(HmGet),
+    HSetNx(HSetNx),
+    HRandField(HRandField),
     Echo(Echo),
     Sadd(Sadd),
     Sismember(Sismember),
+    Smembers(Smembers),
+    Smismember(Smismember),
+    Spop(Spop),
+    Srem(Srem),
+    Srandmember(Srandmember),
+    Push(Push),
+    Lmpop(Lmpop),
+    Info(Info),
+    Select(Select),
+    SwapDb(SwapDb),
+    Debug(Debug),
+    Wait(Wait),
+    Auth(Auth),
+    Hello(Hello),
+    Reset(Reset),
+    Object(Object),
+    Touch(Touch),
+    Expire(Expire),
+    Ttl(Ttl),
+    ExpireTime(ExpireTime),
+    Slowlog(Slowlog),
+    Client(Client),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    Publish(Publish),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
     // unrecognized command
-    Unrecognized(Unrecognized),
+    UnknownCommand(UnknownCommand),
 }
 
 #[derive(Debug)]
@@ -52,10 +117,36 @@ pub struct Get {
     key: String,
 }
 
+// SET key value [EX seconds | PX milliseconds] [NX | XX]
 #[derive(Debug)]
 pub struct Set {
     key: String,
     value: RespFrame,
+    expire: Option<Duration>,
+    condition: Option<SetCondition>,
+}
+
+// SETEX key seconds value / PSETEX key milliseconds value
+#[derive(Debug)]
+pub struct SetEx {
+    key: String,
+    value: RespFrame,
+    timeout: i64,
+    unit: TimeUnit,
+}
+
+// GETEX key [EX seconds | PX milliseconds | PERSIST]
+#[derive(Debug)]
+pub struct GetEx {
+    key: String,
+    option: Option<GetExOption>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GetExOption {
+    // EX / PX
+    Expire(Duration),
+    Persist,
 }
 
 #[derive(Debug)]
@@ -77,6 +168,21 @@ pub struct HGetAll {
     sort: bool,
 }
 
+#[derive(Debug)]
+pub struct HSetNx {
+    key: String,
+    field: String,
+    value: RespFrame,
+}
+
+// HRANDFIELD key [count [WITHVALUES]]
+#[derive(Debug)]
+pub struct HRandField {
+    key: String,
+    count: Option<i64>,
+    with_values: bool,
+}
+
 #[derive(Debug)]
 pub struct Echo {
     msg: String,
@@ -101,7 +207,213 @@ pub struct Sismember {
 }
 
 #[derive(Debug)]
-pub struct Unrecognized;
+pub struct Smembers {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct Smismember {
+    key: String,
+    members: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct Spop {
+    key: String,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Srem {
+    key: String,
+    members: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct Srandmember {
+    key: String,
+    count: Option<i64>,
+}
+
+// LPUSH key element [element ...] / RPUSH key element [element ...]
+#[derive(Debug)]
+pub struct Push {
+    key: String,
+    end: ListEnd,
+    elements: Vec<BulkString>,
+}
+
+// LMPOP numkeys key [key ...] LEFT | RIGHT [COUNT count]
+#[derive(Debug)]
+pub struct Lmpop {
+    keys: Vec<String>,
+    end: ListEnd,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct Info {
+    section: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Select {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct SwapDb {
+    a: usize,
+    b: usize,
+}
+
+// DEBUG SLEEP <seconds>
+#[derive(Debug)]
+pub struct Debug {
+    sleep: Duration,
+}
+
+// WAIT numreplicas timeout, the arguments are validated but ignored
+#[derive(Debug)]
+pub struct Wait;
+
+// AUTH password
+#[derive(Debug)]
+pub struct Auth {
+    password: String,
+}
+
+// HELLO [protover [AUTH username password]]
+#[derive(Debug)]
+pub struct Hello {
+    protover: Option<u8>,
+    // username and password
+    auth: Option<(String, String)>,
+}
+
+// RESET, clears the state of the connection
+#[derive(Debug)]
+pub struct Reset;
+
+// OBJECT IDLETIME key / OBJECT REFCOUNT key / OBJECT FREQ key
+#[derive(Debug)]
+pub struct Object {
+    action: ObjectAction,
+    key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectAction {
+    IdleTime,
+    RefCount,
+    Freq,
+}
+
+// TOUCH key [key ...]
+#[derive(Debug)]
+pub struct Touch {
+    keys: Vec<String>,
+}
+
+// unit of the EXPIRE / TTL family, the P-prefixed commands use milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeUnit {
+    Seconds,
+    Millis,
+}
+
+// EXPIRE key seconds [NX | XX | GT | LT] / PEXPIRE key milliseconds [NX | XX | GT | LT]
+#[derive(Debug)]
+pub struct Expire {
+    key: String,
+    timeout: i64,
+    unit: TimeUnit,
+    condition: Option<ExpireCondition>,
+}
+
+// TTL key / PTTL key
+#[derive(Debug)]
+pub struct Ttl {
+    key: String,
+    unit: TimeUnit,
+}
+
+// EXPIRETIME key / PEXPIRETIME key
+#[derive(Debug)]
+pub struct ExpireTime {
+    key: String,
+    unit: TimeUnit,
+}
+
+// SLOWLOG GET [count] / SLOWLOG RESET / SLOWLOG LEN
+#[derive(Debug)]
+pub struct Slowlog {
+    action: SlowlogAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlowlogAction {
+    // newest entries, 10 by default, a negative count returns all of them
+    Get(Option<i64>),
+    Reset,
+    Len,
+}
+
+// CLIENT SETNAME name / CLIENT GETNAME / CLIENT ID / CLIENT LIST / CLIENT KILL ID id
+#[derive(Debug)]
+pub struct Client {
+    action: ClientAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClientAction {
+    // an empty name clears it
+    SetName(String),
+    GetName,
+    Id,
+    List,
+    Kill(u64),
+}
+
+// SUBSCRIBE channel [channel ...] / PSUBSCRIBE pattern [pattern ...]
+#[derive(Debug)]
+pub struct Subscribe {
+    kind: SubscriptionKind,
+    names: Vec<BulkString>,
+}
+
+// UNSUBSCRIBE [channel ...] / PUNSUBSCRIBE [pattern ...], no argument unsubscribes from all
+#[derive(Debug)]
+pub struct Unsubscribe {
+    kind: SubscriptionKind,
+    names: Vec<BulkString>,
+}
+
+// PUBLISH channel message
+#[derive(Debug)]
+pub struct Publish {
+    channel: BulkString,
+    message: BulkString,
+}
+
+// MULTI, queue the following commands until EXEC
+#[derive(Debug)]
+pub struct Multi;
+
+// EXEC, run the queued commands
+#[derive(Debug)]
+pub struct Exec;
+
+// DISCARD, drop the queued commands
+#[derive(Debug)]
+pub struct Discard;
+
+// a command the server doesn't know, answered with an error like redis does
+#[derive(Debug)]
+pub struct UnknownCommand {
+    name: String,
+    args: Vec<String>,
+}
 
 /// 解析redis-cli 发送的命令
 impl TryFrom<RespFrame> for Command {
@@ -120,17 +432,52 @@ impl TryFrom<RespArray> for Command {
     type Error = CommandError;
     fn try_from(v: RespArray) -> Result<Self, Self::Error> {
         match v.first() {
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
+            Some(RespFrame::BulkString(ref cmd)) if cmd.is_null() => Err(
+                CommandError::InvalidCommand("Command name must not be a null BulkString".into()),
+            ),
+            Some(RespFrame::BulkString(ref cmd)) => match cmd.to_ascii_lowercase().as_slice() {
                 b"get" => Ok(Get::try_from(v)?.into()),
                 b"set" => Ok(Set::try_from(v)?.into()),
+                b"setex" | b"psetex" => Ok(SetEx::try_from(v)?.into()),
+                b"getex" => Ok(GetEx::try_from(v)?.into()),
                 b"hget" => Ok(HGet::try_from(v)?.into()),
                 b"hset" => Ok(HSet::try_from(v)?.into()),
                 b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
                 b"hmget" => Ok(HmGet::try_from(v)?.into()),
+                b"hsetnx" => Ok(HSetNx::try_from(v)?.into()),
+                b"hrandfield" => Ok(HRandField::try_from(v)?.into()),
                 b"echo" => Ok(Echo::try_from(v)?.into()),
                 b"sadd" => Ok(Sadd::try_from(v)?.into()),
                 b"sismember" => Ok(Sismember::try_from(v)?.into()),
-                _ => Ok(Unrecognized.into()),
+                b"smembers" => Ok(Smembers::try_from(v)?.into()),
+                b"smismember" => Ok(Smismember::try_from(v)?.into()),
+                b"spop" => Ok(Spop::try_from(v)?.into()),
+                b"srem" => Ok(Srem::try_from(v)?.into()),
+                b"srandmember" => Ok(Srandmember::try_from(v)?.into()),
+                b"lpush" | b"rpush" => Ok(Push::try_from(v)?.into()),
+                b"lmpop" => Ok(Lmpop::try_from(v)?.into()),
+                b"info" => Ok(Info::try_from(v)?.into()),
+                b"select" => Ok(Select::try_from(v)?.into()),
+                b"swapdb" => Ok(SwapDb::try_from(v)?.into()),
+                b"debug" => Ok(Debug::try_from(v)?.into()),
+                b"wait" => Ok(Wait::try_from(v)?.into()),
+                b"auth" => Ok(Auth::try_from(v)?.into()),
+                b"hello" => Ok(Hello::try_from(v)?.into()),
+                b"reset" => Ok(Reset::try_from(v)?.into()),
+                b"object" => Ok(Object::try_from(v)?.into()),
+                b"touch" => Ok(Touch::try_from(v)?.into()),
+                b"expire" | b"pexpire" => Ok(Expire::try_from(v)?.into()),
+                b"ttl" | b"pttl" => Ok(Ttl::try_from(v)?.into()),
+                b"expiretime" | b"pexpiretime" => Ok(ExpireTime::try_from(v)?.into()),
+                b"slowlog" => Ok(Slowlog::try_from(v)?.into()),
+                b"client" => Ok(Client::try_from(v)?.into()),
+                b"subscribe" | b"psubscribe" => Ok(Subscribe::try_from(v)?.into()),
+                b"unsubscribe" | b"punsubscribe" => Ok(Unsubscribe::try_from(v)?.into()),
+                b"publish" => Ok(Publish::try_from(v)?.into()),
+                b"multi" => Ok(Multi::try_from(v)?.into()),
+                b"exec" => Ok(Exec::try_from(v)?.into()),
+                b"discard" => Ok(Discard::try_from(v)?.into()),
+                _ => Ok(UnknownCommand::from(v).into()),
             },
             _ => Err(CommandError::InvalidCommand(
                 "Command must have a BulkString as the first argument".to_string(),
@@ -139,12 +486,125 @@ impl TryFrom<RespArray> for Command {
     }
 }
 
-impl CommandExecutor for Unrecognized {
+impl Command {
+    /// lowercase name of the command, None if it's unrecognized
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self {
+            Command::Get(_) => "get",
+            Command::Set(_) => "set",
+            Command::SetEx(cmd) => match cmd.unit {
+                TimeUnit::Seconds => "setex",
+                TimeUnit::Millis => "psetex",
+            },
+            Command::GetEx(_) => "getex",
+            Command::HGet(_) => "hget",
+            Command::HSet(_) => "hset",
+            Command::HGetAll(_) => "hgetall",
+            Command::HmGet(_) => "hmget",
+            Command::HSetNx(_) => "hsetnx",
+            Command::HRandField(_) => "hrandfield",
+            Command::Echo(_) => "echo",
+            Command::Sadd(_) => "sadd",
+            Command::Sismember(_) => "sismember",
+            Command::Smembers(_) => "smembers",
+            Command::Smismember(_) => "smismember",
+            Command::Spop(_) => "spop",
+            Command::Srem(_) => "srem",
+            Command::Srandmember(_) => "srandmember",
+            Command::Push(cmd) => match cmd.end {
+                ListEnd::Left => "lpush",
+                ListEnd::Right => "rpush",
+            },
+            Command::Lmpop(_) => "lmpop",
+            Command::Info(_) => "info",
+            Command::Select(_) => "select",
+            Command::SwapDb(_) => "swapdb",
+            Command::Debug(_) => "debug",
+            Command::Wait(_) => "wait",
+            Command::Auth(_) => "auth",
+            Command::Hello(_) => "hello",
+            Command::Reset(_) => "reset",
+            Command::Object(_) => "object",
+            Command::Touch(_) => "touch",
+            Command::Expire(cmd) => match cmd.unit {
+                TimeUnit::Seconds => "expire",
+                TimeUnit::Millis => "pexpire",
+            },
+            Command::Ttl(cmd) => match cmd.unit {
+                TimeUnit::Seconds => "ttl",
+                TimeUnit::Millis => "pttl",
+            },
+            Command::ExpireTime(cmd) => match cmd.unit {
+                TimeUnit::Seconds => "expiretime",
+                TimeUnit::Millis => "pexpiretime",
+            },
+            Command::Slowlog(_) => "slowlog",
+            Command::Client(_) => "client",
+            Command::Subscribe(cmd) => match cmd.kind {
+                SubscriptionKind::Channel => "subscribe",
+                SubscriptionKind::Pattern => "psubscribe",
+            },
+            Command::Unsubscribe(cmd) => match cmd.kind {
+                SubscriptionKind::Channel => "unsubscribe",
+                SubscriptionKind::Pattern => "punsubscribe",
+            },
+            Command::Publish(_) => "publish",
+            Command::Multi(_) => "multi",
+            Command::Exec(_) => "exec",
+            Command::Discard(_) => "discard",
+            Command::UnknownCommand(_) => return None,
+        };
+        Some(name)
+    }
+}
+
+// like redis, the name and the arguments are cut at 128 bytes
+const UNKNOWN_COMMAND_MAX_LEN: usize = 128;
+
+impl CommandExecutor for UnknownCommand {
     fn execute(self, _: &Backend) -> RespFrame {
-        RESP_OK.clone()
+        let mut args = String::new();
+        for arg in &self.args {
+            if args.len() >= UNKNOWN_COMMAND_MAX_LEN {
+                break;
+            }
+            let arg = truncate(arg, UNKNOWN_COMMAND_MAX_LEN - args.len());
+            args.push_str(&format!("'{}' ", arg));
+        }
+        let msg = format!(
+            "ERR unknown command '{}', with args beginning with: {}",
+            truncate(&self.name, UNKNOWN_COMMAND_MAX_LEN),
+            args
+        );
+        // the error is a simple string, it must not break the line
+        SimpleError::new(msg.replace(['\r', '\n'], " ")).into()
+    }
+}
+
+impl From<RespArray> for UnknownCommand {
+    fn from(value: RespArray) -> Self {
+        let mut args = value
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg.as_bytes().unwrap_or_default()).into_owned());
+        Self {
+            name: args.next().unwrap_or_default(),
+            args: args.collect(),
+        }
     }
 }
 
+// cut at a char boundary no further than `max` bytes
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 fn validate_command(
     value: &RespArray,
     names: &[&'static str],
@@ -159,13 +619,13 @@ fn validate_command(
     }
 
     for (i, name) in names.iter().enumerate() {
-        match value[i] {
-            RespFrame::BulkString(ref cmd) => {
-                if cmd.as_ref().to_ascii_lowercase() != name.as_bytes() {
+        match &value[i] {
+            RespFrame::BulkString(cmd) => {
+                if !cmd.eq_ignore_ascii_case(name.as_bytes()) {
                     return Err(CommandError::InvalidCommand(format!(
                         "Invalid command: expected {}, got {}",
                         name,
-                        String::from_utf8_lossy(cmd.as_ref())
+                        String::from_utf8_lossy(cmd)
                     )));
                 }
             }
@@ -179,24 +639,55 @@ fn validate_command(
     Ok(())
 }
 
+// the name of the command in the frame, None if the frame is not a command
+pub(crate) fn command_name(frame: &RespFrame) -> Option<String> {
+    match frame {
+        RespFrame::Array(array) => match array.first() {
+            Some(name @ RespFrame::BulkString(_)) => name
+                .as_bytes()
+                .map(|name| String::from_utf8_lossy(name).into_owned()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 // fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
 //     Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
 // }
 
+// null bulk string 不是合法的参数, 在这里统一拒绝, 避免被当成空字符串处理
 fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
+    borrow_args(&value, start)?;
     match value.0 {
-        Some(frames) => {
-            if frames.len() > start {
-                Ok(frames.into_iter().skip(start).collect::<Vec<RespFrame>>())
-            } else {
-                Err(CommandError::InvalidArgument(
-                    "Not enough arguments".to_string(),
-                ))
-            }
-        }
-        None => Err(CommandError::InvalidArgument(
+        Some(frames) => Ok(frames.into_iter().skip(start).collect::<Vec<RespFrame>>()),
+        None => unreachable!("checked by borrow_args"),
+    }
+}
+
+// 和 extract_args 做同样的校验, 但只借用参数, 适合只需要检查参数而不需要保存的命令
+fn borrow_args(value: &RespArray, start: usize) -> Result<&[RespFrame], CommandError> {
+    let Some(frames) = value.0.as_deref() else {
+        return Err(CommandError::InvalidArgument(
             "No arguments provided".to_string(),
-        )),
+        ));
+    };
+    if let Some(i) = frames
+        .iter()
+        .skip(start)
+        .position(|frame| matches!(frame, RespFrame::BulkString(arg) if arg.is_null()))
+    {
+        return Err(CommandError::InvalidArgument(format!(
+            "argument {} must not be a null BulkString",
+            start + i
+        )));
+    }
+    if frames.len() > start {
+        Ok(&frames[start..])
+    } else {
+        Err(CommandError::InvalidArgument(
+            "Not enough arguments".to_string(),
+        ))
     }
 }
 
@@ -223,4 +714,67 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_unknown_command() -> Result<()> {
+        let mut buf = BytesMut::from(&b"*3\r\n$6\r\nFOOBAR\r\n$1\r\nx\r\n$1\r\ny\r\n"[..]);
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd = Command::try_from(frame)?;
+        assert_eq!(cmd.name(), None);
+        assert_eq!(
+            cmd.execute(&Backend::new()),
+            SimpleError::new("ERR unknown command 'FOOBAR', with args beginning with: 'x' 'y' ")
+                .into()
+        );
+
+        let long = "a".repeat(200);
+        let frame = RespArray::new([
+            BulkString::new("foobar").into(),
+            BulkString::new(long.as_str()).into(),
+            BulkString::new("b").into(),
+        ]);
+        let ret = Command::try_from(frame)?.execute(&Backend::new());
+        assert_eq!(
+            ret,
+            SimpleError::new(format!(
+                "ERR unknown command 'foobar', with args beginning with: '{}' ",
+                "a".repeat(128)
+            ))
+            .into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_argument_should_be_rejected() -> Result<()> {
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nget\r\n$-1\r\n"[..]);
+        let frame = RespArray::decode(&mut buf)?;
+        let err = Command::try_from(frame).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid argument: argument 1 must not be a null BulkString"
+        );
+
+        // 空 bulk string 是合法的 key
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nget\r\n$0\r\n\r\n"[..]);
+        let frame = RespArray::decode(&mut buf)?;
+        let Command::Get(get) = Command::try_from(frame)? else {
+            panic!("expected a get command");
+        };
+        assert_eq!(get.key, "");
+
+        let mut buf = BytesMut::from(&b"*3\r\n$4\r\nsadd\r\n$3\r\nkey\r\n$-1\r\n"[..]);
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(matches!(
+            Command::try_from(frame),
+            Err(CommandError::InvalidArgument(_))
+        ));
+
+        let frame = RespArray::new([BulkString::null().into()]);
+        assert!(matches!(
+            Command::try_from(frame),
+            Err(CommandError::InvalidCommand(_))
+        ));
+        Ok(())
+    }
 }
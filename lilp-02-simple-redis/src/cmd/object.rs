@@ -0,0 +1,207 @@
+use crate::cmd::{
+    extract_args, validate_command, CommandError, CommandExecutor, Object, ObjectAction, Touch,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, SimpleError};
+
+// none of the subcommands count as an access of the key
+impl CommandExecutor for Object {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = match self.action {
+            ObjectAction::IdleTime => backend
+                .idletime(&self.key)
+                .map(|idle| idle.as_secs() as i64),
+            ObjectAction::RefCount => backend.refcount(&self.key),
+            ObjectAction::Freq => backend
+                .access_count(&self.key)
+                .map(|count| count.min(i64::MAX as u64) as i64),
+        };
+        match ret {
+            Some(n) => RespFrame::Integer(n),
+            None => SimpleError::new("ERR no such key").into(),
+        }
+    }
+}
+
+// bump the access time like GET does, without sending the value back
+impl CommandExecutor for Touch {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let touched = self.keys.iter().filter(|key| backend.touch(key)).count();
+        RespFrame::Integer(touched as i64)
+    }
+}
+
+// OBJECT IDLETIME key / OBJECT REFCOUNT key / OBJECT FREQ key
+// *3\r\n$6\r\nOBJECT\r\n$8\r\nIDLETIME\r\n$3\r\nkey\r\n
+impl TryFrom<RespArray> for Object {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = value
+            .get(1)
+            .and_then(|arg| arg.as_bytes())
+            .map(|sub| sub.to_ascii_lowercase());
+        let (action, name) = match sub.as_deref() {
+            Some(b"idletime") => (ObjectAction::IdleTime, "idletime"),
+            Some(b"refcount") => (ObjectAction::RefCount, "refcount"),
+            Some(b"freq") => (ObjectAction::Freq, "freq"),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "unknown OBJECT subcommand".to_string(),
+                ))
+            }
+        };
+        validate_command(&value, &["object", name], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => Ok(Object {
+                action,
+                key: String::from_utf8(key)?,
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+// TOUCH key [key ...]
+// *3\r\n$5\r\nTOUCH\r\n$2\r\nk1\r\n$2\r\nk2\r\n
+impl TryFrom<RespArray> for Touch {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["touch"], n_args)?;
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(BulkString(Some(key))) => Ok(String::from_utf8(key)?),
+                _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Touch { keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{test_utils::cmd, Command};
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_object_idletime_from_resp_array() {
+        let ret = Command::try_from(cmd(&["OBJECT", "IDLETIME", "foo"]));
+        assert!(matches!(
+            ret,
+            Ok(Command::Object(Object { action: ObjectAction::IdleTime, key })) if key == "foo"
+        ));
+        let ret = Command::try_from(cmd(&["object", "Freq", "foo"]));
+        assert!(matches!(
+            ret,
+            Ok(Command::Object(Object {
+                action: ObjectAction::Freq,
+                ..
+            }))
+        ));
+
+        assert!(Command::try_from(cmd(&["object", "encoding", "foo"])).is_err());
+        assert!(Command::try_from(cmd(&["object", "idletime"])).is_err());
+        assert!(Command::try_from(cmd(&["object", "refcount", "a", "b"])).is_err());
+    }
+
+    #[test]
+    fn test_object_idletime() {
+        let backend = Backend::new();
+        let ret = backend.execute(cmd(&["object", "idletime", "foo"]));
+        assert_eq!(ret, SimpleError::new("ERR no such key").into());
+
+        backend.execute(cmd(&["set", "foo", "bar"]));
+        backend.execute(cmd(&["hset", "map", "f", "v"]));
+        backend.execute(cmd(&["sadd", "set", "m"]));
+        for key in ["foo", "map", "set"] {
+            let ret = backend.execute(cmd(&["object", "idletime", key]));
+            assert_eq!(ret, RespFrame::Integer(0));
+        }
+    }
+
+    #[test]
+    fn test_idletime_should_grow_until_access() {
+        let backend = Backend::new();
+        backend.set("foo".to_string(), BulkString::new("bar").into());
+
+        thread::sleep(Duration::from_millis(20));
+        let idle = backend.idletime("foo").unwrap();
+        assert!(idle >= Duration::from_millis(20));
+
+        // OBJECT IDLETIME itself is not an access
+        thread::sleep(Duration::from_millis(20));
+        assert!(backend.idletime("foo").unwrap() > idle);
+
+        backend.get("foo");
+        assert!(backend.idletime("foo").unwrap() < idle);
+        // missing keys are not tracked
+        assert_eq!(backend.get("missing"), None);
+        assert_eq!(backend.idletime("missing"), None);
+    }
+
+    #[test]
+    fn test_object_refcount() {
+        let backend = Backend::new();
+        let ret = backend.execute(cmd(&["object", "refcount", "foo"]));
+        assert_eq!(ret, SimpleError::new("ERR no such key").into());
+
+        backend.execute(cmd(&["set", "foo", "bar"]));
+        backend.execute(cmd(&["sadd", "set", "m"]));
+        for key in ["foo", "set"] {
+            let ret = backend.execute(cmd(&["object", "refcount", key]));
+            assert!(matches!(ret, RespFrame::Integer(n) if n >= 1), "{:?}", ret);
+        }
+
+        // small integers are shared
+        backend.execute(cmd(&["set", "n", "42"]));
+        let ret = backend.execute(cmd(&["object", "refcount", "n"]));
+        assert_eq!(ret, RespFrame::Integer(i32::MAX as i64));
+        backend.execute(cmd(&["set", "n", "042"]));
+        let ret = backend.execute(cmd(&["object", "refcount", "n"]));
+        assert_eq!(ret, RespFrame::Integer(1));
+    }
+
+    #[test]
+    fn test_object_freq_should_grow_with_accesses() {
+        let backend = Backend::new();
+        let ret = backend.execute(cmd(&["object", "freq", "foo"]));
+        assert_eq!(ret, SimpleError::new("ERR no such key").into());
+
+        backend.execute(cmd(&["set", "foo", "bar"]));
+        let freq = |backend: &Backend| match backend.execute(cmd(&["object", "freq", "foo"])) {
+            RespFrame::Integer(n) => n,
+            ret => panic!("unexpected reply {:?}", ret),
+        };
+        let before = freq(&backend);
+        assert!(before >= 1);
+        // OBJECT FREQ itself is not an access
+        assert_eq!(freq(&backend), before);
+
+        backend.execute(cmd(&["get", "foo"]));
+        backend.execute(cmd(&["touch", "foo"]));
+        assert_eq!(freq(&backend), before + 2);
+    }
+
+    #[test]
+    fn test_touch() {
+        let backend = Backend::new();
+        backend.execute(cmd(&["set", "foo", "bar"]));
+        backend.execute(cmd(&["sadd", "set", "m"]));
+        thread::sleep(Duration::from_millis(20));
+        let idle = backend.idletime("foo").unwrap();
+        assert!(idle >= Duration::from_millis(20));
+
+        let ret = backend.execute(cmd(&["touch", "foo", "missing", "set"]));
+        assert_eq!(ret, RespFrame::Integer(2));
+        assert!(backend.idletime("foo").unwrap() < idle);
+        assert!(backend.idletime("set").unwrap() < idle);
+        // touching a missing key doesn't create it
+        assert_eq!(backend.idletime("missing"), None);
+
+        assert!(Command::try_from(cmd(&["touch"])).is_err());
+    }
+}
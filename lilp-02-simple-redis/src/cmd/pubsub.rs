@@ -0,0 +1,220 @@
+use crate::cmd::{
+    extract_args, validate_command, CommandError, CommandExecutor, Publish, Subscribe, Unsubscribe,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, SubscriptionKind};
+
+// one confirmation per channel, the connection writes each of them as a reply of its own
+impl CommandExecutor for Subscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let action = match self.kind {
+            SubscriptionKind::Channel => "subscribe",
+            SubscriptionKind::Pattern => "psubscribe",
+        };
+        let replies: Vec<RespFrame> = self
+            .names
+            .into_iter()
+            .map(|name| {
+                let count = backend.subscribe(self.kind, name.clone());
+                confirmation(action, name, count)
+            })
+            .collect();
+        RespArray::new(replies).into()
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let action = match self.kind {
+            SubscriptionKind::Channel => "unsubscribe",
+            SubscriptionKind::Pattern => "punsubscribe",
+        };
+        let names = if self.names.is_empty() {
+            backend.subscriptions(self.kind)
+        } else {
+            self.names
+        };
+        // redis still confirms when there's nothing to unsubscribe from
+        if names.is_empty() {
+            let count = backend.subscription_count();
+            return RespArray::new([confirmation(action, BulkString::null(), count)]).into();
+        }
+        let replies: Vec<RespFrame> = names
+            .into_iter()
+            .map(|name| {
+                let count = backend.unsubscribe(self.kind, &name);
+                confirmation(action, name, count)
+            })
+            .collect();
+        RespArray::new(replies).into()
+    }
+}
+
+// the number of subscribers which got the message
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.publish(&self.channel, &self.message) as i64)
+    }
+}
+
+fn confirmation(action: &str, name: BulkString, count: usize) -> RespFrame {
+    RespArray::new([
+        BulkString::new(action).into(),
+        name.into(),
+        RespFrame::Integer(count as i64),
+    ])
+    .into()
+}
+
+// SUBSCRIBE channel [channel ...] / PSUBSCRIBE pattern [pattern ...]
+// *3\r\n$10\r\nPSUBSCRIBE\r\n$6\r\nnews.*\r\n$7\r\nsport.*\r\n
+impl TryFrom<RespArray> for Subscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let kind = subscription_kind(&value, "psubscribe");
+        let names = parse_names(extract_args(value, 1)?)?;
+        Ok(Subscribe { kind, names })
+    }
+}
+
+// UNSUBSCRIBE [channel ...] / PUNSUBSCRIBE [pattern ...]
+// *2\r\n$12\r\nPUNSUBSCRIBE\r\n$6\r\nnews.*\r\n
+impl TryFrom<RespArray> for Unsubscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let kind = subscription_kind(&value, "punsubscribe");
+        let names = match value.len() {
+            1 => Vec::new(),
+            _ => parse_names(extract_args(value, 1)?)?,
+        };
+        Ok(Unsubscribe { kind, names })
+    }
+}
+
+// PUBLISH channel message
+// *3\r\n$7\r\nPUBLISH\r\n$4\r\nnews\r\n$5\r\nhello\r\n
+impl TryFrom<RespArray> for Publish {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["publish"], 2)?;
+        let mut names = parse_names(extract_args(value, 1)?)?.into_iter();
+        match (names.next(), names.next()) {
+            (Some(channel), Some(message)) => Ok(Publish { channel, message }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid channel or message".to_string(),
+            )),
+        }
+    }
+}
+
+// the command is dispatched by name, so it's the pattern variant or the plain one
+fn subscription_kind(value: &RespArray, pattern_name: &str) -> SubscriptionKind {
+    let is_pattern = value
+        .first()
+        .and_then(|name| name.as_bytes())
+        .is_some_and(|name| name.eq_ignore_ascii_case(pattern_name.as_bytes()));
+    if is_pattern {
+        SubscriptionKind::Pattern
+    } else {
+        SubscriptionKind::Channel
+    }
+}
+
+fn parse_names(args: Vec<RespFrame>) -> Result<Vec<BulkString>, CommandError> {
+    args.into_iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(name) => Ok(name),
+            _ => Err(CommandError::InvalidArgument(
+                "Channels and patterns must be BulkStrings".to_string(),
+            )),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::test_utils::cmd;
+    use anyhow::Result;
+
+    fn reply(action: &str, name: Option<&str>, count: i64) -> RespFrame {
+        let name = name.map_or(BulkString::null(), BulkString::new);
+        RespArray::new([
+            BulkString::new(action).into(),
+            name.into(),
+            RespFrame::Integer(count),
+        ])
+        .into()
+    }
+
+    #[test]
+    fn test_subscribe_and_publish() -> Result<()> {
+        let backend = Backend::new();
+        let subscriber = backend.session();
+        let mut messages = subscriber.pubsub_messages().expect("not taken yet");
+
+        let ret = subscriber.execute(cmd(&["subscribe", "news", "sports"]));
+        assert_eq!(
+            ret,
+            RespArray::new([
+                reply("subscribe", Some("news"), 1),
+                reply("subscribe", Some("sports"), 2)
+            ])
+            .into()
+        );
+        let ret = subscriber.execute(cmd(&["psubscribe", "news.*"]));
+        assert_eq!(
+            ret,
+            RespArray::new([reply("psubscribe", Some("news.*"), 3)]).into()
+        );
+
+        let ret = backend.execute(cmd(&["publish", "news", "hello"]));
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert_eq!(
+            messages.try_recv()?,
+            RespArray::new([
+                BulkString::new("message").into(),
+                BulkString::new("news").into(),
+                BulkString::new("hello").into(),
+            ])
+            .into()
+        );
+        let ret = backend.execute(cmd(&["publish", "weather", "rain"]));
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        let ret = subscriber.execute(cmd(&["unsubscribe"]));
+        assert_eq!(
+            ret,
+            RespArray::new([
+                reply("unsubscribe", Some("news"), 2),
+                reply("unsubscribe", Some("sports"), 1)
+            ])
+            .into()
+        );
+        let ret = subscriber.execute(cmd(&["unsubscribe"]));
+        assert_eq!(ret, RespArray::new([reply("unsubscribe", None, 1)]).into());
+        let ret = subscriber.execute(cmd(&["punsubscribe", "news.*"]));
+        assert_eq!(
+            ret,
+            RespArray::new([reply("punsubscribe", Some("news.*"), 0)]).into()
+        );
+        let ret = backend.execute(cmd(&["publish", "news", "hello"]));
+        assert_eq!(ret, RespFrame::Integer(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pubsub_args() {
+        let backend = Backend::new();
+        for args in [
+            &["subscribe"][..],
+            &["psubscribe"],
+            &["publish", "news"],
+            &["publish", "news", "a", "b"],
+        ] {
+            assert!(matches!(backend.execute(cmd(args)), RespFrame::Error(_)));
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::cmd::{validate_command, CommandError, CommandExecutor, Reset};
+use crate::{Backend, RespArray, RespFrame, SimpleString};
+
+impl CommandExecutor for Reset {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.reset_session();
+        SimpleString::unchecked("RESET").into()
+    }
+}
+
+// RESET
+// *1\r\n$5\r\nRESET\r\n
+impl TryFrom<RespArray> for Reset {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["reset"], 0)?;
+        Ok(Reset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, SimpleError};
+
+    #[test]
+    fn test_reset_should_clear_session_state() {
+        let backend = Backend::new().with_requirepass("secret").session();
+        backend.execute(cmd(&["auth", "secret"]));
+        backend.execute(cmd(&["select", "3"]));
+        backend.execute(cmd(&["hello", "3"]));
+        let id = backend.client_id();
+
+        let ret = backend.execute(cmd(&["RESET"]));
+        assert_eq!(ret, SimpleString::unchecked("RESET").into());
+        assert_eq!(backend.selected_db(), 0);
+        assert_eq!(backend.protocol_version(), 2);
+        assert_eq!(backend.client_id(), id);
+        assert_eq!(
+            backend.execute(cmd(&["get", "k"])),
+            SimpleError::new(CommandError::NoAuth.to_string()).into()
+        );
+    }
+
+    #[test]
+    fn test_reset_is_allowed_before_auth() {
+        let backend = Backend::new().with_requirepass("secret").session();
+        let ret = backend.execute(cmd(&["reset"]));
+        assert_eq!(ret, SimpleString::unchecked("RESET").into());
+        assert!(matches!(
+            backend.execute(cmd(&["reset", "now"])),
+            RespFrame::Error(_)
+        ));
+    }
+}
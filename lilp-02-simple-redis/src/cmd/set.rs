@@ -1,5 +1,8 @@
-use crate::cmd::{extract_args, validate_command, CommandError, CommandExecutor, Sadd, Sismember};
-use crate::{BulkString, RespArray, RespEncode, RespFrame};
+use crate::cmd::{
+    extract_args, validate_command, CommandError, CommandExecutor, Sadd, Sismember, Smembers,
+    Smismember, Spop, Srandmember, Srem,
+};
+use crate::{BulkString, RespArray, RespEncode, RespFrame, RespNull, RespSet, SimpleError};
 
 impl CommandExecutor for Sadd {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
@@ -20,6 +23,67 @@ impl CommandExecutor for Sismember {
     }
 }
 
+impl CommandExecutor for Smembers {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let members: Vec<RespFrame> = backend
+            .smembers(&self.key)
+            .into_iter()
+            .map(|m| m.into())
+            .collect();
+        // RESP3 的客户端用 Set 类型, RESP2 只有数组
+        if backend.protocol_version() >= 3 {
+            RespSet::new(members).into()
+        } else {
+            RespArray::new(members).into()
+        }
+    }
+}
+
+impl CommandExecutor for Smismember {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let ret: Vec<RespFrame> = backend
+            .smismember(&self.key, &self.members)
+            .into_iter()
+            .map(|exists| RespFrame::Integer(exists as i64))
+            .collect();
+        RespArray::new(ret).into()
+    }
+}
+
+impl CommandExecutor for Spop {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let members = backend.spop(&self.key, self.count.unwrap_or(1));
+        members_to_frame(members, self.count.is_some())
+    }
+}
+
+impl CommandExecutor for Srem {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        RespFrame::Integer(backend.srem(&self.key, &self.members) as i64)
+    }
+}
+
+impl CommandExecutor for Srandmember {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.srandmember(&self.key, self.count.unwrap_or(1)) {
+            Ok(members) => members_to_frame(members, self.count.is_some()),
+            Err(_) => SimpleError::new("ERR value is out of range").into(),
+        }
+    }
+}
+
+// 不带 count 时返回单个 BulkString 或 Null, 带 count 时返回数组
+fn members_to_frame(members: Vec<BulkString>, with_count: bool) -> RespFrame {
+    if with_count {
+        let ret: Vec<RespFrame> = members.into_iter().map(|m| m.into()).collect();
+        return RespArray::new(ret).into();
+    }
+    match members.into_iter().next() {
+        Some(member) => member.into(),
+        None => RespFrame::Null(RespNull),
+    }
+}
+
 // SADD key member [member ...]
 // *4\r\n$4\r\nSADD\r\n$3\r\nkey\r\n$2\r\nm1\r\n$2\r\nm2\r\n
 impl TryFrom<RespArray> for Sadd {
@@ -69,6 +133,162 @@ impl TryFrom<RespArray> for Sismember {
     }
 }
 
+// SMEMBERS key
+// *2\r\n$8\r\nSMEMBERS\r\n$3\r\nkey\r\n
+impl TryFrom<RespArray> for Smembers {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smembers"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => Ok(Smembers {
+                key: String::from_utf8(key)?,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid Smembers key".to_string(),
+            )),
+        }
+    }
+}
+
+// SMISMEMBER key member [member ...]
+// *4\r\n$10\r\nSMISMEMBER\r\n$3\r\nkey\r\n$2\r\nm1\r\n$2\r\nm2\r\n
+impl TryFrom<RespArray> for Smismember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 3 {
+            return Err(CommandError::InvalidArgument(
+                "smismember command must have at least 2 arguments".to_string(),
+            ));
+        }
+        let n_args = value.len() - 1;
+        validate_command(&value, &["smismember"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => String::from_utf8(key)?,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid Smismember key".to_string(),
+                ))
+            }
+        };
+        let members = args
+            .map(|arg| match arg {
+                RespFrame::BulkString(member) => Ok(member),
+                _ => Err(CommandError::InvalidArgument(
+                    "Invalid Smismember member".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Smismember { key, members })
+    }
+}
+
+// SPOP key [count]
+// *3\r\n$4\r\nSPOP\r\n$3\r\nkey\r\n$1\r\n2\r\n
+impl TryFrom<RespArray> for Spop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "spop")?;
+        let count = match count {
+            Some(count) if count < 0 => {
+                return Err(CommandError::InvalidArgument(
+                    "value is out of range, must be positive".to_string(),
+                ))
+            }
+            count => count.map(|c| c as usize),
+        };
+        Ok(Spop { key, count })
+    }
+}
+
+// SREM key member [member ...]
+// *4\r\n$4\r\nSREM\r\n$3\r\nkey\r\n$2\r\nm1\r\n$2\r\nm2\r\n
+impl TryFrom<RespArray> for Srem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 3 {
+            return Err(CommandError::InvalidArgument(
+                "srem command must have at least 2 arguments".to_string(),
+            ));
+        }
+        let n_args = value.len() - 1;
+        validate_command(&value, &["srem"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => String::from_utf8(key)?,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid Srem key".to_string(),
+                ))
+            }
+        };
+        let members = args
+            .map(|arg| match arg {
+                RespFrame::BulkString(member) => Ok(member),
+                _ => Err(CommandError::InvalidArgument(
+                    "Invalid Srem member".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Srem { key, members })
+    }
+}
+
+// SRANDMEMBER key [count]
+// *3\r\n$11\r\nSRANDMEMBER\r\n$3\r\nkey\r\n$2\r\n-2\r\n
+impl TryFrom<RespArray> for Srandmember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "srandmember")?;
+        Ok(Srandmember { key, count })
+    }
+}
+
+fn extract_key_and_count(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Option<i64>), CommandError> {
+    if !(2..=3).contains(&value.len()) {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have 1 or 2 arguments",
+            name
+        )));
+    }
+    let n_args = value.len() - 1;
+    validate_command(&value, &[name], n_args)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(BulkString(Some(key)))) => String::from_utf8(key)?,
+        _ => {
+            return Err(CommandError::InvalidArgument(format!(
+                "Invalid {} key",
+                name
+            )))
+        }
+    };
+    let count = match args.next() {
+        Some(RespFrame::BulkString(BulkString(Some(count)))) => Some(
+            String::from_utf8(count)?
+                .parse::<i64>()
+                .map_err(|_| CommandError::InvalidArgument(format!("Invalid {} count", name)))?,
+        ),
+        Some(_) => {
+            return Err(CommandError::InvalidArgument(format!(
+                "Invalid {} count",
+                name
+            )))
+        }
+        None => None,
+    };
+    Ok((key, count))
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::BytesMut;
@@ -150,4 +370,205 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_smismember_from_resp_array() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$10\r\nSMISMEMBER\r\n$4\r\nlilp\r\n$2\r\nm1\r\n$2\r\nm2\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+
+        let result: Smismember = frame.try_into()?;
+        assert_eq!(result.key, "lilp");
+        assert_eq!(
+            result.members,
+            [BulkString::new("m1"), BulkString::new("m2")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_smismember_execute() -> anyhow::Result<()> {
+        let backend = crate::Backend::new();
+        let sadd = Sadd {
+            key: "lilp".to_string(),
+            members: vec![
+                BulkString::new("m1"),
+                BulkString::new("m2"),
+                BulkString::new("m3"),
+            ],
+        };
+        sadd.execute(&backend);
+
+        let members = vec![
+            BulkString::new("m1"),
+            BulkString::new("m4"),
+            BulkString::new("m2"),
+            BulkString::new("m3"),
+        ];
+        let smismember = Smismember {
+            key: "lilp".to_string(),
+            members: members.clone(),
+        };
+        let result = smismember.execute(&backend);
+        let expected = RespArray::new([1.into(), 0.into(), 1.into(), 1.into()]);
+        assert_eq!(result, expected.into());
+
+        // missing key returns all zeros
+        let smismember = Smismember {
+            key: "missing".to_string(),
+            members,
+        };
+        let result = smismember.execute(&backend);
+        let expected = RespArray::new([0.into(), 0.into(), 0.into(), 0.into()]);
+        assert_eq!(result, expected.into());
+
+        Ok(())
+    }
+
+    fn sadd_members(backend: &crate::Backend, key: &str, members: &[&str]) {
+        let sadd = Sadd {
+            key: key.to_string(),
+            members: members.iter().map(|m| BulkString::new(*m)).collect(),
+        };
+        sadd.execute(backend);
+    }
+
+    #[test]
+    fn test_spop_from_resp_array() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$4\r\nSPOP\r\n$4\r\nlilp\r\n$1\r\n2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Spop = frame.try_into()?;
+        assert_eq!(result.key, "lilp");
+        assert_eq!(result.count, Some(2));
+
+        buf.extend_from_slice(b"*3\r\n$4\r\nSPOP\r\n$4\r\nlilp\r\n$2\r\n-2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Spop::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_srandmember_from_resp_array() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$11\r\nSRANDMEMBER\r\n$4\r\nlilp\r\n$2\r\n-2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Srandmember = frame.try_into()?;
+        assert_eq!(result.key, "lilp");
+        assert_eq!(result.count, Some(-2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spop_execute() {
+        let members = ["m1", "m2", "m3", "m4", "m5"];
+        let b1 = crate::Backend::with_seed(42);
+        let b2 = crate::Backend::with_seed(42);
+        sadd_members(&b1, "lilp", &members);
+        sadd_members(&b2, "lilp", &members);
+
+        // same seed, same selection
+        let spop = |backend: &crate::Backend| {
+            Spop {
+                key: "lilp".to_string(),
+                count: Some(2),
+            }
+            .execute(backend)
+        };
+        let result = spop(&b1);
+        assert_eq!(result, spop(&b2));
+        let RespFrame::Array(RespArray(Some(popped))) = result else {
+            panic!("expected array");
+        };
+        assert_eq!(popped.len(), 2);
+        assert_eq!(b1.set.get("lilp").unwrap().len(), 3);
+
+        // popping the rest removes the set
+        let result = Spop {
+            key: "lilp".to_string(),
+            count: Some(10),
+        }
+        .execute(&b1);
+        assert!(matches!(result, RespFrame::Array(RespArray(Some(v))) if v.len() == 3));
+        assert!(b1.set.get("lilp").is_none());
+
+        // missing key
+        let result = Spop {
+            key: "lilp".to_string(),
+            count: None,
+        }
+        .execute(&b1);
+        assert_eq!(result, RespFrame::Null(RespNull));
+    }
+
+    #[test]
+    fn test_srem_execute() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$4\r\nSREM\r\n$4\r\nlilp\r\n$2\r\nm1\r\n$2\r\nm4\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let srem: Srem = frame.try_into()?;
+        assert_eq!(srem.key, "lilp");
+        assert_eq!(srem.members, [BulkString::new("m1"), BulkString::new("m4")]);
+
+        let backend = crate::Backend::new();
+        sadd_members(&backend, "lilp", &["m1", "m2"]);
+        assert_eq!(srem.execute(&backend), RespFrame::Integer(1));
+        assert_eq!(backend.smembers("lilp"), [BulkString::new("m2")]);
+
+        // removing the last member removes the set
+        let srem = Srem {
+            key: "lilp".to_string(),
+            members: vec![BulkString::new("m2")],
+        };
+        assert_eq!(srem.execute(&backend), RespFrame::Integer(1));
+        assert!(backend.set.get("lilp").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_srandmember_execute() {
+        let b1 = crate::Backend::with_seed(7);
+        let b2 = crate::Backend::with_seed(7);
+        sadd_members(&b1, "lilp", &["m1", "m2", "m3"]);
+        sadd_members(&b2, "lilp", &["m1", "m2", "m3"]);
+
+        let srandmember = |backend: &crate::Backend, count| {
+            Srandmember {
+                key: "lilp".to_string(),
+                count,
+            }
+            .execute(backend)
+        };
+        assert_eq!(srandmember(&b1, None), srandmember(&b2, None));
+        assert_eq!(srandmember(&b1, Some(2)), srandmember(&b2, Some(2)));
+
+        // positive count returns distinct members, capped at the set size
+        let RespFrame::Array(RespArray(Some(v))) = srandmember(&b1, Some(5)) else {
+            panic!("expected array");
+        };
+        assert_eq!(v.len(), 3);
+        assert!(v.iter().all(|m| v.iter().filter(|x| *x == m).count() == 1));
+
+        // negative count may return duplicates
+        let result = srandmember(&b1, Some(-10));
+        assert!(matches!(result, RespFrame::Array(RespArray(Some(v))) if v.len() == 10));
+        assert_eq!(b1.set.get("lilp").unwrap().len(), 3);
+
+        // a huge negative count is rejected instead of building the reply
+        let out_of_range: RespFrame = SimpleError::new("ERR value is out of range").into();
+        assert_eq!(srandmember(&b1, Some(i64::MIN)), out_of_range);
+        let max = crate::MAX_RANDOM_COUNT as i64;
+        assert_eq!(srandmember(&b1, Some(-max - 1)), out_of_range);
+
+        let result = Srandmember {
+            key: "missing".to_string(),
+            count: Some(2),
+        }
+        .execute(&b1);
+        assert_eq!(result, RespArray::new([]).into());
+    }
 }
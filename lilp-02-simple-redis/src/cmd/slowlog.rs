@@ -0,0 +1,159 @@
+use std::time::UNIX_EPOCH;
+
+use crate::cmd::{
+    validate_command, CommandError, CommandExecutor, Slowlog, SlowlogAction, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame};
+
+// entries returned by SLOWLOG GET without a count
+const DEFAULT_GET_COUNT: usize = 10;
+
+impl CommandExecutor for Slowlog {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let slowlog = backend.slowlog();
+        match self.action {
+            SlowlogAction::Get(count) => {
+                let count = match count {
+                    None => DEFAULT_GET_COUNT,
+                    Some(count) => usize::try_from(count).unwrap_or(usize::MAX),
+                };
+                // 每条记录: id, 结束时间 (unix 秒), 耗时 (微秒), 命令参数
+                let entries: Vec<RespFrame> = slowlog
+                    .entries(count)
+                    .into_iter()
+                    .map(|entry| {
+                        let timestamp = entry
+                            .timestamp
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs();
+                        let args: Vec<RespFrame> =
+                            entry.args.into_iter().map(RespFrame::from).collect();
+                        RespArray::new(vec![
+                            RespFrame::Integer(entry.id as i64),
+                            RespFrame::Integer(timestamp as i64),
+                            RespFrame::Integer(entry.duration.as_micros() as i64),
+                            RespArray::new(args).into(),
+                        ])
+                        .into()
+                    })
+                    .collect();
+                RespArray::new(entries).into()
+            }
+            SlowlogAction::Reset => {
+                slowlog.reset();
+                RESP_OK.clone()
+            }
+            SlowlogAction::Len => RespFrame::Integer(slowlog.len() as i64),
+        }
+    }
+}
+
+// SLOWLOG GET [count] / SLOWLOG RESET / SLOWLOG LEN
+// *3\r\n$7\r\nSLOWLOG\r\n$3\r\nGET\r\n$1\r\n5\r\n
+impl TryFrom<RespArray> for Slowlog {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = value
+            .get(1)
+            .and_then(|arg| arg.as_bytes())
+            .map(|sub| sub.to_ascii_lowercase());
+        let action = match sub.as_deref() {
+            Some(b"get") if value.len() == 2 => {
+                validate_command(&value, &["slowlog", "get"], 0)?;
+                SlowlogAction::Get(None)
+            }
+            Some(b"get") => {
+                validate_command(&value, &["slowlog", "get"], 1)?;
+                let count = value[2]
+                    .as_bytes()
+                    .and_then(|count| std::str::from_utf8(count).ok())
+                    .and_then(|count| count.parse::<i64>().ok())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument("count is not an integer".to_string())
+                    })?;
+                SlowlogAction::Get(Some(count))
+            }
+            Some(b"reset") => {
+                validate_command(&value, &["slowlog", "reset"], 0)?;
+                SlowlogAction::Reset
+            }
+            Some(b"len") => {
+                validate_command(&value, &["slowlog", "len"], 0)?;
+                SlowlogAction::Len
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "unknown SLOWLOG subcommand, try GET, RESET or LEN".to_string(),
+                ))
+            }
+        };
+        Ok(Slowlog { action })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{test_utils::cmd, Command};
+    use crate::BulkString;
+    use std::time::Duration;
+
+    #[test]
+    fn test_slowlog_from_resp_array() {
+        let action = |args: &[&str]| match Command::try_from(cmd(args)) {
+            Ok(Command::Slowlog(cmd)) => Some(cmd.action),
+            _ => None,
+        };
+        assert_eq!(action(&["SLOWLOG", "get"]), Some(SlowlogAction::Get(None)));
+        assert_eq!(
+            action(&["slowlog", "GET", "-1"]),
+            Some(SlowlogAction::Get(Some(-1)))
+        );
+        assert_eq!(action(&["slowlog", "reset"]), Some(SlowlogAction::Reset));
+        assert_eq!(action(&["slowlog", "len"]), Some(SlowlogAction::Len));
+
+        assert_eq!(action(&["slowlog"]), None);
+        assert_eq!(action(&["slowlog", "get", "x"]), None);
+        assert_eq!(action(&["slowlog", "len", "1"]), None);
+        assert_eq!(action(&["slowlog", "help"]), None);
+    }
+
+    #[test]
+    fn test_slowlog_get_len_reset() {
+        // every command is slow
+        let backend = Backend::new().with_slowlog(Duration::ZERO, 128);
+        backend.execute(cmd(&["set", "a", "1"]));
+        backend.execute(cmd(&["get", "a"]));
+
+        // SLOWLOG LEN itself is logged after it replies
+        assert_eq!(
+            backend.execute(cmd(&["slowlog", "len"])),
+            RespFrame::Integer(2)
+        );
+
+        let RespFrame::Array(RespArray(Some(entries))) =
+            backend.execute(cmd(&["slowlog", "get", "2"]))
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(entries.len(), 2);
+        let RespFrame::Array(RespArray(Some(newest))) = &entries[0] else {
+            panic!("expected an entry");
+        };
+        assert_eq!(newest[0], RespFrame::Integer(2));
+        assert_eq!(
+            newest[3],
+            RespArray::new(vec![
+                BulkString::new("slowlog").into(),
+                BulkString::new("len").into()
+            ])
+            .into()
+        );
+
+        assert_eq!(backend.execute(cmd(&["slowlog", "reset"])), RESP_OK.clone());
+        // only SLOWLOG RESET itself is left
+        assert_eq!(backend.slowlog().len(), 1);
+    }
+}
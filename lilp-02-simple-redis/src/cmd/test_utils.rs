@@ -0,0 +1,10 @@
+use crate::{BulkString, RespArray, RespFrame};
+
+/// the frame a client sends for the command `args`
+pub(crate) fn cmd(args: &[&str]) -> RespFrame {
+    let args: Vec<RespFrame> = args
+        .iter()
+        .map(|arg| BulkString::new(arg.as_bytes()).into())
+        .collect();
+    RespArray::new(args).into()
+}
@@ -0,0 +1,161 @@
+use crate::cmd::{validate_command, CommandError, CommandExecutor, Discard, Exec, Multi, RESP_OK};
+use crate::{Backend, RespArray, RespFrame, SimpleError, TransactionError};
+
+impl CommandExecutor for Multi {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.multi() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => transaction_error(e),
+        }
+    }
+}
+
+// the replies of the queued commands, errors included
+impl CommandExecutor for Exec {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        exec_reply(backend.exec())
+    }
+}
+
+// shared with the async path of the backend, which runs EXEC itself
+pub(crate) fn exec_reply(ret: Result<Vec<RespFrame>, TransactionError>) -> RespFrame {
+    match ret {
+        Ok(replies) => RespArray::new(replies).into(),
+        Err(e) => transaction_error(e),
+    }
+}
+
+impl CommandExecutor for Discard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.discard() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => transaction_error(e),
+        }
+    }
+}
+
+fn transaction_error(e: TransactionError) -> RespFrame {
+    let msg = match e {
+        TransactionError::Nested => "ERR MULTI calls can not be nested",
+        TransactionError::NotStarted => "ERR EXEC or DISCARD without MULTI",
+        TransactionError::Aborted => "EXECABORT Transaction discarded because of previous errors.",
+    };
+    SimpleError::new(msg).into()
+}
+
+// MULTI
+// *1\r\n$5\r\nMULTI\r\n
+impl TryFrom<RespArray> for Multi {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["multi"], 0)?;
+        Ok(Multi)
+    }
+}
+
+// EXEC
+// *1\r\n$4\r\nEXEC\r\n
+impl TryFrom<RespArray> for Exec {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exec"], 0)?;
+        Ok(Exec)
+    }
+}
+
+// DISCARD
+// *1\r\n$7\r\nDISCARD\r\n
+impl TryFrom<RespArray> for Discard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["discard"], 0)?;
+        Ok(Discard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, BulkString, SimpleString};
+
+    fn queued() -> RespFrame {
+        SimpleString::unchecked("QUEUED").into()
+    }
+
+    #[test]
+    fn test_exec_should_run_commands_after_a_failed_one() {
+        let backend = Backend::new().session();
+        backend.execute(cmd(&["set", "name", "lilp"]));
+
+        assert_eq!(backend.execute(cmd(&["multi"])), RESP_OK.clone());
+        assert_eq!(backend.execute(cmd(&["set", "a", "1"])), queued());
+        assert_eq!(backend.execute(cmd(&["lpush", "name", "x"])), queued());
+        assert_eq!(backend.execute(cmd(&["rpush", "jobs", "j1"])), queued());
+        // nothing runs before EXEC
+        assert_eq!(backend.get("a"), None);
+
+        let ret = backend.execute(cmd(&["exec"]));
+        assert_eq!(
+            ret,
+            RespArray::new([
+                RESP_OK.clone(),
+                SimpleError::new(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value"
+                )
+                .into(),
+                RespFrame::Integer(1),
+            ])
+            .into()
+        );
+        assert_eq!(backend.get("a"), Some(BulkString::new("1").into()));
+        assert_eq!(backend.list.get("jobs").map(|list| list.len()), Some(1));
+    }
+
+    #[test]
+    fn test_queue_error_should_abort_exec() {
+        let backend = Backend::new().session();
+        backend.execute(cmd(&["multi"]));
+        assert_eq!(backend.execute(cmd(&["set", "a", "1"])), queued());
+        assert!(matches!(
+            backend.execute(cmd(&["set", "b"])),
+            RespFrame::Error(_)
+        ));
+        assert!(matches!(
+            backend.execute(cmd(&["nosuchcommand"])),
+            RespFrame::Error(_)
+        ));
+
+        let ret = backend.execute(cmd(&["exec"]));
+        assert_eq!(
+            ret,
+            SimpleError::new("EXECABORT Transaction discarded because of previous errors.").into()
+        );
+        assert_eq!(backend.get("a"), None);
+        // the transaction is gone either way
+        assert!(matches!(
+            backend.execute(cmd(&["exec"])),
+            RespFrame::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_multi_discard() {
+        let backend = Backend::new().session();
+        assert!(matches!(
+            backend.execute(cmd(&["discard"])),
+            RespFrame::Error(_)
+        ));
+        backend.execute(cmd(&["multi"]));
+        assert_eq!(
+            backend.execute(cmd(&["multi"])),
+            SimpleError::new("ERR MULTI calls can not be nested").into()
+        );
+        backend.execute(cmd(&["set", "a", "1"]));
+        assert_eq!(backend.execute(cmd(&["discard"])), RESP_OK.clone());
+        assert_eq!(backend.get("a"), None);
+        assert_eq!(backend.execute(cmd(&["set", "a", "1"])), RESP_OK.clone());
+    }
+}
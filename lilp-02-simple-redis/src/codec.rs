@@ -0,0 +1,126 @@
+use anyhow::Result;
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{RespEncode, RespError, RespFrame, DEFAULT_MAX_DEPTH};
+
+/// codec for `Framed<TcpStream, RespCodec>`, a frame split across reads is decoded once complete
+#[derive(Debug)]
+pub struct RespCodec {
+    // frames nested deeper are rejected
+    max_depth: usize,
+}
+
+impl Default for RespCodec {
+    fn default() -> Self {
+        Self::with_max_depth(DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl RespCodec {
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Encoder<RespFrame> for RespCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: RespFrame, dst: &mut BytesMut) -> Result<()> {
+        let encoded = item.encode();
+        dst.extend_from_slice(&encoded);
+        Ok(())
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = RespFrame;
+    type Error = anyhow::Error;
+
+    // RespFrame::decode only advances the buffer once a whole frame is available
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<RespFrame>> {
+        match RespFrame::decode_with_max_depth(src, self.max_depth) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(RespError::NotComplete) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray};
+
+    const GET_HELLO: &[u8] = b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n";
+
+    #[test]
+    fn test_decode_split_frame() -> Result<()> {
+        let expected: RespFrame = RespArray::new([
+            BulkString::new("get").into(),
+            BulkString::new("hello").into(),
+        ])
+        .into();
+
+        // split at every possible position
+        for at in 1..GET_HELLO.len() {
+            let mut codec = RespCodec::default();
+            let mut buf = BytesMut::from(&GET_HELLO[..at]);
+            assert_eq!(codec.decode(&mut buf)?, None, "split at {}", at);
+            assert_eq!(&buf[..], &GET_HELLO[..at]);
+
+            buf.extend_from_slice(&GET_HELLO[at..]);
+            assert_eq!(codec.decode(&mut buf)?, Some(expected.clone()));
+            assert!(buf.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_multiple_frames() -> Result<()> {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::from(&b"+OK\r\n:1\r\n$-1"[..]);
+        assert_eq!(codec.decode(&mut buf)?, Some(RespFrame::from("OK")));
+        assert_eq!(codec.decode(&mut buf)?, Some(RespFrame::Integer(1)));
+        assert_eq!(codec.decode(&mut buf)?, None);
+        buf.extend_from_slice(b"\r\n");
+        assert_eq!(codec.decode(&mut buf)?, Some(BulkString::null().into()));
+        assert_eq!(codec.decode(&mut buf)?, None);
+
+        buf.extend_from_slice(b"?oops\r\n");
+        assert!(codec.decode(&mut buf).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_max_depth() -> Result<()> {
+        let mut codec = RespCodec::with_max_depth(2);
+        let mut buf = BytesMut::from(&b"*1\r\n*1\r\n:1\r\n"[..]);
+        let expected: RespFrame =
+            RespArray::new([RespArray::new([RespFrame::Integer(1)]).into()]).into();
+        assert_eq!(codec.decode(&mut buf)?, Some(expected));
+
+        let mut buf = BytesMut::from(&b"*1\r\n*1\r\n*1\r\n:1\r\n"[..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(
+            err.downcast::<RespError>()?,
+            RespError::InvalidFrame("max depth exceeded".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode() -> Result<()> {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::new();
+        let frame: RespFrame = RespArray::new([
+            BulkString::new("get").into(),
+            BulkString::new("hello").into(),
+        ])
+        .into();
+        codec.encode(frame, &mut buf)?;
+        codec.encode(RespFrame::Integer(1), &mut buf)?;
+        assert_eq!(&buf[..], b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n:1\r\n");
+        Ok(())
+    }
+}
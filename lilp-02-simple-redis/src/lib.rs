@@ -2,7 +2,9 @@ mod backend;
 mod resp;
 
 pub mod cmd;
+pub mod codec;
 pub mod network;
+pub mod stats;
 
 pub use backend::*;
 pub use resp::*;
@@ -1,5 +1,8 @@
 use anyhow::Result;
-use simple_redis::{network, Backend};
+use simple_redis::{
+    cmd::CommandFilter, network, stats, Backend, DEFAULT_SLOWLOG_MAX_LEN, DEFAULT_SLOWLOG_THRESHOLD,
+};
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tracing::{info, warn};
 
@@ -9,24 +12,58 @@ async fn main() -> Result<()> {
         .with_max_level(tracing::Level::INFO) // 设置日志级别为INFO
         .init();
 
+    // --trace-commands: log every decoded command, for protocol debugging
+    // --allow-commands=get,set / --deny-commands=flushall,keys: comma separated command names
+    // --requirepass=<password>: connections must AUTH before other commands
+    // --stats-port=<port>: serve GET /stats as JSON over HTTP on this port, disabled by default
+    // --slowlog-log-slower-than=<micros> / --slowlog-max-len=<n>: SLOWLOG settings, 10ms and 128 by default
+    let mut trace_commands = false;
+    let mut filter = CommandFilter::default();
+    let mut requirepass = None;
+    let mut stats_port = None;
+    let mut slowlog_threshold = DEFAULT_SLOWLOG_THRESHOLD;
+    let mut slowlog_max_len = DEFAULT_SLOWLOG_MAX_LEN;
+    for arg in std::env::args().skip(1) {
+        if arg == "--trace-commands" {
+            trace_commands = true;
+        } else if let Some(names) = arg.strip_prefix("--allow-commands=") {
+            filter = CommandFilter::allow(names.split(','));
+        } else if let Some(names) = arg.strip_prefix("--deny-commands=") {
+            filter = CommandFilter::deny(names.split(','));
+        } else if let Some(password) = arg.strip_prefix("--requirepass=") {
+            requirepass = Some(password.to_string());
+        } else if let Some(port) = arg.strip_prefix("--stats-port=") {
+            stats_port = Some(port.parse::<u16>()?);
+        } else if let Some(micros) = arg.strip_prefix("--slowlog-log-slower-than=") {
+            slowlog_threshold = Duration::from_micros(micros.parse()?);
+        } else if let Some(len) = arg.strip_prefix("--slowlog-max-len=") {
+            slowlog_max_len = len.parse()?;
+        } else {
+            warn!("Ignoring unknown argument: {}", arg);
+        }
+    }
+
     let addr = "0.0.0.0:6379";
     info!("Simple-Redis-Server is listening on {}", addr);
     let listener = TcpListener::bind(addr).await?;
 
-    let backend = Backend::new();
-    loop {
-        let (stream, raddr) = listener.accept().await?;
-        info!("Accepted connection from: {}", raddr);
-        let cloned_backend = backend.clone();
+    let mut backend = Backend::new()
+        .with_command_filter(filter)
+        .with_slowlog(slowlog_threshold, slowlog_max_len);
+    if let Some(password) = requirepass {
+        backend = backend.with_requirepass(password);
+    }
+    if let Some(port) = stats_port {
+        let addr = format!("0.0.0.0:{}", port);
+        info!("Stats endpoint is listening on {}", addr);
+        let listener = TcpListener::bind(addr).await?;
+        let backend = backend.clone();
         tokio::spawn(async move {
-            match network::stream_handler(stream, cloned_backend).await {
-                Ok(_) => {
-                    info!("Connection from {} exited", raddr);
-                }
-                Err(e) => {
-                    warn!("handle error for {}: {:?}", raddr, e);
-                }
+            if let Err(e) = stats::serve(listener, backend).await {
+                warn!("Stats endpoint stopped: {}", e);
             }
         });
     }
+    network::serve(listener, backend, trace_commands).await;
+    Ok(())
 }
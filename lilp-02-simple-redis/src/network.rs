@@ -1,42 +1,151 @@
-use crate::{
-    cmd::{Command, CommandExecutor},
-    Backend, RespDecode, RespEncode, RespError, RespFrame, SimpleError,
-};
-use anyhow::Result;
+use crate::{cmd::command_name, codec::RespCodec, Backend, RespFrame};
+use anyhow::{anyhow, Result};
 use futures::SinkExt;
-use tokio::net::TcpStream;
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+    time::sleep,
+};
 use tokio_stream::StreamExt;
-use tokio_util::codec::{Decoder, Encoder, Framed};
-use tracing::info;
-
-#[derive(Debug)]
-struct RespFrameCodec;
+use tokio_util::codec::Framed;
+use tracing::{info, warn};
 
 #[derive(Debug)]
 struct RedisRequest {
     frame: RespFrame,
     backend: Backend,
+    trace_commands: bool,
 }
 
 #[derive(Debug)]
 struct RedisResponse {
-    frame: RespFrame,
+    // (P)SUBSCRIBE and (P)UNSUBSCRIBE reply once per channel, other commands once
+    frames: Vec<RespFrame>,
+}
+
+// decrements the client count and leaves CLIENT LIST when the connection ends, whichever way it ends
+struct ClientGuard {
+    backend: Backend,
+    // notified by CLIENT KILL
+    shutdown: Arc<Notify>,
+}
+
+impl ClientGuard {
+    fn new(backend: Backend, addr: Option<SocketAddr>) -> Self {
+        backend.client_connected();
+        let shutdown = backend.register_client(addr);
+        Self { backend, shutdown }
+    }
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.backend.unsubscribe_all();
+        self.backend.unregister_client();
+        self.backend.client_disconnected();
+    }
+}
+
+/// accept connections forever, a failing connection never stops the accept loop
+pub async fn serve(listener: TcpListener, backend: Backend, trace_commands: bool) {
+    loop {
+        let (stream, raddr) = match listener.accept().await {
+            Ok(ret) => ret,
+            Err(e) => {
+                // e.g. too many open files, back off a little instead of spinning
+                warn!("Failed to accept connection: {}", e);
+                sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        info!("Accepted connection from: {}", raddr);
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            match stream_handler(stream, backend, trace_commands).await {
+                Ok(_) => {
+                    info!("Connection from {} exited", raddr);
+                }
+                Err(e) => {
+                    warn!("handle error for {}: {:?}", raddr, e);
+                }
+            }
+        });
+    }
+}
+
+/// handle a client connection, when `trace_commands` is set every decoded command is logged
+///
+/// a client going away, even in the middle of a reply, closes the connection cleanly
+pub async fn stream_handler(
+    stream: TcpStream,
+    backend: Backend,
+    trace_commands: bool,
+) -> Result<()> {
+    let peer = stream.peer_addr().ok();
+    // every connection selects its own db
+    let backend = backend.session();
+    let guard = ClientGuard::new(backend.clone(), peer);
+    match frame_handler(stream, backend, trace_commands, &guard.shutdown).await {
+        Err(e) if is_disconnect(&e) => {
+            info!("Connection from {:?} closed by peer: {}", peer, e);
+            Ok(())
+        }
+        ret => ret,
+    }
+}
+
+fn is_disconnect(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause.downcast_ref::<io::Error>().is_some_and(|e| {
+            matches!(
+                e.kind(),
+                io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::UnexpectedEof
+            )
+        })
+    })
 }
 
-pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+async fn frame_handler(
+    stream: TcpStream,
+    backend: Backend,
+    trace_commands: bool,
+    shutdown: &Notify,
+) -> Result<()> {
     // how to get a frame from the stream?
-    let mut framed = Framed::new(stream, RespFrameCodec);
+    let mut framed = Framed::new(stream, RespCodec::default());
+    let mut messages = backend
+        .pubsub_messages()
+        .ok_or_else(|| anyhow!("pub/sub messages of the session already taken"))?;
     loop {
-        match framed.next().await {
+        let frame = tokio::select! {
+            frame = framed.next() => frame,
+            // messages published to the channels the connection subscribes to
+            Some(message) = messages.recv() => {
+                framed.send(message).await?;
+                continue;
+            }
+            _ = shutdown.notified() => {
+                info!("Connection killed by CLIENT KILL");
+                return Ok(());
+            }
+        };
+        match frame {
             Some(Ok(frame)) => {
                 info!("Received frame: {:?}", frame);
                 let request = RedisRequest {
                     frame,
                     backend: backend.clone(),
+                    trace_commands,
                 };
                 let response = request_handler(request).await?;
-                info!("Sending response: {:?}", response.frame);
-                framed.send(response.frame).await?;
+                for frame in response.frames {
+                    info!("Sending response: {:?}", frame);
+                    framed.send(frame).await?;
+                }
             }
             Some(Err(e)) => return Err(e),
             None => return Ok(()),
@@ -46,38 +155,262 @@ pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
 
 async fn request_handler(request: RedisRequest) -> Result<RedisResponse> {
     let (frame, backend) = (request.frame, request.backend);
+    let split = command_name(&frame).is_some_and(|name| {
+        ["subscribe", "psubscribe", "unsubscribe", "punsubscribe"]
+            .iter()
+            .any(|cmd| name.eq_ignore_ascii_case(cmd))
+    });
+    // DEBUG SLEEP 异步等待, 不阻塞 runtime
+    let response = backend.execute_async(frame, request.trace_commands).await;
+    let frames = match response {
+        RespFrame::Array(replies) if split => replies.0.unwrap_or_default(),
+        frame => vec![frame],
+    };
+    Ok(RedisResponse { frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::test_utils::cmd, BulkString, RespArray, SimpleString};
+    use std::time::Instant;
+    use tokio::time::timeout;
+
+    async fn handle(frame: RespFrame) -> Result<RespFrame> {
+        let request = RedisRequest {
+            frame,
+            backend: Backend::new(),
+            trace_commands: true,
+        };
+        let mut frames = request_handler(request).await?.frames;
+        assert_eq!(frames.len(), 1);
+        Ok(frames.remove(0))
+    }
+
+    #[tokio::test]
+    async fn test_debug_sleep() -> Result<()> {
+        let ok: RespFrame = SimpleString::unchecked("OK").into();
+
+        let start = Instant::now();
+        assert_eq!(handle(cmd(&["debug", "sleep", "0"])).await?, ok);
+        assert!(start.elapsed() < Duration::from_millis(100));
 
-    match Command::try_from(frame) {
-        Ok(cmd) => {
-            let response = cmd.execute(&backend);
-            Ok(RedisResponse { frame: response })
+        let start = Instant::now();
+        assert_eq!(handle(cmd(&["debug", "sleep", "0.05"])).await?, ok);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_debug_sleep_inside_exec_should_not_block_runtime() -> Result<()> {
+        let backend = Backend::new();
+        for args in [
+            &["multi"][..],
+            &["debug", "sleep", "0.05"],
+            &["set", "a", "1"],
+        ] {
+            backend.execute(cmd(args));
         }
-        Err(e) => {
-            let response = SimpleError::new(e.to_string()).into();
-            Ok(RedisResponse { frame: response })
+        let exec = tokio::spawn(request_handler(RedisRequest {
+            frame: cmd(&["exec"]),
+            backend: backend.clone(),
+            trace_commands: false,
+        }));
+
+        // the test runtime has a single thread, EXEC blocking it would finish before this wakes
+        sleep(Duration::from_millis(10)).await;
+        assert!(!exec.is_finished());
+
+        let ok: RespFrame = SimpleString::unchecked("OK").into();
+        let frames = exec.await??.frames;
+        assert_eq!(frames, vec![RespArray::new(vec![ok.clone(), ok]).into()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_dropped_mid_response() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        backend.set(
+            "big".to_string(),
+            BulkString::new(vec![b'x'; 4 << 20]).into(),
+        );
+        tokio::spawn(serve(listener, backend.clone(), false));
+
+        // ask for big replies and go away without reading them
+        for _ in 0..3 {
+            let mut client = Framed::new(TcpStream::connect(addr).await?, RespCodec::default());
+            for _ in 0..4 {
+                client.send(cmd(&["get", "big"])).await?;
+            }
+            drop(client);
         }
+
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RespCodec::default());
+        client.send(cmd(&["set", "hello", "world"])).await?;
+        let ok: RespFrame = SimpleString::unchecked("OK").into();
+        assert_eq!(client.next().await.transpose()?, Some(ok));
+        drop(client);
+
+        // every connection has been cleaned up
+        timeout(Duration::from_secs(5), async {
+            while backend.connected_clients() > 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        Ok(())
     }
-}
 
-impl Encoder<RespFrame> for RespFrameCodec {
-    type Error = anyhow::Error;
+    #[test]
+    fn test_is_disconnect() {
+        let e: anyhow::Error = io::Error::from(io::ErrorKind::BrokenPipe).into();
+        assert!(is_disconnect(&e));
+        assert!(is_disconnect(&e.context("send reply")));
+        let e: anyhow::Error = io::Error::from(io::ErrorKind::PermissionDenied).into();
+        assert!(!is_disconnect(&e));
+    }
 
-    fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encoded = item.encode();
-        dst.extend_from_slice(&encoded);
+    #[tokio::test]
+    async fn test_slowlog_should_record_debug_sleep() -> Result<()> {
+        let backend = Backend::new().with_slowlog(Duration::from_millis(10), 128);
+        for args in [&["debug", "sleep", "0.02"][..], &["get", "a"]] {
+            let request = RedisRequest {
+                frame: cmd(args),
+                backend: backend.clone(),
+                trace_commands: false,
+            };
+            request_handler(request).await?;
+        }
+
+        let entries = backend.slowlog().entries(10);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].args,
+            ["debug", "sleep", "0.02"].map(BulkString::from).to_vec()
+        );
+        assert!(entries[0].duration >= Duration::from_millis(20));
+
+        let ret = backend.execute(cmd(&["slowlog", "get"]));
+        let RespFrame::Array(RespArray(Some(ret))) = ret else {
+            panic!("expected an array");
+        };
+        assert_eq!(ret.len(), 1);
         Ok(())
     }
-}
 
-impl Decoder for RespFrameCodec {
-    type Item = RespFrame;
-    type Error = anyhow::Error;
+    #[tokio::test]
+    async fn test_client_list_and_kill() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        tokio::spawn(serve(listener, backend.clone(), false));
 
-    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
-            Err(e) => Err(e.into()),
+        let mut ids = Vec::new();
+        let mut clients = Vec::new();
+        for name in ["first", "second"] {
+            let mut client = Framed::new(TcpStream::connect(addr).await?, RespCodec::default());
+            client.send(cmd(&["client", "setname", name])).await?;
+            client.next().await.transpose()?;
+            client.send(cmd(&["client", "id"])).await?;
+            let Some(RespFrame::Integer(id)) = client.next().await.transpose()? else {
+                panic!("expected an integer");
+            };
+            ids.push(id);
+            clients.push(client);
         }
+
+        let RespFrame::BulkString(list) = backend.execute(cmd(&["client", "list"])) else {
+            panic!("expected a bulk string");
+        };
+        let list = String::from_utf8(list.to_vec())?;
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", list);
+        assert!(lines[0].starts_with(&format!("id={} addr=127.0.0.1:", ids[0])));
+        assert!(lines[0].contains(" name=first age="));
+        assert!(lines[1].contains(" name=second "));
+
+        let ret = backend.execute(cmd(&["client", "kill", "id", &ids[0].to_string()]));
+        assert_eq!(ret, RespFrame::Integer(1));
+        let mut killed = clients.remove(0);
+        let ret = timeout(Duration::from_secs(5), killed.next()).await?;
+        assert!(ret.is_none());
+
+        let other = &mut clients[0];
+        other.send(cmd(&["client", "getname"])).await?;
+        assert_eq!(
+            other.next().await.transpose()?,
+            Some(BulkString::new("second").into())
+        );
+        timeout(Duration::from_secs(5), async {
+            while backend.clients().len() > 1 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        let ret = backend.execute(cmd(&["client", "kill", "id", &ids[0].to_string()]));
+        assert_eq!(ret, RespFrame::Integer(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_psubscribe() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        tokio::spawn(serve(listener, backend.clone(), false));
+
+        let mut subscriber = Framed::new(TcpStream::connect(addr).await?, RespCodec::default());
+        subscriber
+            .send(cmd(&["psubscribe", "news.*", "weather.*"]))
+            .await?;
+        for (pattern, count) in [("news.*", 1), ("weather.*", 2)] {
+            let confirmation: RespFrame = RespArray::new([
+                BulkString::new("psubscribe").into(),
+                BulkString::new(pattern).into(),
+                RespFrame::Integer(count),
+            ])
+            .into();
+            assert_eq!(subscriber.next().await.transpose()?, Some(confirmation));
+        }
+
+        let ret = backend.execute(cmd(&["publish", "sports", "goal"]));
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = backend.execute(cmd(&["publish", "news.tech", "rust 2.0"]));
+        assert_eq!(ret, RespFrame::Integer(1));
+        let message = timeout(Duration::from_secs(5), subscriber.next()).await?;
+        let expected: RespFrame = RespArray::new([
+            BulkString::new("pmessage").into(),
+            BulkString::new("news.*").into(),
+            BulkString::new("news.tech").into(),
+            BulkString::new("rust 2.0").into(),
+        ])
+        .into();
+        assert_eq!(message.transpose()?, Some(expected));
+
+        subscriber.send(cmd(&["punsubscribe", "news.*"])).await?;
+        let confirmation: RespFrame = RespArray::new([
+            BulkString::new("punsubscribe").into(),
+            BulkString::new("news.*").into(),
+            RespFrame::Integer(1),
+        ])
+        .into();
+        assert_eq!(subscriber.next().await.transpose()?, Some(confirmation));
+        let ret = backend.execute(cmd(&["publish", "news.tech", "rust 3.0"]));
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        // the patterns go away with the connection
+        drop(subscriber);
+        timeout(Duration::from_secs(5), async {
+            while backend.connected_clients() > 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        let ret = backend.execute(cmd(&["publish", "weather.today", "sunny"]));
+        assert_eq!(ret, RespFrame::Integer(0));
+        Ok(())
     }
 }
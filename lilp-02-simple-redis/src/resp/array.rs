@@ -4,7 +4,9 @@ use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 
-use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+use super::{calc_total_length, enter_nested, parse_length, BUF_CAP, CRLF_LEN, DEFAULT_MAX_DEPTH};
+
+const NULL_ARRAY: &[u8] = b"*-1\r\n";
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespArray(pub(crate) Option<Vec<RespFrame>>);
@@ -16,18 +18,22 @@ pub struct RespArray(pub(crate) Option<Vec<RespFrame>>);
 impl RespEncode for RespArray {
     fn encode(self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(BUF_CAP);
-        match self.0 {
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        match &self.0 {
             Some(frames) => {
-                buf.extend_from_slice(&format!("*{}\r\n", frames.len()).into_bytes());
+                buf.extend_from_slice(format!("*{}\r\n", frames.len()).as_bytes());
                 for frame in frames {
-                    buf.extend_from_slice(&frame.encode());
+                    frame.encode_into(buf);
                 }
             }
             None => {
-                buf.extend_from_slice(b"*-1\r\n"); // RESP的空数组表示
+                buf.extend_from_slice(NULL_ARRAY); // RESP的空数组表示
             }
         }
-        buf
     }
 }
 
@@ -38,14 +44,27 @@ impl RespEncode for RespArray {
 impl RespDecode for RespArray {
     const PREFIX: &'static str = "*";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Self::decode_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        Self::expect_length_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl RespArray {
+    // `depth` is the number of nesting levels left, this frame included
+    pub(crate) fn decode_nested(buf: &mut BytesMut, depth: usize) -> Result<Self, RespError> {
+        let depth = enter_nested(depth)?;
         println!("buf: {:?}", String::from_utf8_lossy(&buf));
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        // 如果是空数组 null array: "*-1\r\n" parse_length 中匹配到长度为-1 len 返回值是0
-        if len == 0 {
+        // 如果是空数组 null array: "*-1\r\n" parse_length 中匹配到长度为-1 len 返回值也是0
+        // 需要和长度为0的数组 "*0\r\n" 区分开
+        if buf.starts_with(NULL_ARRAY) {
             buf.advance(end + CRLF_LEN);
             return Ok(RespArray(None));
         }
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+        let total_len = calc_total_length(buf, end, len, Self::PREFIX, depth)?;
 
         if buf.len() < total_len {
             return Err(RespError::NotComplete);
@@ -56,15 +75,16 @@ impl RespDecode for RespArray {
 
         let mut frames = Vec::with_capacity(len);
         for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
+            frames.push(RespFrame::decode_with_max_depth(buf, depth)?);
         }
 
         Ok(RespArray::new(frames))
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+    pub(crate) fn expect_length_nested(buf: &[u8], depth: usize) -> Result<usize, RespError> {
+        let depth = enter_nested(depth)?;
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+        calc_total_length(buf, end, len, Self::PREFIX, depth)
     }
 }
 
@@ -125,6 +145,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_encode_into_should_match_encode() {
+        let frames: Vec<RespFrame> = vec![
+            RespArray::new(vec![
+                BulkString::new("set").into(),
+                BulkString::null().into(),
+                BulkString::new("").into(),
+                RespArray(None).into(),
+                RespArray::new([123.into(), 1.5.into(), true.into()]).into(),
+            ])
+            .into(),
+            BulkString::new("hello").into(),
+            RespArray(None).into(),
+        ];
+
+        let mut buf = Vec::new();
+        for frame in &frames {
+            let start = buf.len();
+            frame.encode_into(&mut buf);
+            assert_eq!(&buf[start..], &frame.clone().encode()[..]);
+        }
+        let expected: Vec<u8> = frames.into_iter().flat_map(|f| f.encode()).collect();
+        assert_eq!(buf, expected);
+    }
+
     // #[test]
     // fn test_null_array_encode() {
     //     let frame: RespFrame = RespNullArray.into();
@@ -162,6 +207,11 @@ mod tests {
         let frame = RespArray::decode(&mut buf)?;
         assert_eq!(frame, RespArray(None));
 
+        buf.extend_from_slice(b"*0\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert_eq!(frame, RespArray::new([]));
+        assert!(buf.is_empty());
+
         Ok(())
     }
 }
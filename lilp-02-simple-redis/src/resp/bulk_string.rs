@@ -4,7 +4,9 @@ use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError};
 
-use super::{parse_length, CRLF_LEN};
+use super::{add_length, parse_length, CRLF_LEN, MAX_BULK_LENGTH};
+
+const NULL_BULK_STRING: &[u8] = b"$-1\r\n";
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
 pub struct BulkString(pub(crate) Option<Vec<u8>>);
@@ -15,14 +17,18 @@ pub struct BulkString(pub(crate) Option<Vec<u8>>);
 // - bulk string: "$<length>\r\n<data>\r\n"
 impl RespEncode for BulkString {
     fn encode(self) -> Vec<u8> {
-        match self.0 {
-            None => b"$-1\r\n".to_vec(),
+        let mut buf = Vec::with_capacity(self.as_ref().len() + 16);
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        match &self.0 {
+            None => buf.extend_from_slice(NULL_BULK_STRING),
             Some(data) => {
-                let mut buf = Vec::with_capacity(data.len() + 16);
-                buf.extend_from_slice(&format!("${}\r\n", data.len()).into_bytes());
-                buf.extend_from_slice(&data);
+                buf.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
+                buf.extend_from_slice(data);
                 buf.extend_from_slice(b"\r\n");
-                buf
             }
         }
     }
@@ -32,12 +38,14 @@ impl RespDecode for BulkString {
     const PREFIX: &'static str = "$";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        // 空bulk string $-1\r\n  parse_length 中匹配到长度为-1 len 返回值是0
-        if len == 0 {
+        // null bulk string $-1\r\n  parse_length 中匹配到长度为-1 len 返回值也是0
+        // 需要和空 bulk string $0\r\n\r\n 区分开, 后者还有一个 CRLF
+        if buf.starts_with(NULL_BULK_STRING) {
             // end 是 前面标识长度的index 加上CRLF BulkString就结束了.解析完毕
             buf.advance(end + CRLF_LEN);
             return Ok(BulkString(None));
         }
+        check_length(len)?;
         let remained = &buf[end + CRLF_LEN..];
         if remained.len() < len + CRLF_LEN {
             return Err(RespError::NotComplete);
@@ -51,11 +59,27 @@ impl RespDecode for BulkString {
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        if len > buf.len() {
+        if buf.starts_with(NULL_BULK_STRING) {
+            return Ok(end + CRLF_LEN);
+        }
+        check_length(len)?;
+        let total = add_length(end + CRLF_LEN, len + CRLF_LEN)?;
+        // 数组中的元素会按这个长度切分 buf, 数据不完整时不能返回超出 buf 的长度
+        if total > buf.len() {
             return Err(RespError::NotComplete);
         }
-        Ok(end + CRLF_LEN + len + CRLF_LEN)
+        Ok(total)
+    }
+}
+
+fn check_length(len: usize) -> Result<(), RespError> {
+    if len > MAX_BULK_LENGTH {
+        return Err(RespError::InvalidFrame(format!(
+            "bulk string length {} exceeds {}",
+            len, MAX_BULK_LENGTH
+        )));
     }
+    Ok(())
 }
 
 // - null bulk string: "$-1\r\n"
@@ -81,6 +105,21 @@ impl BulkString {
     pub fn new(s: impl Into<Vec<u8>>) -> Self {
         BulkString(Some(s.into()))
     }
+
+    /// null bulk string ($-1\r\n)
+    pub fn null() -> Self {
+        BulkString(None)
+    }
+
+    /// 是否是 null bulk string, Deref 和 AsRef 会把它当成空的, 需要区分时用这个方法
+    pub fn is_null(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// 是否是空 bulk string ($0\r\n\r\n), null bulk string 不算空
+    pub fn is_empty(&self) -> bool {
+        matches!(&self.0, Some(data) if data.is_empty())
+    }
 }
 
 impl AsRef<[u8]> for BulkString {
@@ -159,6 +198,7 @@ mod tests {
         assert_eq!(frame, BulkString::new(b"hello"));
 
         buf.extend_from_slice(b"$5\r\nhello");
+        assert_eq!(BulkString::expect_length(&buf), Err(RespError::NotComplete));
         let ret = BulkString::decode(&mut buf);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
@@ -173,6 +213,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_null_and_empty_bulk_string_decode() -> Result<()> {
+        let mut buf = BytesMut::from(&b"$-1\r\n$0\r\n\r\n"[..]);
+        assert_eq!(BulkString::expect_length(&buf)?, 5);
+
+        let frame = BulkString::decode(&mut buf)?;
+        assert!(frame.is_null());
+        assert!(!frame.is_empty());
+
+        assert_eq!(BulkString::expect_length(&buf)?, 6);
+        let frame = BulkString::decode(&mut buf)?;
+        assert!(!frame.is_null());
+        assert!(frame.is_empty());
+        assert!(buf.is_empty());
+
+        assert_eq!(BulkString::null().encode(), b"$-1\r\n");
+        assert_eq!(BulkString::new("").encode(), b"$0\r\n\r\n");
+        Ok(())
+    }
+
     // #[test]
     // fn test_null_bulk_string_decode() -> Result<()> {
     //     let mut buf = BytesMut::new();
@@ -1,6 +1,6 @@
 use crate::{
     BulkString, RespArray, RespDecode, RespError, RespMap, RespNull, RespSet, SimpleError,
-    SimpleString,
+    SimpleString, DEFAULT_MAX_DEPTH,
 };
 use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
@@ -25,6 +25,18 @@ pub enum RespFrame {
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Self::decode_with_max_depth(buf, DEFAULT_MAX_DEPTH)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        Self::expect_length_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl RespFrame {
+    /// like `decode`, with arrays, sets and maps nested at most `max_depth` levels,
+    /// deeper frames are rejected with `RespError::InvalidFrame`
+    pub fn decode_with_max_depth(buf: &mut BytesMut, max_depth: usize) -> Result<Self, RespError> {
         let mut iter = buf.iter().peekable();
         match iter.peek() {
             Some(b'+') => {
@@ -56,7 +68,7 @@ impl RespDecode for RespFrame {
                 //     Ok(frame) => Ok(frame.into()),
                 //     Err(RespError::NotComplete) => Err(RespError::NotComplete),
                 //     Err(_) => {
-                let frame = RespArray::decode(buf)?;
+                let frame = RespArray::decode_nested(buf, max_depth)?;
                 Ok(frame.into())
                 // }
                 // }
@@ -74,11 +86,11 @@ impl RespDecode for RespFrame {
                 Ok(frame.into())
             }
             Some(b'%') => {
-                let frame = RespMap::decode(buf)?;
+                let frame = RespMap::decode_nested(buf, max_depth)?;
                 Ok(frame.into())
             }
             Some(b'~') => {
-                let frame = RespSet::decode(buf)?;
+                let frame = RespSet::decode_nested(buf, max_depth)?;
                 Ok(frame.into())
             }
             None => Err(RespError::NotComplete),
@@ -89,12 +101,13 @@ impl RespDecode for RespFrame {
         }
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+    // `depth` is the number of nesting levels left, this frame included
+    pub(crate) fn expect_length_nested(buf: &[u8], depth: usize) -> Result<usize, RespError> {
         let mut iter = buf.iter().peekable();
         match iter.peek() {
-            Some(b'*') => RespArray::expect_length(buf),
-            Some(b'~') => RespSet::expect_length(buf),
-            Some(b'%') => RespMap::expect_length(buf),
+            Some(b'*') => RespArray::expect_length_nested(buf, depth),
+            Some(b'~') => RespSet::expect_length_nested(buf, depth),
+            Some(b'%') => RespMap::expect_length_nested(buf, depth),
             Some(b'$') => BulkString::expect_length(buf),
             Some(b':') => i64::expect_length(buf),
             Some(b'+') => SimpleString::expect_length(buf),
@@ -105,6 +118,23 @@ impl RespDecode for RespFrame {
             _ => Err(RespError::NotComplete),
         }
     }
+
+    /// 借用 bulk string / simple string 的内容, 不消耗 frame, null bulk string 返回 None
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            RespFrame::BulkString(BulkString(Some(data))) => Some(data),
+            RespFrame::SimpleString(s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
+
+    /// integer frame 的值, 其他类型返回 None (不会尝试解析 bulk string)
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            RespFrame::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
 }
 
 impl From<&str> for RespFrame {
@@ -127,5 +157,59 @@ impl<const N: usize> From<&[u8; N]> for RespFrame {
 
 #[cfg(test)]
 mod tests {
-    // TODO: Add tests
+    use super::*;
+    use crate::RespEncode;
+
+    #[test]
+    fn test_as_bytes() {
+        let frame: RespFrame = BulkString::new("hello").into();
+        assert_eq!(frame.as_bytes(), Some(&b"hello"[..]));
+        let frame: RespFrame = BulkString::new("").into();
+        assert_eq!(frame.as_bytes(), Some(&b""[..]));
+        let frame: RespFrame = BulkString::null().into();
+        assert_eq!(frame.as_bytes(), None);
+        let frame: RespFrame = SimpleString::unchecked("OK").into();
+        assert_eq!(frame.as_bytes(), Some(&b"OK"[..]));
+
+        assert_eq!(RespFrame::Integer(1).as_bytes(), None);
+        assert_eq!(RespFrame::Null(RespNull).as_bytes(), None);
+        let frame: RespFrame = SimpleError::new("ERR").into();
+        assert_eq!(frame.as_bytes(), None);
+    }
+
+    #[test]
+    fn test_as_i64() {
+        assert_eq!(RespFrame::Integer(42).as_i64(), Some(42));
+        assert_eq!(RespFrame::Integer(-1).as_i64(), Some(-1));
+        assert_eq!(RespFrame::Integer(i64::MIN).as_i64(), Some(i64::MIN));
+
+        let frame: RespFrame = BulkString::new("42").into();
+        assert_eq!(frame.as_i64(), None);
+        assert_eq!(RespFrame::Double(42.0).as_i64(), None);
+        assert_eq!(RespFrame::Null(RespNull).as_i64(), None);
+    }
+
+    #[test]
+    fn test_decode_should_reject_deep_nesting() {
+        let mut frame: RespFrame = RespFrame::Integer(1);
+        for _ in 0..200 {
+            frame = RespArray::new([frame]).into();
+        }
+        let encoded = frame.encode();
+
+        let mut buf = BytesMut::from(&encoded[..]);
+        assert_eq!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrame("max depth exceeded".to_string()))
+        );
+        assert_eq!(
+            RespFrame::expect_length(&encoded),
+            Err(RespError::InvalidFrame("max depth exceeded".to_string()))
+        );
+
+        // the limit is configurable
+        let mut buf = BytesMut::from(&encoded[..]);
+        assert!(RespFrame::decode_with_max_depth(&mut buf, 200).is_ok());
+        assert!(buf.is_empty());
+    }
 }
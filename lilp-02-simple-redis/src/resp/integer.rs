@@ -5,10 +5,20 @@ use crate::{RespDecode, RespEncode, RespError};
 use super::{extract_simple_frame_data, CRLF_LEN};
 
 // - integer: ":[<+|->]<value>\r\n"
+// 不需要处理正负号 否则会出现错误 Error: Bad integer value
+// 计数类的回复很频繁, 用 itoa 直接写入 buf, 避免 format! 分配字符串
 impl RespEncode for i64 {
     fn encode(self) -> Vec<u8> {
-        // 不需要处理正负号 否则会出现错误 Error: Bad integer value
-        format!(":{}\r\n", self).into_bytes()
+        // ":" + 最多20个字符 + CRLF
+        let mut buf = Vec::with_capacity(23);
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.push(b':');
+        buf.extend_from_slice(itoa::Buffer::new().format(*self).as_bytes());
+        buf.extend_from_slice(b"\r\n");
     }
 }
 
@@ -19,7 +29,7 @@ impl RespDecode for i64 {
         // split the buffer
         let data = buf.split_to(end + CRLF_LEN);
         let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(s.parse()?)
+        parse_integer(&s)
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -28,6 +38,25 @@ impl RespDecode for i64 {
     }
 }
 
+// 显式处理正负号, 空值和溢出都视为非法帧, 而不是 ParseIntError
+fn parse_integer(s: &str) -> Result<i64, RespError> {
+    let (negative, digits) = match s.as_bytes().first() {
+        Some(b'+') => (false, &s[1..]),
+        Some(b'-') => (true, &s[1..]),
+        _ => (false, s),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RespError::InvalidFrame(format!("invalid integer: {:?}", s)));
+    }
+
+    let ret = if negative {
+        format!("-{}", digits).parse::<i64>()
+    } else {
+        digits.parse::<i64>()
+    };
+    ret.map_err(|_| RespError::InvalidFrame(format!("integer out of range: {}", s)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,14 +75,17 @@ mod tests {
         println!("{:?}", String::from_utf8(frame.encode()));
     }
 
-    #[ignore]
     #[test]
     fn test_integer_encode() {
         let frame: RespFrame = 123.into();
-        assert_eq!(frame.encode(), b":+123\r\n");
+        assert_eq!(frame.encode(), b":123\r\n");
 
-        let frame: RespFrame = (-123).into();
-        assert_eq!(frame.encode(), b":-123\r\n");
+        let frame: RespFrame = (-5).into();
+        assert_eq!(frame.encode(), b":-5\r\n");
+
+        assert_eq!(0i64.encode(), b":0\r\n");
+        assert_eq!(i64::MIN.encode(), b":-9223372036854775808\r\n");
+        assert_eq!(i64::MAX.encode(), b":9223372036854775807\r\n");
     }
 
     #[test]
@@ -71,4 +103,47 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_integer_decode_bounds() -> Result<()> {
+        let mut buf = BytesMut::from(&b":9223372036854775807\r\n"[..]);
+        assert_eq!(i64::decode(&mut buf)?, i64::MAX);
+
+        let mut buf = BytesMut::from(&b":-9223372036854775808\r\n"[..]);
+        assert_eq!(i64::decode(&mut buf)?, i64::MIN);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_decode_out_of_range() {
+        let mut buf = BytesMut::from(&b":99999999999999999999\r\n"[..]);
+        let ret = i64::decode(&mut buf);
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))));
+
+        let mut buf = BytesMut::from(&b":-9223372036854775809\r\n"[..]);
+        let ret = i64::decode(&mut buf);
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))));
+    }
+
+    #[test]
+    fn test_integer_decode_invalid() {
+        for data in [
+            &b":\r\n"[..],
+            b":+\r\n",
+            b":-\r\n",
+            b":+-1\r\n",
+            b":12a\r\n",
+        ] {
+            let mut buf = BytesMut::from(data);
+            let ret = i64::decode(&mut buf);
+            assert!(matches!(ret, Err(RespError::InvalidFrame(_))), "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn test_integer_decode_not_complete() {
+        let mut buf = BytesMut::from(&b":123\r"[..]);
+        assert_eq!(i64::decode(&mut buf).unwrap_err(), RespError::NotComplete);
+    }
 }
@@ -1,12 +1,12 @@
 use bytes::{Buf, BytesMut};
 
-use crate::{RespDecode, RespEncode, RespError, RespFrame, SimpleString};
+use crate::{BulkString, RespDecode, RespEncode, RespError, RespFrame, SimpleString};
 use std::{
     collections::BTreeMap,
     ops::{Deref, DerefMut},
 };
 
-use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+use super::{calc_total_length, enter_nested, parse_length, BUF_CAP, CRLF_LEN, DEFAULT_MAX_DEPTH};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespMap(pub(crate) BTreeMap<String, RespFrame>);
@@ -18,7 +18,12 @@ impl RespEncode for RespMap {
         let mut buf = Vec::with_capacity(BUF_CAP);
         buf.extend_from_slice(&format!("%{}\r\n", self.len()).into_bytes());
         for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
+            // key 含有 CR/LF 时退化为 BulkString
+            let key = match SimpleString::new(key.as_str()) {
+                Ok(key) => key.encode(),
+                Err(_) => BulkString::new(key).encode(),
+            };
+            buf.extend_from_slice(&key);
             buf.extend_from_slice(&value.encode());
         }
         buf
@@ -29,8 +34,20 @@ impl RespEncode for RespMap {
 impl RespDecode for RespMap {
     const PREFIX: &'static str = "%";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Self::decode_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        Self::expect_length_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl RespMap {
+    // `depth` is the number of nesting levels left, this frame included
+    pub(crate) fn decode_nested(buf: &mut BytesMut, depth: usize) -> Result<Self, RespError> {
+        let depth = enter_nested(depth)?;
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+        let total_len = calc_total_length(buf, end, len, Self::PREFIX, depth)?;
 
         if buf.len() < total_len {
             return Err(RespError::NotComplete);
@@ -41,16 +58,17 @@ impl RespDecode for RespMap {
         let mut frames = RespMap::new();
         for _ in 0..len {
             let key = SimpleString::decode(buf)?;
-            let value = RespFrame::decode(buf)?;
+            let value = RespFrame::decode_with_max_depth(buf, depth)?;
             frames.insert(key.0, value);
         }
 
         Ok(frames)
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+    pub(crate) fn expect_length_nested(buf: &[u8], depth: usize) -> Result<usize, RespError> {
+        let depth = enter_nested(depth)?;
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+        calc_total_length(buf, end, len, Self::PREFIX, depth)
     }
 }
 
@@ -83,7 +101,6 @@ impl DerefMut for RespMap {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::BulkString;
     use anyhow::Result;
 
     #[test]
@@ -102,6 +119,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_map_encode_crlf_key() {
+        let mut map = RespMap::new();
+        map.insert("a\r\nb".to_string(), 1.into());
+
+        let frame: RespFrame = map.into();
+        assert_eq!(&frame.encode(), b"%1\r\n$4\r\na\r\nb\r\n:1\r\n");
+    }
+
     #[test]
     fn test_map_decode() -> Result<()> {
         let mut buf = BytesMut::new();
@@ -17,6 +17,12 @@ use thiserror::Error;
 const BUF_CAP: usize = 4096;
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
+// like proto-max-bulk-len of redis, a longer bulk string is rejected instead of waiting for it
+const MAX_BULK_LENGTH: usize = 512 * 1024 * 1024;
+
+/// arrays, sets and maps nested deeper than this are rejected by `RespDecode::decode`,
+/// so malicious input can't blow the stack
+pub const DEFAULT_MAX_DEPTH: usize = 128;
 
 pub use self::{
     array::RespArray, bulk_string::BulkString, frame::RespFrame, map::RespMap, null::RespNull,
@@ -24,8 +30,14 @@ pub use self::{
 };
 
 #[enum_dispatch]
-pub trait RespEncode {
+pub trait RespEncode: Clone {
     fn encode(self) -> Vec<u8>;
+
+    /// 编码到调用方提供的 buf 中, 批量编码时可以复用同一个 buf, 避免每个 frame 都分配一次
+    /// 默认实现会 clone 后调用 encode, 频繁使用的类型需要自己实现
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.clone().encode());
+    }
 }
 
 pub trait RespDecode: Sized {
@@ -117,16 +129,23 @@ fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
     Ok((end, s.parse()?))
 }
 
-fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
+// `depth` is the number of nesting levels left for the elements
+fn calc_total_length(
+    buf: &[u8],
+    end: usize,
+    len: usize,
+    prefix: &str,
+    depth: usize,
+) -> Result<usize, RespError> {
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
     match prefix {
         "*" | "~" => {
             // find nth CRLF in the buffer, for array and set, we need to find 1 CRLF for each element
             for _ in 0..len {
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
+                let len = RespFrame::expect_length_nested(data, depth)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
+                total = add_length(total, len)?;
             }
             Ok(total)
         }
@@ -134,21 +153,33 @@ fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result
             // find nth CRLF in the buffer. For map, we need to find 2 CRLF for each key-value pair
             for _ in 0..len {
                 let len = SimpleString::expect_length(data)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
+                total = add_length(total, len)?;
 
-                data = &data[len..];
-                total += len;
-
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
+                let len = RespFrame::expect_length_nested(data, depth)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
+                total = add_length(total, len)?;
             }
             Ok(total)
         }
         // for other types, we just need to find the length of the data
-        _ => Ok(len as usize + CRLF_LEN),
+        _ => add_length(len, CRLF_LEN),
     }
 }
 
+// one level deeper into an array, set or map, returns the levels left for its elements
+fn enter_nested(depth: usize) -> Result<usize, RespError> {
+    depth
+        .checked_sub(1)
+        .ok_or_else(|| RespError::InvalidFrame("max depth exceeded".to_string()))
+}
+
+// the lengths come from the peer, they must not overflow
+fn add_length(a: usize, b: usize) -> Result<usize, RespError> {
+    a.checked_add(b)
+        .ok_or_else(|| RespError::InvalidFrame("frame length overflows".to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,14 +190,34 @@ mod tests {
         let buf = b"*2\r\n$3\r\nset\r\n$5\r\nhello\r\n";
         let (end, len) = parse_length(buf, "*")?;
         println!("end: {}, len: {}", end, len);
-        let total_len = calc_total_length(buf, end, len, "*")?;
+        let total_len = calc_total_length(buf, end, len, "*", DEFAULT_MAX_DEPTH)?;
         assert_eq!(total_len, buf.len());
 
         let buf = b"*2\r\n$3\r\nset\r\n";
         let (end, len) = parse_length(buf, "*")?;
-        let ret = calc_total_length(buf, end, len, "*");
+        let ret = calc_total_length(buf, end, len, "*", DEFAULT_MAX_DEPTH);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
         Ok(())
     }
+
+    #[test]
+    fn test_calc_array_length_should_reject_huge_nested_length() -> Result<()> {
+        let buf = format!("*2\r\n$3\r\nset\r\n${}\r\nhello\r\n", isize::MAX);
+        let (end, len) = parse_length(buf.as_bytes(), "*")?;
+        let ret = calc_total_length(buf.as_bytes(), end, len, "*", DEFAULT_MAX_DEPTH);
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))), "{:?}", ret);
+
+        let buf = format!("*1\r\n*1\r\n${}\r\n", MAX_BULK_LENGTH + 1);
+        let ret = RespArray::expect_length(buf.as_bytes());
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))), "{:?}", ret);
+
+        assert_eq!(
+            add_length(usize::MAX, CRLF_LEN),
+            Err(RespError::InvalidFrame(
+                "frame length overflows".to_string()
+            ))
+        );
+        Ok(())
+    }
 }
@@ -3,7 +3,7 @@ use bytes::{Buf, BytesMut};
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 use std::ops::Deref;
 
-use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+use super::{calc_total_length, enter_nested, parse_length, BUF_CAP, CRLF_LEN, DEFAULT_MAX_DEPTH};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespSet(pub(crate) Vec<RespFrame>);
@@ -24,9 +24,21 @@ impl RespEncode for RespSet {
 impl RespDecode for RespSet {
     const PREFIX: &'static str = "~";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Self::decode_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        Self::expect_length_nested(buf, DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl RespSet {
+    // `depth` is the number of nesting levels left, this frame included
+    pub(crate) fn decode_nested(buf: &mut BytesMut, depth: usize) -> Result<Self, RespError> {
+        let depth = enter_nested(depth)?;
         let (end, len) = parse_length(buf, Self::PREFIX)?;
 
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+        let total_len = calc_total_length(buf, end, len, Self::PREFIX, depth)?;
 
         if buf.len() < total_len {
             return Err(RespError::NotComplete);
@@ -36,15 +48,16 @@ impl RespDecode for RespSet {
 
         let mut frames = Vec::new();
         for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
+            frames.push(RespFrame::decode_with_max_depth(buf, depth)?);
         }
 
         Ok(RespSet::new(frames))
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+    pub(crate) fn expect_length_nested(buf: &[u8], depth: usize) -> Result<usize, RespError> {
+        let depth = enter_nested(depth)?;
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+        calc_total_length(buf, end, len, Self::PREFIX, depth)
     }
 }
 
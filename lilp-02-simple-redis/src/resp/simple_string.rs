@@ -10,7 +10,20 @@ use super::{extract_simple_frame_data, CRLF_LEN};
 pub struct SimpleString(pub(crate) String);
 
 impl SimpleString {
-    pub fn new(s: impl Into<String>) -> Self {
+    // simple string 不能包含 CR/LF, 否则会破坏帧结构
+    pub fn new(s: impl Into<String>) -> Result<Self, RespError> {
+        let s = s.into();
+        if s.contains(['\r', '\n']) {
+            return Err(RespError::InvalidFrame(format!(
+                "simple string contains CR or LF: {:?}",
+                s
+            )));
+        }
+        Ok(SimpleString(s))
+    }
+
+    // only for known-safe literals, e.g. "OK"
+    pub fn unchecked(s: impl Into<String>) -> Self {
         SimpleString(s.into())
     }
 }
@@ -31,7 +44,7 @@ impl RespDecode for SimpleString {
         // split the buffer 截出第一片数据 +OK\r\n
         let data = buf.split_to(end + CRLF_LEN);
         let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        Ok(SimpleString::new(s.to_string()))
+        Ok(SimpleString::unchecked(s.to_string()))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -69,7 +82,7 @@ mod tests {
 
     #[test]
     fn test_simple_string_encode() {
-        let frame: RespFrame = SimpleString::new("OK".to_string()).into();
+        let frame: RespFrame = SimpleString::unchecked("OK".to_string()).into();
 
         assert_eq!(frame.encode(), b"+OK\r\n");
     }
@@ -80,7 +93,7 @@ mod tests {
         buf.extend_from_slice(b"+OK\r\n");
 
         let frame = SimpleString::decode(&mut buf)?;
-        assert_eq!(frame, SimpleString::new("OK".to_string()));
+        assert_eq!(frame, SimpleString::unchecked("OK".to_string()));
 
         buf.extend_from_slice(b"+hello\r");
 
@@ -89,7 +102,18 @@ mod tests {
 
         buf.put_u8(b'\n');
         let frame = SimpleString::decode(&mut buf)?;
-        assert_eq!(frame, SimpleString::new("hello".to_string()));
+        assert_eq!(frame, SimpleString::unchecked("hello".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simple_string_new() -> Result<()> {
+        assert_eq!(SimpleString::new("OK")?, SimpleString::unchecked("OK"));
+
+        let ret = SimpleString::new("a\r\nb");
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))));
+        assert!(SimpleString::new("a\nb").is_err());
 
         Ok(())
     }
@@ -0,0 +1,85 @@
+use crate::Backend;
+use axum::{extract::State, routing::get, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io};
+use tokio::net::TcpListener;
+
+/// read-only view of the server for dashboards, served over HTTP as JSON
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Stats {
+    /// keys in all dbs
+    pub dbsize: usize,
+    pub connected_clients: usize,
+    /// number of calls per command name
+    pub commands: BTreeMap<String, u64>,
+}
+
+impl Stats {
+    pub fn new(backend: &Backend) -> Self {
+        Self {
+            dbsize: backend.keyspaces().iter().map(|ks| ks.key_count()).sum(),
+            connected_clients: backend.connected_clients(),
+            commands: backend.command_stats().into_iter().collect(),
+        }
+    }
+}
+
+/// `GET /stats`, sharing the data with the RESP server through `backend`
+pub fn router(backend: Backend) -> Router {
+    Router::new()
+        .route("/stats", get(stats_handler))
+        .with_state(backend)
+}
+
+/// serve the stats endpoint until the listener fails
+pub async fn serve(listener: TcpListener, backend: Backend) -> io::Result<()> {
+    axum::serve(listener, router(backend)).await
+}
+
+async fn stats_handler(State(backend): State<Backend>) -> Json<Stats> {
+    Json(Stats::new(&backend))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::test_utils::cmd;
+    use anyhow::Result;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    #[tokio::test]
+    async fn test_stats_endpoint() -> Result<()> {
+        let backend = Backend::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(serve(listener, backend.clone()));
+
+        let session = backend.session();
+        for key in ["a", "b", "c"] {
+            session.execute(cmd(&["set", key, "1"]));
+        }
+        session.execute(cmd(&["GET", "a"]));
+        session.execute(cmd(&["nosuchcommand"]));
+
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut res = String::new();
+        stream.read_to_string(&mut res).await?;
+        assert!(res.starts_with("HTTP/1.1 200 OK"), "{}", res);
+
+        let (_, body) = res.split_once("\r\n\r\n").unwrap();
+        let stats: Stats = serde_json::from_str(body)?;
+        assert_eq!(stats.dbsize, 3);
+        assert_eq!(stats.connected_clients, 0);
+        assert_eq!(
+            stats.commands,
+            BTreeMap::from([("get".to_string(), 1), ("set".to_string(), 3)])
+        );
+        Ok(())
+    }
+}
@@ -1,46 +1,181 @@
 //! 该案例实现了一个简单的基于 TCP 的聊天服务器。
 //! 主要功能包括：用户连接、断开连接、发送和接收消息的处理。
 use anyhow::Result;
+use bytes::BytesMut;
+use chrono::{DateTime, Utc};
+use clap::Parser;
 use console_subscriber::ConsoleLayer;
 use dashmap::DashMap;
 use futures::{stream::SplitStream, SinkExt, StreamExt};
-use std::{fmt, net::SocketAddr, sync::Arc};
-use tokio::sync::watch;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use std::{
+    env, fmt,
+    fs::{File as StdFile, OpenOptions},
+    future::Future,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::{mpsc, watch};
+use tokio::{fs::File, io::AsyncWriteExt, task::JoinHandle};
 use tokio::{
     net::{TcpListener, TcpStream},
-    sync::broadcast,
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio::{
+    signal,
+    time::{sleep, timeout},
 };
-use tokio_util::codec::{Framed, LinesCodec};
+use tokio_util::codec::{Decoder, Encoder, Framed, LinesCodec, LinesCodecError};
 use tracing::{info, level_filters::LevelFilter, warn};
 use tracing_subscriber::{
     fmt::Layer as FmtLayer, layer::SubscriberExt, util::SubscriberInitExt, Layer as _,
 };
 
+const BIND_ADDR: &str = "0.0.0.0:8080";
 const MAX_MESSAGES: usize = 128;
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+const MAX_USERNAME_LENGTH: usize = 32;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const MAX_PEERS: usize = 1024;
+/// 慢的peer错过了最旧的消息时收到的提示
+const DROPPED_NOTICE: &str = "(messages dropped)";
+/// 关闭服务器时等待消息发送完成的时间
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(100);
+
+/// 命令行参数
+#[derive(Debug, Parser)]
+struct Opts {
+    /// 配置文件，未指定时读取环境变量 `CHAT_CONFIG`，都没有时使用默认配置
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// 监听地址，覆盖配置文件中的 `bind`
+    #[arg(long)]
+    bind: Option<String>,
+    /// 聊天记录的路径
+    transcript: Option<PathBuf>,
+}
+
+/// 服务器配置，可以从 YAML 文件读取，未设置的项使用默认值
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct ChatConfig {
+    /// 监听地址
+    bind: String,
+    /// 广播队列的长度，peer落后更多时会丢失最旧的消息
+    capacity: usize,
+    /// 单行消息的最大长度，超过的行会被丢弃
+    max_line_length: usize,
+    /// 用户名的最大字符数
+    max_username_length: usize,
+    /// 超过该时间没有收到消息就断开连接，配置文件中以秒为单位
+    #[serde_as(as = "DurationSeconds<u64>")]
+    idle_timeout: Duration,
+    /// 最大在线peer数量
+    max_peers: usize,
+    /// 每个peer待发送消息队列的长度，满了之后丢弃最旧的消息
+    write_buffer: usize,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            bind: BIND_ADDR.to_string(),
+            capacity: MAX_MESSAGES,
+            max_line_length: MAX_LINE_LENGTH,
+            max_username_length: MAX_USERNAME_LENGTH,
+            idle_timeout: IDLE_TIMEOUT,
+            max_peers: MAX_PEERS,
+            write_buffer: MAX_MESSAGES,
+        }
+    }
+}
+
+impl ChatConfig {
+    /// 读取配置文件，`path` 为空时使用环境变量 `CHAT_CONFIG`，都没有时使用默认配置
+    fn load(path: Option<PathBuf>) -> Result<Self> {
+        let path = path.or_else(|| env::var_os("CHAT_CONFIG").map(PathBuf::from));
+        match path {
+            Some(path) => Ok(serde_yaml::from_reader(StdFile::open(path)?)?),
+            None => Ok(Self::default()),
+        }
+    }
+}
 
 /// 保存服务器状态，包括在线的peer和消息发送者
 #[derive(Debug)]
 struct State {
+    config: ChatConfig,
     peers: DashMap<SocketAddr, String>,
+    /// 已占用的peer名额，检查和占用是一次原子操作，并发加入时也不会超过`max_peers`
+    online: AtomicUsize,
+    /// 每个peer发送流的关闭信号
+    shutdowns: DashMap<SocketAddr, watch::Sender<()>>,
     sender: broadcast::Sender<Arc<Message>>,
+    /// 可选的聊天记录
+    transcript: Option<Transcript>,
 }
 
 impl State {
     /// 创建一个新的State实例
-    fn new() -> Self {
-        let (sender, _) = broadcast::channel(MAX_MESSAGES);
+    fn new(config: ChatConfig, transcript: Option<Transcript>) -> Self {
+        let (sender, _) = broadcast::channel(config.capacity);
         State {
+            config,
             peers: DashMap::new(),
+            online: AtomicUsize::new(0),
+            shutdowns: DashMap::new(),
             sender,
+            transcript,
         }
     }
 }
 
+/// 聊天记录，由单独的任务追加写入 JSONL 文件，避免磁盘 IO 阻塞广播
+#[derive(Debug)]
+struct Transcript {
+    /// 关闭后为`None`，之后的记录会被丢弃
+    sender: Mutex<Option<mpsc::UnboundedSender<TranscriptEntry>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// 聊天记录中的一行
+#[derive(Debug, Serialize)]
+struct TranscriptEntry {
+    timestamp: DateTime<Utc>,
+    event: &'static str,
+    sender: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+}
+
 /// 表示一个连接的peer
 #[derive(Debug)]
 struct Peer {
     username: String,
-    stream: SplitStream<Framed<TcpStream, LinesCodec>>,
+    stream: SplitStream<Framed<TcpStream, ChatCodec>>,
+    /// 只发给该peer的消息，比如错误提示，和广播的消息共用发送队列
+    private: mpsc::Sender<String>,
+    /// 服务器关闭时收到通知
+    shutdown: watch::Receiver<()>,
+}
+
+/// 包装 `LinesCodec`，超长的行作为 `Line::TooLong` 返回而不是错误，
+/// 因为 `Framed` 在解码出错后就会结束
+#[derive(Debug)]
+struct ChatCodec(LinesCodec);
+
+/// 解码出的一行
+#[derive(Debug)]
+enum Line {
+    Text(String),
+    TooLong,
 }
 
 /// 表示聊天消息的枚举类型
@@ -52,6 +187,8 @@ enum Message {
     UserLeft(String),
     /// 用户发送的聊天消息
     Chat { sender: String, content: String },
+    /// 服务器发出的通知
+    Server(String),
 }
 
 /// 主函数，启动聊天服务器
@@ -74,20 +211,52 @@ async fn main() -> Result<()> {
         server.serve().await.unwrap();
     });
 
-    let addr = "0.0.0.0:8080";
-    let listener = TcpListener::bind(addr).await?;
-    info!("Starting chat server on {}", addr);
-    let state = Arc::new(State::new());
+    let opts = Opts::parse();
+    let mut config = ChatConfig::load(opts.config)?;
+    if let Some(bind) = opts.bind {
+        config.bind = bind;
+    }
+    let listener = TcpListener::bind(&config.bind).await?;
+    info!("Starting chat server on {}", config.bind);
+    let transcript = opts.transcript.map(Transcript::open).transpose()?;
+    let state = Arc::new(State::new(config, transcript));
+
+    run(listener, state, async {
+        let _ = signal::ctrl_c().await;
+    })
+    .await
+}
 
+/// 接受连接直到收到关闭信号
+///
+/// # 参数
+/// - `listener` - 监听的 TCP 套接字
+/// - `state` - 包含当前服务器状态的共享指针
+/// - `shutdown` - 完成时关闭服务器
+async fn run(
+    listener: TcpListener,
+    state: Arc<State>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    tokio::pin!(shutdown);
     loop {
-        let (stream, addr) = listener.accept().await?;
-        info!("Accepted connection from: {}", addr);
-        let state_cloned = state.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_client(state_cloned, addr, stream).await {
-                warn!("Failed to handle client {}: {}", addr, e);
+        tokio::select! {
+            _ = &mut shutdown => {
+                info!("Shutting down chat server");
+                state.shutdown().await;
+                return Ok(());
             }
-        });
+            ret = listener.accept() => {
+                let (stream, addr) = ret?;
+                info!("Accepted connection from: {}", addr);
+                let state_cloned = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_client(state_cloned, addr, stream).await {
+                        warn!("Failed to handle client {}: {}", addr, e);
+                    }
+                });
+            }
+        }
     }
 }
 
@@ -101,26 +270,72 @@ async fn main() -> Result<()> {
 /// # 返回
 /// 如果成功则返回 `Ok(())`，否则返回错误。
 async fn handle_client(state: Arc<State>, addr: SocketAddr, stream: TcpStream) -> Result<()> {
-    let mut stream = Framed::new(stream, LinesCodec::new());
+    let codec = ChatCodec(LinesCodec::new_with_max_length(
+        state.config.max_line_length,
+    ));
+    let mut stream = Framed::new(stream, codec);
+
+    // 人数已满时直接拒绝，不用再输入用户名，名额在`State::add`中才真正占用
+    if state.online.load(Ordering::Acquire) >= state.config.max_peers {
+        return reject_full(addr, stream).await;
+    }
+
     stream.send("Enter your username:").await?;
 
     let username = match stream.next().await {
-        Some(Ok(username)) => username,
+        Some(Ok(Line::Text(username))) => trim_cr(username),
+        Some(Ok(Line::TooLong)) => return Err(LinesCodecError::MaxLineLengthExceeded.into()),
         Some(Err(e)) => return Err(e.into()),
         None => return Ok(()),
     };
 
-    // 用于关闭客户端peer发送流
-    let (_shutdown_tx, shutdown_rx) = watch::channel(());
-    let mut peer = state.add(addr, username, stream, shutdown_rx).await;
+    if username.chars().count() > state.config.max_username_length {
+        let msg = format!(
+            "[error: username exceeds {} characters]",
+            state.config.max_username_length
+        );
+        stream.send(msg).await?;
+        return Ok(());
+    }
+
+    let mut peer = match state.add(addr, username, stream).await {
+        Ok(peer) => peer,
+        Err(stream) => return reject_full(addr, stream).await,
+    };
 
     let message = Arc::new(Message::user_joined(&peer.username));
     info!("{}", message);
     state.broadcast(message.clone()).await;
 
-    while let Some(line) = peer.stream.next().await {
+    loop {
+        // 长时间不发消息的连接会被断开
+        let line = tokio::select! {
+            _ = peer.shutdown.changed() => break,
+            line = timeout(state.config.idle_timeout, peer.stream.next()) => line,
+        };
+        let line = match line {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(_) => {
+                info!(
+                    "{} is idle for {:?}, disconnecting",
+                    addr, state.config.idle_timeout
+                );
+                let _ = peer.private.send("[idle timeout, bye]".to_string()).await;
+                break;
+            }
+        };
         let line = match line {
-            Ok(line) => line,
+            Ok(Line::Text(line)) => trim_cr(line),
+            // 超长的行只提示发送者并丢弃，不断开连接
+            Ok(Line::TooLong) => {
+                let msg = format!(
+                    "[error: line exceeds {} bytes, dropped]",
+                    state.config.max_line_length
+                );
+                let _ = peer.private.send(msg).await;
+                continue;
+            }
             Err(e) => {
                 warn!("Failed to read line from {}: {}", addr, e);
                 break;
@@ -130,81 +345,255 @@ async fn handle_client(state: Arc<State>, addr: SocketAddr, stream: TcpStream) -
         state.broadcast(message.clone()).await;
     }
 
-    state.peers.remove(&addr);
+    // 移除关闭信号的发送端后 peer 的发送流也会结束
+    state.remove(&addr);
 
     let message = Arc::new(Message::user_left(&peer.username));
     info!("{}", message);
 
     state.broadcast(message).await;
 
-    // 发送消息关闭客户端peer发送流 不发送也可以 shutdown_tx出了作用域会自动关闭select! 中的 shutdown_rx.changed()就结束了 直接break
-    // let _ = _shutdown_tx.send(());
+    Ok(())
+}
 
+/// 告诉客户端人数已满并断开连接
+async fn reject_full(addr: SocketAddr, mut stream: Framed<TcpStream, ChatCodec>) -> Result<()> {
+    info!("Server is full, rejecting {}", addr);
+    stream.send("Server full, try again later").await?;
     Ok(())
 }
 
+/// 去掉 telnet / Windows 客户端行尾的 `\r`
+fn trim_cr(mut line: String) -> String {
+    if line.ends_with('\r') {
+        line.pop();
+    }
+    line
+}
+
 impl State {
     /// 广播消息给所有的peer
     ///
     /// # 参数
     /// - `message` - 要广播的消息
     async fn broadcast(&self, message: Arc<Message>) {
+        if let Some(transcript) = &self.transcript {
+            transcript.record(&message);
+        }
         let _ = self.sender.send(message);
     }
 
+    /// 广播告别消息并关闭所有peer
+    async fn shutdown(&self) {
+        self.broadcast(Arc::new(Message::server("Server shutting down")))
+            .await;
+        for tx in self.shutdowns.iter() {
+            let _ = tx.send(());
+        }
+        // 等待消息发送完成
+        sleep(SHUTDOWN_GRACE).await;
+        if let Some(transcript) = &self.transcript {
+            transcript.close().await;
+        }
+    }
+
+    /// 移除peer
+    fn remove(&self, addr: &SocketAddr) {
+        if self.peers.remove(addr).is_some() {
+            self.online.fetch_sub(1, Ordering::AcqRel);
+        }
+        self.shutdowns.remove(addr);
+    }
+
+    /// 占用一个peer名额，人数已满时返回false
+    fn try_reserve(&self) -> bool {
+        self.online
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < self.config.max_peers).then_some(n + 1)
+            })
+            .is_ok()
+    }
+
     /// 添加新的peer到状态中
     ///
+    /// 每个peer有一个有界的发送队列，由单独的任务从广播中转发过来，再由写任务写到连接上。
+    /// peer读得慢时队列会满，转发任务不再接收广播，最旧的消息被广播丢弃，
+    /// 之后给peer发一条提示，而不是断开连接。
+    ///
     /// # 参数
     /// - `addr` - 客户端的套接字地址
     /// - `username` - 客户端的用户名
     /// - `stream` - 客户端的 TCP 流
-    /// - `shutdown_rx` - 用于接收关闭信号的接收器
     ///
     /// # 返回
-    /// 返回一个新的 `Peer` 实例
+    /// 返回一个新的 `Peer` 实例，人数已满时原样返回 `stream`
     async fn add(
         &self,
         addr: SocketAddr,
         username: String,
-        stream: Framed<TcpStream, LinesCodec>,
-        mut shutdown_rx: watch::Receiver<()>,
-    ) -> Peer {
+        stream: Framed<TcpStream, ChatCodec>,
+    ) -> Result<Peer, Framed<TcpStream, ChatCodec>> {
+        if !self.try_reserve() {
+            return Err(stream);
+        }
         self.peers.insert(addr, username.clone());
 
+        // 用于关闭客户端peer发送流
+        let (shutdown_tx, mut shutdown_rx) = watch::channel(());
+        let shutdown = shutdown_tx.subscribe();
+        self.shutdowns.insert(addr, shutdown_tx);
+
         let mut receiver = self.sender.subscribe();
         let (mut stream_sender, stream_receiver) = stream.split();
+        let (queue, mut queue_rx) = mpsc::channel::<String>(self.config.write_buffer);
 
+        // 转发任务，关闭后队列里剩下的消息还会被写任务发完
+        let forward = queue.clone();
         tokio::spawn(async move {
             loop {
-                tokio::select! {
+                // 优先把已经广播的消息转发完再处理关闭信号
+                let msg = tokio::select! {
+                    biased;
+                    result = receiver.recv() => match result {
+                        Ok(message) => message.to_string(),
+                        Err(RecvError::Lagged(n)) => {
+                            warn!("{} is too slow, {} messages dropped", addr, n);
+                            DROPPED_NOTICE.to_string()
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
                     _ = shutdown_rx.changed() => {
-                        break;
-                    }
-                    result = receiver.recv() => {
-                        match result {
-                            Ok(message) => {
-                                if let Err(e) = stream_sender.send(message.to_string()).await {
-                                    warn!("Failed to send message to {}: {}", addr, e);
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                warn!("Failed to receive message for {}: {}", addr, e);
+                        while let Ok(message) = receiver.try_recv() {
+                            if forward.send(message.to_string()).await.is_err() {
                                 break;
                             }
                         }
+                        break;
                     }
+                };
+                // 队列满时在这里等待，期间错过的广播会变成 Lagged
+                if forward.send(msg).await.is_err() {
+                    break;
                 }
             }
         });
 
-        Peer {
+        // 写任务，转发任务和 `Peer` 都结束后队列关闭，写任务也随之结束
+        tokio::spawn(async move {
+            while let Some(msg) = queue_rx.recv().await {
+                if let Err(e) = stream_sender.send(msg).await {
+                    warn!("Failed to send message to {}: {}", addr, e);
+                    break;
+                }
+            }
+        });
+
+        Ok(Peer {
             username,
             stream: stream_receiver,
+            private: queue,
+            shutdown,
+        })
+    }
+}
+
+impl Transcript {
+    /// 以追加模式打开聊天记录文件，并启动写入任务
+    fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        let mut file = File::from_std(file);
+        let (sender, mut receiver) = mpsc::unbounded_channel::<TranscriptEntry>();
+
+        let writer = tokio::spawn(async move {
+            while let Some(entry) = receiver.recv().await {
+                let mut line = match serde_json::to_vec(&entry) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        warn!("Failed to serialize transcript entry: {}", e);
+                        continue;
+                    }
+                };
+                line.push(b'\n');
+                if let Err(e) = file.write_all(&line).await {
+                    warn!("Failed to write transcript: {}", e);
+                    continue;
+                }
+                let _ = file.flush().await;
+            }
+        });
+        Ok(Self {
+            sender: Mutex::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+        })
+    }
+
+    /// 记录一条消息，服务器通知不记录
+    fn record(&self, message: &Message) {
+        let (event, sender, content) = match message {
+            Message::UserJoined(username) => ("join", username, None),
+            Message::UserLeft(username) => ("leave", username, None),
+            Message::Chat { sender, content } => ("chat", sender, Some(content.clone())),
+            Message::Server(_) => return,
+        };
+        let entry = TranscriptEntry {
+            timestamp: Utc::now(),
+            event,
+            sender: sender.clone(),
+            content,
+        };
+        let sender = self.sender.lock().unwrap();
+        if !sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(entry).is_ok())
+        {
+            warn!("Transcript writer is closed");
+        }
+    }
+
+    /// 关闭通道，并等待写入任务把已经缓冲的记录写完
+    async fn close(&self) {
+        self.sender.lock().unwrap().take();
+        let writer = self.writer.lock().unwrap().take();
+        if let Some(writer) = writer {
+            if let Err(e) = writer.await {
+                warn!("Transcript writer failed: {}", e);
+            }
+        }
+    }
+}
+
+impl Decoder for ChatCodec {
+    type Item = Line;
+    type Error = LinesCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Line>, LinesCodecError> {
+        match self.0.decode(buf) {
+            Ok(line) => Ok(line.map(Line::Text)),
+            Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Some(Line::TooLong)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Line>, LinesCodecError> {
+        match self.0.decode_eof(buf) {
+            Ok(line) => Ok(line.map(Line::Text)),
+            Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Some(Line::TooLong)),
+            Err(e) => Err(e),
         }
     }
 }
 
+impl<T: AsRef<str>> Encoder<T> for ChatCodec {
+    type Error = LinesCodecError;
+
+    fn encode(&mut self, line: T, buf: &mut BytesMut) -> Result<(), LinesCodecError> {
+        self.0.encode(line, buf)
+    }
+}
+
 impl Message {
     /// 创建用户加入的消息
     ///
@@ -214,8 +603,7 @@ impl Message {
     /// # 返回
     /// 返回一个新的 `Message::UserJoined` 实例
     fn user_joined(username: &str) -> Self {
-        let content = format!("{} has joined the chat", username);
-        Self::UserJoined(content)
+        Self::UserJoined(username.to_string())
     }
 
     /// 创建用户离开的消息
@@ -226,8 +614,12 @@ impl Message {
     /// # 返回
     /// 返回一个新的 `Message::UserLeft` 实例
     fn user_left(username: &str) -> Self {
-        let content = format!("{} has left the chat", username);
-        Self::UserLeft(content)
+        Self::UserLeft(username.to_string())
+    }
+
+    /// 创建服务器通知
+    fn server(content: impl Into<String>) -> Self {
+        Self::Server(content.into())
     }
 
     /// 创建聊天消息
@@ -256,9 +648,332 @@ impl fmt::Display for Message {
     /// 返回格式化结果
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::UserJoined(content) => write!(f, "[{}]", content),
-            Self::UserLeft(content) => write!(f, "[{} :(]", content),
+            Self::UserJoined(username) => write!(f, "[{} has joined the chat]", username),
+            Self::UserLeft(username) => write!(f, "[{} has left the chat :(]", username),
             Self::Chat { sender, content } => write!(f, "{}: {}", sender, content),
+            Self::Server(content) => write!(f, "[{}]", content),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    struct TestServer {
+        state: Arc<State>,
+        addr: SocketAddr,
+        shutdown: oneshot::Sender<()>,
+        handle: JoinHandle<Result<()>>,
+    }
+
+    async fn start_server(config: ChatConfig) -> Result<TestServer> {
+        start_server_with_transcript(config, None).await
+    }
+
+    async fn start_server_with_transcript(
+        config: ChatConfig,
+        transcript: Option<Transcript>,
+    ) -> Result<TestServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(State::new(config, transcript));
+        let (shutdown, rx) = oneshot::channel();
+        let handle = tokio::spawn(run(listener, state.clone(), async {
+            let _ = rx.await;
+        }));
+        Ok(TestServer {
+            state,
+            addr,
+            shutdown,
+            handle,
+        })
+    }
+
+    async fn connect(addr: SocketAddr, username: &str) -> Result<Framed<TcpStream, LinesCodec>> {
+        let mut client = Framed::new(TcpStream::connect(addr).await?, LinesCodec::new());
+        assert_eq!(client.next().await.unwrap()?, "Enter your username:");
+        client.send(format!("{}\r", username)).await?;
+        assert_eq!(
+            client.next().await.unwrap()?,
+            format!("[{} has joined the chat]", username)
+        );
+        Ok(client)
+    }
+
+    #[test]
+    fn config_should_be_loaded_from_file() -> Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/chat.yml");
+        let config = ChatConfig::load(Some(path))?;
+        assert_eq!(config.bind, "127.0.0.1:9090");
+        assert_eq!(config.max_line_length, 1024);
+        assert_eq!(config.max_username_length, 8);
+        assert_eq!(config.idle_timeout, Duration::from_secs(60));
+        assert_eq!(config.max_peers, 10);
+        assert_eq!(config.write_buffer, 4);
+
+        // the broadcast channel keeps the last `capacity` messages only
+        let state = State::new(config, None);
+        let mut receiver = state.sender.subscribe();
+        for i in 0..17 {
+            state
+                .sender
+                .send(Arc::new(Message::server(i.to_string())))?;
+        }
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_fields_should_use_defaults() -> Result<()> {
+        let config: ChatConfig = serde_yaml::from_str("max_peers: 2")?;
+        assert_eq!(config.max_peers, 2);
+        assert_eq!(config.bind, BIND_ADDR);
+        assert_eq!(config.capacity, MAX_MESSAGES);
+        assert_eq!(config.idle_timeout, IDLE_TIMEOUT);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn long_username_should_be_rejected() -> Result<()> {
+        let server = start_server(ChatConfig {
+            max_username_length: 4,
+            ..Default::default()
+        })
+        .await?;
+        let mut client = Framed::new(TcpStream::connect(server.addr).await?, LinesCodec::new());
+        assert_eq!(client.next().await.unwrap()?, "Enter your username:");
+        client.send("alice").await?;
+        assert_eq!(
+            client.next().await.unwrap()?,
+            "[error: username exceeds 4 characters]"
+        );
+        assert!(client.next().await.is_none());
+        assert!(server.state.peers.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn over_length_line_should_not_close_connection() -> Result<()> {
+        let server = start_server(ChatConfig {
+            max_line_length: 16,
+            ..Default::default()
+        })
+        .await?;
+        let mut client = connect(server.addr, "alice").await?;
+
+        client.send("a".repeat(64)).await?;
+        let line = client.next().await.unwrap()?;
+        assert!(line.starts_with("[error:"), "{}", line);
+
+        client.send("hello\r").await?;
+        assert_eq!(client.next().await.unwrap()?, "alice: hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn idle_client_should_be_disconnected() -> Result<()> {
+        let server = start_server(ChatConfig {
+            idle_timeout: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .await?;
+        let mut client = connect(server.addr, "bob").await?;
+        assert_eq!(server.state.peers.len(), 1);
+
+        // the server closes the connection once the client is idle
+        let closed = timeout(Duration::from_secs(2), async {
+            while let Some(Ok(_)) = client.next().await {}
+        })
+        .await;
+        assert!(closed.is_ok());
+        assert!(server.state.peers.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn server_full_should_reject_new_peers() -> Result<()> {
+        let server = start_server(ChatConfig {
+            max_peers: 2,
+            ..Default::default()
+        })
+        .await?;
+        let _alice = connect(server.addr, "alice").await?;
+        let _bob = connect(server.addr, "bob").await?;
+
+        let mut client = Framed::new(TcpStream::connect(server.addr).await?, LinesCodec::new());
+        assert_eq!(
+            client.next().await.unwrap()?,
+            "Server full, try again later"
+        );
+        assert!(client.next().await.is_none());
+        assert_eq!(server.state.peers.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_joins_should_not_exceed_max_peers() -> Result<()> {
+        let server = start_server(ChatConfig {
+            max_peers: 2,
+            ..Default::default()
+        })
+        .await?;
+        // all of them are asked for a username before any has joined
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let mut client = Framed::new(TcpStream::connect(server.addr).await?, LinesCodec::new());
+            assert_eq!(client.next().await.unwrap()?, "Enter your username:");
+            clients.push(client);
+        }
+        for (i, client) in clients.iter_mut().enumerate() {
+            client.send(format!("user{}", i)).await?;
+        }
+
+        // the joined ones see a join message first
+        let mut rejected = 0;
+        for client in clients.iter_mut() {
+            if client.next().await.unwrap()? == "Server full, try again later" {
+                rejected += 1;
+            }
+        }
+        assert_eq!(rejected, 1);
+        assert_eq!(server.state.peers.len(), 2);
+        assert_eq!(server.state.online.load(Ordering::Acquire), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn slow_consumer_should_get_drop_notice() -> Result<()> {
+        let server = start_server(ChatConfig {
+            write_buffer: 4,
+            ..Default::default()
+        })
+        .await?;
+        // bob doesn't read until alice is done
+        let mut bob = connect(server.addr, "bob").await?;
+        let alice = connect(server.addr, "alice").await?;
+        assert_eq!(bob.next().await.unwrap()?, "[alice has joined the chat]");
+
+        // enough to fill up the socket buffers between the server and bob
+        let line = "x".repeat(MAX_LINE_LENGTH - 16);
+        let (mut alice_tx, mut alice_rx) = alice.split();
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(line)) = alice_rx.next().await {
+                if line == "alice: done" {
+                    break;
+                }
+            }
+            alice_rx
+        });
+        for _ in 0..2048 {
+            alice_tx.send(line.as_str()).await?;
+        }
+        alice_tx.send("done").await?;
+        let mut alice_rx = timeout(Duration::from_secs(10), reader).await??;
+
+        let mut dropped = false;
+        timeout(Duration::from_secs(10), async {
+            while let Some(Ok(line)) = bob.next().await {
+                dropped |= line == DROPPED_NOTICE;
+                if line == "alice: done" {
+                    break;
+                }
+            }
+        })
+        .await?;
+        assert!(dropped);
+
+        // bob is still connected
+        alice_tx.send("still there?").await?;
+        assert_eq!(bob.next().await.unwrap()?, "alice: still there?");
+        assert_eq!(alice_rx.next().await.unwrap()?, "alice: still there?");
+        assert_eq!(server.state.peers.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chat_messages_should_be_written_to_transcript() -> Result<()> {
+        let path = env::temp_dir().join(format!("lilp_chat_{}.jsonl", nanoid::nanoid!()));
+        let transcript = Transcript::open(&path)?;
+        let server = start_server_with_transcript(ChatConfig::default(), Some(transcript)).await?;
+        let mut alice = connect(server.addr, "alice").await?;
+        alice.send("hello").await?;
+        assert_eq!(alice.next().await.unwrap()?, "alice: hello");
+        let mut bob = connect(server.addr, "bob").await?;
+        bob.send("hi").await?;
+        assert_eq!(bob.next().await.unwrap()?, "bob: hi");
+
+        // the transcript is written by a separate task, wait for it to catch up
+        let chats = timeout(Duration::from_secs(2), async {
+            loop {
+                let content = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+                let chats: Vec<serde_json::Value> = content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .filter(|entry: &serde_json::Value| entry["event"] == "chat")
+                    .collect();
+                if chats.len() == 2 {
+                    break chats;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(chats[0]["sender"], "alice");
+        assert_eq!(chats[0]["content"], "hello");
+        assert_eq!(chats[1]["sender"], "bob");
+        assert_eq!(chats[1]["content"], "hi");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shutdown_should_flush_transcript() -> Result<()> {
+        let path = env::temp_dir().join(format!("lilp_chat_{}.jsonl", nanoid::nanoid!()));
+        let transcript = Transcript::open(&path)?;
+        let server = start_server_with_transcript(ChatConfig::default(), Some(transcript)).await?;
+        let transcript = server.state.transcript.as_ref().unwrap();
+        for i in 0..100 {
+            transcript.record(&Message::Chat {
+                sender: "alice".to_string(),
+                content: i.to_string(),
+            });
+        }
+
+        // no waiting for the writer, the shutdown drains what was recorded
+        server.shutdown.send(()).unwrap();
+        server.handle.await??;
+        let content = tokio::fs::read_to_string(&path).await?;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(content.lines().count(), 100);
+
+        // recorded after the shutdown
+        transcript.record(&Message::UserLeft("alice".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shutdown_should_say_goodbye_to_peers() -> Result<()> {
+        let server = start_server(ChatConfig::default()).await?;
+        let mut client = connect(server.addr, "carol").await?;
+
+        server.shutdown.send(()).unwrap();
+        server.handle.await??;
+
+        let mut lines = vec![];
+        while let Some(Ok(line)) = client.next().await {
+            lines.push(line);
         }
+        assert!(
+            lines.contains(&"[Server shutting down]".to_string()),
+            "{:?}",
+            lines
+        );
+        Ok(())
     }
 }
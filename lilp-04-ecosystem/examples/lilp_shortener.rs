@@ -1,8 +1,17 @@
+use axum::extract::DefaultBodyLimit;
+use axum::middleware::from_fn_with_state;
 use axum::routing::{get, post};
 use axum::Router;
-use ecosystem::handler::{redirect, shorten, AppState};
-use std::sync::Arc;
+use clap::Parser;
+use ecosystem::config::ShortenerConfig;
+use ecosystem::cors::cors_layer;
+use ecosystem::handler::{
+    list_urls, preview, qr_code, redirect, shorten, shorten_batch, stats, AppState,
+};
+use ecosystem::rate_limit::rate_limit;
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
+use tower::ServiceBuilder;
 use tracing::info;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::fmt::Layer as FmtLayer;
@@ -12,11 +21,17 @@ use tracing_subscriber::Layer;
 
 const LISTEN_ADDR: &str = "127.0.0.1:9876";
 
+#[derive(Debug, Parser)]
+struct Opts {
+    /// 允许跨域访问的源，可以指定多次，`*`表示允许任意源，不指定时只允许同源访问
+    #[arg(long = "cors-origin")]
+    cors_origins: Vec<String>,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let state = AppState {
-        listen_addr: Arc::new(LISTEN_ADDR.to_string()),
-    };
+    let opts = Opts::parse();
+    let state = AppState::with_config(LISTEN_ADDR, ShortenerConfig::from_env()?);
 
     let layer = FmtLayer::new().with_filter(LevelFilter::INFO);
     tracing_subscriber::registry().with(layer).init();
@@ -25,11 +40,32 @@ async fn main() -> anyhow::Result<()> {
     info!("Listening on: {}", LISTEN_ADDR);
 
     let app = Router::new()
-        .route("/", post(shorten))
+        .route(
+            "/",
+            post(shorten).layer(
+                ServiceBuilder::new()
+                    .layer(DefaultBodyLimit::max(state.config.max_body_size()))
+                    .layer(from_fn_with_state(state.clone(), rate_limit)),
+            ),
+        )
         .route("/:id", get(redirect))
+        .route("/:id/preview", get(preview))
+        .route("/:id/qr", get(qr_code))
+        .route("/:id/stats", get(stats))
+        .route("/api/urls", get(list_urls))
+        .route(
+            "/api/shorten/batch",
+            // 按URL的数量限流，在handler里完成
+            post(shorten_batch).layer(DefaultBodyLimit::max(state.config.max_batch_body_size())),
+        )
+        .layer(cors_layer(&opts.cors_origins)?)
         .with_state(state);
 
-    axum::serve(listener, app.into_make_service()).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -1,3 +1,3 @@
 mod lilp;
 
-pub use lilp::handler;
+pub use lilp::{config, cors, handler, rate_limit};
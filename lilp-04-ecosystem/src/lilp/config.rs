@@ -0,0 +1,178 @@
+//! `config`模块定义了短链接服务的配置。
+//!
+//! 目前包括生成id的长度和字母表，禁止跳转的目标域名，以及请求体的大小上限，可以通过环境变量配置：
+//! - `SHORTENER_ID_LENGTH`: id的长度，默认为6。
+//! - `SHORTENER_ID_ALPHABET`: id使用的字母表，默认为nanoid的`SAFE`字母表。
+//! - `SHORTENER_DENIED_HOSTS`: 逗号分隔的域名列表，这些域名及其子域名不能作为目标，默认为空。
+//! - `SHORTENER_MAX_BODY_SIZE`: 缩短单个URL时请求体的最大字节数，默认为64 KiB。
+//! - `SHORTENER_MAX_BATCH_BODY_SIZE`: 批量缩短时请求体的最大字节数，默认为1 MiB。
+
+use anyhow::{bail, Context};
+
+/// 默认的id长度
+pub const DEFAULT_ID_LENGTH: usize = 6;
+/// id的最大长度，与`urls.id`列的长度一致
+pub const MAX_ID_LENGTH: usize = 32;
+/// 缩短单个URL时默认的请求体上限
+pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024;
+/// 批量缩短时默认的请求体上限
+pub const DEFAULT_MAX_BATCH_BODY_SIZE: usize = 1024 * 1024;
+
+/// ShortenerConfig结构体，包含了短链接服务的配置
+#[derive(Debug, Clone)]
+pub struct ShortenerConfig {
+    id_length: usize,
+    id_alphabet: Vec<char>,
+    denied_hosts: Vec<String>,
+    max_body_size: usize,
+    max_batch_body_size: usize,
+}
+
+impl ShortenerConfig {
+    /// 创建配置，并检查长度和字母表是否合法
+    ///
+    /// 长度需要在`1..=MAX_ID_LENGTH`之间，字母表需要是2到255个不重复的ASCII字符
+    pub fn try_new(id_length: usize, id_alphabet: &str) -> anyhow::Result<Self> {
+        if !(1..=MAX_ID_LENGTH).contains(&id_length) {
+            bail!("id length must be between 1 and {}", MAX_ID_LENGTH);
+        }
+
+        let mut chars: Vec<char> = id_alphabet.chars().collect();
+        if !chars.iter().all(|c| c.is_ascii_graphic()) {
+            bail!("id alphabet must only contain printable ASCII characters");
+        }
+        chars.sort_unstable();
+        chars.dedup();
+        if chars.len() != id_alphabet.len() || !(2..=255).contains(&chars.len()) {
+            bail!("id alphabet must contain 2 to 255 distinct characters");
+        }
+
+        Ok(Self {
+            id_length,
+            id_alphabet: id_alphabet.chars().collect(),
+            ..Default::default()
+        })
+    }
+
+    /// 设置禁止跳转的目标域名，匹配时忽略大小写，并且包含子域名
+    pub fn with_denied_hosts<S: AsRef<str>>(mut self, hosts: impl IntoIterator<Item = S>) -> Self {
+        self.denied_hosts = hosts
+            .into_iter()
+            .map(|h| h.as_ref().trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|h| !h.is_empty())
+            .collect();
+        self
+    }
+
+    /// 设置请求体的大小上限，超过时返回`413 Payload Too Large`
+    pub fn with_body_limits(mut self, max_body_size: usize, max_batch_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self.max_batch_body_size = max_batch_body_size;
+        self
+    }
+
+    /// 缩短单个URL时请求体的上限
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// 批量缩短时请求体的上限
+    pub fn max_batch_body_size(&self) -> usize {
+        self.max_batch_body_size
+    }
+
+    /// 目标域名是否被禁止
+    pub fn is_host_denied(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.denied_hosts.iter().any(|denied| {
+            host == *denied
+                || host
+                    .strip_suffix(denied.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+
+    /// 从环境变量读取配置，未设置的项使用默认值
+    pub fn from_env() -> anyhow::Result<Self> {
+        let id_length = match std::env::var("SHORTENER_ID_LENGTH") {
+            Ok(v) => v.parse().context("invalid SHORTENER_ID_LENGTH")?,
+            Err(_) => DEFAULT_ID_LENGTH,
+        };
+        let id_alphabet = std::env::var("SHORTENER_ID_ALPHABET")
+            .unwrap_or_else(|_| nanoid::alphabet::SAFE.iter().collect());
+        let denied_hosts = std::env::var("SHORTENER_DENIED_HOSTS").unwrap_or_default();
+        let max_body_size = match std::env::var("SHORTENER_MAX_BODY_SIZE") {
+            Ok(v) => v.parse().context("invalid SHORTENER_MAX_BODY_SIZE")?,
+            Err(_) => DEFAULT_MAX_BODY_SIZE,
+        };
+        let max_batch_body_size = match std::env::var("SHORTENER_MAX_BATCH_BODY_SIZE") {
+            Ok(v) => v.parse().context("invalid SHORTENER_MAX_BATCH_BODY_SIZE")?,
+            Err(_) => DEFAULT_MAX_BATCH_BODY_SIZE,
+        };
+        Ok(Self::try_new(id_length, &id_alphabet)?
+            .with_denied_hosts(denied_hosts.split(','))
+            .with_body_limits(max_body_size, max_batch_body_size))
+    }
+
+    /// 按配置生成一个随机id
+    pub fn generate_id(&self) -> String {
+        nanoid::format(nanoid::rngs::default, &self.id_alphabet, self.id_length)
+    }
+}
+
+impl Default for ShortenerConfig {
+    fn default() -> Self {
+        Self {
+            id_length: DEFAULT_ID_LENGTH,
+            id_alphabet: nanoid::alphabet::SAFE.to_vec(),
+            denied_hosts: Vec::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_batch_body_size: DEFAULT_MAX_BATCH_BODY_SIZE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_id_should_respect_length_and_alphabet() -> anyhow::Result<()> {
+        let alphabet = "23456789abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+        let config = ShortenerConfig::try_new(8, alphabet)?;
+        for _ in 0..100 {
+            let id = config.generate_id();
+            assert_eq!(id.len(), 8);
+            assert!(id.chars().all(|c| alphabet.contains(c)), "{}", id);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn default_config_should_generate_6_char_ids() {
+        assert_eq!(
+            ShortenerConfig::default().generate_id().len(),
+            DEFAULT_ID_LENGTH
+        );
+    }
+
+    #[test]
+    fn denied_hosts_should_match_subdomains() {
+        let config = ShortenerConfig::default().with_denied_hosts(["Evil.com", " ", "bad.org."]);
+        assert!(config.is_host_denied("evil.com"));
+        assert!(config.is_host_denied("www.EVIL.com"));
+        assert!(config.is_host_denied("bad.org"));
+        assert!(!config.is_host_denied("notevil.com"));
+        assert!(!config.is_host_denied("evil.com.cn"));
+        assert!(!ShortenerConfig::default().is_host_denied("evil.com"));
+    }
+
+    #[test]
+    fn invalid_config_should_be_rejected() {
+        assert!(ShortenerConfig::try_new(0, "abc").is_err());
+        assert!(ShortenerConfig::try_new(MAX_ID_LENGTH + 1, "abc").is_err());
+        assert!(ShortenerConfig::try_new(6, "a").is_err());
+        assert!(ShortenerConfig::try_new(6, "aab").is_err());
+        assert!(ShortenerConfig::try_new(6, "ab中").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! `cors`模块根据配置构建短链接服务的CORS中间件。
+//!
+//! 没有配置允许的源时保持同源策略，`*`表示允许任意源。
+
+use axum::http::{HeaderValue, Method};
+use http::header::CONTENT_TYPE;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::lilp::error::AppError;
+
+/// 根据允许的源构建CorsLayer
+///
+/// # 参数
+///
+/// * `origins` - 允许的源，例如`https://example.com`，`*`表示允许任意源，为空时只允许同源访问
+///
+/// # 返回值
+///
+/// 返回一个Result，源不是合法的header值时返回AppError
+pub fn cors_layer(origins: &[String]) -> Result<CorsLayer, AppError> {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|o| o.parse::<HeaderValue>())
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([CONTENT_TYPE]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::routing::post;
+    use axum::Router;
+    use http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN};
+    use http::StatusCode;
+    use tower::ServiceExt;
+
+    fn preflight(origin: &str) -> Request {
+        Request::options("/")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn app(origins: &[&str]) -> anyhow::Result<Router> {
+        let origins: Vec<String> = origins.iter().map(|o| o.to_string()).collect();
+        Ok(Router::new()
+            .route("/", post(|| async { StatusCode::CREATED }))
+            .layer(cors_layer(&origins)?))
+    }
+
+    #[tokio::test]
+    async fn preflight_should_allow_configured_origin() -> anyhow::Result<()> {
+        let res = app(&["https://example.com"])?
+            .oneshot(preflight("https://example.com"))
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+
+        let res = app(&["https://example.com"])?
+            .oneshot(preflight("https://evil.com"))
+            .await?;
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn preflight_should_allow_any_origin_with_wildcard() -> anyhow::Result<()> {
+        let res = app(&["*"])?
+            .oneshot(preflight("https://example.com"))
+            .await?;
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn preflight_should_be_same_origin_by_default() -> anyhow::Result<()> {
+        let res = app(&[])?.oneshot(preflight("https://example.com")).await?;
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        Ok(())
+    }
+}
@@ -3,18 +3,41 @@
 //! 主要包括以下函数：
 //! - `get_url`: 从数据库中获取给定id的URL。
 //! - `shorten`: 将给定的URL缩短，并将其存储到数据库中。
+//! - `list_urls`: 按插入顺序分页列出已存储的URL。
+//! - `list_urls_after`: 按插入顺序列出给定游标之后的URL。
+//! - `record_unique_visit`: 记录一次访问，用于统计独立访客数。
+//! - `unique_visitors`: 获取给定id的独立访客数。
 //!
 //! 此模块还包含了`UrlRecord`结构体，用于表示数据库中的URL记录。
-#[cfg(not(test))]
-use nanoid::nanoid;
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
 use sqlx::FromRow;
+use std::net::IpAddr;
+use tokio::sync::OnceCell;
+use tracing::warn;
 
-use crate::lilp::db_config::get_pgsql_pool;
+use crate::lilp::config::ShortenerConfig;
+use crate::lilp::db_config::get_pool;
 use crate::lilp::error::AppError;
 
+/// 分页查询时单页的最大条数
+pub const MAX_LIST_LIMIT: i64 = 100;
+
+/// 生成id冲突时最多尝试的次数
+pub const MAX_SHORTEN_ATTEMPTS: usize = 5;
+
+/// 每个id最多记录的独立访客数，超过后不再增长
+pub const MAX_TRACKED_VISITORS: i64 = 10_000;
+
+/// 访客哈希的密钥在`secrets`表中的名字
+const VISITOR_SECRET: &str = "visitor";
+
 /// UrlRecord结构体，用于表示数据库中的URL记录
-#[derive(Debug, FromRow)]
-struct UrlRecord {
+#[derive(Debug, FromRow, Serialize)]
+pub struct UrlRecord {
+    /// 插入序号，作为下一页的`after`参数
+    #[sqlx(default)]
+    seq: i64,
     #[sqlx(default)]
     id: String,
     #[sqlx(default)]
@@ -29,43 +52,167 @@ struct UrlRecord {
 ///
 /// # 返回值
 ///
-/// 返回一个Result，如果查询成功，返回URL的字符串，否则返回AppError
+/// 返回一个Result，如果查询成功，返回URL的字符串，id不存在时返回`AppError::UrlNotFound`
 pub async fn get_url(id: &str) -> Result<String, AppError> {
-    let pool = get_pgsql_pool().await;
-    let ret: UrlRecord = sqlx::query_as("SELECT url FROM urls WHERE id = $1")
+    let pool = get_pool().await;
+    let ret: Option<UrlRecord> = sqlx::query_as("SELECT url FROM urls WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    ret.map(|r| r.url).ok_or(AppError::UrlNotFound)
+}
+
+/// 按插入顺序分页列出已存储的URL
+///
+/// # 参数
+///
+/// * `limit` - 返回的最大条数，会被限制在`1..=MAX_LIST_LIMIT`之间
+/// * `offset` - 跳过的条数
+///
+/// # 返回值
+///
+/// 返回一个Result，如果查询成功，返回URL记录的列表，否则返回AppError
+pub async fn list_urls(limit: i64, offset: i64) -> Result<Vec<UrlRecord>, AppError> {
+    let pool = get_pool().await;
+    let ret = sqlx::query_as("SELECT seq, id, url FROM urls ORDER BY seq LIMIT $1 OFFSET $2")
+        .bind(limit.clamp(1, MAX_LIST_LIMIT))
+        .bind(offset.max(0))
+        .fetch_all(pool)
+        .await?;
+    Ok(ret)
+}
+
+/// 按插入顺序列出给定游标之后的URL
+///
+/// 使用插入序号做游标分页，翻页期间有新的插入也不会重复或遗漏记录
+///
+/// # 参数
+///
+/// * `limit` - 返回的最大条数，会被限制在`1..=MAX_LIST_LIMIT`之间
+/// * `after` - 上一页最后一条记录的`seq`
+///
+/// # 返回值
+///
+/// 返回一个Result，如果查询成功，返回URL记录的列表，否则返回AppError
+pub async fn list_urls_after(limit: i64, after: i64) -> Result<Vec<UrlRecord>, AppError> {
+    let pool = get_pool().await;
+    let ret = sqlx::query_as("SELECT seq, id, url FROM urls WHERE seq > $1 ORDER BY seq LIMIT $2")
+        .bind(after)
+        .bind(limit.clamp(1, MAX_LIST_LIMIT))
+        .fetch_all(pool)
+        .await?;
+    Ok(ret)
+}
+
+/// 记录一次访问，用于统计独立访客数
+///
+/// 不保存原始IP，只保存IP加上每日轮换的盐之后的哈希，因此同一个IP在同一天内只计一次。
+///
+/// # 参数
+///
+/// * `id` - 被访问的URL的id
+/// * `ip` - 访客的IP
+///
+/// # 返回值
+///
+/// 返回一个Result，如果操作失败，返回AppError
+pub async fn record_unique_visit(id: &str, ip: IpAddr) -> Result<(), AppError> {
+    let pool = get_pool().await;
+    let visitor = visitor_hash(visitor_secret().await?, Utc::now().date_naive(), ip);
+    sqlx::query(
+        "INSERT INTO url_visitors (id, visitor) SELECT $1, $2 WHERE (SELECT count(*) FROM url_visitors WHERE id = $1) < $3 ON CONFLICT DO NOTHING",
+    )
+        .bind(id)
+        .bind(visitor)
+        .bind(MAX_TRACKED_VISITORS)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 获取给定id的独立访客数
+///
+/// # 参数
+///
+/// * `id` - 需要查询的URL的id
+///
+/// # 返回值
+///
+/// 返回一个Result，如果查询成功，返回独立访客数，否则返回AppError
+pub async fn unique_visitors(id: &str) -> Result<i64, AppError> {
+    let pool = get_pool().await;
+    let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM url_visitors WHERE id = $1")
         .bind(id)
         .fetch_one(pool)
         .await?;
-    Ok(ret.url)
+    Ok(count)
+}
+
+/// 访客哈希使用的密钥，优先从环境变量`SHORTENER_VISITOR_SECRET`读取，
+/// 否则使用保存在数据库中的密钥，第一次使用时随机生成。
+/// 密钥在重启后保持不变，否则重启当天的回访会被当作新的独立访客
+async fn visitor_secret() -> Result<&'static str, AppError> {
+    static SECRET: OnceCell<String> = OnceCell::const_new();
+    let secret = SECRET
+        .get_or_try_init(|| async {
+            if let Ok(secret) = std::env::var("SHORTENER_VISITOR_SECRET") {
+                return Ok(secret);
+            }
+            stored_secret(VISITOR_SECRET).await
+        })
+        .await?;
+    Ok(secret)
+}
+
+/// 获取数据库中保存的密钥，不存在时生成一个，多个实例同时生成时以先写入的为准
+async fn stored_secret(name: &str) -> Result<String, AppError> {
+    let pool = get_pool().await;
+    sqlx::query("INSERT INTO secrets (name, value) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(name)
+        .bind(nanoid::nanoid!(32))
+        .execute(pool)
+        .await?;
+    let (secret,): (String,) = sqlx::query_as("SELECT value FROM secrets WHERE name = $1")
+        .bind(name)
+        .fetch_one(pool)
+        .await?;
+    Ok(secret)
+}
+
+/// 计算访客的哈希，盐由密钥和日期组成，每天轮换
+fn visitor_hash(secret: &str, date: NaiveDate, ip: IpAddr) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(secret.as_bytes());
+    hasher.update(date.to_string().as_bytes());
+    hasher.update(ip.to_string().as_bytes());
+    hasher.finalize().to_hex().to_string()
 }
 
 /// 将给定的URL缩短，并将其存储到数据库中
 ///
+/// 生成的id与已有的id冲突时会换一个id重试，最多尝试`MAX_SHORTEN_ATTEMPTS`次
+///
 /// # 参数
 ///
 /// * `url` - 需要缩短的URL
+/// * `config` - 生成id使用的配置
 ///
 /// # 返回值
 ///
-/// 返回一个Result，如果操作成功，返回生成的短URL的id，否则返回AppError
-pub async fn shorten(url: &str) -> Result<String, AppError> {
-    let pool = get_pgsql_pool().await;
-    #[cfg(test)]
-    let mut test_num = 0;
-    loop {
-        #[cfg(test)]
-        let id = {
-            test_num += 1;
-            if test_num > 3 {
-                format!("test{}", test_num)
-            } else {
-                "test0".to_string()
-            }
-        };
-
-        #[cfg(not(test))]
-        let id = nanoid!(6);
+/// 返回一个Result，如果操作成功，返回短URL的id以及是否是新创建的，
+/// URL已经缩短过时返回已有的id和`false`，否则返回AppError
+pub async fn shorten(url: &str, config: &ShortenerConfig) -> Result<(String, bool), AppError> {
+    shorten_with(url, || config.generate_id()).await
+}
 
+/// `shorten`的实现，`next_id`每次调用返回一个新的候选id，测试中可以用它制造冲突
+async fn shorten_with(
+    url: &str,
+    mut next_id: impl FnMut() -> String,
+) -> Result<(String, bool), AppError> {
+    let pool = get_pool().await;
+    for _ in 0..MAX_SHORTEN_ATTEMPTS {
+        let id = next_id();
         let result = sqlx::query_as::<_, UrlRecord>(
             "INSERT INTO urls (id, url) VALUES ($1, $2) ON CONFLICT(url) DO UPDATE SET url=EXCLUDED.url RETURNING id",
         )
@@ -75,25 +222,165 @@ pub async fn shorten(url: &str) -> Result<String, AppError> {
             .await;
 
         match result {
-            Ok(ret) => return Ok(ret.id),
-            Err(sqlx::Error::Database(db_err)) if db_err.constraint() == Some("urls_pkey") => {
-                continue;
+            // url冲突时返回的是已有的id，和这次生成的id不同
+            Ok(ret) => {
+                let created = ret.id == id;
+                return Ok((ret.id, created));
+            }
+            // url冲突时会更新已有的记录并返回已有的id，所以这里的唯一约束冲突只可能是
+            // 不同的url生成了相同的id，换一个id重试
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                warn!("Id {} is already taken, retrying", id);
             }
             Err(e) => return Err(AppError::DatabaseError(e)),
         }
     }
+    Err(AppError::IdExhausted(MAX_SHORTEN_ATTEMPTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visitor_hash_should_rotate_daily() {
+        let ip: IpAddr = "1.2.3.4".parse().unwrap();
+        let day1 = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let day2 = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+
+        let hash = visitor_hash("secret", day1, ip);
+        assert_eq!(hash, visitor_hash("secret", day1, ip));
+        assert_ne!(hash, visitor_hash("secret", day2, ip));
+        assert_ne!(
+            hash,
+            visitor_hash("secret", day1, "1.2.3.5".parse().unwrap())
+        );
+        assert!(!hash.contains("1.2.3.4"));
+    }
 }
 
+/// 需要数据库的测试，开启`sqlite` feature时使用内存中的SQLite运行，否则需要PostgreSQL
 #[cfg(test)]
 mod pgsql_tests {
     use super::*;
 
     /// 测试shorten函数
-    #[ignore]
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
     #[tokio::test]
     async fn test_shorten() -> anyhow::Result<()> {
         let url = "https://www.rust-lang.org/3";
-        let _id = shorten(url).await?;
+        let _id = shorten(url, &ShortenerConfig::default()).await?;
+        Ok(())
+    }
+
+    /// 测试重复缩短同一个URL时返回已有的id
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_shorten_should_report_existing_url() -> anyhow::Result<()> {
+        let url = format!("https://www.rust-lang.org/repeat/{}", nanoid::nanoid!());
+        let (id, created) = shorten(&url, &ShortenerConfig::default()).await?;
+        assert!(created);
+        let (again, created) = shorten(&url, &ShortenerConfig::default()).await?;
+        assert_eq!(again, id);
+        assert!(!created);
+        Ok(())
+    }
+
+    /// 测试id冲突时会换一个id重试
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_shorten_should_retry_on_id_collision() -> anyhow::Result<()> {
+        let (taken, _) = shorten(
+            &format!("https://www.rust-lang.org/taken/{}", nanoid::nanoid!()),
+            &ShortenerConfig::default(),
+        )
+        .await?;
+
+        let fresh = nanoid::nanoid!(8);
+        let mut ids = vec![fresh.clone(), taken.clone(), taken.clone()];
+        let url = format!("https://www.rust-lang.org/collision/{}", nanoid::nanoid!());
+        let (id, created) = shorten_with(&url, || ids.pop().unwrap()).await?;
+        assert_eq!(id, fresh);
+        assert!(created);
+        assert_eq!(get_url(&id).await?, url);
+
+        // 一直冲突时，重试次数是有限的
+        let url = format!("https://www.rust-lang.org/collision/{}", nanoid::nanoid!());
+        let ret = shorten_with(&url, || taken.clone()).await;
+        assert!(matches!(
+            ret,
+            Err(AppError::IdExhausted(MAX_SHORTEN_ATTEMPTS))
+        ));
+        Ok(())
+    }
+
+    /// 测试record_unique_visit函数，同一个IP只计一次
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_record_unique_visit() -> anyhow::Result<()> {
+        let (id, _) = shorten(
+            &format!("https://www.rust-lang.org/visit/{}", nanoid::nanoid!()),
+            &ShortenerConfig::default(),
+        )
+        .await?;
+        let before = unique_visitors(&id).await?;
+
+        record_unique_visit(&id, "10.0.0.1".parse()?).await?;
+        record_unique_visit(&id, "10.0.0.1".parse()?).await?;
+        assert_eq!(unique_visitors(&id).await?, before + 1);
+
+        record_unique_visit(&id, "10.0.0.2".parse()?).await?;
+        assert_eq!(unique_visitors(&id).await?, before + 2);
+        Ok(())
+    }
+
+    /// 测试生成的密钥保存在数据库中，再次获取时不变
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_stored_secret() -> anyhow::Result<()> {
+        let name = nanoid::nanoid!(8);
+        let secret = stored_secret(&name).await?;
+        assert_eq!(secret.len(), 32);
+        assert_eq!(stored_secret(&name).await?, secret);
+        Ok(())
+    }
+
+    /// 测试list_urls函数的分页
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_list_urls() -> anyhow::Result<()> {
+        for i in 0..3 {
+            shorten(
+                &format!("https://www.rust-lang.org/list/{}", i),
+                &ShortenerConfig::default(),
+            )
+            .await?;
+        }
+
+        let all = list_urls(3, 0).await?;
+        assert_eq!(all.len(), 3);
+        assert!(all.windows(2).all(|w| w[0].seq < w[1].seq));
+        let page = list_urls(2, 1).await?;
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].id, all[1].id);
+        assert_eq!(page[1].id, all[2].id);
+
+        // 游标分页，新插入的记录排在最后，不影响已经翻过的页
+        let page = list_urls_after(2, all[0].seq).await?;
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].id, all[1].id);
+        assert_eq!(page[1].id, all[2].id);
+        shorten(
+            "https://www.rust-lang.org/list/new",
+            &ShortenerConfig::default(),
+        )
+        .await?;
+        let page = list_urls_after(2, all[0].seq).await?;
+        assert_eq!(page[0].id, all[1].id);
+        assert_eq!(page[1].id, all[2].id);
+
+        let clamped = list_urls(MAX_LIST_LIMIT + 1, 0).await?;
+        assert!(clamped.len() <= MAX_LIST_LIMIT as usize);
         Ok(())
     }
 }
@@ -1,59 +1,103 @@
-//! 这个模块负责初始化和管理 PostgreSQL 数据库连接池。
+//! 这个模块负责初始化和管理数据库连接池。
 //! 主要功能包括：
-//! - 通过环境变量 `DATABASE_RUST_BOOTCAMP` 获取数据库连接 URL。
-//! - 检查指定的数据库是否存在，如果不存在则创建它。
-//! - 在数据库中创建必要的表（例如：`urls` 表）。
-//! - 提供异步函数 `get_pgsql_pool` 来获取数据库连接池。
+//! - 通过环境变量 `DATABASE_RUST_BOOTCAMP` 获取数据库连接 URL，根据 URL 的 scheme 选择 PostgreSQL 或 SQLite。
+//! - 检查指定的 PostgreSQL 数据库是否存在，如果不存在则创建它。
+//! - 通过迁移创建和演进必要的表（例如：`urls` 表），已执行的迁移记录在 `_migrations` 表中。
+//! - 开启 `sqlite` feature 后支持 SQLite，未设置环境变量时使用内存数据库，主要用于测试。
+//! - 提供异步函数 `get_pool` 来获取数据库连接池。
 
-use sqlx::{postgres, PgPool, Pool, Postgres};
+use sqlx::any::{install_default_drivers, AnyPoolOptions};
+use sqlx::{AnyPool, Executor};
 use tokio::sync::OnceCell;
 use tokio_postgres::{Client, NoTls};
 use tracing::info;
 
-/// 全局的 PostgreSQL 连接池。
-pub static PGSQL_POOL: OnceCell<PgPool> = OnceCell::const_new();
+/// 开启 `sqlite` feature 且未设置 `DATABASE_RUST_BOOTCAMP` 时使用的数据库
+#[cfg(feature = "sqlite")]
+pub const SQLITE_MEMORY_URL: &str = "sqlite::memory:";
 
-/// 获取 PostgreSQL 连接池的异步函数。
+/// 全局的数据库连接池。
+pub static POOL: OnceCell<AnyPool> = OnceCell::const_new();
+
+/// 获取数据库连接池的异步函数。
 /// 如果连接池尚未初始化，则进行初始化。
-/// 初始化过程中会检查数据库是否存在，如果不存在则创建它。
+/// 对于 PostgreSQL，初始化过程中会检查数据库是否存在，如果不存在则创建它，然后执行迁移。
+/// 对于 SQLite，会直接创建表。
 /// 需要先设置环境变量 export DATABASE_RUST_BOOTCAMP="postgres://postgres:password@ip:port/rust_bootcamp"
 ///
 /// # 返回
 ///
-/// 返回一个指向 PostgreSQL 连接池的静态引用。
+/// 返回一个指向数据库连接池的静态引用。
 ///
 /// # 示例
 ///
 /// ```rust,ignore
-/// let pool = get_pgsql_pool().await;
+/// let pool = get_pool().await;
 /// ```
-pub async fn get_pgsql_pool() -> &'static Pool<Postgres> {
-    PGSQL_POOL
-        .get_or_init(|| async {
-            let database_url = std::env::var("DATABASE_RUST_BOOTCAMP").expect(
-                "Please set the database URL in the environment variable DATABASE_RUST_BOOTCAMP.",
-            );
-            // 检查数据库是否存在
-            if let Err(_) = check_database_exists(&database_url).await {
-                // 创建数据库
-                if let Err(e) = create_database(&database_url).await {
-                    panic!("Failed to create database: {}", e);
+pub async fn get_pool() -> &'static AnyPool {
+    POOL.get_or_init(|| async {
+        install_default_drivers();
+        let database_url = database_url();
+        let pool = if database_url.starts_with("sqlite:") {
+            connect_sqlite(&database_url).await
+        } else {
+            connect_pgsql(&database_url).await
+        };
+        let pool = pool.expect("Failed to create pool.");
+        info!("Database connection pool created.");
+        pool
+    })
+    .await
+}
+
+fn database_url() -> String {
+    match std::env::var("DATABASE_RUST_BOOTCAMP") {
+        Ok(url) => url,
+        #[cfg(feature = "sqlite")]
+        Err(_) => SQLITE_MEMORY_URL.to_string(),
+        #[cfg(not(feature = "sqlite"))]
+        Err(_) => panic!(
+            "Please set the database URL in the environment variable DATABASE_RUST_BOOTCAMP."
+        ),
+    }
+}
+
+async fn connect_pgsql(url: &str) -> anyhow::Result<AnyPool> {
+    // 检查数据库是否存在
+    if check_database_exists(url).await.is_err() {
+        // 创建数据库
+        if let Err(e) = create_database(url).await {
+            panic!("Failed to create database: {}", e);
+        }
+    }
+    let pool = AnyPoolOptions::new()
+        .max_connections(1)
+        .connect(url)
+        .await?;
+    Ok(pool)
+}
+
+/// 内存数据库只存在于单个连接中，所以只使用一个连接，并且在每个新连接上创建表
+async fn connect_sqlite(url: &str) -> anyhow::Result<AnyPool> {
+    let pool = AnyPoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .after_connect(|conn, _meta| {
+            Box::pin(async move {
+                for sql in SQLITE_SCHEMA {
+                    conn.execute(*sql).await?;
                 }
-            }
-
-            let pgsql_pool = postgres::PgPoolOptions::new()
-                .max_connections(1)
-                .connect(&database_url)
-                .await
-                .expect("Failed to create pool.");
-            info!("Database connection pool created.");
-            pgsql_pool
+                Ok(())
+            })
         })
-        .await
+        .connect(url)
+        .await?;
+    Ok(pool)
 }
 
 /// 检查数据库是否存在的异步函数。
-/// 如果数据库存在，则创建必要的表。
+/// 如果数据库存在，则执行迁移。
 ///
 /// # 参数
 ///
@@ -63,15 +107,10 @@ pub async fn get_pgsql_pool() -> &'static Pool<Postgres> {
 ///
 /// 返回一个 `anyhow::Result` 类型，表示检查结果。
 async fn check_database_exists(url: &str) -> anyhow::Result<()> {
-    let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("Connection error: {}", e);
-        }
-    });
+    let mut client = connect(url).await?;
     client.simple_query("SELECT 1").await?;
-    // 到这里说明数据库存在，执行创建表的操作
-    create_table(client).await?;
+    // 到这里说明数据库存在，执行迁移
+    run_migrations(&mut client).await?;
     Ok(())
 }
 
@@ -95,25 +134,105 @@ async fn create_database(url: &str) -> anyhow::Result<()> {
     let db_name = url.split('/').last().unwrap();
     println!("db_name: {}", db_name);
 
-    let (client, connection) = tokio_postgres::connect(&db_url, NoTls).await?;
-
-    // 启动一个异步任务来处理连接的后台任务
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("Connection error: {}", e);
-        }
-    });
+    let client = connect(&db_url).await?;
 
     // 创建数据库
     client
         .simple_query(&format!("CREATE DATABASE {}", db_name))
         .await?;
-    // 到这里说明数据库存在，执行创建表的操作
-    create_table(client).await?;
+    // 上面的连接在维护数据库上，迁移要连接到新建的数据库执行
+    let mut client = connect(url).await?;
+    run_migrations(&mut client).await?;
     Ok(())
 }
 
-/// 创建表的异步函数。
+/// 连接 PostgreSQL，并启动一个异步任务来处理连接的后台任务
+async fn connect(url: &str) -> anyhow::Result<Client> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("Connection error: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+/// `urls` 表，语法同时兼容 PostgreSQL 和 SQLite
+const CREATE_URLS: &str = r#"
+    CREATE TABLE IF NOT EXISTS urls (
+        id VARCHAR(32) PRIMARY KEY,
+        url TEXT NOT NULL UNIQUE,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#;
+
+/// SQLite 的 `urls` 表，`seq` 是自增的插入序号，用于按插入顺序分页。
+/// SQLite 不能给已有的表追加自增列，所以 `seq` 直接作为主键，`id` 改为唯一约束。
+const CREATE_SQLITE_URLS: &str = r#"
+    CREATE TABLE IF NOT EXISTS urls (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id VARCHAR(32) NOT NULL UNIQUE,
+        url TEXT NOT NULL UNIQUE,
+        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+    "#;
+
+/// `url_visitors` 表，语法同时兼容 PostgreSQL 和 SQLite
+const CREATE_URL_VISITORS: &str = r#"
+    CREATE TABLE IF NOT EXISTS url_visitors (
+        id VARCHAR(32) NOT NULL,
+        visitor CHAR(64) NOT NULL,
+        PRIMARY KEY (id, visitor)
+    )
+    "#;
+
+/// `secrets` 表，保存服务生成的密钥，重启后继续使用，语法同时兼容 PostgreSQL 和 SQLite
+const CREATE_SECRETS: &str = r#"
+    CREATE TABLE IF NOT EXISTS secrets (
+        name VARCHAR(32) PRIMARY KEY,
+        value TEXT NOT NULL
+    )
+    "#;
+
+/// SQLite 没有历史数据需要升级，直接创建表即可
+const SQLITE_SCHEMA: &[&str] = &[CREATE_SQLITE_URLS, CREATE_URL_VISITORS, CREATE_SECRETS];
+
+/// 按顺序执行的 PostgreSQL 迁移，版本号为下标加1，每个迁移由若干条语句组成。
+/// 已发布的迁移不能再修改，新的表结构变更只能追加到末尾。
+///
+/// 引入迁移之前创建的数据库没有 `_migrations` 记录，所以前面的迁移需要保持幂等。
+const MIGRATIONS: &[&[&str]] = &[
+    // 1: 短链接表，ALTER 用于升级旧的表
+    &[
+        CREATE_URLS,
+        "ALTER TABLE urls ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now()",
+        "ALTER TABLE urls ALTER COLUMN id TYPE VARCHAR(32)",
+    ],
+    // 2: 独立访客表
+    &[
+        CREATE_URL_VISITORS,
+        "ALTER TABLE url_visitors ALTER COLUMN id TYPE VARCHAR(32)",
+    ],
+    // 3: 自增的插入序号，用于按插入顺序分页，已有的记录按创建时间回填
+    &[
+        "ALTER TABLE urls ADD COLUMN IF NOT EXISTS seq BIGINT",
+        "CREATE SEQUENCE IF NOT EXISTS urls_seq_seq OWNED BY urls.seq",
+        r#"
+        UPDATE urls SET seq = ordered.n
+        FROM (SELECT id, row_number() OVER (ORDER BY created_at, id) AS n FROM urls) AS ordered
+        WHERE urls.id = ordered.id
+        "#,
+        "SELECT setval('urls_seq_seq', (SELECT coalesce(max(seq), 0) + 1 FROM urls), false)",
+        "ALTER TABLE urls ALTER COLUMN seq SET DEFAULT nextval('urls_seq_seq')",
+        "ALTER TABLE urls ALTER COLUMN seq SET NOT NULL",
+        "CREATE UNIQUE INDEX IF NOT EXISTS urls_seq_idx ON urls (seq)",
+    ],
+    // 4: 服务生成的密钥
+    &[CREATE_SECRETS],
+];
+
+/// 执行尚未执行的迁移。
+/// 每个迁移在单独的事务中执行，并在同一事务中记录版本号，已执行的迁移会被跳过。
 ///
 /// # 参数
 ///
@@ -121,18 +240,40 @@ async fn create_database(url: &str) -> anyhow::Result<()> {
 ///
 /// # 返回
 ///
-/// 返回一个 `anyhow::Result` 类型，表示创建结果。
-async fn create_table(client: Client) -> anyhow::Result<()> {
+/// 返回一个 `anyhow::Result` 类型，表示迁移结果。
+pub async fn run_migrations(client: &mut Client) -> anyhow::Result<()> {
     client
-        .simple_query(
+        .batch_execute(
             r#"
-        CREATE TABLE IF NOT EXISTS urls (
-            id CHAR(6) PRIMARY KEY,
-            url TEXT NOT NULL UNIQUE
-        )
+        CREATE TABLE IF NOT EXISTS _migrations (
+            version INT PRIMARY KEY,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
         "#,
         )
         .await?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate() {
+        let version = i as i32 + 1;
+        let tx = client.transaction().await?;
+        // 防止多个实例同时执行同一个迁移
+        tx.batch_execute("LOCK TABLE _migrations IN EXCLUSIVE MODE")
+            .await?;
+        let applied = tx
+            .query_opt("SELECT 1 FROM _migrations WHERE version = $1", &[&version])
+            .await?
+            .is_some();
+        if applied {
+            continue;
+        }
+        for sql in *migration {
+            tx.batch_execute(sql).await?;
+        }
+        tx.execute("INSERT INTO _migrations (version) VALUES ($1)", &[&version])
+            .await?;
+        tx.commit().await?;
+        info!("Database migration {} applied.", version);
+    }
     Ok(())
 }
 
@@ -140,15 +281,34 @@ async fn create_table(client: Client) -> anyhow::Result<()> {
 mod pgsql_tests {
     use super::*;
 
-    /// 测试获取 PostgreSQL 连接池的异步函数。
+    /// 测试获取数据库连接池的异步函数。
     ///
     /// # 返回
     ///
     /// 返回一个 `anyhow::Result` 类型，表示测试结果。
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_get_pool() -> anyhow::Result<()> {
+        let pool = get_pool().await;
+        sqlx::query("SELECT 1").execute(pool).await?;
+        Ok(())
+    }
+
+    /// 测试迁移重复执行是幂等的
     #[ignore]
     #[tokio::test]
-    async fn test_pgsql_pool() -> anyhow::Result<()> {
-        let _conn = get_pgsql_pool().await;
+    async fn test_run_migrations_twice() -> anyhow::Result<()> {
+        let url = std::env::var("DATABASE_RUST_BOOTCAMP")?;
+        let (mut client, connection) = tokio_postgres::connect(&url, NoTls).await?;
+        tokio::spawn(connection);
+
+        run_migrations(&mut client).await?;
+        run_migrations(&mut client).await?;
+        let row = client
+            .query_one("SELECT count(*) FROM _migrations", &[])
+            .await?;
+        let count: i64 = row.get(0);
+        assert_eq!(count, MIGRATIONS.len() as i64);
         Ok(())
     }
 }
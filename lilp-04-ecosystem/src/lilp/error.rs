@@ -5,12 +5,20 @@
 //! - `InvalidUrl`: 无效的URL错误，包含了无效的URL字符串。
 //! - `UrlNotFound`: URL未找到错误。
 //! - `InvalidHeader`: 无效的header值错误，包装了`InvalidHeaderValue`。
+//! - `QrCodeError`: 二维码生成错误，包装了`QrError`。
+//! - `ImageError`: 图片编码错误，包装了`image::ImageError`。
+//! - `IdExhausted`: 多次重试后仍然无法生成不冲突的id。
+//! - `BatchTooLarge`: 批量缩短的URL数量超过上限。
+//! - `RateLimited`: 超过限流，包含了需要等待的时间。
 //!
 //! 此外，`AppError`实现了`IntoResponse` trait，可以将`AppError`转换为HTTP响应。这使得错误处理更加方便，可以直接将错误转换为对应的HTTP状态码和错误消息。
 
+use std::time::Duration;
+
 use axum::response::IntoResponse;
-use http::header::InvalidHeaderValue;
+use http::header::{InvalidHeaderValue, RETRY_AFTER};
 use http::StatusCode;
+use qrcode::types::QrError;
 use thiserror::Error;
 
 /// AppError枚举，定义了应用可能会遇到的错误类型。
@@ -31,17 +39,53 @@ pub enum AppError {
     /// 无效的header值错误，包装了InvalidHeaderValue。
     #[error("Invalid header value: {0}")]
     InvalidHeader(#[from] InvalidHeaderValue),
+
+    /// 二维码生成错误，包装了QrError。
+    #[error("QR code error: {0}")]
+    QrCodeError(#[from] QrError),
+
+    /// 图片编码错误，包装了image::ImageError。
+    #[error("Image error: {0}")]
+    ImageError(#[from] image::ImageError),
+
+    /// 多次重试后仍然无法生成不冲突的id，包含了尝试的次数。
+    #[error("Failed to generate a unique id after {0} attempts")]
+    IdExhausted(usize),
+
+    /// 批量缩短的URL数量超过上限，包含了上限。
+    #[error("Too many URLs in a batch, at most {0} are allowed")]
+    BatchTooLarge(usize),
+
+    /// 超过限流，包含了需要等待的时间。
+    #[error("Too many requests")]
+    RateLimited(Duration),
 }
 
 /// AppError的IntoResponse实现，将AppError转换为HTTP响应。
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
+        if let AppError::RateLimited(wait) = self {
+            // 至少等待1秒
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                self.to_string(),
+            )
+                .into_response();
+        }
         // 根据不同的错误类型，设置不同的HTTP状态码和错误消息。
         let (status, error_message) = match self {
             AppError::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AppError::InvalidUrl(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             AppError::UrlNotFound => (StatusCode::NOT_FOUND, self.to_string()),
-            AppError::InvalidHeader(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::InvalidHeader(_) | AppError::BatchTooLarge(_) => {
+                (StatusCode::BAD_REQUEST, self.to_string())
+            }
+            AppError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::QrCodeError(_) | AppError::ImageError(_) | AppError::IdExhausted(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
+            }
         };
         // 将状态码和错误消息转换为HTTP响应。
         (status, error_message).into_response()
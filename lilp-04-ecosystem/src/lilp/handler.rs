@@ -1,17 +1,49 @@
+use crate::lilp::config::ShortenerConfig;
 use crate::lilp::db;
+use crate::lilp::db::UrlRecord;
 use crate::lilp::error::AppError;
-use axum::extract::{Path, State};
+use crate::lilp::rate_limit::RateLimiter;
+use axum::extract::{ConnectInfo, Path, Query, State};
 use axum::response::IntoResponse;
 use axum::Json;
-use http::header::LOCATION;
+use futures::{stream, StreamExt};
+use http::header::{CONTENT_TYPE, LOCATION};
 use http::{HeaderMap, StatusCode};
+use image::{ImageFormat, Luma};
+use qrcode::render::svg;
+use qrcode::QrCode;
 use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use tracing::warn;
+use url::Url;
+
+/// 批量缩短时同时处理的URL数量
+const BATCH_CONCURRENCY: usize = 8;
+/// 一次批量缩短最多包含的URL数量
+pub const MAX_BATCH_SIZE: usize = 100;
 
 /// AppState结构体，包含了应用的状态信息
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub listen_addr: Arc<String>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub config: Arc<ShortenerConfig>,
+}
+
+impl AppState {
+    pub fn new(listen_addr: impl Into<String>) -> Self {
+        Self::with_config(listen_addr, ShortenerConfig::default())
+    }
+
+    pub fn with_config(listen_addr: impl Into<String>, config: ShortenerConfig) -> Self {
+        Self {
+            listen_addr: Arc::new(listen_addr.into()),
+            rate_limiter: Arc::new(RateLimiter::default()),
+            config: Arc::new(config),
+        }
+    }
 }
 
 /// ShortenReq结构体，用于接收缩短URL请求的数据
@@ -24,28 +56,485 @@ pub struct ShortenReq {
 #[derive(Debug, Serialize)]
 struct ShortenRes {
     url: String,
+    /// URL已经缩短过时为false
+    created: bool,
+}
+
+/// BatchShortenRes枚举，表示批量缩短中单个URL的结果
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum BatchShortenRes {
+    Ok { url: String, id: String },
+    Err { url: String, error: String },
+}
+
+/// StatsRes结构体，用于返回短链接的统计信息
+#[derive(Debug, Serialize)]
+pub struct StatsRes {
+    id: String,
+    unique_visitors: i64,
+}
+
+/// PreviewRes结构体，用于返回短链接指向的目标URL
+#[derive(Debug, Serialize)]
+pub struct PreviewRes {
+    id: String,
+    target: String,
+}
+
+/// ListUrlsParams结构体，用于接收分页查询的参数
+#[derive(Debug, Deserialize)]
+pub struct ListUrlsParams {
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+    /// 上一页最后一条记录的`seq`，设置时按游标分页，忽略`offset`
+    after: Option<i64>,
+}
+
+fn default_limit() -> i64 {
+    20
+}
+
+/// QrParams结构体，用于接收二维码请求的参数
+#[derive(Debug, Default, Deserialize)]
+pub struct QrParams {
+    #[serde(default)]
+    format: QrFormat,
+}
+
+/// 二维码的图片格式，默认为png
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
 }
 
 /// shorten函数，用于处理缩短URL的请求
 /// 接收一个AppState的状态和一个ShortenReq的请求数据
-/// 返回一个Result，包含了一个可以转换为响应的类型，或者一个AppError
+/// 返回一个Result，新创建时状态码为201，URL已经缩短过时为200，或者一个AppError
 pub async fn shorten(
     State(state): State<AppState>,
     Json(data): Json<ShortenReq>,
 ) -> Result<impl IntoResponse, AppError> {
-    let short_url_id = db::shorten(&data.url).await?;
+    validate_url(&data.url, &state.config)?;
+    let (short_url_id, created) = db::shorten(&data.url, &state.config).await?;
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
     let body = Json(ShortenRes {
-        url: format!("http://{}/{}", state.listen_addr, short_url_id),
+        url: short_url(&state, &short_url_id),
+        created,
     });
-    Ok((StatusCode::CREATED, body))
+    Ok((status, body))
+}
+
+/// shorten_batch函数，用于批量缩短URL
+/// 接收一个URL数组，以有限的并发逐个缩短，结果的顺序与输入一致
+/// 单个URL失败时只在对应位置返回错误，不影响其他URL
+/// 超过`MAX_BATCH_SIZE`个URL时返回400，每个URL消耗一个限流令牌，令牌不足时整批返回429
+pub async fn shorten_batch(
+    State(state): State<AppState>,
+    conn: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(urls): Json<Vec<String>>,
+) -> Result<Json<Vec<BatchShortenRes>>, AppError> {
+    if urls.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BatchTooLarge(MAX_BATCH_SIZE));
+    }
+    let limiter = &state.rate_limiter;
+    if let Some(ip) = limiter.client_ip(&headers, conn.as_ref()) {
+        limiter
+            .check_n(ip, urls.len() as u32)
+            .map_err(AppError::RateLimited)?;
+    }
+    let config = &state.config;
+    let results = stream::iter(urls)
+        .map(|url| async move {
+            let ret = match validate_url(&url, config) {
+                Ok(()) => db::shorten(&url, config).await.map(|(id, _)| id),
+                Err(e) => Err(e),
+            };
+            match ret {
+                Ok(id) => BatchShortenRes::Ok { url, id },
+                Err(e) => BatchShortenRes::Err {
+                    url,
+                    error: e.to_string(),
+                },
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(Json(results))
 }
 
 /// redirect函数，用于处理重定向的请求
-/// 接收一个id作为路径参数
+/// 接收一个id作为路径参数，并记录一次独立访问
 /// 返回一个Result，包含了一个可以转换为响应的类型，或者一个AppError
-pub async fn redirect(Path(id): Path<String>) -> Result<impl IntoResponse, AppError> {
+pub async fn redirect(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    conn: Option<ConnectInfo<SocketAddr>>,
+    req_headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
     let full_url = db::get_url(&id).await?;
+    // 存储时已经校验过，这里再校验一次，防止旧数据或配置变更后跳转到危险的地址
+    validate_url(&full_url, &state.config)?;
+    if let Some(ip) = state.rate_limiter.client_ip(&req_headers, conn.as_ref()) {
+        // 统计失败不影响跳转
+        if let Err(e) = db::record_unique_visit(&id, ip).await {
+            warn!("Failed to record visit for {}: {}", id, e);
+        }
+    }
     let mut headers = HeaderMap::new();
     headers.insert(LOCATION, full_url.parse()?);
     Ok((StatusCode::PERMANENT_REDIRECT, headers))
 }
+
+/// stats函数，用于返回短链接的统计信息
+/// 接收一个id作为路径参数
+/// 返回一个Result，包含了统计信息的JSON，id不存在时返回404
+pub async fn stats(Path(id): Path<String>) -> Result<Json<StatsRes>, AppError> {
+    db::get_url(&id).await?;
+    let unique_visitors = db::unique_visitors(&id).await?;
+    Ok(Json(StatsRes {
+        id,
+        unique_visitors,
+    }))
+}
+
+/// preview函数，用于在跳转前查看短链接指向的目标URL
+/// 接收一个id作为路径参数，不跳转也不记录访问
+/// 返回一个Result，包含了id和目标URL的JSON，id不存在时返回404
+pub async fn preview(Path(id): Path<String>) -> Result<Json<PreviewRes>, AppError> {
+    let target = db::get_url(&id).await?;
+    Ok(Json(PreviewRes { id, target }))
+}
+
+/// list_urls函数，用于按插入顺序分页列出已存储的URL
+/// 接收limit和offset作为查询参数，limit最大为100，
+/// 也可以用after代替offset，after为上一页最后一条记录的seq
+/// 返回一个Result，包含了URL记录的JSON数组，或者一个AppError
+pub async fn list_urls(
+    Query(params): Query<ListUrlsParams>,
+) -> Result<Json<Vec<UrlRecord>>, AppError> {
+    let records = match params.after {
+        Some(after) => db::list_urls_after(params.limit, after).await?,
+        None => db::list_urls(params.limit, params.offset).await?,
+    };
+    Ok(Json(records))
+}
+
+/// qr_code函数，用于返回短链接的二维码
+/// 接收一个id作为路径参数，format查询参数可选png（默认）或svg
+/// 返回一个Result，包含了二维码图片，id不存在时返回404
+pub async fn qr_code(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<QrParams>,
+) -> Result<impl IntoResponse, AppError> {
+    db::get_url(&id).await?;
+    let (content_type, body) = render_qr(&short_url(&state, &id), params.format)?;
+    Ok(([(CONTENT_TYPE, content_type)], body))
+}
+
+/// 检查URL是否是合法的http(s)地址，并且目标域名没有被禁止
+///
+/// 只允许http(s)，从而拒绝`javascript:`、`data:`等危险的scheme，避免被用作开放重定向
+fn validate_url(url: &str, config: &ShortenerConfig) -> Result<(), AppError> {
+    match Url::parse(url) {
+        Ok(u) if matches!(u.scheme(), "http" | "https") => match u.host_str() {
+            Some(host) if !config.is_host_denied(host) => Ok(()),
+            _ => Err(AppError::InvalidUrl(url.to_string())),
+        },
+        _ => Err(AppError::InvalidUrl(url.to_string())),
+    }
+}
+
+/// 根据id拼出完整的短链接
+fn short_url(state: &AppState, id: &str) -> String {
+    format!("http://{}/{}", state.listen_addr, id)
+}
+
+/// 将url渲染为指定格式的二维码，返回content type和图片内容
+fn render_qr(url: &str, format: QrFormat) -> Result<(&'static str, Vec<u8>), AppError> {
+    let code = QrCode::new(url.as_bytes())?;
+    match format {
+        QrFormat::Png => {
+            let image = code.render::<Luma<u8>>().build();
+            let mut buf = Cursor::new(Vec::new());
+            image.write_to(&mut buf, ImageFormat::Png)?;
+            Ok(("image/png", buf.into_inner()))
+        }
+        QrFormat::Svg => {
+            let image = code.render::<svg::Color>().build();
+            Ok(("image/svg+xml", image.into_bytes()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::extract::DefaultBodyLimit;
+    use axum::routing::post;
+    use axum::Router;
+    use http::Request;
+    use tower::ServiceExt;
+
+    const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn test_state() -> AppState {
+        AppState::new("127.0.0.1:9876")
+    }
+
+    #[tokio::test]
+    async fn shorten_batch_should_report_invalid_urls_per_item() -> anyhow::Result<()> {
+        let urls = vec!["not a url".to_string(), "ftp://example.com".to_string()];
+        let Json(results) =
+            shorten_batch(State(test_state()), None, HeaderMap::new(), Json(urls)).await?;
+        let results = serde_json::to_value(results)?;
+        assert_eq!(
+            results,
+            serde_json::json!([
+                { "url": "not a url", "error": "Invalid URL: not a url" },
+                { "url": "ftp://example.com", "error": "Invalid URL: ftp://example.com" },
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shorten_batch_should_be_bounded_and_rate_limited() -> anyhow::Result<()> {
+        let state = test_state();
+        let conn = || Some(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 12345))));
+        let urls = |n: usize| vec!["ftp://example.com".to_string(); n];
+
+        let ret = shorten_batch(
+            State(state.clone()),
+            conn(),
+            HeaderMap::new(),
+            Json(urls(MAX_BATCH_SIZE + 1)),
+        )
+        .await;
+        let res = ret.unwrap_err().into_response();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // 每个URL一个令牌，令牌不足时整批拒绝，也不消耗令牌
+        let capacity = crate::lilp::rate_limit::DEFAULT_CAPACITY as usize;
+        let ret = shorten_batch(
+            State(state.clone()),
+            conn(),
+            HeaderMap::new(),
+            Json(urls(capacity + 1)),
+        )
+        .await;
+        let res = ret.unwrap_err().into_response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let Json(results) = shorten_batch(
+            State(state.clone()),
+            conn(),
+            HeaderMap::new(),
+            Json(urls(capacity)),
+        )
+        .await?;
+        assert_eq!(results.len(), capacity);
+        let ret = shorten_batch(State(state), conn(), HeaderMap::new(), Json(urls(1))).await;
+        assert!(matches!(ret, Err(AppError::RateLimited(_))));
+        Ok(())
+    }
+
+    /// 测试shorten_batch函数
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_shorten_batch() -> anyhow::Result<()> {
+        let urls = vec![
+            "https://www.rust-lang.org/batch/1".to_string(),
+            "javascript:alert(1)".to_string(),
+            "https://www.rust-lang.org/batch/2".to_string(),
+        ];
+        let Json(results) = shorten_batch(
+            State(test_state()),
+            None,
+            HeaderMap::new(),
+            Json(urls.clone()),
+        )
+        .await?;
+        assert_eq!(results.len(), 3);
+        for (url, ret) in urls.iter().zip(&results) {
+            let ret = serde_json::to_value(ret)?;
+            assert_eq!(&ret["url"], url);
+        }
+        assert!(matches!(results[0], BatchShortenRes::Ok { .. }));
+        assert!(matches!(results[1], BatchShortenRes::Err { .. }));
+        assert!(matches!(results[2], BatchShortenRes::Ok { .. }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn oversized_body_should_be_rejected() -> anyhow::Result<()> {
+        let config = ShortenerConfig::default().with_body_limits(64, 1024);
+        let state = AppState::with_config("127.0.0.1:9876", config);
+        let app = Router::new()
+            .route(
+                "/",
+                post(shorten).layer(DefaultBodyLimit::max(state.config.max_body_size())),
+            )
+            .route(
+                "/api/shorten/batch",
+                post(shorten_batch)
+                    .layer(DefaultBodyLimit::max(state.config.max_batch_body_size())),
+            )
+            .with_state(state);
+
+        let url = format!("https://www.rust-lang.org/{}", "x".repeat(64));
+        let req = Request::post("/")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(
+                &serde_json::json!({ "url": url }),
+            )?))?;
+        let res = app.clone().oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // 批量缩短的上限更大，同样的URL不会被拒绝
+        let req = Request::post("/api/shorten/batch")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(&[format!("ftp://{}", url)])?))?;
+        let res = app.oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_url_should_reject_dangerous_targets() {
+        let config = ShortenerConfig::default().with_denied_hosts(["evil.com"]);
+        assert!(validate_url("https://www.rust-lang.org/", &config).is_ok());
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "https://evil.com/phish",
+            "http://login.evil.com/",
+        ] {
+            assert!(
+                matches!(validate_url(url, &config), Err(AppError::InvalidUrl(_))),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn shorten_should_reject_javascript_url() {
+        let req = ShortenReq {
+            url: "javascript:alert(1)".to_string(),
+        };
+        let ret = shorten(State(test_state()), Json(req)).await;
+        assert!(matches!(ret, Err(AppError::InvalidUrl(_))));
+    }
+
+    /// 测试重复缩短同一个URL，第二次返回200和created: false
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_shorten_same_url_twice() -> anyhow::Result<()> {
+        let url = format!("https://www.rust-lang.org/twice/{}", nanoid::nanoid!());
+        let mut bodies = Vec::new();
+        for status in [StatusCode::CREATED, StatusCode::OK] {
+            let req = ShortenReq { url: url.clone() };
+            let res = shorten(State(test_state()), Json(req))
+                .await?
+                .into_response();
+            assert_eq!(res.status(), status);
+            let body = to_bytes(res.into_body(), usize::MAX).await?;
+            bodies.push(serde_json::from_slice::<serde_json::Value>(&body)?);
+        }
+        assert_eq!(bodies[0]["created"], true);
+        assert_eq!(bodies[1]["created"], false);
+        assert_eq!(bodies[0]["url"], bodies[1]["url"]);
+        Ok(())
+    }
+
+    /// 测试redirect函数，目标被禁止后不再跳转
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_redirect_should_recheck_target() -> anyhow::Result<()> {
+        let url = "https://www.rust-lang.org/redirect";
+        let (id, _) = db::shorten(url, &ShortenerConfig::default()).await?;
+        let res = redirect(
+            State(test_state()),
+            Path(id.clone()),
+            None,
+            HeaderMap::new(),
+        )
+        .await?
+        .into_response();
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[LOCATION], url);
+
+        let config = ShortenerConfig::default().with_denied_hosts(["rust-lang.org"]);
+        let state = AppState::with_config("127.0.0.1:9876", config);
+        let ret = redirect(State(state), Path(id), None, HeaderMap::new()).await;
+        assert!(matches!(ret, Err(AppError::InvalidUrl(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn render_qr_should_produce_png_and_svg() -> anyhow::Result<()> {
+        let (content_type, png) = render_qr("http://127.0.0.1:9876/abc123", QrFormat::Png)?;
+        assert_eq!(content_type, "image/png");
+        assert!(png.starts_with(PNG_MAGIC));
+
+        let (content_type, svg) = render_qr("http://127.0.0.1:9876/abc123", QrFormat::Svg)?;
+        assert_eq!(content_type, "image/svg+xml");
+        assert!(String::from_utf8(svg)?.contains("<svg"));
+        Ok(())
+    }
+
+    /// 测试preview函数
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_preview() -> anyhow::Result<()> {
+        let url = "https://www.rust-lang.org/preview";
+        let (id, _) = db::shorten(url, &ShortenerConfig::default()).await?;
+        let Json(res) = preview(Path(id.clone())).await?;
+        assert_eq!(res.id, id);
+        assert_eq!(res.target, url);
+
+        let ret = preview(Path("nope".to_string())).await;
+        let res = ret.unwrap_err().into_response();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    /// 测试qr_code函数
+    #[cfg_attr(not(feature = "sqlite"), ignore)]
+    #[tokio::test]
+    async fn test_qr_code() -> anyhow::Result<()> {
+        let (id, _) =
+            db::shorten("https://www.rust-lang.org/qr", &ShortenerConfig::default()).await?;
+        let res = qr_code(State(test_state()), Path(id), Query(QrParams::default()))
+            .await?
+            .into_response();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_TYPE], "image/png");
+        let body = to_bytes(res.into_body(), usize::MAX).await?;
+        assert!(body.starts_with(PNG_MAGIC));
+
+        let ret = qr_code(
+            State(test_state()),
+            Path("nope".to_string()),
+            Query(QrParams::default()),
+        )
+        .await;
+        assert!(matches!(ret, Err(AppError::UrlNotFound)));
+        Ok(())
+    }
+}
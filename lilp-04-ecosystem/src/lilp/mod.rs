@@ -1,4 +1,7 @@
+pub mod config;
+pub mod cors;
 pub(crate) mod db;
 pub(crate) mod db_config;
 pub mod error;
 pub mod handler;
+pub mod rate_limit;
@@ -0,0 +1,220 @@
+//! `rate_limit`模块实现了按客户端IP限流的令牌桶。
+//!
+//! 每个IP对应一个`Bucket`，容量为`capacity`，令牌在`period`内匀速补满。
+//! 请求到来时消耗一个令牌，令牌不足时返回`429 Too Many Requests`，并在`Retry-After`中给出需要等待的秒数。
+//! 批量缩短按URL的数量消耗令牌，由handler调用`check_n`完成。
+//! 已经补满的桶和新建的桶没有区别，桶的数量变多时会被清理掉，避免每个IP都永久占用内存。
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+use http::HeaderMap;
+
+use crate::lilp::error::AppError;
+use crate::lilp::handler::AppState;
+
+/// 默认每个IP每分钟最多创建10个短链接
+pub const DEFAULT_CAPACITY: u32 = 10;
+pub const DEFAULT_PERIOD: Duration = Duration::from_secs(60);
+
+/// 桶的数量达到这个值后，新IP到来时清理已经补满的桶
+const PRUNE_THRESHOLD: usize = 1024;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// 单个IP的令牌桶
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// RateLimiter结构体，保存所有IP的令牌桶
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    /// 每秒补充的令牌数
+    refill_rate: f64,
+    /// 是否信任`X-Forwarded-For`，只有在反向代理之后才应该开启
+    trust_forwarded_for: bool,
+    buckets: DashMap<IpAddr, Bucket>,
+    /// 桶的数量达到它时清理一次，清理后设为剩余数量的两倍，避免活跃的IP很多时每次都清理
+    prune_at: AtomicUsize,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_rate: capacity as f64 / period.as_secs_f64(),
+            trust_forwarded_for: false,
+            buckets: DashMap::new(),
+            prune_at: AtomicUsize::new(PRUNE_THRESHOLD),
+        }
+    }
+
+    pub fn trust_forwarded_for(mut self, trust: bool) -> Self {
+        self.trust_forwarded_for = trust;
+        self
+    }
+
+    /// 尝试为`ip`消耗一个令牌，令牌不足时返回需要等待的时间
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_n(ip, 1)
+    }
+
+    /// 尝试为`ip`一次消耗`n`个令牌，令牌不足时不消耗任何令牌，并返回需要等待的时间
+    ///
+    /// `n`超过容量时永远无法满足，返回补满整个桶需要的时间
+    pub fn check_n(&self, ip: IpAddr, n: u32) -> Result<(), Duration> {
+        let n = n as f64;
+        let now = Instant::now();
+        if self.buckets.len() >= self.prune_at.load(Ordering::Relaxed)
+            && !self.buckets.contains_key(&ip)
+        {
+            self.prune(now);
+        }
+        let mut bucket = self.buckets.entry(ip).or_insert_with(|| Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_rate).min(self.capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= n {
+            bucket.tokens -= n;
+            Ok(())
+        } else {
+            let missing = n.min(self.capacity) - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / self.refill_rate))
+        }
+    }
+
+    /// 删除到`now`为止已经补满的桶
+    fn prune(&self, now: Instant) {
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens + elapsed * self.refill_rate < self.capacity
+        });
+        let prune_at = (self.buckets.len() * 2).max(PRUNE_THRESHOLD);
+        self.prune_at.store(prune_at, Ordering::Relaxed);
+    }
+
+    /// 获取客户端IP，优先使用`X-Forwarded-For`中的第一个地址（如果信任的话）
+    pub(crate) fn client_ip(
+        &self,
+        headers: &HeaderMap,
+        conn: Option<&ConnectInfo<SocketAddr>>,
+    ) -> Option<IpAddr> {
+        let forwarded = self
+            .trust_forwarded_for
+            .then(|| headers.get(X_FORWARDED_FOR))
+            .flatten()
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        forwarded.or_else(|| conn.map(|ConnectInfo(addr)| addr.ip()))
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_PERIOD)
+    }
+}
+
+/// rate_limit中间件，超过限制的请求返回429
+/// 无法获取客户端IP时不做限制
+pub async fn rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let limiter = &state.rate_limiter;
+    let ip = limiter.client_ip(req.headers(), req.extensions().get());
+    if let Some(ip) = ip {
+        if let Err(wait) = limiter.check(ip) {
+            return AppError::RateLimited(wait).into_response();
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::post;
+    use axum::Router;
+    use http::header::RETRY_AFTER;
+    use http::StatusCode;
+    use tower::ServiceExt;
+
+    fn create_request(ip: [u8; 4]) -> Request {
+        let mut req = Request::post("/").body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((ip, 12345))));
+        req
+    }
+
+    #[tokio::test]
+    async fn rate_limit_should_reject_over_limit_creates() -> anyhow::Result<()> {
+        let state = AppState::new("127.0.0.1:9876");
+        let app = Router::new()
+            .route("/", post(|| async { StatusCode::CREATED }))
+            .layer(from_fn_with_state(state.clone(), rate_limit))
+            .with_state(state);
+
+        for _ in 0..DEFAULT_CAPACITY {
+            let res = app.clone().oneshot(create_request([10, 0, 0, 1])).await?;
+            assert_eq!(res.status(), StatusCode::CREATED);
+        }
+        let res = app.clone().oneshot(create_request([10, 0, 0, 1])).await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = res.headers()[RETRY_AFTER].to_str()?.parse()?;
+        assert!(retry_after >= 1);
+
+        // 其他IP不受影响
+        let res = app.oneshot(create_request([10, 0, 0, 2])).await?;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        Ok(())
+    }
+
+    #[test]
+    fn full_buckets_should_be_pruned() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(20));
+        for i in 0..PRUNE_THRESHOLD as u32 {
+            assert!(limiter.check(IpAddr::from(i.to_be_bytes())).is_ok());
+        }
+        assert_eq!(limiter.buckets.len(), PRUNE_THRESHOLD);
+
+        // 这些桶都补满了，下一个新IP到来时被清理，之前用完令牌的IP不受影响
+        std::thread::sleep(Duration::from_millis(30));
+        let busy = IpAddr::from([10, 0, 0, 1]);
+        assert!(limiter.check_n(busy, 2).is_ok());
+        assert_eq!(limiter.buckets.len(), 1);
+        assert!(limiter.check(busy).is_err());
+    }
+
+    #[test]
+    fn client_ip_should_honor_forwarded_for_only_when_trusted() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, "1.2.3.4, 10.0.0.1".parse().unwrap());
+        let conn = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 80)));
+
+        let limiter = RateLimiter::default();
+        assert_eq!(
+            limiter.client_ip(&headers, Some(&conn)),
+            Some(IpAddr::from([127, 0, 0, 1]))
+        );
+        let limiter = RateLimiter::default().trust_forwarded_for(true);
+        assert_eq!(
+            limiter.client_ip(&headers, Some(&conn)),
+            Some(IpAddr::from([1, 2, 3, 4]))
+        );
+    }
+}
@@ -17,6 +17,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
 
 const CHANNEL_SIZE: usize = 1024;
+pub const DEFAULT_LOCALE: &str = "en";
 
 impl MetadataService {
     pub async fn materialize(
@@ -26,7 +27,13 @@ impl MetadataService {
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
         tokio::spawn(async move {
             while let Some(Ok(req)) = stream.next().await {
-                let content = Content::materialize(req.id);
+                let mut content = Content::materialize(req.id);
+                content.locale = if req.locale.is_empty() {
+                    DEFAULT_LOCALE.to_string()
+                } else {
+                    req.locale
+                };
+                content.template_id = req.template_id;
                 tx.send(Ok(content)).await.unwrap();
             }
         });
@@ -53,6 +60,8 @@ impl Content {
             views: rng.gen_range(123432..10000000),
             likes: rng.gen_range(1234..100000),
             dislikes: rng.gen_range(123..10000),
+            locale: DEFAULT_LOCALE.to_string(),
+            template_id: String::new(),
         }
     }
 
@@ -71,7 +80,22 @@ impl<'a> Tpl<'a> {
 
 impl MaterializeRequest {
     pub fn new_with_ids(ids: &[u32]) -> impl Stream<Item = Self> {
-        let reqs: HashSet<_> = ids.iter().map(|id| Self { id: *id }).collect();
+        Self::new_with_template(ids, "", "")
+    }
+
+    pub fn new_with_template(
+        ids: &[u32],
+        template_id: &str,
+        locale: &str,
+    ) -> impl Stream<Item = Self> {
+        let reqs: HashSet<_> = ids
+            .iter()
+            .map(|id| Self {
+                id: *id,
+                template_id: template_id.to_string(),
+                locale: locale.to_string(),
+            })
+            .collect();
         stream::iter(reqs)
     }
 }
@@ -109,9 +133,18 @@ mod tests {
         let config = AppConfig::load()?;
         let service = MetadataService::new(config);
         let stream = tokio_stream::iter(vec![
-            Ok(MaterializeRequest { id: 1 }),
-            Ok(MaterializeRequest { id: 2 }),
-            Ok(MaterializeRequest { id: 3 }),
+            Ok(MaterializeRequest {
+                id: 1,
+                ..Default::default()
+            }),
+            Ok(MaterializeRequest {
+                id: 2,
+                ..Default::default()
+            }),
+            Ok(MaterializeRequest {
+                id: 3,
+                ..Default::default()
+            }),
         ]);
 
         let response = service.materialize(stream).await?;
@@ -120,4 +153,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn materialize_should_render_with_template() -> Result<()> {
+        let config = AppConfig::load()?;
+        let service = MetadataService::new(config);
+        let stream = tokio_stream::iter(vec![Ok(MaterializeRequest {
+            id: 1,
+            template_id: "remind".to_string(),
+            locale: "fr".to_string(),
+        })]);
+
+        let response = service.materialize(stream).await?;
+        let ret = response.into_inner().next().await.unwrap()?;
+        assert_eq!(ret.template_id, "remind");
+        assert_eq!(ret.locale, "fr");
+
+        Ok(())
+    }
 }
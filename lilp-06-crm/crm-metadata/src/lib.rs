@@ -5,7 +5,7 @@ mod config;
 
 use std::pin::Pin;
 
-pub use abi::Tpl;
+pub use abi::{Tpl, DEFAULT_LOCALE};
 pub use config::AppConfig;
 use futures::Stream;
 use pb::{
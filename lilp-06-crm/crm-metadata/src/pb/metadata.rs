@@ -24,6 +24,12 @@ pub struct Content {
     pub likes: u64,
     #[prost(uint64, tag = "11")]
     pub dislikes: u64,
+    /// locale of the materialized content
+    #[prost(string, tag = "12")]
+    pub locale: ::prost::alloc::string::String,
+    /// template the content was rendered with, empty for the default template
+    #[prost(string, tag = "13")]
+    pub template_id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -41,6 +47,12 @@ pub struct Publisher {
 pub struct MaterializeRequest {
     #[prost(uint32, tag = "1")]
     pub id: u32,
+    /// template used to render the content
+    #[prost(string, tag = "2")]
+    pub template_id: ::prost::alloc::string::String,
+    /// requested locale, empty means the default locale
+    #[prost(string, tag = "3")]
+    pub locale: ::prost::alloc::string::String,
 }
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
@@ -13,9 +13,18 @@ async fn test_metadata() -> Result<()> {
     let addr = start_server().await?;
     let mut client = MetadataClient::connect(format!("http://{}", addr)).await?;
     let stream = tokio_stream::iter(vec![
-        MaterializeRequest { id: 1 },
-        MaterializeRequest { id: 2 },
-        MaterializeRequest { id: 3 },
+        MaterializeRequest {
+            id: 1,
+            ..Default::default()
+        },
+        MaterializeRequest {
+            id: 2,
+            ..Default::default()
+        },
+        MaterializeRequest {
+            id: 3,
+            ..Default::default()
+        },
     ]);
     let request = Request::new(stream);
     let response = client.materialize(request).await?.into_inner();
@@ -17,8 +17,8 @@ const CHANNEL_SIZE: usize = 1024;
 
 use crate::{
     pb::{
-        notification_server::NotificationServer, send_request::Msg, EmailMessage, SendRequest,
-        SendResponse,
+        notification_server::NotificationServer, send_request::Msg, EmailMessage, InAppMessage,
+        SendRequest, SendResponse, SmsMessage,
     },
     AppConfig, NotificationService, NotificationServiceInner, ResponseStream, ServiceResult,
 };
@@ -93,6 +93,30 @@ impl SendRequest {
 
         SendRequest { msg: Some(msg) }
     }
+
+    pub fn new_sms(sender: String, recipients: &[String], contents: &[Content]) -> Self {
+        let tpl = Tpl(contents);
+        let msg = Msg::Sms(SmsMessage {
+            message_id: Uuid::new_v4().to_string(),
+            sender,
+            recipients: recipients.to_vec(),
+            body: tpl.to_body(),
+        });
+
+        SendRequest { msg: Some(msg) }
+    }
+
+    pub fn new_in_app(title: String, device_id: String, contents: &[Content]) -> Self {
+        let tpl = Tpl(contents);
+        let msg = Msg::InApp(InAppMessage {
+            message_id: Uuid::new_v4().to_string(),
+            device_id,
+            title,
+            body: tpl.to_body(),
+        });
+
+        SendRequest { msg: Some(msg) }
+    }
 }
 
 fn dummy_send() -> mpsc::Sender<Msg> {
@@ -1,15 +1,19 @@
 use anyhow::Result;
 use proto_builder_trait::tonic::BuilderAttributes;
-use std::fs;
+use std::{env, fs, path::PathBuf};
 
 fn main() -> Result<()> {
     fs::create_dir_all("src/pb")?;
     let builder = tonic_build::configure();
+    // embedded by the reflection service
+    let descriptor = PathBuf::from(env::var("OUT_DIR")?).join("crm_descriptor.bin");
     builder
         .out_dir("src/pb")
+        .file_descriptor_set_path(descriptor)
+        .extern_path(".notification", "::crm_send::pb")
         .with_derive_builder(&["WelcomeRequest", "RecallRequest", "RemindRequest"], None)
         .with_field_attributes(
-            &["WelcomeRequest.content_ids"],
+            &["WelcomeRequest.content_ids", "RemindRequest.content_ids"],
             &[r#"#[builder(setter(each(name="content_id", into)))]"#],
         )
         .compile(
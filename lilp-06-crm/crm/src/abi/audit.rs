@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::mpsc};
+use tracing::warn;
+
+/// a notification attempt, one json line per entry in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// users are identified by their email in user-stat
+    pub user_id: String,
+    pub rpc: String,
+    pub template_id: String,
+    pub sent_at: DateTime<Utc>,
+    /// `sent`, or `failed: <reason>`
+    pub status: String,
+}
+
+/// appends entries to a jsonl file, the writes happen in a background task so sending isn't slowed down
+#[derive(Clone)]
+pub struct AuditLog {
+    sender: mpsc::UnboundedSender<AuditEntry>,
+}
+
+impl AuditLog {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let (sender, mut receiver) = mpsc::unbounded_channel::<AuditEntry>();
+        // the task ends once every sender is dropped, after writing what's left
+        tokio::spawn(async move {
+            while let Some(entry) = receiver.recv().await {
+                let mut line = match serde_json::to_vec(&entry) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        warn!("Failed to serialize audit entry {:?}: {:?}", entry, e);
+                        continue;
+                    }
+                };
+                line.push(b'\n');
+                if let Err(e) = file.write_all(&line).await {
+                    warn!("Failed to write audit entry {:?}: {:?}", entry, e);
+                }
+            }
+            let _ = file.flush().await;
+        });
+        Ok(Self { sender })
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        if self.sender.send(entry).is_err() {
+            warn!("Audit log writer is gone, entry dropped");
+        }
+    }
+}
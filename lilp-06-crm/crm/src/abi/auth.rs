@@ -3,6 +3,8 @@ use jwt_simple::prelude::*;
 use tonic::{service::Interceptor, Request, Status};
 use tracing::info;
 
+use super::RequestId;
+
 #[derive(Debug, Clone)]
 pub struct DecodingKey(Ed25519PublicKey);
 
@@ -57,7 +59,9 @@ impl Interceptor for DecodingKey {
             None => return Err(Status::unauthenticated("missing token")),
         };
 
+        let request_id = RequestId::from_metadata(req.metadata());
         req.extensions_mut().insert(user);
+        req.extensions_mut().insert(request_id);
         Ok(req)
     }
 }
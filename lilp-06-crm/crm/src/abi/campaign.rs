@@ -0,0 +1,127 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::pb::CampaignStatusResponse;
+
+pub type CampaignId = String;
+
+/// progress of one notification fan-out, updated while it's running
+#[derive(Debug, Default)]
+pub struct Progress {
+    total: AtomicUsize,
+    sent: AtomicUsize,
+    failed: AtomicUsize,
+    error: OnceLock<String>,
+    finished_at: OnceLock<Instant>,
+}
+
+/// progress of the campaigns started by welcome / recall / remind, keyed by campaign id.
+/// finished campaigns are evicted once they're older than the ttl
+#[derive(Clone)]
+pub struct Campaigns {
+    ttl: Duration,
+    campaigns: Arc<DashMap<CampaignId, Arc<Progress>>>,
+}
+
+impl Campaigns {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            campaigns: Arc::default(),
+        }
+    }
+
+    /// register a new campaign, returns its id and the progress to update
+    pub fn start(&self) -> (CampaignId, Arc<Progress>) {
+        self.campaigns
+            .retain(|_, progress| !progress.expired(self.ttl));
+        let id = Uuid::new_v4().to_string();
+        let progress = Arc::new(Progress::default());
+        self.campaigns.insert(id.clone(), progress.clone());
+        (id, progress)
+    }
+
+    pub fn status(&self, id: &str) -> Option<CampaignStatusResponse> {
+        let progress = self.campaigns.get(id)?;
+        (!progress.expired(self.ttl)).then(|| progress.status(id))
+    }
+}
+
+impl Progress {
+    /// a recipient was found, it's pending until `sent` or `failed` is called
+    pub fn add(&self) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// none of the recipients could be notified, `error` is why the last one failed
+    pub fn fail(&self, error: &str) {
+        let _ = self.error.set(error.to_string());
+    }
+
+    /// all recipients have been found and notified
+    pub fn finish(&self) {
+        let _ = self.finished_at.set(Instant::now());
+    }
+
+    fn expired(&self, ttl: Duration) -> bool {
+        self.finished_at
+            .get()
+            .is_some_and(|finished_at| finished_at.elapsed() >= ttl)
+    }
+
+    fn status(&self, id: &str) -> CampaignStatusResponse {
+        // check `finished_at` first, so a finished campaign never reports pending recipients
+        let done = self.finished_at.get().is_some();
+        let sent = self.sent.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let total = self.total.load(Ordering::Relaxed).max(sent + failed);
+        CampaignStatusResponse {
+            campaign_id: id.to_string(),
+            total: total as _,
+            sent: sent as _,
+            failed: failed as _,
+            pending: (total - sent - failed) as _,
+            done,
+            error: self.error.get().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finished_campaigns_should_be_evicted_after_ttl() {
+        let campaigns = Campaigns::new(Duration::from_millis(50));
+        let (running, _running) = campaigns.start();
+        let (finished, progress) = campaigns.start();
+        progress.add();
+        progress.sent();
+        progress.finish();
+        assert!(campaigns.status(&finished).unwrap().done);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(campaigns.status(&finished).is_none());
+        // evicted on the next start, the running one is kept
+        campaigns.start();
+        assert_eq!(campaigns.campaigns.len(), 2);
+        assert!(!campaigns.status(&running).unwrap().done);
+    }
+}
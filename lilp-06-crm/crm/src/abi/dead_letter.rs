@@ -0,0 +1,36 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use crm_send::pb::SendRequest;
+use prost_types::Timestamp;
+use tonic::Status;
+
+use crate::pb::DeadLetter;
+
+/// notifications which still failed after the retries, kept in memory until they're drained
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    letters: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+impl DeadLetters {
+    pub fn push(&self, user_id: String, rpc: &str, payload: SendRequest, error: &Status) {
+        let now = Utc::now();
+        let letter = DeadLetter {
+            user_id,
+            rpc: rpc.to_string(),
+            payload: Some(payload),
+            error: error.message().to_string(),
+            failed_at: Some(Timestamp {
+                seconds: now.timestamp(),
+                nanos: now.timestamp_subsec_nanos() as i32,
+            }),
+        };
+        self.letters.lock().unwrap().push(letter);
+    }
+
+    /// take all the dead letters, oldest first
+    pub fn drain(&self) -> Vec<DeadLetter> {
+        std::mem::take(&mut *self.letters.lock().unwrap())
+    }
+}
@@ -0,0 +1,167 @@
+// the errors are rpc statuses, like the results of the calls they wrap
+#![allow(clippy::result_large_err)]
+
+use std::{future::Future, time::Duration};
+
+use tokio::time::{self, Instant};
+use tonic::{metadata::MetadataMap, Code, Request, Status};
+
+/// header carrying the time the caller is willing to wait, e.g. `100m` for 100 milliseconds
+pub const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+tokio::task_local! {
+    // deadline of the rpc being served, downstream calls made on its behalf get what's left of it
+    static DEADLINE: Instant;
+}
+
+/// point in time after which the caller has given up on the rpc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Self(Instant::now() + timeout)
+    }
+
+    /// read the deadline from `grpc-timeout`, None if it's absent or invalid
+    pub fn from_metadata(metadata: &MetadataMap) -> Option<Self> {
+        let v = metadata.get(GRPC_TIMEOUT_HEADER)?.to_str().ok()?;
+        parse_timeout(v).map(Self::after)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    /// the deadline of the rpc being served, if it has one
+    pub fn current() -> Option<Self> {
+        DEADLINE.try_with(|at| Self(*at)).ok()
+    }
+}
+
+/// run `fut` until the deadline, it fails as deadline exceeded once the deadline passes.
+/// the downstream calls it makes carry the remaining budget, tasks it spawns don't
+pub async fn with_deadline<T, F>(deadline: Option<Deadline>, fut: F) -> Result<T, Status>
+where
+    F: Future<Output = Result<T, Status>>,
+{
+    let Some(Deadline(at)) = deadline else {
+        return fut.await;
+    };
+    DEADLINE
+        .scope(at, async move {
+            time::timeout_at(at, fut)
+                .await
+                .unwrap_or_else(|_| Err(deadline_exceeded()))
+        })
+        .await
+}
+
+/// run `fut` to completion, the downstream calls it makes fail as deadline exceeded once the
+/// deadline passes
+pub(crate) async fn scope<F: Future>(deadline: Deadline, fut: F) -> F::Output {
+    DEADLINE.scope(deadline.0, fut).await
+}
+
+/// run a downstream call with the remaining budget of the current rpc, if any
+pub(crate) async fn within_budget<T, F>(fut: F) -> Result<T, Status>
+where
+    F: Future<Output = Result<T, Status>>,
+{
+    let Some(deadline) = Deadline::current() else {
+        return fut.await;
+    };
+    match time::timeout(deadline.remaining(), fut).await {
+        // the downstream service enforces the propagated timeout too, tonic cancels the call
+        // once it's exceeded, which may happen a tick before the timer here fires
+        Ok(Err(e)) if e.code() == Code::Cancelled || deadline.remaining().is_zero() => {
+            Err(deadline_exceeded())
+        }
+        Ok(ret) => ret,
+        Err(_) => Err(deadline_exceeded()),
+    }
+}
+
+/// let the downstream service know how long the current rpc can still wait
+pub(crate) fn propagate<T>(req: &mut Request<T>) {
+    if let Some(deadline) = Deadline::current() {
+        req.set_timeout(deadline.remaining());
+    }
+}
+
+fn deadline_exceeded() -> Status {
+    Status::deadline_exceeded("deadline exceeded")
+}
+
+// at most 8 digits followed by the unit, see the grpc over http2 spec
+fn parse_timeout(v: &str) -> Option<Duration> {
+    if v.len() < 2 || v.len() > 9 {
+        return None;
+    }
+    let (value, unit) = v.split_at(v.len() - 1);
+    let value: u64 = value.parse().ok()?;
+    let timeout = match unit {
+        "H" => Duration::from_secs(value * 60 * 60),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    };
+    Some(timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_should_be_parsed() {
+        assert_eq!(parse_timeout("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_timeout("2S"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_timeout("m"), None);
+        assert_eq!(parse_timeout("10x"), None);
+        assert_eq!(parse_timeout("123456789m"), None);
+    }
+
+    #[test]
+    fn deadline_should_be_read_from_metadata() {
+        let mut metadata = MetadataMap::new();
+        assert_eq!(Deadline::from_metadata(&metadata), None);
+        metadata.insert(GRPC_TIMEOUT_HEADER, "1S".parse().unwrap());
+        let deadline = Deadline::from_metadata(&metadata).unwrap();
+        assert!(deadline.remaining() <= Duration::from_secs(1));
+        assert!(deadline.remaining() > Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn downstream_requests_should_carry_the_remaining_budget() {
+        let deadline = Some(Deadline::after(Duration::from_secs(1)));
+        let req = with_deadline(deadline, async {
+            let mut req = Request::new(());
+            propagate(&mut req);
+            Ok(req)
+        })
+        .await
+        .unwrap();
+        assert!(req.metadata().get(GRPC_TIMEOUT_HEADER).is_some());
+
+        // no deadline outside of an rpc
+        let mut req = Request::new(());
+        propagate(&mut req);
+        assert!(req.metadata().get(GRPC_TIMEOUT_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn with_deadline_should_fail_once_exceeded() {
+        let deadline = Some(Deadline::after(Duration::from_millis(10)));
+        let ret: Result<(), Status> = with_deadline(deadline, async {
+            time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        })
+        .await;
+        assert_eq!(ret.unwrap_err().code(), tonic::Code::DeadlineExceeded);
+    }
+}
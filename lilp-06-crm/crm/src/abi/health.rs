@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use futures::future;
+use tokio::time::sleep;
+use tonic_health::server::HealthReporter;
+use tracing::info;
+
+use crate::{pb::crm_server::CrmServer, CrmService};
+
+// a dependency found down is probed again this often, so the service recovers without traffic
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// keep the health of the crm service in sync with its dependencies:
+/// SERVING once all of them are connected, NOT_SERVING while any of them is down
+pub(crate) async fn report_health(svc: CrmService, mut reporter: HealthReporter) {
+    let mut changes = [
+        svc.user_stats.subscribe(),
+        svc.notification.subscribe(),
+        svc.metadata.subscribe(),
+    ];
+    let mut serving = None;
+    loop {
+        // connects the dependencies which aren't yet, outages are reported by the clients
+        let (user_stats, notification, metadata) = future::join3(
+            svc.user_stats.get(),
+            svc.notification.get(),
+            svc.metadata.get(),
+        )
+        .await;
+        let up = user_stats.is_ok() && notification.is_ok() && metadata.is_ok();
+        if serving != Some(up) {
+            info!("CRM service serving: {}", up);
+            if up {
+                reporter.set_serving::<CrmServer<CrmService>>().await;
+            } else {
+                reporter.set_not_serving::<CrmServer<CrmService>>().await;
+            }
+            serving = Some(up);
+        }
+
+        let changed = future::select_all(changes.iter_mut().map(|rx| Box::pin(rx.changed())));
+        tokio::select! {
+            (ret, _, _) = changed => {
+                if ret.is_err() {
+                    return;
+                }
+            }
+            _ = sleep(PROBE_INTERVAL) => {}
+        }
+    }
+}
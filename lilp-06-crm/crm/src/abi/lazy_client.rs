@@ -0,0 +1,226 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{watch, Mutex};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code, Status,
+};
+use tracing::warn;
+
+use super::deadline;
+
+const BACKOFF_BASE: Duration = Duration::from_millis(100);
+const BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// a grpc client that connects on first use, and reconnects with backoff once the channel is dropped
+pub struct LazyClient<T> {
+    endpoint: Endpoint,
+    make: fn(Channel) -> T,
+    state: Mutex<State<T>>,
+    // whether the dependency is up, as far as the last connect or call can tell
+    available: watch::Sender<bool>,
+}
+
+struct State<T> {
+    client: Option<T>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl<T: Clone> LazyClient<T> {
+    pub fn new(
+        dst: impl Into<String>,
+        make: fn(Channel) -> T,
+    ) -> Result<Self, tonic::transport::Error> {
+        let endpoint = Endpoint::from_shared(dst.into())?;
+        Ok(Self::with_endpoint(endpoint, make))
+    }
+
+    /// like `new`, for an endpoint with custom settings, e.g. keep-alive
+    pub fn with_endpoint(endpoint: Endpoint, make: fn(Channel) -> T) -> Self {
+        Self {
+            endpoint,
+            make,
+            state: Mutex::new(State {
+                client: None,
+                failures: 0,
+                retry_at: None,
+            }),
+            available: watch::Sender::new(false),
+        }
+    }
+
+    /// notified whenever the dependency is found to be up or down
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.available.subscribe()
+    }
+
+    /// get the connected client, connect if there's none yet
+    pub async fn get(&self) -> Result<T, Status> {
+        let mut state = self.state.lock().await;
+        if let Some(client) = &state.client {
+            return Ok(client.clone());
+        }
+
+        if let Some(retry_at) = state.retry_at {
+            if Instant::now() < retry_at {
+                return Err(Status::unavailable(format!(
+                    "{} is unavailable, retry later",
+                    self.endpoint.uri()
+                )));
+            }
+        }
+
+        match self.endpoint.connect().await {
+            Ok(channel) => {
+                let client = (self.make)(channel);
+                state.client = Some(client.clone());
+                state.failures = 0;
+                state.retry_at = None;
+                self.set_available(true);
+                Ok(client)
+            }
+            Err(e) => {
+                state.failures += 1;
+                state.retry_at = Some(Instant::now() + backoff(state.failures));
+                self.set_available(false);
+                warn!("Failed to connect to {}: {:?}", self.endpoint.uri(), e);
+                Err(Status::unavailable(format!(
+                    "failed to connect to {}",
+                    self.endpoint.uri()
+                )))
+            }
+        }
+    }
+
+    /// run `f` with the client, the channel is dropped if the dependency turns out to be unavailable.
+    /// inside an rpc with a deadline, it fails as deadline exceeded once the deadline passes
+    pub async fn call<R, F, Fut>(&self, f: F) -> Result<R, Status>
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<R, Status>>,
+    {
+        let ret = deadline::within_budget(async {
+            let client = self.get().await?;
+            f(client).await
+        })
+        .await;
+        if matches!(&ret, Err(e) if e.code() == Code::Unavailable) {
+            self.reset().await;
+        }
+        ret
+    }
+
+    /// drop the current channel, the next call will reconnect
+    pub async fn reset(&self) {
+        self.state.lock().await.client = None;
+        self.set_available(false);
+    }
+
+    // only notify the subscribers on changes
+    fn set_available(&self, available: bool) {
+        self.available.send_if_modified(|current| {
+            let changed = *current != available;
+            *current = available;
+            changed
+        });
+    }
+}
+
+fn backoff(failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    BACKOFF_BASE.saturating_mul(factor).min(BACKOFF_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChannelConfig;
+    use anyhow::Result;
+    use crm_metadata::{
+        pb::{metadata_client::MetadataClient, MaterializeRequest},
+        AppConfig, MetadataService,
+    };
+    use futures::StreamExt;
+    use std::net::SocketAddr;
+    use tokio::time::sleep;
+    use tonic::transport::Server;
+
+    #[test]
+    fn backoff_should_grow_and_cap() {
+        assert_eq!(backoff(1), BACKOFF_BASE);
+        assert_eq!(backoff(2), BACKOFF_BASE * 2);
+        assert_eq!(backoff(3), BACKOFF_BASE * 4);
+        assert_eq!(backoff(100), BACKOFF_MAX);
+    }
+
+    #[tokio::test]
+    async fn lazy_client_should_connect_once_dependency_is_up() -> Result<()> {
+        let addr: SocketAddr = "[::1]:61100".parse()?;
+        let client = LazyClient::new(format!("http://{}", addr), MetadataClient::new)?;
+
+        let available = client.subscribe();
+        // dependency is down, the call fails but doesn't panic
+        let ret = client.get().await;
+        assert_eq!(ret.unwrap_err().code(), Code::Unavailable);
+        assert!(!*available.borrow());
+
+        let config: AppConfig =
+            serde_yaml::from_str(include_str!("../../../crm-metadata/metadata.yml"))?;
+        let svc = MetadataService::new(config).into_server();
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(svc)
+                .serve(addr)
+                .await
+                .unwrap();
+        });
+        sleep(BACKOFF_BASE + Duration::from_millis(50)).await;
+
+        let contents = client
+            .call(
+                |mut c| async move { c.materialize(MaterializeRequest::new_with_ids(&[1])).await },
+            )
+            .await?
+            .into_inner();
+        let contents: Vec<_> = contents.collect().await;
+        assert_eq!(contents.len(), 1);
+        assert!(*available.borrow());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn channel_with_keep_alive_should_connect() -> Result<()> {
+        let addr: SocketAddr = "[::1]:61101".parse()?;
+        let config: AppConfig =
+            serde_yaml::from_str(include_str!("../../../crm-metadata/metadata.yml"))?;
+        let svc = MetadataService::new(config).into_server();
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(svc)
+                .serve(addr)
+                .await
+                .unwrap();
+        });
+        sleep(Duration::from_millis(50)).await;
+
+        let channel = ChannelConfig {
+            http2_keep_alive_interval: Some(10),
+            keep_alive_timeout: Some(5),
+            tcp_keepalive: Some(30),
+        };
+        let endpoint = channel.endpoint(format!("http://{}", addr))?;
+        let client = LazyClient::with_endpoint(endpoint, MetadataClient::new);
+        let contents = client
+            .call(
+                |mut c| async move { c.materialize(MaterializeRequest::new_with_ids(&[1])).await },
+            )
+            .await?
+            .into_inner();
+        assert_eq!(contents.collect::<Vec<_>>().await.len(), 1);
+        Ok(())
+    }
+}
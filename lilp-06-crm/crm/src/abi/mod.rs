@@ -1,166 +1,482 @@
+mod audit;
 pub mod auth;
+mod campaign;
+mod dead_letter;
+mod deadline;
+mod health;
+mod lazy_client;
+mod request_id;
+mod scheduler;
+mod template_cache;
+mod validate;
 
-use crate::pb::{RecallRequest, RecallResponse, RemindRequest, RemindResponse};
+pub use audit::{AuditEntry, AuditLog};
+pub use campaign::{CampaignId, Campaigns, Progress};
+pub use dead_letter::DeadLetters;
+pub use deadline::{with_deadline, Deadline, GRPC_TIMEOUT_HEADER};
+pub(crate) use health::report_health;
+pub use lazy_client::LazyClient;
+pub use request_id::{with_request_id, RequestId, REQUEST_ID_HEADER};
+pub use scheduler::Scheduler;
+pub use template_cache::TemplateCache;
+
+use crate::pb::{Channel, RecallRequest, RecallResponse, RemindRequest, RemindResponse};
 use crate::{
     pb::{WelcomeRequest, WelcomeResponse},
     CrmService,
 };
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use crm_metadata::pb::{Content, MaterializeRequest};
 use crm_send::pb::SendRequest;
-use futures::StreamExt;
-use std::sync::Arc;
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Response, Status};
-use tracing::warn;
-use user_stat::pb::QueryRequest;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tonic::{Code, Response, Status};
+use tracing::{info, warn};
+use user_stat::pb::{QueryRequest, User};
+
+// grows linearly with the attempts
+const SEND_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// outcome of a notification fan-out, a failed send doesn't abort the rest
+#[derive(Debug, Default)]
+pub struct SendSummary {
+    pub succeeded: usize,
+    pub failed: Vec<Status>,
+}
 
 impl CrmService {
-    pub async fn welcome(&self, req: WelcomeRequest) -> Result<Response<WelcomeResponse>, Status> {
+    pub async fn welcome(
+        &self,
+        req: WelcomeRequest,
+        rid: &RequestId,
+    ) -> Result<Response<WelcomeResponse>, Status> {
+        req.validate()?;
+        let channel = req.channel();
         let request_id = req.id;
         let d1 = Utc::now() - Duration::days(req.interval as _);
         let d2 = d1 + Duration::days(1);
         let query = QueryRequest::new_with_dt("created_at", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
-
-        let contents = self
-            .metadata
-            .clone()
-            .materialize(MaterializeRequest::new_with_ids(&req.content_ids))
+        let res_user_stats = self
+            .user_stats
+            .call(|mut c| async move { c.query(with_request_id(query, rid)).await })
             .await?
             .into_inner();
 
-        let contents: Vec<Content> = contents
-            .filter_map(|v| async move { v.ok() })
-            .collect()
-            .await;
-        let contents = Arc::new(contents);
+        let suppressed = AtomicUsize::new(0);
+        let users = self.unsuppressed(res_user_stats, &suppressed);
+        if req.dry_run {
+            let recipient_ids: Vec<String> = users.map_ok(|user| user.email).try_collect().await?;
+            return Ok(Response::new(WelcomeResponse {
+                id: request_id,
+                recipient_count: recipient_ids.len() as _,
+                recipient_ids,
+                suppressed: suppressed.load(Ordering::Relaxed) as _,
+                ..Default::default()
+            }));
+        }
 
-        let (tx, rx) = mpsc::channel(1024);
+        let (contents, degraded) = self
+            .materialize(&req.content_ids, &req.template_id, &req.locale, rid)
+            .await?;
 
-        let sender = self.config.server.sender_email.clone();
-        tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new("Welcome".to_string(), sender, &[user.email], &contents);
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
-                }
-            }
+        let users: Vec<User> = users.try_collect().await?;
+        let svc = self.clone();
+        let reqs = users.into_iter().map(move |user| {
+            let req = svc.notification(channel, "Welcome", &user.email, &contents);
+            (user.email, req)
         });
-        let reqs = ReceiverStream::new(rx);
-
-        // NOTE: this is an alternative solution
-        // let sender = self.config.server.sender_email.clone();
-        // let reqs = res.filter_map(move |v| {
-        //     let sender: String = sender.clone();
-        //     let contents = contents.clone();
-        //     async move {
-        //         let v = v.ok()?;
-        //         Some(gen_send_req("Welcome".to_string(), sender, v, &contents))
-        //     }
-        // });
-
-        self.notification.clone().send(reqs).await?;
-
-        Ok(Response::new(WelcomeResponse { id: request_id }))
+        let campaign_id = self.start_campaign(reqs, "welcome", req.template_id, rid);
+        let suppressed = suppressed.load(Ordering::Relaxed);
+        info!(
+            "[{}] request {} started campaign {}, suppressed: {}",
+            rid, request_id, campaign_id, suppressed
+        );
+
+        Ok(Response::new(WelcomeResponse {
+            id: request_id,
+            suppressed: suppressed as _,
+            campaign_id,
+            degraded,
+            ..Default::default()
+        }))
     }
 
-    pub async fn recall(&self, req: RecallRequest) -> Result<Response<RecallResponse>, Status> {
+    pub async fn recall(
+        &self,
+        req: RecallRequest,
+        rid: &RequestId,
+    ) -> Result<Response<RecallResponse>, Status> {
+        req.validate()?;
         let request_id = req.id.clone();
         let d1 = Utc::now() - Duration::days(req.last_visit_interval as _);
         let d2 = Utc::now();
         let query = QueryRequest::new_with_dt("last_visit", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
-
-        let contents = self
-            .metadata
-            .clone()
-            .materialize(MaterializeRequest::new_with_ids(&req.content_ids))
+        let res_user_stats = self
+            .user_stats
+            .call(|mut c| async move { c.query(with_request_id(query, rid)).await })
             .await?
             .into_inner();
 
-        let contents: Vec<Content> = contents
-            .filter_map(|v| async move { v.ok() })
-            .collect()
-            .await;
-        let contents = Arc::new(contents);
-
-        let (tx, rx) = mpsc::channel(1024);
+        let (contents, degraded) = self.materialize(&req.content_ids, "", "", rid).await?;
 
+        let suppressed = AtomicUsize::new(0);
+        let users: Vec<User> = self
+            .unsuppressed(res_user_stats, &suppressed)
+            .try_collect()
+            .await?;
         let sender = self.config.server.sender_email.clone();
-        tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new(
-                    "Recall Reminder".to_string(),
-                    sender,
-                    &[user.email],
-                    &contents,
-                );
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
-                }
-            }
+        let reqs = users.into_iter().map(move |user| {
+            let req = SendRequest::new(
+                "Recall Reminder".to_string(),
+                sender.clone(),
+                std::slice::from_ref(&user.email),
+                &contents,
+            );
+            (user.email, req)
         });
-        let reqs = ReceiverStream::new(rx);
+        let campaign_id = self.start_campaign(reqs, "recall", String::new(), rid);
+        let suppressed = suppressed.load(Ordering::Relaxed);
+        info!(
+            "[{}] request {} started campaign {}, suppressed: {}",
+            rid, request_id, campaign_id, suppressed
+        );
+
+        Ok(Response::new(RecallResponse {
+            id: request_id,
+            suppressed: suppressed as _,
+            campaign_id,
+            degraded,
+        }))
+    }
 
-        self.notification.clone().send(reqs).await?;
+    /// send the reminders now, or schedule them if `send_at` is set
+    pub async fn remind(
+        &self,
+        mut req: RemindRequest,
+        rid: &RequestId,
+    ) -> Result<Response<RemindResponse>, Status> {
+        req.validate()?;
+        let Some(send_at) = req.send_at.take() else {
+            return self.send_reminders(req, rid).await;
+        };
+        let at = DateTime::from_timestamp(send_at.seconds, send_at.nanos.max(0) as _)
+            .ok_or_else(|| Status::invalid_argument("invalid send_at"))?;
 
-        Ok(Response::new(RecallResponse { id: request_id }))
+        let request_id = req.id.clone();
+        let svc = self.clone();
+        let job_rid = rid.clone();
+        let job_id = self.scheduler.schedule(at, async move {
+            if let Err(e) = svc.send_reminders(req, &job_rid).await {
+                warn!("[{}] failed to send scheduled reminders: {:?}", job_rid, e);
+            }
+        });
+        info!(
+            "[{}] request {} scheduled at {}: {}",
+            rid, request_id, at, job_id
+        );
+
+        Ok(Response::new(RemindResponse {
+            id: request_id,
+            job_id,
+            ..Default::default()
+        }))
     }
 
-    pub async fn remind(&self, req: RemindRequest) -> Result<Response<RemindResponse>, Status> {
+    async fn send_reminders(
+        &self,
+        req: RemindRequest,
+        rid: &RequestId,
+    ) -> Result<Response<RemindResponse>, Status> {
         let request_id = req.id.clone();
         let d1 = Utc::now() - Duration::days(req.last_visit_interval as _);
         let d2 = Utc::now();
         let query = QueryRequest::new_with_dt("last_visit", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
+        let res_user_stats = self
+            .user_stats
+            .call(|mut c| async move { c.query(with_request_id(query, rid)).await })
+            .await?
+            .into_inner();
+
+        let (contents, degraded) = self
+            .materialize(&req.content_ids, &req.template_id, &req.locale, rid)
+            .await?;
+
+        let suppressed = AtomicUsize::new(0);
+        let channel = req.channel();
+        let users: Vec<User> = self
+            .unsuppressed(res_user_stats, &suppressed)
+            .try_collect()
+            .await?;
+        let svc = self.clone();
+        let reqs = users.into_iter().map(move |user| {
+            let req = svc.notification(channel, "Remind Notification", &user.email, &contents);
+            (user.email, req)
+        });
+        let campaign_id = self.start_campaign(reqs, "remind", req.template_id, rid);
+        let suppressed = suppressed.load(Ordering::Relaxed);
+        info!(
+            "[{}] request {} started campaign {}, suppressed: {}",
+            rid, request_id, campaign_id, suppressed
+        );
 
+        Ok(Response::new(RemindResponse {
+            id: request_id,
+            suppressed: suppressed as _,
+            campaign_id,
+            degraded,
+            ..Default::default()
+        }))
+    }
+
+    /// the notification of the user through `channel`, the user-stats service only knows
+    /// the emails, so sms and in-app are addressed by email too and resolved by the sender
+    fn notification(
+        &self,
+        channel: Channel,
+        subject: &str,
+        email: &str,
+        contents: &[Content],
+    ) -> SendRequest {
+        let recipients = [email.to_string()];
+        match channel {
+            Channel::Email => SendRequest::new(
+                subject.to_string(),
+                self.config.server.sender_email.clone(),
+                &recipients,
+                contents,
+            ),
+            Channel::Sms => {
+                SendRequest::new_sms(self.config.server.sms_sender.clone(), &recipients, contents)
+            }
+            Channel::InApp => {
+                SendRequest::new_in_app(subject.to_string(), email.to_string(), contents)
+            }
+        }
+    }
+
+    /// the users who didn't opt out, the suppressed ones are counted in `suppressed`.
+    /// errors of the user-stats stream are passed through, so a partial list is never used
+    fn unsuppressed<'a>(
+        &'a self,
+        users: impl Stream<Item = Result<User, Status>> + 'a,
+        suppressed: &'a AtomicUsize,
+    ) -> impl Stream<Item = Result<User, Status>> + 'a {
+        users.try_filter(move |user| {
+            let skip = self.is_suppressed(&user.email);
+            if skip {
+                suppressed.fetch_add(1, Ordering::Relaxed);
+            }
+            future::ready(!skip)
+        })
+    }
+
+    /// like `materialize_fresh`, but while the metadata service is unavailable the cached contents
+    /// or the default template are used instead. the flag tells whether it fell back
+    async fn materialize(
+        &self,
+        ids: &[u32],
+        template_id: &str,
+        locale: &str,
+        rid: &RequestId,
+    ) -> Result<(Arc<Vec<Content>>, bool), Status> {
+        match self.materialize_fresh(ids, template_id, locale, rid).await {
+            Ok(contents) => {
+                self.templates
+                    .insert(template_id, locale, ids, contents.clone());
+                Ok((contents, false))
+            }
+            Err(e) if e.code() == Code::Unavailable => {
+                let fallback = self
+                    .templates
+                    .get(template_id, locale, ids)
+                    .or_else(|| self.default_contents());
+                let Some(contents) = fallback else {
+                    return Err(e);
+                };
+                warn!(
+                    "[{}] metadata unavailable, fallback to the cached or default template: {:?}",
+                    rid, e
+                );
+                Ok((contents, true))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn default_contents(&self) -> Option<Arc<Vec<Content>>> {
+        let tpl = self.config.server.default_template.as_ref()?;
+        Some(Arc::new(vec![Content {
+            name: tpl.name.clone(),
+            description: tpl.description.clone(),
+            url: tpl.url.clone(),
+            locale: self.config.server.default_locale.clone(),
+            ..Default::default()
+        }]))
+    }
+
+    /// fetch the contents in the requested locale, missing ones fallback to the default locale
+    async fn materialize_fresh(
+        &self,
+        ids: &[u32],
+        template_id: &str,
+        locale: &str,
+        rid: &RequestId,
+    ) -> Result<Arc<Vec<Content>>, Status> {
+        let default_locale = self.config.server.default_locale.as_str();
+        let locale = if locale.is_empty() {
+            default_locale
+        } else {
+            locale
+        };
+        let mut contents = self.fetch_contents(ids, template_id, locale, rid).await?;
+
+        if locale != default_locale {
+            let missing: Vec<u32> = ids
+                .iter()
+                .filter(|id| !contents.iter().any(|c| c.id == **id))
+                .copied()
+                .collect();
+            if !missing.is_empty() {
+                let fallback = self
+                    .fetch_contents(&missing, template_id, default_locale, rid)
+                    .await?;
+                contents.extend(fallback);
+            }
+        }
+        Ok(Arc::new(contents))
+    }
+
+    async fn fetch_contents(
+        &self,
+        ids: &[u32],
+        template_id: &str,
+        locale: &str,
+        rid: &RequestId,
+    ) -> Result<Vec<Content>, Status> {
+        let req = MaterializeRequest::new_with_template(ids, template_id, locale);
         let contents = self
             .metadata
-            .clone()
-            .materialize(MaterializeRequest::new_with_ids(&vec![]))
+            .call(|mut c| async move { c.materialize(with_request_id(req, rid)).await })
             .await?
             .into_inner();
 
-        let contents: Vec<Content> = contents
+        Ok(contents
             .filter_map(|v| async move { v.ok() })
             .collect()
-            .await;
-        let contents = Arc::new(contents);
-
-        let (tx, rx) = mpsc::channel(1024);
+            .await)
+    }
 
-        let sender = self.config.server.sender_email.clone();
+    /// fan the requests out in the background, returns the id to poll the progress with.
+    /// the caller's deadline doesn't reach the spawned sends, they're given `campaign_timeout`
+    fn start_campaign(
+        &self,
+        reqs: impl Iterator<Item = (String, SendRequest)> + Send + 'static,
+        rpc: &'static str,
+        template_id: String,
+        rid: &RequestId,
+    ) -> CampaignId {
+        let (campaign_id, progress) = self.campaigns.start();
+        let svc = self.clone();
+        let id = campaign_id.clone();
+        let rid = rid.clone();
+        let timeout = std::time::Duration::from_secs(self.config.server.campaign_timeout);
+        let deadline = Deadline::after(timeout);
         tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new(
-                    "Remind Notification".to_string(),
-                    sender,
-                    &[user.email],
-                    &contents,
-                );
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
-                }
-            }
+            let fan_out = svc.fan_out(stream::iter(reqs), rpc, &template_id, &rid, &progress);
+            let summary = deadline::scope(deadline, fan_out).await;
+            info!("[{}] campaign {} notified: {:?}", rid, id, summary);
         });
-        let reqs = ReceiverStream::new(rx);
+        campaign_id
+    }
 
-        self.notification.clone().send(reqs).await?;
+    /// send each request on its own, with at most `send_concurrency` in flight.
+    /// requests are paired with the id of the user they're for, every attempt goes to the audit log
+    /// and is counted in `progress`
+    pub async fn fan_out(
+        &self,
+        reqs: impl Stream<Item = (String, SendRequest)>,
+        rpc: &str,
+        template_id: &str,
+        rid: &RequestId,
+        progress: &Progress,
+    ) -> SendSummary {
+        let limit = self.config.server.send_concurrency.max(1);
+        let summary = reqs
+            .map(|(user_id, req)| {
+                progress.add();
+                async move {
+                    let ret = self.send_with_retries(req.clone(), rid).await;
+                    if let Err(e) = &ret {
+                        self.dead_letters.push(user_id.clone(), rpc, req, e);
+                    }
+                    if let Some(audit) = &self.audit {
+                        audit.record(AuditEntry {
+                            user_id,
+                            rpc: rpc.to_string(),
+                            template_id: template_id.to_string(),
+                            sent_at: Utc::now(),
+                            status: match &ret {
+                                Ok(()) => "sent".to_string(),
+                                Err(e) => format!("failed: {}", e.message()),
+                            },
+                        });
+                    }
+                    ret
+                }
+            })
+            .buffer_unordered(limit)
+            .fold(SendSummary::default(), |mut summary, res| async move {
+                match res {
+                    Ok(()) => {
+                        progress.sent();
+                        summary.succeeded += 1
+                    }
+                    Err(e) => {
+                        warn!("[{}] failed to send notification: {:?}", rid, e);
+                        progress.failed();
+                        summary.failed.push(e);
+                    }
+                }
+                summary
+            })
+            .await;
+        if let (0, Some(e)) = (summary.succeeded, summary.failed.last()) {
+            progress.fail(e.message());
+        }
+        progress.finish();
+        summary
+    }
 
-        Ok(Response::new(RemindResponse { id: request_id }))
+    /// retry while the notification service is unavailable, up to `send_retries` times
+    async fn send_with_retries(&self, req: SendRequest, rid: &RequestId) -> Result<(), Status> {
+        let mut attempt = 0;
+        loop {
+            match self.send_one(req.clone(), rid).await {
+                Err(e)
+                    if e.code() == Code::Unavailable
+                        && attempt < self.config.server.send_retries =>
+                {
+                    attempt += 1;
+                    warn!("[{}] retrying notification ({}): {:?}", rid, attempt, e);
+                    tokio::time::sleep(SEND_RETRY_DELAY * attempt).await;
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    async fn send_one(&self, req: SendRequest, rid: &RequestId) -> Result<(), Status> {
+        let mut res = self
+            .notification
+            .call(
+                |mut c| async move { c.send(with_request_id(tokio_stream::once(req), rid)).await },
+            )
+            .await?
+            .into_inner();
+        while res.message().await?.is_some() {}
+        Ok(())
     }
 }
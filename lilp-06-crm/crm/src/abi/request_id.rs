@@ -0,0 +1,83 @@
+use std::fmt;
+
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    Request,
+};
+use uuid::Uuid;
+
+use super::deadline;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// id used to trace a request across services
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// read the id from `x-request-id`, generate one if it's absent or invalid
+    pub fn from_metadata(metadata: &MetadataMap) -> Self {
+        metadata
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .map(Self::new)
+            .unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// wrap `msg` into a request carrying the request id, for outgoing calls.
+/// the deadline of the rpc being served is propagated too
+pub fn with_request_id<T>(msg: T, id: &RequestId) -> Request<T> {
+    let mut req = Request::new(msg);
+    if let Ok(v) = MetadataValue::try_from(id.as_str()) {
+        req.metadata_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    deadline::propagate(&mut req);
+    req
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_id_should_be_read_from_metadata() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(REQUEST_ID_HEADER, "abc".parse().unwrap());
+        assert_eq!(RequestId::from_metadata(&metadata).as_str(), "abc");
+    }
+
+    #[test]
+    fn request_id_should_be_generated_when_absent() {
+        let id = RequestId::from_metadata(&MetadataMap::new());
+        assert!(Uuid::parse_str(id.as_str()).is_ok());
+    }
+
+    #[test]
+    fn with_request_id_should_set_metadata() {
+        let req = with_request_id((), &RequestId::new("abc"));
+        let v = req.metadata().get(REQUEST_ID_HEADER).unwrap();
+        assert_eq!(v.to_str().unwrap(), "abc");
+    }
+}
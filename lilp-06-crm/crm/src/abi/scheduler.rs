@@ -0,0 +1,78 @@
+use std::{future::Future, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use tokio::{task::JoinHandle, time::sleep};
+use uuid::Uuid;
+
+/// runs jobs at a later time, pending jobs can be cancelled by their id
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    jobs: Arc<DashMap<String, JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    /// run `job` at `at` (right away if it's in the past), returns the job id
+    pub fn schedule<F>(&self, at: DateTime<Utc>, job: F) -> String
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let id = Uuid::new_v4().to_string();
+        let delay = (at - Utc::now()).to_std().unwrap_or_default();
+        let jobs = self.jobs.clone();
+        let job_id = id.clone();
+
+        // hold the entry until the handle is stored, so a job firing right away can't remove itself first
+        let entry = self.jobs.entry(id.clone());
+        let handle = tokio::spawn(async move {
+            sleep(delay).await;
+            job.await;
+            jobs.remove(&job_id);
+        });
+        entry.or_insert(handle);
+        id
+    }
+
+    /// cancel a pending job, returns false if it's unknown or already done
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.remove(id) {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// number of jobs not run yet
+    pub fn pending(&self) -> usize {
+        self.jobs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn cancelled_job_should_not_run() {
+        let scheduler = Scheduler::default();
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = ran.clone();
+        let at = Utc::now() + chrono::Duration::milliseconds(50);
+        let id = scheduler.schedule(at, async move {
+            flag.store(true, Ordering::SeqCst);
+        });
+        assert_eq!(scheduler.pending(), 1);
+        assert!(scheduler.cancel(&id));
+        assert!(!scheduler.cancel(&id));
+
+        sleep(Duration::from_millis(100)).await;
+        assert!(!ran.load(Ordering::SeqCst));
+        assert_eq!(scheduler.pending(), 0);
+    }
+}
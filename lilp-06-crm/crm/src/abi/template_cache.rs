@@ -0,0 +1,77 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crm_metadata::pb::Content;
+use dashmap::DashMap;
+
+/// template id, locale and content ids of a materialize call
+type Key = (String, String, Vec<u32>);
+
+/// the last successfully materialized contents per template, used while the metadata service is down
+pub struct TemplateCache {
+    ttl: Duration,
+    entries: DashMap<Key, (Instant, Arc<Vec<Content>>)>,
+}
+
+impl TemplateCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: DashMap::new(),
+        }
+    }
+
+    pub fn insert(
+        &self,
+        template_id: &str,
+        locale: &str,
+        ids: &[u32],
+        contents: Arc<Vec<Content>>,
+    ) {
+        self.entries
+            .insert(key(template_id, locale, ids), (Instant::now(), contents));
+    }
+
+    /// the cached contents, expired ones are dropped
+    pub fn get(&self, template_id: &str, locale: &str, ids: &[u32]) -> Option<Arc<Vec<Content>>> {
+        let key = key(template_id, locale, ids);
+        let contents = {
+            let entry = self.entries.get(&key)?;
+            let (cached_at, contents) = entry.value();
+            (cached_at.elapsed() < self.ttl).then(|| contents.clone())
+        };
+        if contents.is_none() {
+            self.entries.remove(&key);
+        }
+        contents
+    }
+}
+
+fn key(template_id: &str, locale: &str, ids: &[u32]) -> Key {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    (template_id.to_string(), locale.to_string(), ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_contents_should_expire() {
+        let cache = TemplateCache::new(Duration::from_millis(50));
+        let contents = Arc::new(vec![Content {
+            id: 1,
+            ..Default::default()
+        }]);
+        cache.insert("welcome", "en", &[2, 1], contents.clone());
+        assert_eq!(cache.get("welcome", "en", &[1, 2]), Some(contents));
+        assert!(cache.get("welcome", "fr", &[1, 2]).is_none());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(cache.get("welcome", "en", &[1, 2]).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+// validation errors are returned to the client as is, like the rpc results
+#![allow(clippy::result_large_err)]
+
+use tonic::Status;
+
+use crate::pb::{Channel, RecallRequest, RemindRequest, WelcomeRequest};
+
+/// the longest look-back window, also keeps the date math from overflowing
+const MAX_INTERVAL_DAYS: u32 = 3650;
+
+impl WelcomeRequest {
+    /// reject the request before any downstream call
+    pub fn validate(&self) -> Result<(), Status> {
+        validate_id(&self.id)?;
+        // 0 means the users registered today
+        if self.interval > MAX_INTERVAL_DAYS {
+            return Err(Status::invalid_argument(format!(
+                "interval must be at most {} days, got {}",
+                MAX_INTERVAL_DAYS, self.interval
+            )));
+        }
+        if self.content_ids.is_empty() && !self.dry_run {
+            return Err(Status::invalid_argument("content_ids must not be empty"));
+        }
+        validate_channel(self.channel)
+    }
+}
+
+impl RecallRequest {
+    /// reject the request before any downstream call
+    pub fn validate(&self) -> Result<(), Status> {
+        validate_id(&self.id)?;
+        validate_last_visit_interval(self.last_visit_interval)?;
+        if self.content_ids.is_empty() {
+            return Err(Status::invalid_argument("content_ids must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+impl RemindRequest {
+    /// reject the request before any downstream call, or before it's scheduled
+    pub fn validate(&self) -> Result<(), Status> {
+        validate_id(&self.id)?;
+        validate_last_visit_interval(self.last_visit_interval)?;
+        if self.template_id.is_empty() {
+            return Err(Status::invalid_argument("template_id must not be empty"));
+        }
+        if self.content_ids.is_empty() {
+            return Err(Status::invalid_argument("content_ids must not be empty"));
+        }
+        if let Some(send_at) = &self.send_at {
+            if !(0..1_000_000_000).contains(&send_at.nanos) {
+                return Err(Status::invalid_argument(format!(
+                    "send_at nanos must be within [0, 1e9), got {}",
+                    send_at.nanos
+                )));
+            }
+        }
+        validate_channel(self.channel)
+    }
+}
+
+fn validate_id(id: &str) -> Result<(), Status> {
+    if id.is_empty() {
+        return Err(Status::invalid_argument("id must not be empty"));
+    }
+    Ok(())
+}
+
+/// a channel added by a newer client is rejected instead of falling back to email
+fn validate_channel(channel: i32) -> Result<(), Status> {
+    if Channel::try_from(channel).is_err() {
+        return Err(Status::invalid_argument(format!(
+            "unknown channel {}",
+            channel
+        )));
+    }
+    Ok(())
+}
+
+/// the window is [now - interval, now], an empty one can't match any user
+fn validate_last_visit_interval(days: u32) -> Result<(), Status> {
+    if days == 0 || days > MAX_INTERVAL_DAYS {
+        return Err(Status::invalid_argument(format!(
+            "last_visit_interval must be within [1, {}] days, got {}",
+            MAX_INTERVAL_DAYS, days
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn welcome() -> WelcomeRequest {
+        WelcomeRequest {
+            id: "welcome".to_string(),
+            interval: 7,
+            content_ids: vec![1, 2],
+            ..Default::default()
+        }
+    }
+
+    fn recall() -> RecallRequest {
+        RecallRequest {
+            id: "recall".to_string(),
+            last_visit_interval: 7,
+            content_ids: vec![1],
+        }
+    }
+
+    fn remind() -> RemindRequest {
+        RemindRequest {
+            id: "remind".to_string(),
+            last_visit_interval: 7,
+            template_id: "remind".to_string(),
+            content_ids: vec![1],
+            ..Default::default()
+        }
+    }
+
+    fn assert_invalid(ret: Result<(), Status>, msg: &str) {
+        let e = ret.unwrap_err();
+        assert_eq!(e.code(), Code::InvalidArgument);
+        assert!(e.message().contains(msg), "{}", e.message());
+    }
+
+    #[test]
+    fn valid_requests_should_pass() {
+        assert!(welcome().validate().is_ok());
+        assert!(recall().validate().is_ok());
+        assert!(remind().validate().is_ok());
+
+        let dry_run = WelcomeRequest {
+            content_ids: vec![],
+            dry_run: true,
+            ..welcome()
+        };
+        assert!(dry_run.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_welcome_should_be_rejected() {
+        let req = WelcomeRequest {
+            id: String::new(),
+            ..welcome()
+        };
+        assert_invalid(req.validate(), "id must not be empty");
+
+        let req = WelcomeRequest {
+            interval: MAX_INTERVAL_DAYS + 1,
+            ..welcome()
+        };
+        assert_invalid(req.validate(), "interval");
+
+        let req = WelcomeRequest {
+            content_ids: vec![],
+            ..welcome()
+        };
+        assert_invalid(req.validate(), "content_ids");
+
+        let req = WelcomeRequest {
+            channel: 42,
+            ..welcome()
+        };
+        assert_invalid(req.validate(), "unknown channel");
+
+        for channel in [Channel::Sms, Channel::InApp] {
+            let req = WelcomeRequest {
+                channel: channel as _,
+                ..welcome()
+            };
+            assert!(req.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn invalid_recall_should_be_rejected() {
+        let req = RecallRequest {
+            id: String::new(),
+            ..recall()
+        };
+        assert_invalid(req.validate(), "id must not be empty");
+
+        for days in [0, MAX_INTERVAL_DAYS + 1] {
+            let req = RecallRequest {
+                last_visit_interval: days,
+                ..recall()
+            };
+            assert_invalid(req.validate(), "last_visit_interval");
+        }
+
+        let req = RecallRequest {
+            content_ids: vec![],
+            ..recall()
+        };
+        assert_invalid(req.validate(), "content_ids");
+    }
+
+    #[test]
+    fn invalid_remind_should_be_rejected() {
+        let req = RemindRequest {
+            id: String::new(),
+            ..remind()
+        };
+        assert_invalid(req.validate(), "id must not be empty");
+
+        for days in [0, MAX_INTERVAL_DAYS + 1] {
+            let req = RemindRequest {
+                last_visit_interval: days,
+                ..remind()
+            };
+            assert_invalid(req.validate(), "last_visit_interval");
+        }
+
+        let req = RemindRequest {
+            template_id: String::new(),
+            ..remind()
+        };
+        assert_invalid(req.validate(), "template_id");
+
+        let req = RemindRequest {
+            content_ids: vec![],
+            ..remind()
+        };
+        assert_invalid(req.validate(), "content_ids");
+
+        let req = RemindRequest {
+            send_at: Some(prost_types::Timestamp {
+                seconds: 0,
+                nanos: -1,
+            }),
+            ..remind()
+        };
+        assert_invalid(req.validate(), "send_at");
+
+        let req = RemindRequest {
+            channel: -1,
+            ..remind()
+        };
+        assert_invalid(req.validate(), "unknown channel");
+    }
+}
@@ -1,11 +1,15 @@
 use anyhow::{bail, Result};
+use crm_metadata::DEFAULT_LOCALE;
 use serde::{Deserialize, Serialize};
-use std::{env, fs::File};
+use std::{env, fs::File, time::Duration};
+use tonic::transport::{Endpoint, Identity, Server, ServerTlsConfig};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub channel: ChannelConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,10 +21,84 @@ pub struct AuthConfig {
 pub struct ServerConfig {
     pub port: u16,
     pub sender_email: String,
+    /// sender of the sms notifications, the gateway's default number if empty
+    #[serde(default)]
+    pub sms_sender: String,
     pub metadata: String,
     pub user_stats: String,
     pub notification: String,
     pub tls: Option<TlsConfig>,
+    /// locale used when the requested one is missing
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
+    /// max notifications in flight during a fan-out
+    #[serde(default = "default_send_concurrency")]
+    pub send_concurrency: usize,
+    /// times a notification is retried while the notification service is unavailable,
+    /// the ones still failing end up in the dead letters
+    #[serde(default)]
+    pub send_retries: u32,
+    /// emails of the users who opted out of notifications
+    #[serde(default)]
+    pub suppressed: Vec<String>,
+    /// jsonl file recording every notification attempt, no audit log if missing
+    #[serde(default)]
+    pub audit_log: Option<String>,
+    /// sent when the metadata service is unavailable and nothing is cached,
+    /// the rpc fails instead if missing
+    #[serde(default)]
+    pub default_template: Option<DefaultTemplate>,
+    /// seconds a successfully materialized template is kept as the fallback
+    #[serde(default = "default_template_cache_ttl")]
+    pub template_cache_ttl: u64,
+    /// seconds the notifications of a campaign are given, the ones not sent by then end up
+    /// in the dead letters. campaigns run in the background, past the deadline of their rpc
+    #[serde(default = "default_campaign_timeout")]
+    pub campaign_timeout: u64,
+    /// seconds the progress of a finished campaign can still be queried
+    #[serde(default = "default_campaign_ttl")]
+    pub campaign_ttl: u64,
+    /// seconds between http2 keep-alive pings sent to the clients, no pings if missing
+    #[serde(default)]
+    pub http2_keepalive_interval: Option<u64>,
+    /// seconds to wait for a ping to be acked before the connection is closed
+    #[serde(default)]
+    pub http2_keepalive_timeout: Option<u64>,
+    /// seconds of idleness before tcp keepalive probes are sent, disabled if missing
+    #[serde(default)]
+    pub tcp_keepalive: Option<u64>,
+    /// max concurrent streams per connection, unlimited if missing
+    #[serde(default)]
+    pub max_concurrent_streams: Option<u32>,
+}
+
+impl ServerConfig {
+    /// a server builder with the tls and keep-alive settings, unset ones keep tonic's defaults
+    pub fn builder(&self) -> Result<Server, tonic::transport::Error> {
+        let mut server = Server::builder()
+            .http2_keepalive_interval(self.http2_keepalive_interval.map(Duration::from_secs))
+            .tcp_keepalive(self.tcp_keepalive.map(Duration::from_secs))
+            .max_concurrent_streams(self.max_concurrent_streams);
+        // tonic defaults to 20s, None would disable the timeout
+        if let Some(secs) = self.http2_keepalive_timeout {
+            server = server.http2_keepalive_timeout(Some(Duration::from_secs(secs)));
+        }
+        if let Some(tls) = &self.tls {
+            let identity = Identity::from_pem(&tls.cert, &tls.key);
+            server = server.tls_config(ServerTlsConfig::new().identity(identity))?;
+        }
+        Ok(server)
+    }
+}
+
+/// the content sent in place of the materialized ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub url: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +107,53 @@ pub struct TlsConfig {
     pub key: String,
 }
 
+/// keep-alive settings of the channels to the downstream services, unset ones keep tonic's defaults
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ChannelConfig {
+    /// seconds between http2 keep-alive pings, idle connections are pinged too
+    pub http2_keep_alive_interval: Option<u64>,
+    /// seconds to wait for a ping to be acked before the connection is closed
+    pub keep_alive_timeout: Option<u64>,
+    /// seconds of idleness before tcp keepalive probes are sent
+    pub tcp_keepalive: Option<u64>,
+}
+
+impl ChannelConfig {
+    pub fn endpoint(&self, dst: impl Into<String>) -> Result<Endpoint, tonic::transport::Error> {
+        let mut endpoint = Endpoint::from_shared(dst.into())?
+            .tcp_keepalive(self.tcp_keepalive.map(Duration::from_secs));
+        if let Some(secs) = self.http2_keep_alive_interval {
+            endpoint = endpoint
+                .http2_keep_alive_interval(Duration::from_secs(secs))
+                .keep_alive_while_idle(true);
+        }
+        if let Some(secs) = self.keep_alive_timeout {
+            endpoint = endpoint.keep_alive_timeout(Duration::from_secs(secs));
+        }
+        Ok(endpoint)
+    }
+}
+
+fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
+}
+
+fn default_send_concurrency() -> usize {
+    16
+}
+
+fn default_template_cache_ttl() -> u64 {
+    300
+}
+
+fn default_campaign_timeout() -> u64 {
+    600
+}
+
+fn default_campaign_ttl() -> u64 {
+    3600
+}
+
 impl AppConfig {
     pub fn load() -> Result<Self> {
         // read from  ./app.yml, or /etc/config/app.yml, or from env CHAT_CONFIG
@@ -3,29 +3,57 @@ mod config;
 
 pub mod pb;
 
-pub use config::AppConfig;
+pub use abi::{
+    with_deadline, with_request_id, AuditEntry, AuditLog, CampaignId, Campaigns, DeadLetters,
+    Deadline, LazyClient, Progress, RequestId, Scheduler, SendSummary, TemplateCache,
+    GRPC_TIMEOUT_HEADER, REQUEST_ID_HEADER,
+};
+pub use config::{AppConfig, AuthConfig, ChannelConfig, DefaultTemplate, ServerConfig};
+
+use std::{ops::Deref, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use crm_metadata::pb::metadata_client::MetadataClient;
 use crm_send::pb::notification_client::NotificationClient;
+use dashmap::DashSet;
 use pb::{
     crm_server::{Crm, CrmServer},
-    RecallRequest, RecallResponse, RemindRequest, RemindResponse, WelcomeRequest, WelcomeResponse,
+    CampaignStatusRequest, CampaignStatusResponse, DeadLetter, DrainDeadLettersRequest,
+    DrainDeadLettersResponse, RecallRequest, RecallResponse, RemindRequest, RemindResponse,
+    WelcomeRequest, WelcomeResponse,
 };
 use tonic::{
-    async_trait, service::interceptor::InterceptedService, transport::Channel, Request, Response,
-    Status,
+    async_trait,
+    service::interceptor::InterceptedService,
+    transport::{server::Router, Channel},
+    Request, Response, Status,
 };
 use tracing::info;
 use user_stat::pb::user_stats_client::UserStatsClient;
 
 use crate::abi::auth;
 
+#[derive(Clone)]
 pub struct CrmService {
+    inner: Arc<CrmServiceInner>,
+}
+
+pub struct CrmServiceInner {
     config: AppConfig,
-    user_stats: UserStatsClient<Channel>,
-    notification: NotificationClient<Channel>,
-    metadata: MetadataClient<Channel>,
+    user_stats: LazyClient<UserStatsClient<Channel>>,
+    notification: LazyClient<NotificationClient<Channel>>,
+    metadata: LazyClient<MetadataClient<Channel>>,
+    /// emails of the users who opted out, they're never notified
+    suppression: DashSet<String>,
+    /// reminders waiting for their `send_at`
+    scheduler: Scheduler,
+    /// progress of the notifications sent by welcome / recall / remind
+    campaigns: Campaigns,
+    /// fallback for when the metadata service is unavailable
+    templates: TemplateCache,
+    /// notifications which still failed after the retries
+    dead_letters: DeadLetters,
+    audit: Option<AuditLog>,
 }
 
 #[async_trait]
@@ -35,8 +63,10 @@ impl Crm for CrmService {
         request: Request<WelcomeRequest>,
     ) -> Result<Response<WelcomeResponse>, Status> {
         let user: &auth::User = request.extensions().get().unwrap();
-        info!("User: {:?}", user);
-        self.welcome(request.into_inner()).await
+        let rid = request_id(&request);
+        info!("[{}] User: {:?}", rid, user);
+        let deadline = Deadline::from_metadata(request.metadata());
+        with_deadline(deadline, self.welcome(request.into_inner(), &rid)).await
     }
 
     async fn recall(
@@ -45,9 +75,11 @@ impl Crm for CrmService {
     ) -> Result<Response<RecallResponse>, Status> {
         // 作业
         let user: &auth::User = request.extensions().get().unwrap();
-        info!("User: {:?}", user);
+        let rid = request_id(&request);
+        info!("[{}] User: {:?}", rid, user);
         // 调用实现的 recall 方法
-        self.recall(request.into_inner()).await
+        let deadline = Deadline::from_metadata(request.metadata());
+        with_deadline(deadline, self.recall(request.into_inner(), &rid)).await
     }
 
     async fn remind(
@@ -56,28 +88,134 @@ impl Crm for CrmService {
     ) -> Result<Response<RemindResponse>, Status> {
         // 作业
         let user: &auth::User = request.extensions().get().unwrap();
-        info!("User: {:?}", user);
-        self.remind(request.into_inner()).await
+        let rid = request_id(&request);
+        info!("[{}] User: {:?}", rid, user);
+        // the deadline bounds the rpc, not the notifications sent in the background
+        let deadline = Deadline::from_metadata(request.metadata());
+        with_deadline(deadline, self.remind(request.into_inner(), &rid)).await
     }
+
+    async fn campaign_status(
+        &self,
+        request: Request<CampaignStatusRequest>,
+    ) -> Result<Response<CampaignStatusResponse>, Status> {
+        let id = request.into_inner().campaign_id;
+        self.campaign_status(&id)
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("campaign {} not found", id)))
+    }
+
+    async fn drain_dead_letters(
+        &self,
+        _request: Request<DrainDeadLettersRequest>,
+    ) -> Result<Response<DrainDeadLettersResponse>, Status> {
+        let dead_letters = self.drain_dead_letters();
+        Ok(Response::new(DrainDeadLettersResponse { dead_letters }))
+    }
+}
+
+/// the interceptor sets the request id, fallback to a new one in case it's missing
+fn request_id<T>(request: &Request<T>) -> RequestId {
+    request
+        .extensions()
+        .get::<RequestId>()
+        .cloned()
+        .unwrap_or_default()
 }
 
 impl CrmService {
     pub async fn try_new(config: AppConfig) -> Result<Self> {
-        let user_stats = UserStatsClient::connect(config.server.user_stats.clone()).await?;
-        let notification = NotificationClient::connect(config.server.notification.clone()).await?;
-        let metadata = MetadataClient::connect(config.server.metadata.clone()).await?;
-        Ok(Self {
+        // connect lazily, so a dependency being down doesn't prevent the service from booting
+        let channel = &config.channel;
+        let user_stats = LazyClient::with_endpoint(
+            channel.endpoint(config.server.user_stats.clone())?,
+            UserStatsClient::new,
+        );
+        let notification = LazyClient::with_endpoint(
+            channel.endpoint(config.server.notification.clone())?,
+            NotificationClient::new,
+        );
+        let metadata = LazyClient::with_endpoint(
+            channel.endpoint(config.server.metadata.clone())?,
+            MetadataClient::new,
+        );
+        let suppression = config.server.suppressed.iter().cloned().collect();
+        let templates = TemplateCache::new(Duration::from_secs(config.server.template_cache_ttl));
+        let campaigns = Campaigns::new(Duration::from_secs(config.server.campaign_ttl));
+        let audit = match &config.server.audit_log {
+            Some(path) => Some(AuditLog::open(path).await?),
+            None => None,
+        };
+        let inner = CrmServiceInner {
             config,
             user_stats,
             notification,
             metadata,
+            suppression,
+            scheduler: Scheduler::default(),
+            campaigns,
+            templates,
+            dead_letters: DeadLetters::default(),
+            audit,
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
         })
     }
 
-    pub fn into_server(
+    /// stop notifying the user, e.g. once they opted out
+    pub fn add_suppression(&self, email: impl Into<String>) {
+        self.suppression.insert(email.into());
+    }
+
+    pub fn is_suppressed(&self, email: &str) -> bool {
+        self.suppression.contains(email)
+    }
+
+    /// cancel a scheduled reminder, returns false if it's unknown or already sent
+    pub fn cancel_reminder(&self, job_id: &str) -> bool {
+        self.scheduler.cancel(job_id)
+    }
+
+    /// progress of a campaign, welcome / recall / remind reply as soon as it's started,
+    /// finished ones are forgotten after `campaign_ttl`
+    pub fn campaign_status(&self, campaign_id: &str) -> Option<CampaignStatusResponse> {
+        self.campaigns.status(campaign_id)
+    }
+
+    /// take the notifications which still failed after the retries, to send them again
+    pub fn drain_dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.drain()
+    }
+
+    /// the server configured by `config.server`, ready to serve.
+    /// reflection and health are served too, they don't need a token so grpcurl
+    /// and the health probes work without one. must be called within a tokio runtime
+    pub fn into_server(self) -> Result<Router> {
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(pb::FILE_DESCRIPTOR_SET)
+            .build()?;
+        let (reporter, health) = tonic_health::server::health_reporter();
+        tokio::spawn(abi::report_health(self.clone(), reporter));
+        let mut server = self.config.server.builder()?;
+        Ok(server
+            .add_service(reflection)
+            .add_service(health)
+            .add_service(self.into_service()?))
+    }
+
+    pub fn into_service(
         self,
     ) -> Result<InterceptedService<CrmServer<CrmService>, auth::DecodingKey>> {
         let dk = auth::DecodingKey::load(&self.config.auth.pk)?;
         Ok(CrmServer::with_interceptor(self, dk))
     }
 }
+
+impl Deref for CrmService {
+    type Target = CrmServiceInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
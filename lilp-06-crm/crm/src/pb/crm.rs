@@ -12,12 +12,40 @@ pub struct WelcomeRequest {
     #[prost(uint32, repeated, tag = "3")]
     #[builder(setter(each(name = "content_id", into)))]
     pub content_ids: ::prost::alloc::vec::Vec<u32>,
+    /// only compute the recipients, don't send any notification
+    #[prost(bool, tag = "4")]
+    pub dry_run: bool,
+    /// template used to render the contents
+    #[prost(string, tag = "5")]
+    pub template_id: ::prost::alloc::string::String,
+    /// locale of the contents, fallback to the default locale if missing
+    #[prost(string, tag = "6")]
+    pub locale: ::prost::alloc::string::String,
+    /// channel the notifications are sent through, email by default
+    #[prost(enumeration = "Channel", tag = "7")]
+    pub channel: i32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct WelcomeResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// number of users the welcome message would be sent to, only filled in dry run mode
+    #[prost(uint32, tag = "6")]
+    pub recipient_count: u32,
+    /// ids of the users the welcome message would be sent to, only filled in dry run mode.
+    /// like the dead letters, a user is identified by the user-stats key, their email
+    #[prost(string, repeated, tag = "7")]
+    pub recipient_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// number of target users skipped because they opted out
+    #[prost(uint32, tag = "3")]
+    pub suppressed: u32,
+    /// id to query the progress of the notifications with, empty in dry run mode
+    #[prost(string, tag = "4")]
+    pub campaign_id: ::prost::alloc::string::String,
+    /// the metadata service was unavailable, a cached or the default template was sent
+    #[prost(bool, tag = "5")]
+    pub degraded: bool,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -36,6 +64,15 @@ pub struct RecallRequest {
 pub struct RecallResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// number of target users skipped because they opted out
+    #[prost(uint32, tag = "2")]
+    pub suppressed: u32,
+    /// id to query the progress of the notifications with
+    #[prost(string, tag = "3")]
+    pub campaign_id: ::prost::alloc::string::String,
+    /// the metadata service was unavailable, a cached or the default template was sent
+    #[prost(bool, tag = "4")]
+    pub degraded: bool,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -46,12 +83,127 @@ pub struct RemindRequest {
     pub id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub last_visit_interval: u32,
+    /// template used to render the contents
+    #[prost(string, tag = "3")]
+    pub template_id: ::prost::alloc::string::String,
+    /// locale of the contents, fallback to the default locale if missing
+    #[prost(string, tag = "4")]
+    pub locale: ::prost::alloc::string::String,
+    /// send the reminder at this time instead of right away
+    #[prost(message, optional, tag = "5")]
+    pub send_at: ::core::option::Option<::prost_types::Timestamp>,
+    /// channel the notifications are sent through, email by default
+    #[prost(enumeration = "Channel", tag = "6")]
+    pub channel: i32,
+    /// contents rendered into the template
+    #[prost(uint32, repeated, tag = "7")]
+    #[builder(setter(each(name = "content_id", into)))]
+    pub content_ids: ::prost::alloc::vec::Vec<u32>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RemindResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// number of target users skipped because they opted out
+    #[prost(uint32, tag = "2")]
+    pub suppressed: u32,
+    /// id of the scheduled job, only filled when `send_at` is set
+    #[prost(string, tag = "3")]
+    pub job_id: ::prost::alloc::string::String,
+    /// id to query the progress of the notifications with, empty when `send_at` is set
+    #[prost(string, tag = "4")]
+    pub campaign_id: ::prost::alloc::string::String,
+    /// the metadata service was unavailable, a cached or the default template was sent
+    #[prost(bool, tag = "5")]
+    pub degraded: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CampaignStatusRequest {
+    #[prost(string, tag = "1")]
+    pub campaign_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CampaignStatusResponse {
+    #[prost(string, tag = "1")]
+    pub campaign_id: ::prost::alloc::string::String,
+    /// recipients found so far, grows until the campaign is done
+    #[prost(uint32, tag = "2")]
+    pub total: u32,
+    #[prost(uint32, tag = "3")]
+    pub sent: u32,
+    #[prost(uint32, tag = "4")]
+    pub failed: u32,
+    /// recipients found but not sent to yet
+    #[prost(uint32, tag = "5")]
+    pub pending: u32,
+    /// all recipients have been found and notified
+    #[prost(bool, tag = "6")]
+    pub done: bool,
+    /// set once the campaign is done and every notification failed, the reason of the last one
+    #[prost(string, tag = "7")]
+    pub error: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DrainDeadLettersRequest {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DrainDeadLettersResponse {
+    /// oldest first, they're removed from the store
+    #[prost(message, repeated, tag = "1")]
+    pub dead_letters: ::prost::alloc::vec::Vec<DeadLetter>,
+}
+/// a notification which still failed after the retries
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeadLetter {
+    /// email of the user the notification was for
+    #[prost(string, tag = "1")]
+    pub user_id: ::prost::alloc::string::String,
+    /// rpc which sent it, e.g. welcome
+    #[prost(string, tag = "2")]
+    pub rpc: ::prost::alloc::string::String,
+    /// the notification as it was sent, it can be sent again as is
+    #[prost(message, optional, tag = "3")]
+    pub payload: ::core::option::Option<::crm_send::pb::SendRequest>,
+    /// error of the last attempt
+    #[prost(string, tag = "4")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "5")]
+    pub failed_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// how the notifications are delivered, the users are addressed by their email on all of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Channel {
+    Email = 0,
+    Sms = 1,
+    InApp = 2,
+}
+impl Channel {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Channel::Email => "EMAIL",
+            Channel::Sms => "SMS",
+            Channel::InApp => "IN_APP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "EMAIL" => Some(Self::Email),
+            "SMS" => Some(Self::Sms),
+            "IN_APP" => Some(Self::InApp),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod crm_client {
@@ -188,6 +340,44 @@ pub mod crm_client {
                 .insert(GrpcMethod::new("crm.Crm", "Remind"));
             self.inner.unary(req, path, codec).await
         }
+        /// progress of the notifications sent by welcome / recall / remind
+        pub async fn campaign_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CampaignStatusRequest>,
+        ) -> std::result::Result<tonic::Response<super::CampaignStatusResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/CampaignStatus");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "CampaignStatus"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// admin: take the notifications which failed for good, to send them again
+        pub async fn drain_dead_letters(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DrainDeadLettersRequest>,
+        ) -> std::result::Result<tonic::Response<super::DrainDeadLettersResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/DrainDeadLetters");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "DrainDeadLetters"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -212,6 +402,16 @@ pub mod crm_server {
             &self,
             request: tonic::Request<super::RemindRequest>,
         ) -> std::result::Result<tonic::Response<super::RemindResponse>, tonic::Status>;
+        /// progress of the notifications sent by welcome / recall / remind
+        async fn campaign_status(
+            &self,
+            request: tonic::Request<super::CampaignStatusRequest>,
+        ) -> std::result::Result<tonic::Response<super::CampaignStatusResponse>, tonic::Status>;
+        /// admin: take the notifications which failed for good, to send them again
+        async fn drain_dead_letters(
+            &self,
+            request: tonic::Request<super::DrainDeadLettersRequest>,
+        ) -> std::result::Result<tonic::Response<super::DrainDeadLettersResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct CrmServer<T: Crm> {
@@ -403,6 +603,87 @@ pub mod crm_server {
                     };
                     Box::pin(fut)
                 }
+                "/crm.Crm/CampaignStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct CampaignStatusSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::CampaignStatusRequest> for CampaignStatusSvc<T> {
+                        type Response = super::CampaignStatusResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CampaignStatusRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Crm>::campaign_status(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CampaignStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/crm.Crm/DrainDeadLetters" => {
+                    #[allow(non_camel_case_types)]
+                    struct DrainDeadLettersSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::DrainDeadLettersRequest>
+                        for DrainDeadLettersSvc<T>
+                    {
+                        type Response = super::DrainDeadLettersResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DrainDeadLettersRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Crm>::drain_dead_letters(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DrainDeadLettersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
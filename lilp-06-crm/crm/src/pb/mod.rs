@@ -1,3 +1,6 @@
 mod crm;
 
 pub use crm::*;
+
+/// descriptors of the CRM protos, served by gRPC reflection
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("crm_descriptor");
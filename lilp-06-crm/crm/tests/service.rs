@@ -0,0 +1,1097 @@
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use crm::{
+    pb::{
+        crm_client::CrmClient, CampaignStatusRequest, CampaignStatusResponse, RemindRequest,
+        RemindRequestBuilder, WelcomeRequestBuilder,
+    },
+    with_deadline, AppConfig, AuditEntry, CrmService, Deadline, DefaultTemplate, RequestId,
+    GRPC_TIMEOUT_HEADER, REQUEST_ID_HEADER,
+};
+use crm_metadata::{
+    pb::{
+        metadata_server::{Metadata, MetadataServer},
+        Content, MaterializeRequest,
+    },
+    MetadataService,
+};
+use crm_send::pb::{
+    notification_server::{Notification, NotificationServer},
+    send_request::Msg,
+    SendRequest, SendResponse,
+};
+use futures::{Stream, StreamExt};
+use tokio::time::sleep;
+use tonic::{
+    async_trait,
+    transport::{Channel, Server},
+    Request, Response, Status, Streaming,
+};
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use tonic_reflection::pb::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest,
+};
+use user_stat::pb::{
+    user_stats_server::{UserStats, UserStatsServer},
+    QueryRequest, RawQueryRequest, User,
+};
+
+const PORT_BASE: u16 = 61000;
+const SLOW_METADATA_DELAY: Duration = Duration::from_secs(2);
+const SLOW_SEND_DELAY: Duration = Duration::from_secs(3);
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+#[tokio::test]
+async fn welcome_dry_run_should_not_send() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE,
+        fake_users(3),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("dry-run")
+        .interval(7u32)
+        .content_ids([1u32, 2, 3])
+        .dry_run(true)
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+
+    assert_eq!(res.recipient_count, 3);
+    assert_eq!(
+        res.recipient_ids,
+        ["user0@acme.org", "user1@acme.org", "user2@acme.org"]
+    );
+    assert_eq!(notification.sent(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_dry_run_should_fail_on_user_stats_error() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut users = fake_users(2);
+    users.push(User {
+        email: "broken@acme.org".to_string(),
+        name: "broken".to_string(),
+    });
+    let config = start_mocks(
+        PORT_BASE + 210,
+        users,
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("dry-run-broken")
+        .interval(7u32)
+        .dry_run(true)
+        .build()?;
+    let e = svc.welcome(req, &RequestId::default()).await.unwrap_err();
+    assert_eq!(e.code(), tonic::Code::Internal);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_send_to_all_users() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 10,
+        fake_users(3),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome")
+        .interval(7u32)
+        .content_ids([1u32, 2, 3])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    assert_eq!(res.recipient_count, 0);
+    assert!(res.recipient_ids.is_empty());
+    assert_eq!(notification.sent(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_send_through_requested_channel() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 170,
+        fake_users(2),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-sms")
+        .interval(7u32)
+        .content_ids([1u32])
+        .channel(crm::pb::Channel::Sms)
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    let sent = notification.sent.lock().unwrap().clone();
+    assert_eq!(sent.len(), 2);
+    for req in sent {
+        match req.msg {
+            Some(Msg::Sms(sms)) => {
+                assert_eq!(sms.recipients.len(), 1);
+                assert!(!sms.body.is_empty());
+            }
+            msg => panic!("expected an sms, got {:?}", msg),
+        }
+    }
+    // none of them went out as email
+    assert!(notification.recipients().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_use_requested_locale() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 20,
+        fake_users(1),
+        MockMetadata,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-fr")
+        .interval(7u32)
+        .content_ids([1u32])
+        .template_id("welcome")
+        .locale("fr")
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    let bodies = notification.bodies();
+    assert_eq!(bodies.len(), 1);
+    assert!(bodies[0].contains("Bonjour"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_fallback_to_default_locale() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 30,
+        fake_users(1),
+        MockMetadata,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-de")
+        .interval(7u32)
+        .content_ids([1u32])
+        .template_id("welcome")
+        .locale("de")
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    let bodies = notification.bodies();
+    assert_eq!(bodies.len(), 1);
+    assert!(bodies[0].contains("Hello"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn remind_should_render_requested_template() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 190,
+        fake_users(1),
+        MockMetadata,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    for template_id in ["remind", "holiday"] {
+        let req = RemindRequestBuilder::default()
+            .id(format!("remind-{}", template_id))
+            .last_visit_interval(7u32)
+            .content_ids([1u32])
+            .template_id(template_id)
+            .locale("fr")
+            .build()?;
+        let res = svc.remind(req, &RequestId::default()).await?.into_inner();
+        wait_for_campaign(&svc, &res.campaign_id).await?;
+    }
+
+    let bodies = notification.bodies();
+    assert_eq!(bodies.len(), 2);
+    assert!(bodies[0].contains("Bonjour") && bodies[0].contains("\"remind\""));
+    assert!(bodies[1].contains("Bonjour") && bodies[1].contains("\"holiday\""));
+    assert_ne!(bodies[0], bodies[1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_fan_out_should_be_bounded() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut users = fake_users(20);
+    users.push(User {
+        email: "fail@acme.org".to_string(),
+        name: "fail".to_string(),
+    });
+    let mut config = start_mocks(
+        PORT_BASE + 40,
+        users,
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    config.server.send_concurrency = 4;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-bounded")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    // the failed send doesn't abort the batch
+    assert_eq!(notification.sent(), 20);
+    let peak = notification.peak.load(Ordering::SeqCst);
+    assert!(peak > 1 && peak <= 4, "peak concurrency: {}", peak);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_propagate_request_id() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 50,
+        fake_users(2),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-traced")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let res = svc
+        .welcome(req, &RequestId::new("trace-1"))
+        .await?
+        .into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    let request_ids = notification.request_ids.lock().unwrap().clone();
+    assert_eq!(request_ids, vec!["trace-1".to_string(); 2]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_skip_suppressed_users() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut config = start_mocks(
+        PORT_BASE + 60,
+        fake_users(3),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    config.server.suppressed = vec!["user0@acme.org".to_string()];
+    let svc = CrmService::try_new(config).await?;
+    svc.add_suppression("user2@acme.org");
+    assert!(svc.is_suppressed("user0@acme.org"));
+    assert!(!svc.is_suppressed("user1@acme.org"));
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-suppressed")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    assert_eq!(res.suppressed, 2);
+    assert_eq!(
+        notification.recipients(),
+        vec!["user1@acme.org".to_string()]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduled_remind_should_fire_after_delay() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 70,
+        fake_users(2),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let res = svc
+        .remind(remind_at("remind-later", 200)?, &RequestId::default())
+        .await?
+        .into_inner();
+    assert!(!res.job_id.is_empty());
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(notification.sent(), 0);
+
+    sleep(Duration::from_millis(400)).await;
+    assert_eq!(notification.sent(), 2);
+    assert!(!svc.cancel_reminder(&res.job_id));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelled_remind_should_not_fire() -> Result<()> {
+    let notification = MockNotification::default();
+    let config = start_mocks(
+        PORT_BASE + 80,
+        fake_users(2),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let res = svc
+        .remind(remind_at("remind-cancelled", 100)?, &RequestId::default())
+        .await?
+        .into_inner();
+    assert!(svc.cancel_reminder(&res.job_id));
+
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(notification.sent(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_be_audited() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut config = start_mocks(
+        PORT_BASE + 90,
+        fake_users(1),
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let path = std::env::temp_dir().join(format!("crm-audit-{}.jsonl", uuid::Uuid::new_v4()));
+    config.server.audit_log = Some(path.to_string_lossy().into_owned());
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-audited")
+        .interval(7u32)
+        .content_ids([1u32])
+        .template_id("welcome")
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+    // the audit log is written in the background
+    drop(svc);
+    sleep(Duration::from_millis(50)).await;
+
+    let content = std::fs::read_to_string(&path)?;
+    let entries: Vec<AuditEntry> = content
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].user_id, "user0@acme.org");
+    assert_eq!(entries[0].rpc, "welcome");
+    assert_eq!(entries[0].template_id, "welcome");
+    assert_eq!(entries[0].status, "sent");
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_status_should_report_progress() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut users = fake_users(2);
+    users.push(User {
+        email: "fail@acme.org".to_string(),
+        name: "fail".to_string(),
+    });
+    let config = start_mocks(
+        PORT_BASE + 100,
+        users,
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-campaign")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    assert!(!res.campaign_id.is_empty());
+
+    // the reply doesn't wait for the notifications, each of them takes the mock 10ms
+    let status = svc
+        .campaign_status(&res.campaign_id)
+        .expect("campaign started");
+    assert!(!status.done);
+    assert!(status.sent + status.failed < 3);
+
+    let status = wait_for_campaign(&svc, &res.campaign_id).await?;
+    assert_eq!(status.campaign_id, res.campaign_id);
+    assert_eq!(status.total, 3);
+    assert_eq!(status.sent, 2);
+    assert_eq!(status.failed, 1);
+    assert_eq!(status.pending, 0);
+    // some of them went out
+    assert!(status.error.is_empty());
+
+    assert!(svc.campaign_status("unknown").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_notifications_should_end_up_in_dead_letters() -> Result<()> {
+    let notification = MockNotification::default();
+    let users = vec![User {
+        email: "fail@acme.org".to_string(),
+        name: "fail".to_string(),
+    }];
+    let mut config = start_mocks(
+        PORT_BASE + 180,
+        users,
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    config.server.send_retries = 2;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-dead-letter")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    // the first attempt and the 2 retries
+    assert_eq!(notification.request_ids.lock().unwrap().len(), 3);
+    assert_eq!(notification.sent(), 0);
+    let letters = svc.drain_dead_letters();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].user_id, "fail@acme.org");
+    assert_eq!(letters[0].rpc, "welcome");
+    assert_eq!(letters[0].error, "mailbox unavailable");
+    assert!(matches!(
+        &letters[0].payload,
+        Some(SendRequest {
+            msg: Some(Msg::Email(_))
+        })
+    ));
+    // drained
+    assert!(svc.drain_dead_letters().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_should_report_when_every_send_fails() -> Result<()> {
+    let notification = MockNotification::default();
+    let users = (0..2)
+        .map(|i| User {
+            email: format!("fail@acme{}.org", i),
+            name: format!("fail{}", i),
+        })
+        .collect();
+    let mut config = start_mocks(
+        PORT_BASE + 200,
+        users,
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    config.server.send_retries = 0;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-all-failed")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    let status = wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    assert_eq!(status.total, 2);
+    assert_eq!(status.sent, 0);
+    assert_eq!(status.failed, 2);
+    assert_eq!(status.error, "mailbox unavailable");
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_sends_should_time_out() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut users = fake_users(1);
+    users.push(User {
+        email: "slow@acme.org".to_string(),
+        name: "slow".to_string(),
+    });
+    let mut config = start_mocks(
+        PORT_BASE + 220,
+        users,
+        metadata_service()?,
+        notification.clone(),
+    )
+    .await?;
+    config.server.campaign_timeout = 1;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-campaign-timeout")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let start = Instant::now();
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    sleep(Duration::from_secs(1)).await;
+    let status = wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    assert!(start.elapsed() < SLOW_SEND_DELAY);
+    assert_eq!(status.sent, 1);
+    assert_eq!(status.failed, 1);
+    let letters = svc.drain_dead_letters();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].user_id, "slow@acme.org");
+    assert_eq!(letters[0].error, "deadline exceeded");
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_fallback_to_default_template_if_metadata_unavailable() -> Result<()> {
+    let notification = MockNotification::default();
+    let mut config = start_mocks(
+        PORT_BASE + 110,
+        fake_users(3),
+        UnavailableMetadata,
+        notification.clone(),
+    )
+    .await?;
+    config.server.default_template = Some(DefaultTemplate {
+        name: "Welcome aboard".to_string(),
+        description: String::new(),
+        url: String::new(),
+    });
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-degraded")
+        .interval(7u32)
+        .content_ids([1u32, 2])
+        .build()?;
+    let res = svc.welcome(req, &RequestId::default()).await?.into_inner();
+    wait_for_campaign(&svc, &res.campaign_id).await?;
+
+    assert!(res.degraded);
+    assert_eq!(notification.sent(), 3);
+    assert!(notification
+        .bodies()
+        .iter()
+        .all(|body| body.contains("Welcome aboard")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_request_should_be_rejected_before_downstream_calls() -> Result<()> {
+    // none of the dependencies are running, any downstream call would fail as unavailable
+    let config: AppConfig = serde_yaml::from_str(include_str!("../crm.yml"))?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-empty")
+        .interval(7u32)
+        .build()?;
+    let e = svc.welcome(req, &RequestId::default()).await.unwrap_err();
+    assert_eq!(e.code(), tonic::Code::InvalidArgument);
+    assert_eq!(e.message(), "content_ids must not be empty");
+    Ok(())
+}
+
+#[tokio::test]
+async fn server_with_keepalive_settings_should_serve() -> Result<()> {
+    let mut config: AppConfig = serde_yaml::from_str(include_str!("../crm.yml"))?;
+    config.server.tls = None;
+    config.server.http2_keepalive_interval = Some(10);
+    config.server.http2_keepalive_timeout = Some(5);
+    config.server.tcp_keepalive = Some(30);
+    config.server.max_concurrent_streams = Some(8);
+    let addr: SocketAddr = format!("[::1]:{}", PORT_BASE + 120).parse()?;
+    let server = CrmService::try_new(config).await?.into_server()?;
+    tokio::spawn(async move { server.serve(addr).await.unwrap() });
+    sleep(Duration::from_millis(10)).await;
+
+    // the request has no token, the reply still proves it went through the server
+    let mut client = CrmClient::connect(format!("http://{}", addr)).await?;
+    let req = CampaignStatusRequest {
+        campaign_id: "unknown".to_string(),
+    };
+    let e = client.campaign_status(req).await.unwrap_err();
+    assert_eq!(e.code(), tonic::Code::Unauthenticated);
+    assert_eq!(e.message(), "missing token");
+    Ok(())
+}
+
+#[tokio::test]
+async fn reflection_should_list_crm_service() -> Result<()> {
+    let mut config: AppConfig = serde_yaml::from_str(include_str!("../crm.yml"))?;
+    config.server.tls = None;
+    let addr: SocketAddr = format!("[::1]:{}", PORT_BASE + 130).parse()?;
+    let server = CrmService::try_new(config).await?.into_server()?;
+    tokio::spawn(async move { server.serve(addr).await.unwrap() });
+    sleep(Duration::from_millis(10)).await;
+
+    // the generated client has no `connect`, it's built without the transport feature
+    let channel = Channel::from_shared(format!("http://{}", addr))?
+        .connect()
+        .await?;
+    let mut client = ServerReflectionClient::new(channel);
+    let req = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut stream = client
+        .server_reflection_info(futures::stream::iter([req]))
+        .await?
+        .into_inner();
+    let res = stream.message().await?.expect("a reply");
+    let services: Vec<String> = match res.message_response {
+        Some(MessageResponse::ListServicesResponse(list)) => {
+            list.service.into_iter().map(|s| s.name).collect()
+        }
+        other => panic!("unexpected reply {:?}", other),
+    };
+    assert!(services.contains(&"crm.Crm".to_string()), "{:?}", services);
+    Ok(())
+}
+
+#[tokio::test]
+async fn health_should_be_serving_once_dependencies_connect() -> Result<()> {
+    let config = start_mocks(
+        PORT_BASE + 140,
+        fake_users(1),
+        metadata_service()?,
+        MockNotification::default(),
+    )
+    .await?;
+    let addr: SocketAddr = format!("[::1]:{}", config.server.port).parse()?;
+    let server = CrmService::try_new(config).await?.into_server()?;
+    tokio::spawn(async move { server.serve(addr).await.unwrap() });
+    sleep(Duration::from_millis(10)).await;
+
+    let channel = Channel::from_shared(format!("http://{}", addr))?
+        .connect()
+        .await?;
+    let mut client = HealthClient::new(channel);
+    let req = HealthCheckRequest {
+        service: "crm.Crm".to_string(),
+    };
+    // the status is reported in the background once the dependencies are connected
+    let mut status = None;
+    for _ in 0..50 {
+        if let Ok(res) = client.check(req.clone()).await {
+            status = Some(res.into_inner().status());
+            if status == Some(ServingStatus::Serving) {
+                break;
+            }
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(status, Some(ServingStatus::Serving));
+    Ok(())
+}
+
+#[tokio::test]
+async fn health_should_not_be_serving_if_a_dependency_is_down() -> Result<()> {
+    // none of the dependencies are running
+    let mut config: AppConfig = serde_yaml::from_str(include_str!("../crm.yml"))?;
+    config.server.tls = None;
+    let addr: SocketAddr = format!("[::1]:{}", PORT_BASE + 150).parse()?;
+    let server = CrmService::try_new(config).await?.into_server()?;
+    tokio::spawn(async move { server.serve(addr).await.unwrap() });
+    sleep(Duration::from_millis(10)).await;
+
+    let channel = Channel::from_shared(format!("http://{}", addr))?
+        .connect()
+        .await?;
+    let mut client = HealthClient::new(channel);
+    let req = HealthCheckRequest {
+        service: "crm.Crm".to_string(),
+    };
+    let mut status = None;
+    for _ in 0..50 {
+        if let Ok(res) = client.check(req.clone()).await {
+            status = Some(res.into_inner().status());
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(status, Some(ServingStatus::NotServing));
+    Ok(())
+}
+
+#[tokio::test]
+async fn welcome_should_fail_once_deadline_exceeded() -> Result<()> {
+    let metadata = SlowMetadata::default();
+    let config = start_mocks(
+        PORT_BASE + 160,
+        fake_users(3),
+        metadata.clone(),
+        MockNotification::default(),
+    )
+    .await?;
+    let svc = CrmService::try_new(config).await?;
+
+    let req = WelcomeRequestBuilder::default()
+        .id("welcome-deadline")
+        .interval(7u32)
+        .content_ids([1u32])
+        .build()?;
+    let deadline = Deadline::after(Duration::from_millis(100));
+    let start = Instant::now();
+    let e = with_deadline(Some(deadline), svc.welcome(req, &RequestId::default()))
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), tonic::Code::DeadlineExceeded);
+    assert!(start.elapsed() < SLOW_METADATA_DELAY);
+    // the downstream call was told about the remaining budget
+    let timeout = metadata.timeout.lock().unwrap().clone();
+    assert!(timeout.is_some());
+    Ok(())
+}
+
+/// users with a `broken@` email are streamed as an error
+#[derive(Clone)]
+struct MockUserStats {
+    users: Vec<User>,
+}
+
+/// only knows about the `en` and `fr` locales
+#[derive(Clone)]
+struct MockMetadata;
+
+/// always down
+#[derive(Clone)]
+struct UnavailableMetadata;
+
+/// takes `SLOW_METADATA_DELAY` to reply, records the `grpc-timeout` it was called with
+#[derive(Clone, Default)]
+struct SlowMetadata {
+    timeout: Arc<Mutex<Option<String>>>,
+}
+
+/// recipients with a `fail@` email are unavailable, `slow@` ones take `SLOW_SEND_DELAY`
+#[derive(Clone, Default)]
+struct MockNotification {
+    sent: Arc<Mutex<Vec<SendRequest>>>,
+    request_ids: Arc<Mutex<Vec<String>>>,
+    in_flight: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl UserStats for MockUserStats {
+    type QueryStream = ResponseStream<User>;
+    type RawQueryStream = ResponseStream<User>;
+
+    async fn query(
+        &self,
+        _request: Request<QueryRequest>,
+    ) -> Result<Response<Self::QueryStream>, Status> {
+        Ok(Response::new(self.stream()))
+    }
+
+    async fn raw_query(
+        &self,
+        _request: Request<RawQueryRequest>,
+    ) -> Result<Response<Self::RawQueryStream>, Status> {
+        Ok(Response::new(self.stream()))
+    }
+}
+
+impl MockUserStats {
+    #[allow(clippy::result_large_err)]
+    fn stream(&self) -> ResponseStream<User> {
+        let users = self.users.clone().into_iter().map(|user| {
+            if user.email.starts_with("broken@") {
+                Err(Status::internal("broken user record"))
+            } else {
+                Ok(user)
+            }
+        });
+        Box::pin(futures::stream::iter(users))
+    }
+}
+
+#[async_trait]
+impl Metadata for MockMetadata {
+    type MaterializeStream = ResponseStream<Content>;
+
+    async fn materialize(
+        &self,
+        request: Request<Streaming<MaterializeRequest>>,
+    ) -> Result<Response<Self::MaterializeStream>, Status> {
+        let contents = request
+            .into_inner()
+            .filter_map(|req| async move {
+                let req = req.ok()?;
+                let name = match req.locale.as_str() {
+                    "en" => "Hello",
+                    "fr" => "Bonjour",
+                    _ => return None,
+                };
+                Some(Content {
+                    id: req.id,
+                    name: name.to_string(),
+                    locale: req.locale,
+                    template_id: req.template_id,
+                    ..Default::default()
+                })
+            })
+            .map(Ok);
+        Ok(Response::new(Box::pin(contents)))
+    }
+}
+
+#[async_trait]
+impl Metadata for UnavailableMetadata {
+    type MaterializeStream = ResponseStream<Content>;
+
+    async fn materialize(
+        &self,
+        _request: Request<Streaming<MaterializeRequest>>,
+    ) -> Result<Response<Self::MaterializeStream>, Status> {
+        Err(Status::unavailable("metadata is down"))
+    }
+}
+
+#[async_trait]
+impl Metadata for SlowMetadata {
+    type MaterializeStream = ResponseStream<Content>;
+
+    async fn materialize(
+        &self,
+        request: Request<Streaming<MaterializeRequest>>,
+    ) -> Result<Response<Self::MaterializeStream>, Status> {
+        let timeout = request
+            .metadata()
+            .get(GRPC_TIMEOUT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        *self.timeout.lock().unwrap() = timeout;
+        sleep(SLOW_METADATA_DELAY).await;
+        Err(Status::unavailable("metadata is too slow"))
+    }
+}
+
+#[async_trait]
+impl Notification for MockNotification {
+    type SendStream = ResponseStream<SendResponse>;
+
+    async fn send(
+        &self,
+        request: Request<Streaming<SendRequest>>,
+    ) -> Result<Response<Self::SendStream>, Status> {
+        if let Some(id) = request.metadata().get(REQUEST_ID_HEADER) {
+            self.request_ids
+                .lock()
+                .unwrap()
+                .push(id.to_str().unwrap().to_string());
+        }
+
+        let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(current, Ordering::SeqCst);
+        sleep(Duration::from_millis(10)).await;
+
+        // drain the whole request stream so the recorded requests are complete once the call returns
+        let reqs: Vec<_> = request
+            .into_inner()
+            .filter_map(|req| async move { req.ok() })
+            .collect()
+            .await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let has_recipient = |prefix: &str| {
+            reqs.iter().any(|req| match &req.msg {
+                Some(Msg::Email(email)) => email.recipients.iter().any(|r| r.starts_with(prefix)),
+                _ => false,
+            })
+        };
+        if has_recipient("slow@") {
+            sleep(SLOW_SEND_DELAY).await;
+        }
+        if has_recipient("fail@") {
+            return Err(Status::unavailable("mailbox unavailable"));
+        }
+        let ret: Vec<_> = reqs
+            .iter()
+            .map(|_| SendResponse::default())
+            .map(Ok)
+            .collect();
+        self.sent.lock().unwrap().extend(reqs);
+        Ok(Response::new(Box::pin(futures::stream::iter(ret))))
+    }
+}
+
+impl MockNotification {
+    fn sent(&self) -> usize {
+        self.sent.lock().unwrap().len()
+    }
+
+    fn recipients(&self) -> Vec<String> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|req| match &req.msg {
+                Some(Msg::Email(email)) => Some(email.recipients.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn bodies(&self) -> Vec<String> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|req| match &req.msg {
+                Some(Msg::Email(email)) => Some(email.body.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// poll the campaign until it's done
+async fn wait_for_campaign(svc: &CrmService, campaign_id: &str) -> Result<CampaignStatusResponse> {
+    for _ in 0..100 {
+        match svc.campaign_status(campaign_id) {
+            Some(status) if status.done => return Ok(status),
+            _ => sleep(Duration::from_millis(10)).await,
+        }
+    }
+    anyhow::bail!("campaign {} didn't complete", campaign_id)
+}
+
+fn fake_users(n: usize) -> Vec<User> {
+    (0..n)
+        .map(|i| User {
+            email: format!("user{}@acme.org", i),
+            name: format!("user{}", i),
+        })
+        .collect()
+}
+
+/// a remind request to be sent `delay_ms` from now
+fn remind_at(id: &str, delay_ms: i64) -> Result<RemindRequest> {
+    let at = chrono::Utc::now() + chrono::Duration::milliseconds(delay_ms);
+    let send_at = prost_types::Timestamp {
+        seconds: at.timestamp(),
+        nanos: at.timestamp_subsec_nanos() as _,
+    };
+    Ok(RemindRequestBuilder::default()
+        .id(id)
+        .last_visit_interval(7u32)
+        .template_id("remind")
+        .content_ids([1u32])
+        .send_at(send_at)
+        .build()?)
+}
+
+fn metadata_service() -> Result<MetadataService> {
+    let config: crm_metadata::AppConfig =
+        serde_yaml::from_str(include_str!("../../crm-metadata/metadata.yml"))?;
+    Ok(MetadataService::new(config))
+}
+
+async fn start_mocks(
+    port: u16,
+    users: Vec<User>,
+    metadata: impl Metadata,
+    notification: MockNotification,
+) -> Result<AppConfig> {
+    let user_stats_addr: SocketAddr = format!("[::1]:{}", port).parse()?;
+    let metadata_addr: SocketAddr = format!("[::1]:{}", port + 1).parse()?;
+    let notification_addr: SocketAddr = format!("[::1]:{}", port + 2).parse()?;
+
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(UserStatsServer::new(MockUserStats { users }))
+            .serve(user_stats_addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(MetadataServer::new(metadata))
+            .serve(metadata_addr)
+            .await
+            .unwrap();
+    });
+
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(NotificationServer::new(notification))
+            .serve(notification_addr)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_millis(10)).await;
+
+    let mut config: AppConfig = serde_yaml::from_str(include_str!("../crm.yml"))?;
+    config.server.port = port + 3;
+    config.server.metadata = format!("http://{}", metadata_addr);
+    config.server.user_stats = format!("http://{}", user_stats_addr);
+    config.server.notification = format!("http://{}", notification_addr);
+    config.server.tls = None;
+    Ok(config)
+}